@@ -1,9 +1,11 @@
-use writer_core::TextBuffer;
+use writer_core::{Cursor, TextBuffer};
 
 #[derive(Clone, Debug)]
 pub struct EditorState {
     pub buffer: TextBuffer,
     pub doc_name: String,
+    pub find_query: String,
+    pub find_matches: Vec<Cursor>,
 }
 
 impl EditorState {
@@ -11,6 +13,8 @@ impl EditorState {
         Self {
             buffer: TextBuffer::new(),
             doc_name: String::new(),
+            find_query: String::new(),
+            find_matches: Vec::new(),
         }
     }
 
@@ -18,6 +22,8 @@ impl EditorState {
         Self {
             buffer: TextBuffer::new(),
             doc_name: name.to_string(),
+            find_query: String::new(),
+            find_matches: Vec::new(),
         }
     }
 
@@ -25,6 +31,108 @@ impl EditorState {
         Self {
             buffer: TextBuffer::from_text(content),
             doc_name: name.to_string(),
+            find_query: String::new(),
+            find_matches: Vec::new(),
         }
     }
+
+    /// Open a document from its saved content (None if never saved).
+    /// Any in-memory edits are discarded, so this also serves as "revert".
+    pub fn open(name: &str, saved: Option<&str>) -> Self {
+        match saved {
+            Some(content) => Self::with_content(name, content),
+            None => Self::with_name(name),
+        }
+    }
+
+    /// Re-run the search after the query changes.
+    pub fn update_find(&mut self) {
+        self.find_matches = self.buffer.find_all(&self.find_query);
+    }
+
+    /// Move the cursor to the first match after it, wrapping to the top.
+    pub fn find_next(&mut self) -> bool {
+        let here = (self.buffer.cursor.line, self.buffer.cursor.col);
+        let next = self.find_matches.iter()
+            .find(|m| (m.line, m.col) > here)
+            .or_else(|| self.find_matches.first());
+        match next {
+            Some(m) => {
+                self.buffer.cursor = m.clone();
+                self.buffer.ensure_cursor_visible();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// 1-based index of the match under the cursor, if any.
+    pub fn find_position(&self) -> Option<usize> {
+        self.find_matches.iter()
+            .position(|m| *m == self.buffer.cursor)
+            .map(|i| i + 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_revert_restores_saved_content() {
+        let saved = "first line\nsecond line";
+        let mut editor = EditorState::with_content("Notes", saved);
+        editor.buffer.cursor.line = 1;
+        editor.buffer.newline();
+        editor.buffer.insert_char('x');
+        assert!(editor.buffer.modified);
+
+        let editor = EditorState::open(&editor.doc_name, Some(saved));
+        assert_eq!(editor.doc_name, "Notes");
+        assert_eq!(editor.buffer.to_string(), saved);
+        assert!(!editor.buffer.modified);
+    }
+
+    #[test]
+    fn test_revert_never_saved_doc_is_empty() {
+        let mut editor = EditorState::with_name("Untitled");
+        editor.buffer.insert_char('x');
+
+        let editor = EditorState::open(&editor.doc_name, None);
+        assert_eq!(editor.buffer.to_string(), "");
+        assert!(!editor.buffer.modified);
+    }
+
+    #[test]
+    fn test_find_next_cycles_and_scrolls() {
+        let mut text = String::from("needle\n");
+        for _ in 0..30 {
+            text.push_str("hay\n");
+        }
+        text.push_str("a needle");
+        let mut editor = EditorState::with_content("Doc", &text);
+        editor.find_query = String::from("NEEDLE");
+        editor.update_find();
+        assert_eq!(editor.find_matches.len(), 2);
+        assert_eq!(editor.find_position(), Some(1));
+
+        assert!(editor.find_next());
+        assert_eq!((editor.buffer.cursor.line, editor.buffer.cursor.col), (31, 2));
+        assert!(editor.buffer.viewport_top > 0);
+        assert_eq!(editor.find_position(), Some(2));
+
+        // Wraps back to the first match
+        assert!(editor.find_next());
+        assert_eq!((editor.buffer.cursor.line, editor.buffer.cursor.col), (0, 0));
+        assert_eq!(editor.buffer.viewport_top, 0);
+    }
+
+    #[test]
+    fn test_find_next_without_matches() {
+        let mut editor = EditorState::with_content("Doc", "nothing here");
+        editor.find_query = String::from("zzz");
+        editor.update_find();
+        assert!(!editor.find_next());
+        assert_eq!(editor.find_position(), None);
+    }
 }
@@ -1,75 +1,213 @@
-use std::io::Write;
+use std::io::{ErrorKind, Write};
 use std::net::TcpListener;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use usb_device_xous::UsbHid;
+use writer_core::export::{autotype_lines, char_chunks, console_records, resume_slice};
+use writer_core::serialize::WriterConfig;
 
-const EXPORT_PORT: u16 = 7879;
+pub const EXPORT_PORT: u16 = 7879;
 const DEFAULT_AUTOTYPE_DELAY_MS: usize = 30;
+const PAGE_PAUSE_MS: usize = 1000;
+/// Bytes typed per burst when an export can be resumed part-way.
+const RESUME_CHUNK_SIZE: usize = 64;
+/// How often a waiting TCP export checks for a client or a cancel.
+const ACCEPT_POLL_MS: u64 = 100;
+/// Pause after each line when autotyping a line at a time.
+const LINE_PAUSE_MS: usize = 200;
+/// Bytes of document text per console log record.
+const CONSOLE_CHUNK_SIZE: usize = 96;
+/// Pause after each console record so the log buffer can drain.
+const CONSOLE_PAUSE_MS: usize = 2;
+/// USB readiness is polled this often, for at most USB_READY_TIMEOUT_MS.
+const USB_READY_POLL_MS: usize = 500;
+const USB_READY_TIMEOUT_MS: usize = 10_000;
+
+/// The exports that go through the device's USB and ticktimer connections,
+/// on the app's thread or (autotype) a worker it lends them to. TCP
+/// exports need neither. Tests pass `FakeExport`.
+pub trait ExportBackend {
+    fn is_usb_ready(&self) -> bool;
+    fn export_console(&self, content: &str) -> Result<usize, ExportError>;
+    fn set_by_line(&mut self, on: bool);
+    fn export_usb_autotype_with_retry(
+        &self,
+        pages: &[String],
+        from: usize,
+        cancel: &AtomicBool,
+        progress: &mut dyn FnMut(usize),
+    ) -> Result<usize, ExportError>;
+}
+
+/// The app's one export backend, shared with the thread typing a USB export.
+pub type SharedExport = Arc<Mutex<Box<dyn ExportBackend + Send>>>;
 
 pub struct ExportSystem {
     tt: ticktimer_server::Ticktimer,
     usb_dev: UsbHid,
+    // Autotype a line at a time rather than in byte chunks
+    by_line: bool,
+}
+
+/// Destinations offered by the export menu, in menu order.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ExportTarget {
+    Tcp,
+    UsbAutotype,
+    /// Raw markdown saved to the `writer.exports` dict as `{name}.md`
+    Pddb,
+    /// HTML rendering saved to the `writer.exports` dict as `{name}.html`
+    PddbHtml,
+    /// Framed lines written to the debug log, for a host on the serial port
+    Console,
+    /// Base64 chunks shown one QR code at a time, for a phone to scan
+    Qr,
 }
 
-#[derive(Debug)]
+impl ExportTarget {
+    pub fn from_menu_index(index: usize) -> Option<Self> {
+        match index {
+            0 => Some(ExportTarget::Tcp),
+            1 => Some(ExportTarget::UsbAutotype),
+            2 => Some(ExportTarget::Pddb),
+            3 => Some(ExportTarget::PddbHtml),
+            4 => Some(ExportTarget::Console),
+            5 => Some(ExportTarget::Qr),
+            _ => None,
+        }
+    }
+
+    /// Whether to show a "focus the target, press Enter" screen first.
+    /// Autotype types into whatever window has focus on the host, so it
+    /// asks by default; TCP waits for a client and does not.
+    pub fn needs_confirmation(&self, config: &WriterConfig) -> bool {
+        match self {
+            ExportTarget::Tcp => config.confirm_tcp_export,
+            ExportTarget::UsbAutotype => config.confirm_usb_export,
+            ExportTarget::Pddb | ExportTarget::PddbHtml | ExportTarget::Console | ExportTarget::Qr => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, num_derive::FromPrimitive, num_derive::ToPrimitive)]
 pub enum ExportError {
     UsbNotConnected,
     TcpBindFailed,
     TcpAcceptFailed,
     TcpWriteFailed,
+    /// No client connected before the timeout
+    TcpTimeout,
+    /// The user pressed F4 while waiting
+    Cancelled,
 }
 
-impl ExportSystem {
-    pub fn new() -> Self {
-        let tt = ticktimer_server::Ticktimer::new().unwrap();
-        let usb_dev = UsbHid::new();
-        // Set a reasonable default autotype delay
-        usb_dev.set_autotype_delay_ms(DEFAULT_AUTOTYPE_DELAY_MS);
-        Self { tt, usb_dev }
+impl ExportError {
+    /// One-line explanation for the export menu.
+    pub fn message(&self) -> &'static str {
+        match self {
+            ExportError::UsbNotConnected => "USB not connected",
+            ExportError::TcpBindFailed => "Could not listen on port 7879",
+            ExportError::TcpAcceptFailed => "Connection failed",
+            ExportError::TcpWriteFailed => "Connection dropped while sending",
+            ExportError::TcpTimeout => "No client connected in time",
+            ExportError::Cancelled => "Export cancelled",
+        }
     }
+}
 
-    /// Set the delay between keystrokes during USB autotype (in milliseconds).
-    /// Default is 30ms. Lower values type faster but may miss characters on some hosts.
-    pub fn set_autotype_delay(&self, delay_ms: usize) {
-        self.usb_dev.set_autotype_delay_ms(delay_ms);
+/// Send each page over its own TCP connection on port 7879, in order.
+/// Each page waits at most `timeout_ms` for a client; setting `cancel`
+/// stops the wait. Runs without the UI, so it can go on its own thread.
+pub fn export_tcp_pages(pages: &[String], timeout_ms: u64, cancel: &AtomicBool) -> Result<usize, ExportError> {
+    let mut total = 0;
+    for (i, page) in pages.iter().enumerate() {
+        log::info!("TCP export page {}/{}", i + 1, pages.len());
+        total += export_tcp(page, timeout_ms, cancel)?;
     }
+    Ok(total)
+}
 
-    /// Check if USB HID keyboard is available for autotype.
-    pub fn is_usb_ready(&self) -> bool {
-        // Try a quick check - if we can send an empty string, USB is connected
-        self.usb_dev.send_str("").is_ok()
-    }
+/// Export content via TCP on port 7879: wait for one client and send it.
+pub fn export_tcp(content: &str, timeout_ms: u64, cancel: &AtomicBool) -> Result<usize, ExportError> {
+    export_tcp_bytes(content.as_bytes(), timeout_ms, cancel)
+}
 
-    /// Export document content via TCP on port 7879.
-    /// Blocks until a client connects and receives the data.
-    pub fn export_tcp(&self, content: &str) -> Result<usize, ExportError> {
-        log::info!("Starting TCP export on port {}", EXPORT_PORT);
+/// Send binary data (a backup, say) the same way as `export_tcp`.
+pub fn export_tcp_bytes(data: &[u8], timeout_ms: u64, cancel: &AtomicBool) -> Result<usize, ExportError> {
+    log::info!("Starting TCP export on port {}", EXPORT_PORT);
 
-        let listener = match TcpListener::bind(format!("0.0.0.0:{}", EXPORT_PORT)) {
-            Ok(l) => l,
-            Err(e) => {
-                log::error!("Failed to bind port {}: {:?}", EXPORT_PORT, e);
-                return Err(ExportError::TcpBindFailed);
-            }
-        };
+    let listener = match TcpListener::bind(format!("0.0.0.0:{}", EXPORT_PORT)) {
+        Ok(l) => l,
+        Err(e) => {
+            log::error!("Failed to bind port {}: {:?}", EXPORT_PORT, e);
+            return Err(ExportError::TcpBindFailed);
+        }
+    };
+    serve_once(&listener, data, timeout_ms, cancel)
+    // Listener drops and port is released
+}
 
-        // Wait for connection
+/// Wait up to `timeout_ms` for a client on `listener` and send it `data`.
+/// The listener is polled rather than blocked on, so a timeout or `cancel`
+/// ends the wait.
+fn serve_once(listener: &TcpListener, data: &[u8], timeout_ms: u64, cancel: &AtomicBool) -> Result<usize, ExportError> {
+    if let Err(e) = listener.set_nonblocking(true) {
+        log::error!("Failed to make listener non-blocking: {:?}", e);
+        return Err(ExportError::TcpAcceptFailed);
+    }
+    let started = Instant::now();
+    let (mut stream, addr) = loop {
+        if cancel.load(Ordering::Relaxed) {
+            log::info!("TCP export cancelled");
+            return Err(ExportError::Cancelled);
+        }
         match listener.accept() {
-            Ok((mut stream, addr)) => {
-                log::info!("Export connection from {:?}", addr);
-                let bytes = content.as_bytes();
-                if let Err(e) = stream.write_all(bytes) {
-                    log::error!("Failed to write export data: {:?}", e);
-                    return Err(ExportError::TcpWriteFailed);
+            Ok(conn) => break conn,
+            Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                if started.elapsed() >= Duration::from_millis(timeout_ms) {
+                    log::warn!("No TCP client within {} ms", timeout_ms);
+                    return Err(ExportError::TcpTimeout);
                 }
-                log::info!("Export complete: {} bytes sent", bytes.len());
-                Ok(bytes.len())
+                std::thread::sleep(Duration::from_millis(ACCEPT_POLL_MS));
             }
             Err(e) => {
                 log::error!("Accept failed: {:?}", e);
-                Err(ExportError::TcpAcceptFailed)
+                return Err(ExportError::TcpAcceptFailed);
             }
         }
-        // Listener drops and port is released
+    };
+
+    log::info!("Export connection from {:?}", addr);
+    // The stream may inherit non-blocking mode; sending should block
+    stream.set_nonblocking(false).ok();
+    if let Err(e) = stream.write_all(data) {
+        log::error!("Failed to write export data: {:?}", e);
+        return Err(ExportError::TcpWriteFailed);
+    }
+    log::info!("Export complete: {} bytes sent", data.len());
+    Ok(data.len())
+}
+
+impl ExportSystem {
+    pub fn new() -> Self {
+        let tt = ticktimer_server::Ticktimer::new().unwrap();
+        let usb_dev = UsbHid::new();
+        // Set a reasonable default autotype delay
+        usb_dev.set_autotype_delay_ms(DEFAULT_AUTOTYPE_DELAY_MS);
+        Self { tt, usb_dev, by_line: false }
+    }
+
+    /// Type one line per burst, pausing after each line break, instead of
+    /// fixed-size chunks (config.autotype_by_line).
+    pub fn set_by_line(&mut self, on: bool) {
+        self.by_line = on;
+    }
+
+    /// Set the delay between keystrokes during USB autotype (in milliseconds).
+    /// Default is 30ms. Lower values type faster but may miss characters on some hosts.
+    pub fn set_autotype_delay(&self, delay_ms: usize) {
+        self.usb_dev.set_autotype_delay_ms(delay_ms);
     }
 
     /// Export document content via USB keyboard autotype.
@@ -90,57 +228,453 @@ impl ExportSystem {
         }
     }
 
-    /// Export with progress callback for long documents.
-    /// Useful for showing a progress indicator during export.
-    pub fn export_usb_autotype_chunked<F>(
+    /// Like `export_usb_autotype_pages`, but first waits up to 10s for the
+    /// host to enumerate the keyboard (a cable plugged in just now may not
+    /// be ready yet). Fails with `UsbNotConnected` only once that runs out.
+    pub fn export_usb_autotype_with_retry<F>(
         &self,
-        content: &str,
-        chunk_size: usize,
+        pages: &[String],
+        from: usize,
+        cancel: &AtomicBool,
+        progress: F,
+    ) -> Result<usize, ExportError>
+    where
+        F: FnMut(usize),
+    {
+        // A cancel counts as ready: the chunk loop then reports Cancelled
+        let ready = wait_until_ready(
+            || self.is_usb_ready() || cancel.load(Ordering::Relaxed),
+            |ms| { self.tt.sleep_ms(ms).ok(); },
+            USB_READY_POLL_MS,
+            USB_READY_TIMEOUT_MS,
+        );
+        if !ready {
+            log::error!("USB still not ready after {} ms", USB_READY_TIMEOUT_MS);
+            return Err(ExportError::UsbNotConnected);
+        }
+        self.export_usb_autotype_pages(pages, from, cancel, progress)
+    }
+
+    /// Autotype each page, pausing between pages so the receiving host can
+    /// catch up. Starts `from` bytes into the pages joined together (0 for
+    /// a fresh export) and reports the byte offset reached after each chunk.
+    pub fn export_usb_autotype_pages<F>(
+        &self,
+        pages: &[String],
+        from: usize,
+        cancel: &AtomicBool,
         mut progress: F,
     ) -> Result<usize, ExportError>
     where
-        F: FnMut(usize, usize), // (chars_sent, total_chars)
+        F: FnMut(usize), // offset into the joined pages
     {
-        log::info!("Starting chunked USB autotype: {} chars", content.len());
-        let total = content.len();
-        let mut sent = 0;
-
-        for chunk in content.as_bytes().chunks(chunk_size) {
-            let chunk_str = match std::str::from_utf8(chunk) {
-                Ok(s) => s,
-                Err(_) => {
-                    // Handle UTF-8 boundary issues by converting what we can
-                    let s = String::from_utf8_lossy(chunk);
-                    match self.usb_dev.send_str(&s) {
-                        Ok(n) => {
-                            sent += n;
-                            progress(sent, total);
-                            continue;
-                        }
-                        Err(e) => {
-                            log::error!("USB autotype failed at char {}: {:?}", sent, e);
-                            return Err(ExportError::UsbNotConnected);
-                        }
-                    }
+        let mut total = 0;
+        let mut page_start = 0;
+        for page in pages {
+            let page_end = page_start + page.len();
+            if page_end > from {
+                if total > 0 {
+                    self.tt.sleep_ms(PAGE_PAUSE_MS).ok();
                 }
-            };
+                let skip = from.saturating_sub(page_start);
+                total += self.export_usb_autotype_resume(page, skip, cancel, |at| progress(page_start + at))?;
+            }
+            page_start = page_end;
+        }
+        Ok(total)
+    }
 
-            match self.usb_dev.send_str(chunk_str) {
-                Ok(n) => {
-                    sent += n;
-                    progress(sent, total);
-                }
-                Err(e) => {
-                    log::error!("USB autotype failed at char {}: {:?}", sent, e);
-                    return Err(ExportError::UsbNotConnected);
-                }
+    /// Autotype `content` starting at byte offset `from`, in small chunks.
+    /// `progress` gets the offset into `content` reached after each chunk,
+    /// which is what to save if the export is interrupted.
+    pub fn export_usb_autotype_resume<F>(
+        &self,
+        content: &str,
+        from: usize,
+        cancel: &AtomicBool,
+        mut progress: F,
+    ) -> Result<usize, ExportError>
+    where
+        F: FnMut(usize),
+    {
+        let (start, rest) = resume_slice(content, from);
+        if start > 0 {
+            log::info!("Resuming USB autotype at byte {} of {}", start, content.len());
+        }
+        if self.by_line {
+            self.export_usb_autotype_lines(rest, cancel, |sent, _| progress(start + sent))
+        } else {
+            self.export_usb_autotype_chunked(rest, RESUME_CHUNK_SIZE, cancel, |sent, _| progress(start + sent))
+        }
+    }
+
+    /// Like `export_usb_autotype_chunked`, one line at a time with a pause
+    /// after each, so a host that drops keys mid-way loses at most a line
+    /// and a resume picks up at the start of one.
+    pub fn export_usb_autotype_lines<F>(
+        &self,
+        content: &str,
+        cancel: &AtomicBool,
+        progress: F,
+    ) -> Result<usize, ExportError>
+    where
+        F: FnMut(usize, usize), // (bytes_sent, total_bytes)
+    {
+        log::info!("Starting line-by-line USB autotype: {} lines", autotype_lines(content).len());
+        let send = |line: &str| {
+            if let Err(e) = self.usb_dev.send_str(line) {
+                log::error!("USB autotype failed: {:?}", e);
+                return Err(ExportError::UsbNotConnected);
             }
+            self.tt.sleep_ms(LINE_PAUSE_MS).ok();
+            Ok(())
+        };
+        let sent = type_in_lines(content, cancel, send, progress)?;
+        log::info!("Line-by-line USB autotype complete: {} bytes typed", sent);
+        Ok(sent)
+    }
 
+    /// Export with progress callback for long documents.
+    /// Setting `cancel` stops the export between chunks.
+    pub fn export_usb_autotype_chunked<F>(
+        &self,
+        content: &str,
+        chunk_size: usize,
+        cancel: &AtomicBool,
+        progress: F,
+    ) -> Result<usize, ExportError>
+    where
+        F: FnMut(usize, usize), // (bytes_sent, total_bytes)
+    {
+        log::info!("Starting chunked USB autotype: {} bytes", content.len());
+        let send = |chunk: &str| {
+            if let Err(e) = self.usb_dev.send_str(chunk) {
+                log::error!("USB autotype failed: {:?}", e);
+                return Err(ExportError::UsbNotConnected);
+            }
             // Small pause between chunks to prevent buffer overflow
             self.tt.sleep_ms(50).ok();
+            Ok(())
+        };
+        let sent = type_in_chunks(content, chunk_size, cancel, send, progress)?;
+        log::info!("Chunked USB autotype complete: {} bytes typed", sent);
+        Ok(sent)
+    }
+}
+
+impl ExportBackend for ExportSystem {
+    /// Check if USB HID keyboard is available for autotype.
+    fn is_usb_ready(&self) -> bool {
+        // Try a quick check - if we can send an empty string, USB is connected
+        self.usb_dev.send_str("").is_ok()
+    }
+
+    /// Write the document to the debug log between `---BEGIN WRITER EXPORT---`
+    /// and `---END WRITER EXPORT---` lines, a short piece per record, for a
+    /// host capturing the serial console. Returns the number of records.
+    fn export_console(&self, content: &str) -> Result<usize, ExportError> {
+        let records = console_records(content, CONSOLE_CHUNK_SIZE);
+        for record in &records {
+            log::info!("{}", record);
+            self.tt.sleep_ms(CONSOLE_PAUSE_MS).ok();
         }
+        Ok(records.len())
+    }
 
-        log::info!("Chunked USB autotype complete: {} chars typed", sent);
-        Ok(sent)
+    fn set_by_line(&mut self, on: bool) {
+        ExportSystem::set_by_line(self, on)
+    }
+
+    fn export_usb_autotype_with_retry(
+        &self,
+        pages: &[String],
+        from: usize,
+        cancel: &AtomicBool,
+        progress: &mut dyn FnMut(usize),
+    ) -> Result<usize, ExportError> {
+        ExportSystem::export_usb_autotype_with_retry(self, pages, from, cancel, progress)
+    }
+}
+
+/// Export double: USB is plugged in or not as set, console exports count
+/// their records without logging them, and autotype finishes at once.
+#[cfg(test)]
+#[derive(Default)]
+pub struct FakeExport {
+    pub usb_ready: bool,
+}
+
+#[cfg(test)]
+impl ExportBackend for FakeExport {
+    fn is_usb_ready(&self) -> bool {
+        self.usb_ready
+    }
+    fn export_console(&self, content: &str) -> Result<usize, ExportError> {
+        Ok(console_records(content, CONSOLE_CHUNK_SIZE).len())
+    }
+    fn set_by_line(&mut self, _on: bool) {}
+    fn export_usb_autotype_with_retry(
+        &self,
+        pages: &[String],
+        from: usize,
+        _cancel: &AtomicBool,
+        progress: &mut dyn FnMut(usize),
+    ) -> Result<usize, ExportError> {
+        if !self.usb_ready {
+            return Err(ExportError::UsbNotConnected);
+        }
+        let total: usize = pages.iter().map(|page| page.len()).sum();
+        progress(total);
+        Ok(total - from.min(total))
+    }
+}
+
+/// Poll `is_ready` every `poll_ms` until it returns true or `timeout_ms`
+/// has passed. Returns whether it became ready.
+fn wait_until_ready<R, S>(mut is_ready: R, mut sleep: S, poll_ms: usize, timeout_ms: usize) -> bool
+where
+    R: FnMut() -> bool,
+    S: FnMut(usize),
+{
+    let mut waited = 0;
+    loop {
+        if is_ready() {
+            return true;
+        }
+        if waited >= timeout_ms {
+            return false;
+        }
+        sleep(poll_ms);
+        waited += poll_ms;
+    }
+}
+
+/// Feed `content` to `send` in chunks of at most `chunk_size` bytes,
+/// calling `progress(bytes_sent, total_bytes)` after each one.
+///
+/// `cancel` is only checked between chunks: every chunk handed to `send` is
+/// typed in full, so an abort never leaves a key held down on the host.
+fn type_in_chunks<S, F>(
+    content: &str,
+    chunk_size: usize,
+    cancel: &AtomicBool,
+    mut send: S,
+    mut progress: F,
+) -> Result<usize, ExportError>
+where
+    S: FnMut(&str) -> Result<(), ExportError>,
+    F: FnMut(usize, usize),
+{
+    let total = content.len();
+    let mut sent = 0;
+
+    // Chunks end on character boundaries, so no character is split
+    for chunk in char_chunks(content, chunk_size) {
+        if cancel.load(Ordering::Relaxed) {
+            log::info!("USB autotype cancelled at byte {}", sent);
+            return Err(ExportError::Cancelled);
+        }
+        send(chunk)?;
+        sent += chunk.len();
+        progress(sent, total);
+    }
+    Ok(sent)
+}
+
+/// `type_in_chunks`, but each piece handed to `send` is one line with its
+/// line break (an empty line is just the break).
+fn type_in_lines<S, F>(
+    content: &str,
+    cancel: &AtomicBool,
+    mut send: S,
+    mut progress: F,
+) -> Result<usize, ExportError>
+where
+    S: FnMut(&str) -> Result<(), ExportError>,
+    F: FnMut(usize, usize),
+{
+    let total = content.len();
+    let mut sent = 0;
+    for line in autotype_lines(content) {
+        if cancel.load(Ordering::Relaxed) {
+            log::info!("USB autotype cancelled at byte {}", sent);
+            return Err(ExportError::Cancelled);
+        }
+        send(line)?;
+        sent += line.len();
+        progress(sent, total);
+    }
+    Ok(sent)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_confirmation_defaults() {
+        let config = WriterConfig::default();
+        assert!(ExportTarget::UsbAutotype.needs_confirmation(&config));
+        assert!(!ExportTarget::Tcp.needs_confirmation(&config));
+        // Saving on-device has nothing to focus or connect first
+        assert!(!ExportTarget::Pddb.needs_confirmation(&config));
+    }
+
+    #[test]
+    fn test_confirmation_follows_config() {
+        let config = WriterConfig {
+            confirm_usb_export: false,
+            confirm_tcp_export: true,
+            ..WriterConfig::default()
+        };
+        assert!(!ExportTarget::UsbAutotype.needs_confirmation(&config));
+        assert!(ExportTarget::Tcp.needs_confirmation(&config));
+    }
+
+    #[test]
+    fn test_target_from_menu_index() {
+        assert_eq!(ExportTarget::from_menu_index(0), Some(ExportTarget::Tcp));
+        assert_eq!(ExportTarget::from_menu_index(1), Some(ExportTarget::UsbAutotype));
+        assert_eq!(ExportTarget::from_menu_index(2), Some(ExportTarget::Pddb));
+        assert_eq!(ExportTarget::from_menu_index(3), Some(ExportTarget::PddbHtml));
+        assert_eq!(ExportTarget::from_menu_index(4), Some(ExportTarget::Console));
+        assert_eq!(ExportTarget::from_menu_index(5), Some(ExportTarget::Qr));
+        assert_eq!(ExportTarget::from_menu_index(6), None);
+    }
+
+    #[test]
+    fn test_chunked_progress_increases_to_total() {
+        let content = "The quick brown fox jumps over the lazy dog. ".repeat(5) + "Über";
+        let cancel = AtomicBool::new(false);
+        let mut typed = String::new();
+        let mut reports = Vec::new();
+        let sent = type_in_chunks(
+            &content, 16, &cancel,
+            |chunk| { typed.push_str(chunk); Ok(()) },
+            |sent, total| reports.push((sent, total)),
+        );
+        assert_eq!(sent, Ok(content.len()));
+        assert_eq!(typed, content);
+        assert!(reports.windows(2).all(|w| w[0].0 < w[1].0));
+        assert!(reports.iter().all(|&(_, total)| total == content.len()));
+        assert_eq!(reports.last(), Some(&(content.len(), content.len())));
+    }
+
+    #[test]
+    fn test_chunked_cancel_stops_between_chunks() {
+        let cancel = AtomicBool::new(false);
+        let mut chunks = Vec::new();
+        let result = type_in_chunks(
+            "aaaabbbbcccc", 4, &cancel,
+            |chunk| {
+                chunks.push(chunk.to_string());
+                // Abort while the second chunk is being typed
+                if chunks.len() == 2 {
+                    cancel.store(true, Ordering::Relaxed);
+                }
+                Ok(())
+            },
+            |_, _| {},
+        );
+        assert_eq!(result, Err(ExportError::Cancelled));
+        // The chunk in flight finished; the next was never started
+        assert_eq!(chunks, ["aaaa", "bbbb"]);
+    }
+
+    #[test]
+    fn test_lines_sent_in_order() {
+        let content = "# Notes\n\nfirst line\nzweite Zeile ü\n";
+        let cancel = AtomicBool::new(false);
+        let mut sink = Vec::new();
+        let mut reports = Vec::new();
+        let sent = type_in_lines(
+            content, &cancel,
+            |line| { sink.push(line.to_string()); Ok(()) },
+            |sent, total| reports.push((sent, total)),
+        );
+        assert_eq!(sent, Ok(content.len()));
+        // One send per line, the empty one included, each ending its line
+        assert_eq!(sink, ["# Notes\n", "\n", "first line\n", "zweite Zeile ü\n"]);
+        assert_eq!(sink.concat(), content);
+        let offsets: Vec<usize> = reports.iter().map(|&(sent, _)| sent).collect();
+        assert_eq!(offsets, [8, 9, 20, content.len()]);
+    }
+
+    #[test]
+    fn test_lines_cancel_between_lines() {
+        let cancel = AtomicBool::new(false);
+        let mut sink = Vec::new();
+        let result = type_in_lines(
+            "a\r\nb\r\nc", &cancel,
+            |line| {
+                sink.push(line.to_string());
+                cancel.store(true, Ordering::Relaxed);
+                Ok(())
+            },
+            |_, _| {},
+        );
+        assert_eq!(result, Err(ExportError::Cancelled));
+        assert_eq!(sink, ["a\r\n"]);
+        // A failed send stops at that line
+        let cancel = AtomicBool::new(false);
+        let result = type_in_lines("a\nb", &cancel, |_| Err(ExportError::UsbNotConnected), |_, _| {});
+        assert_eq!(result, Err(ExportError::UsbNotConnected));
+    }
+
+    #[test]
+    fn test_usb_ready_on_third_poll() {
+        let mut polls = 0;
+        let mut slept = 0;
+        let ready = wait_until_ready(
+            || { polls += 1; polls == 3 },
+            |ms| slept += ms,
+            USB_READY_POLL_MS,
+            USB_READY_TIMEOUT_MS,
+        );
+        assert!(ready);
+        assert_eq!(polls, 3);
+        assert_eq!(slept, 2 * USB_READY_POLL_MS);
+    }
+
+    #[test]
+    fn test_usb_never_ready_gives_up() {
+        let mut polls = 0;
+        let mut slept = 0;
+        let ready = wait_until_ready(|| { polls += 1; false }, |ms| slept += ms, 500, 10_000);
+        assert!(!ready);
+        // One poll up front, then one after each of the 20 waits
+        assert_eq!(polls, 21);
+        assert_eq!(slept, 10_000);
+    }
+
+    #[test]
+    fn test_tcp_wait_times_out_without_client() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let cancel = AtomicBool::new(false);
+        let started = Instant::now();
+        assert_eq!(serve_once(&listener, b"text", 150, &cancel), Err(ExportError::TcpTimeout));
+        assert!(started.elapsed() >= Duration::from_millis(150));
+    }
+
+    #[test]
+    fn test_tcp_wait_cancelled() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let cancel = AtomicBool::new(true);
+        assert_eq!(serve_once(&listener, b"text", 60_000, &cancel), Err(ExportError::Cancelled));
+    }
+
+    #[test]
+    fn test_tcp_sends_to_client() {
+        use std::io::Read;
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let client = std::thread::spawn(move || {
+            let mut received = String::new();
+            std::net::TcpStream::connect(addr).unwrap().read_to_string(&mut received).unwrap();
+            received
+        });
+        let cancel = AtomicBool::new(false);
+        assert_eq!(serve_once(&listener, b"# Notes\n", 5_000, &cancel), Ok(8));
+        assert_eq!(client.join().unwrap(), "# Notes\n");
     }
 }
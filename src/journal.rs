@@ -1,4 +1,4 @@
-use writer_core::{TextBuffer, serialize::{epoch_ms_to_date, prev_day, next_day}};
+use writer_core::{TextBuffer, journal::{self, EntrySave, QuickNote, SEARCH_MATCHES_PER_DATE, SEARCH_RESULT_LIMIT, render_template}, serialize::{epoch_ms_to_date, local_epoch_ms, prev_day, next_day}};
 use crate::storage::WriterStorage;
 
 #[derive(Clone, Debug)]
@@ -6,8 +6,11 @@ pub struct JournalState {
     pub buffer: TextBuffer,
     pub current_date: String,
     pub search_query: String,
-    pub search_results: Vec<(String, String)>, // (date, matching line)
+    pub search_results: Vec<(String, usize, String)>, // (date, line index, matching line)
     pub search_cursor: usize, // Currently selected search result
+    pub streak: usize, // Consecutive days journaled, as of the last load or save
+    pub template: String, // Pre-fills days without an entry (empty = blank page)
+    pub tz_offset_minutes: i16, // Correction to the device clock, so "today" matches the wall clock
 }
 
 impl JournalState {
@@ -18,31 +21,74 @@ impl JournalState {
             search_query: String::new(),
             search_results: Vec::new(),
             search_cursor: 0,
+            streak: 0,
+            template: String::new(),
+            tz_offset_minutes: 0,
         }
     }
 
     pub fn jump_to_today(&mut self) {
-        // Get current time from system
-        // In Xous, we'd use llio::LocalTime, but for initialization
-        // we'll set a date that gets updated on first redraw
-        let now_ms = get_current_time_ms();
-        self.current_date = epoch_ms_to_date(now_ms);
+        self.current_date = today(self.tz_offset_minutes);
     }
 
+    /// Load the current day's entry. A day without one starts from the
+    /// journal template, if set; an existing entry is always kept as is.
     pub fn load_entry(&mut self, storage: &WriterStorage) {
         if let Some(content) = storage.load_journal_entry(&self.current_date) {
             self.buffer = TextBuffer::from_text(&content);
+        } else if !self.template.is_empty() {
+            self.buffer = TextBuffer::from_text(&render_template(&self.template, &self.current_date));
         } else {
             self.buffer = TextBuffer::new();
         }
         self.buffer.modified = false;
+        self.refresh_streak(storage);
     }
 
-    pub fn save_entry(&self, storage: &WriterStorage) {
-        if self.buffer.modified || self.buffer.word_count() > 0 {
-            let content = self.buffer.to_string();
-            storage.save_journal_entry(&self.current_date, &content);
+    /// What `save_entry` would do with the day as it stands.
+    pub fn save_action(&self) -> EntrySave {
+        journal::save_decision(self.buffer.modified, &self.buffer.to_string())
+    }
+
+    /// Persist the day if it was edited: write its text, or remove the
+    /// entry if it was cleared. Days that were only looked at are left alone.
+    pub fn save_entry(&mut self, storage: &WriterStorage) {
+        match self.save_action() {
+            EntrySave::Skip => return,
+            EntrySave::Write => storage.save_journal_entry(&self.current_date, &self.buffer.to_string()),
+            EntrySave::Delete => storage.delete_journal_entry(&self.current_date),
         }
+        self.buffer.modified = false;
+        self.refresh_streak(storage);
+    }
+
+    /// Delete the current day's entry, leaving the day empty and unmodified.
+    pub fn delete_entry(&mut self, storage: &WriterStorage) {
+        storage.delete_journal_entry(&self.current_date);
+        self.buffer = TextBuffer::new();
+        self.refresh_streak(storage);
+    }
+
+    /// Days in a row with an entry, ending today (or yesterday, if today
+    /// has no entry yet).
+    pub fn current_streak(storage: &WriterStorage, today: &str) -> usize {
+        journal::current_streak(&storage.list_journal_dates(), today)
+    }
+
+    fn refresh_streak(&mut self, storage: &WriterStorage) {
+        self.streak = Self::current_streak(storage, &today(self.tz_offset_minutes));
+    }
+
+    /// Open the most recent entry. Returns false, staying on the current
+    /// day, if the journal has no entries.
+    pub fn jump_to_latest(&mut self, storage: &WriterStorage) -> bool {
+        let dates = storage.list_journal_dates();
+        let Some(latest) = journal::latest_date(&dates) else {
+            return false;
+        };
+        self.current_date = latest.to_string();
+        self.load_entry(storage);
+        true
     }
 
     pub fn prev_day(&mut self, storage: &WriterStorage) {
@@ -55,23 +101,22 @@ impl JournalState {
         self.load_entry(storage);
     }
 
-    pub fn search_entries(&mut self, storage: &WriterStorage) {
+    /// Search every entry for the query: the first matching line of each
+    /// day, or with `all_matches` every matching line (a few per day at most).
+    pub fn search_entries(&mut self, storage: &WriterStorage, all_matches: bool) {
         self.search_results.clear();
         self.search_cursor = 0;
         if self.search_query.is_empty() {
             return;
         }
-        let query = self.search_query.to_lowercase();
+        let per_date = if all_matches { SEARCH_MATCHES_PER_DATE } else { 1 };
         let dates = storage.list_journal_dates();
         for date in dates {
             if let Some(content) = storage.load_journal_entry(&date) {
-                for line in content.lines() {
-                    if line.to_lowercase().contains(&query) {
-                        self.search_results.push((date.clone(), line.to_string()));
-                        if self.search_results.len() >= 10 {
-                            return;
-                        }
-                        break; // One match per date
+                for (line, text) in journal::matching_lines(&content, &self.search_query, per_date) {
+                    self.search_results.push((date.clone(), line, text));
+                    if self.search_results.len() >= SEARCH_RESULT_LIMIT {
+                        return;
                     }
                 }
             }
@@ -92,12 +137,14 @@ impl JournalState {
         }
     }
 
-    /// Jump to the currently selected search result
+    /// Jump to the currently selected search result, with the cursor on
+    /// the matching line
     pub fn jump_to_search_result(&mut self, storage: &WriterStorage) -> bool {
-        if let Some((date, _)) = self.search_results.get(self.search_cursor) {
+        if let Some((date, line, _)) = self.search_results.get(self.search_cursor).cloned() {
             self.save_entry(storage);
-            self.current_date = date.clone();
+            self.current_date = date;
             self.load_entry(storage);
+            self.buffer.goto_line(line + 1);
             self.search_results.clear();
             self.search_query.clear();
             true
@@ -107,8 +154,79 @@ impl JournalState {
     }
 }
 
-/// Get current epoch milliseconds using llio::LocalTime
+/// Append a timestamped note to today's entry and save it, without
+/// touching the journal screen's state. Returns the entry's date.
+pub fn append_quick_note(storage: &WriterStorage, note: &str, tz_offset_minutes: i16) -> String {
+    let note = QuickNote::new(local_epoch_ms(get_current_time_ms(), tz_offset_minutes), note);
+    let entry = storage.load_journal_entry(&note.date).unwrap_or_default();
+    storage.save_journal_entry(&note.date, &note.append_to(&entry));
+    note.date
+}
+
+/// Get current epoch milliseconds using llio::LocalTime. This is already
+/// local time, in the zone set on the device.
 pub fn get_current_time_ms() -> u64 {
     let mut lt = llio::LocalTime::new();
     lt.get_local_time_ms().unwrap_or(0)
 }
+
+/// Today's date (YYYY-MM-DD) on the device clock plus the configured
+/// correction. Journal entries are keyed by this date everywhere, so one
+/// day never splits in two.
+fn today(tz_offset_minutes: i16) -> String {
+    epoch_ms_to_date(local_epoch_ms(get_current_time_ms(), tz_offset_minutes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_search_result_opens_on_its_line() {
+        let storage = WriterStorage::with_store(Box::<crate::storage::MemoryStore>::default());
+        let text: Vec<String> = (0..40).map(|i| format!("line {}", i)).collect();
+        storage.save_journal_entry("2026-01-21", &text.join("\n"));
+        let mut journal = JournalState::new();
+        journal.search_results = vec![(String::from("2026-01-21"), 30, String::from("line 30"))];
+        assert!(journal.jump_to_search_result(&storage));
+        assert_eq!(journal.current_date, "2026-01-21");
+        assert_eq!((journal.buffer.cursor.line, journal.buffer.cursor.col), (30, 0));
+        let top = journal.buffer.viewport_top;
+        assert!(top <= 30 && 30 < top + journal.buffer.viewport_lines);
+    }
+
+    #[test]
+    fn test_search_result_past_the_end_clamps_to_last_line() {
+        // The entry was edited down since the search: land on its last line
+        let storage = WriterStorage::with_store(Box::<crate::storage::MemoryStore>::default());
+        storage.save_journal_entry("2026-01-21", "only\ntwo lines");
+        let mut journal = JournalState::new();
+        journal.search_results = vec![(String::from("2026-01-21"), 7, String::from("gone"))];
+        assert!(journal.jump_to_search_result(&storage));
+        assert_eq!(journal.buffer.cursor.line, 1);
+    }
+
+    #[test]
+    fn test_untouched_template_is_not_an_entry() {
+        let mut journal = JournalState::new();
+        journal.current_date = String::from("2026-01-21");
+        journal.template = String::from("# {weekday}\n\nToday:");
+        journal.buffer = TextBuffer::from_text(&render_template(&journal.template, &journal.current_date));
+        assert_eq!(journal.save_action(), EntrySave::Skip);
+
+        journal.buffer.insert_char('x');
+        assert_eq!(journal.save_action(), EntrySave::Write);
+    }
+
+    #[test]
+    fn test_cleared_entry_is_deleted() {
+        let mut journal = JournalState::new();
+        journal.buffer = TextBuffer::from_text("yesterday's words");
+        journal.buffer.modified = false;
+        assert_eq!(journal.save_action(), EntrySave::Skip);
+
+        journal.buffer = TextBuffer::new();
+        journal.buffer.modified = true;
+        assert_eq!(journal.save_action(), EntrySave::Delete);
+    }
+}
@@ -0,0 +1,185 @@
+// Esc command bindings per mode: one table that both the Esc dispatcher
+// and the help screen read, so help always lists what the keys really do
+
+/// A command reached with Esc then a key.
+pub struct KeyBinding<A> {
+    /// Keys (after Esc) that run the command
+    pub keys: &'static [char],
+    /// The key as help shows it
+    pub key: &'static str,
+    pub action: A,
+    /// What help says the command does
+    pub help: &'static str,
+}
+
+/// Esc commands in the editor (edit mode).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EditorAction {
+    WordLeft,
+    WordRight,
+    Preview,
+    Save,
+    Undo,
+    Redo,
+    Mark,
+    Copy,
+    Cut,
+    Paste,
+    YankLine,
+    PutLine,
+    DeleteLine,
+    DuplicateLine,
+    DeleteWordBack,
+    DeleteToLineEnd,
+    Yank,
+    YankPop,
+    ExportMenu,
+    FileMenu,
+    Find,
+    GotoLine,
+    Outline,
+    ToggleCheckbox,
+    Reflow,
+    Revert,
+    Back,
+}
+
+pub const EDITOR_BINDINGS: &[KeyBinding<EditorAction>] = &[
+    KeyBinding { keys: &['\u{F702}', '←'], key: "←", action: EditorAction::WordLeft, help: "Word left" },
+    KeyBinding { keys: &['\u{F703}', '→'], key: "→", action: EditorAction::WordRight, help: "Word right" },
+    KeyBinding { keys: &['p'], key: "p", action: EditorAction::Preview, help: "Toggle Preview" },
+    KeyBinding { keys: &['s'], key: "s", action: EditorAction::Save, help: "Save" },
+    KeyBinding { keys: &['z'], key: "z", action: EditorAction::Undo, help: "Undo" },
+    KeyBinding { keys: &['y'], key: "y", action: EditorAction::Redo, help: "Redo" },
+    KeyBinding { keys: &['m'], key: "m", action: EditorAction::Mark, help: "Mark selection" },
+    KeyBinding { keys: &['c'], key: "c", action: EditorAction::Copy, help: "Copy" },
+    KeyBinding { keys: &['x'], key: "x", action: EditorAction::Cut, help: "Cut" },
+    KeyBinding { keys: &['v'], key: "v", action: EditorAction::Paste, help: "Paste" },
+    KeyBinding { keys: &['Y'], key: "Y", action: EditorAction::YankLine, help: "Yank line" },
+    KeyBinding { keys: &['P'], key: "P", action: EditorAction::PutLine, help: "Put line below" },
+    KeyBinding { keys: &['k'], key: "k", action: EditorAction::DeleteLine, help: "Delete line" },
+    KeyBinding { keys: &['j'], key: "j", action: EditorAction::DuplicateLine, help: "Duplicate line" },
+    KeyBinding { keys: &['\u{0008}', '\u{007f}'], key: "Bksp", action: EditorAction::DeleteWordBack, help: "Delete word back" },
+    KeyBinding { keys: &['K'], key: "K", action: EditorAction::DeleteToLineEnd, help: "Delete to line end" },
+    KeyBinding { keys: &['u'], key: "u", action: EditorAction::Yank, help: "Yank last kill" },
+    KeyBinding { keys: &['U'], key: "U", action: EditorAction::YankPop, help: "Cycle older kills" },
+    KeyBinding { keys: &['e'], key: "e", action: EditorAction::ExportMenu, help: "Export menu" },
+    KeyBinding { keys: &['f'], key: "f", action: EditorAction::FileMenu, help: "File menu" },
+    KeyBinding { keys: &['/'], key: "/", action: EditorAction::Find, help: "Find in document" },
+    KeyBinding { keys: &['g'], key: "g", action: EditorAction::GotoLine, help: "Go to line" },
+    KeyBinding { keys: &['o'], key: "o", action: EditorAction::Outline, help: "Outline (headings)" },
+    KeyBinding { keys: &[' '], key: "Spc", action: EditorAction::ToggleCheckbox, help: "Toggle checkbox" },
+    KeyBinding { keys: &['w'], key: "w", action: EditorAction::Reflow, help: "Reflow long lines" },
+    KeyBinding { keys: &['r'], key: "r", action: EditorAction::Revert, help: "Revert to saved" },
+    KeyBinding { keys: &['q'], key: "q", action: EditorAction::Back, help: "Back to doc list" },
+];
+
+/// Esc commands on a journal day.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum JournalAction {
+    WordLeft,
+    WordRight,
+    PrevDay,
+    NextDay,
+    Today,
+    Search,
+    MonthStats,
+    Save,
+    Back,
+}
+
+pub const JOURNAL_BINDINGS: &[KeyBinding<JournalAction>] = &[
+    KeyBinding { keys: &['\u{F702}', '←'], key: "←", action: JournalAction::WordLeft, help: "Word left" },
+    KeyBinding { keys: &['\u{F703}', '→'], key: "→", action: JournalAction::WordRight, help: "Word right" },
+    KeyBinding { keys: &['['], key: "[", action: JournalAction::PrevDay, help: "Previous day" },
+    KeyBinding { keys: &[']'], key: "]", action: JournalAction::NextDay, help: "Next day" },
+    KeyBinding { keys: &['t'], key: "t", action: JournalAction::Today, help: "Today" },
+    KeyBinding { keys: &['/'], key: "/", action: JournalAction::Search, help: "Search" },
+    KeyBinding { keys: &['i'], key: "i", action: JournalAction::MonthStats, help: "Month stats" },
+    KeyBinding { keys: &['s'], key: "s", action: JournalAction::Save, help: "Save" },
+    KeyBinding { keys: &['q'], key: "q", action: JournalAction::Back, help: "Back" },
+];
+
+/// The command bound to `key` (pressed after Esc), if any.
+pub fn lookup<A: Copy>(bindings: &[KeyBinding<A>], key: char) -> Option<A> {
+    bindings.iter().find(|b| b.keys.contains(&key)).map(|b| b.action)
+}
+
+/// Help text: `intro` (title and the keys outside the table), then one
+/// "Esc+k  What it does" line per binding.
+pub fn help_text<A>(intro: &str, bindings: &[KeyBinding<A>]) -> String {
+    let mut text = String::from(intro);
+    for binding in bindings {
+        text.push_str(&format!("\nEsc+{:<2} {}", binding.key, binding.help));
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Every action, in declaration order. The matches below stop compiling
+    // when an action is added, as a reminder to list (and bind) it here.
+    const EDITOR_ACTIONS: [EditorAction; 27] = {
+        use EditorAction::*;
+        [
+            WordLeft, WordRight, Preview, Save, Undo, Redo, Mark, Copy, Cut, Paste, YankLine, PutLine,
+            DeleteLine, DuplicateLine, DeleteWordBack, DeleteToLineEnd, Yank, YankPop, ExportMenu,
+            FileMenu, Find, GotoLine, Outline, ToggleCheckbox, Reflow, Revert, Back,
+        ]
+    };
+
+    const JOURNAL_ACTIONS: [JournalAction; 9] = {
+        use JournalAction::*;
+        [WordLeft, WordRight, PrevDay, NextDay, Today, Search, MonthStats, Save, Back]
+    };
+
+    #[allow(dead_code)]
+    fn listed(editor: EditorAction, journal: JournalAction) {
+        use EditorAction::*;
+        match editor {
+            WordLeft | WordRight | Preview | Save | Undo | Redo | Mark | Copy | Cut | Paste | YankLine
+            | PutLine | DeleteLine | DuplicateLine | DeleteWordBack | DeleteToLineEnd | Yank | YankPop
+            | ExportMenu | FileMenu | Find | GotoLine | Outline | ToggleCheckbox | Reflow | Revert
+            | Back => {}
+        }
+        match journal {
+            JournalAction::WordLeft | JournalAction::WordRight | JournalAction::PrevDay
+            | JournalAction::NextDay | JournalAction::Today | JournalAction::Search
+            | JournalAction::MonthStats | JournalAction::Save | JournalAction::Back => {}
+        }
+    }
+
+    /// Each action is bound exactly once, each key reaches its own action,
+    /// no key is bound twice, and help lists every binding.
+    fn check<A: Copy + PartialEq + core::fmt::Debug>(bindings: &[KeyBinding<A>], actions: &[A]) {
+        for &action in actions {
+            let bound = bindings.iter().filter(|b| b.action == action).count();
+            assert_eq!(bound, 1, "{:?} bound {} times", action, bound);
+        }
+        assert_eq!(bindings.len(), actions.len());
+        let help = help_text("HELP", bindings);
+        for binding in bindings {
+            for &key in binding.keys {
+                assert_eq!(lookup(bindings, key), Some(binding.action), "key {:?}", key);
+            }
+            assert!(help.contains(&format!("Esc+{:<2} {}", binding.key, binding.help)));
+        }
+        assert_eq!(help.lines().count(), 1 + bindings.len());
+    }
+
+    #[test]
+    fn test_editor_help_matches_bindings() {
+        check(EDITOR_BINDINGS, &EDITOR_ACTIONS);
+        assert!(help_text("", EDITOR_BINDINGS).contains("Esc+p  Toggle Preview"));
+        assert!(help_text("", EDITOR_BINDINGS).contains("Esc+Bksp Delete word back"));
+        assert_eq!(lookup(EDITOR_BINDINGS, '!'), None);
+    }
+
+    #[test]
+    fn test_journal_help_matches_bindings() {
+        check(JOURNAL_BINDINGS, &JOURNAL_ACTIONS);
+        assert_eq!(lookup(JOURNAL_BINDINGS, '['), Some(JournalAction::PrevDay));
+    }
+}
@@ -1,21 +1,35 @@
 mod editor;
 mod export;
 mod journal;
+mod keys;
 mod render;
+mod settings;
 mod storage;
 mod typewriter;
 mod ui;
 
 use num_traits::ToPrimitive;
 use num_traits::FromPrimitive;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
 
 use crate::editor::EditorState;
-use crate::journal::JournalState;
-use crate::typewriter::TypewriterState;
-use crate::storage::WriterStorage;
-use crate::render::Renderer;
-use crate::export::ExportSystem;
+use crate::journal::{append_quick_note, JournalState};
+use crate::keys::{help_text, lookup, EditorAction, JournalAction, EDITOR_BINDINGS, JOURNAL_BINDINGS};
+use crate::typewriter::{TypewriterState, SPRINT_MINUTES};
+use crate::storage::{DocError, WriterStorage};
+use crate::render::{BufferView, DocListHeader, FindBar, RenderBackend, Renderer};
+use crate::export::{export_tcp_bytes, export_tcp_pages, ExportBackend, ExportError, ExportSystem, ExportTarget, SharedExport};
+use crate::settings::{change_setting, next_export_page_size, next_line_length_limit, tab_text, SETTINGS_LABELS, TEMPLATE_ROW};
+use crate::ui::{format_number_sep, status_message, visible_status, Clock};
 use writer_core::serialize::WriterConfig;
+use writer_core::docs::{folder_view, match_doc_name, sanitize_name, split_folder, DocEntry, DocSort, NameMatch, RenameError};
+use writer_core::journal::{month_start, JournalStats, DEFAULT_TEMPLATE};
+use writer_core::TextBuffer;
+use writer_core::backup::BACKUP_EXPORT_KEY;
+use writer_core::export::{autotype_lines, autotype_pages, export_pages, html_export_key, markdown_export_key, qr_chunks, resume_slice, ExportResume};
+use writer_core::stats::top_content_words;
+use writer_core::markdown::{extract_headings, to_html};
 
 const SERVER_NAME: &str = "_Writer_";
 const APP_NAME: &str = "Writer";
@@ -26,22 +40,55 @@ const KEY_F2: char = '\u{0012}';
 const KEY_F3: char = '\u{0013}';
 const KEY_F4: char = '\u{0014}';
 
+/// How often the pump thread posts AutosaveTick.
+const AUTOSAVE_TICK_MS: usize = 5000;
+
+/// How often a typewriter sprint's countdown is updated.
+const SPRINT_TICK_MS: usize = 1000;
+
+/// Largest QR export chunk in bytes, header included: small enough that
+/// each code keeps modules big enough for a phone to scan off the screen.
+const QR_CHUNK_BYTES: usize = 200;
+
+/// Rows on the backup screen, in display order.
+const BACKUP_ACTIONS: [&str; 3] = ["Send over TCP (port 7879)", "Save to writer.exports", "Restore from writer.exports"];
+
 #[derive(Clone, Copy, PartialEq, Debug)]
 pub enum AppMode {
     ModeSelect,
+    Settings,
+    TemplateEdit,
+    QuickNote,
+    Backup,
     DocList,
+    DocReorder,
+    GotoDoc,
     EditorEdit,
     EditorPreview,
+    EditorFind,
+    GotoLine,
+    Outline,
+    DocStats,
     FileMenu,
     ExportMenu,
+    ExportConfirm,
+    ExportWaiting,
+    ExportProgress,
+    UsbNotReady,
+    QrExport,
     RenameDoc,
     JournalDay,
     JournalNav,
     JournalSearch,
+    JournalStats,
+    SprintSelect,
     TypewriterEdit,
     TypewriterDone,
     HelpScreen,
     ConfirmExit,
+    ConfirmRevert,
+    ConfirmDeleteEntry,
+    StorageLocked,
 }
 
 #[derive(Debug, num_derive::FromPrimitive, num_derive::ToPrimitive)]
@@ -50,34 +97,103 @@ enum AppOp {
     Rawkeys,
     FocusChange,
     Quit,
+    AutosaveTick,
+    /// From an export thread: (0, bytes sent) or (1, ExportError), then
+    /// the byte offset reached
+    ExportDone,
+    /// From the USB export thread: byte offset reached, characters typed
+    ExportProgress,
+    /// Once a second while a typewriter sprint runs
+    SprintTick,
 }
 
 pub struct WriterApp {
     mode: AppMode,
     mode_cursor: usize,
+    settings_cursor: usize,
     allow_redraw: bool,
-    renderer: Renderer,
+    // Set by redraw(); the main loop draws once per message
+    needs_redraw: bool,
+    renderer: Box<dyn RenderBackend>,
     storage: WriterStorage,
-    export: ExportSystem,
+    export: SharedExport,
+    // Connection to our own server, for worker threads to report back
+    cid: xous::CID,
+    // Set by F4 to stop a running TCP or USB export
+    export_cancel: Arc<AtomicBool>,
+    // USB export progress: (characters typed, total characters)
+    export_progress: (usize, usize),
+    // (lines typed, total lines) when autotyping a line at a time
+    export_lines: Option<(usize, usize)>,
+    // Where a USB export waiting on the "connect USB" prompt starts
+    usb_retry_from: usize,
     config: WriterConfig,
+    tt: Box<dyn Clock>,
+    // Time of the last keypress, for idle autosave
+    last_key_ms: u64,
     editor: EditorState,
     journal: JournalState,
+    // Scratch copy of the journal template while it is being edited
+    template_buffer: TextBuffer,
     typewriter: TypewriterState,
+    // Sprint length picker shown before a typewriter session
+    sprint_cursor: usize,
+    // Cleared to stop the current sprint's tick thread
+    sprint_ticking: Arc<AtomicBool>,
     esc_pending: bool,
-    // Doc list state
-    doc_list: Vec<String>,
+    // Internal clipboard (yank/put, copy/paste)
+    clipboard: String,
+    // Doc list state: the stored index, and the view of it (one folder,
+    // filtered and sorted) that the list shows and doc_cursor points into
+    doc_index: Vec<String>,
+    // Word count cached in the index for each document (None = not yet known)
+    doc_words: Vec<(String, Option<u32>)>,
+    doc_list: Vec<DocEntry>,
+    doc_folder: String, // folder being shown ("" = top level)
     doc_cursor: usize,
+    doc_filter: String,
+    doc_filtering: bool,
+    doc_sort: DocSort,
+    // Go-to-document input state
+    goto_doc_input: String,
+    goto_doc_message: String,
+    goto_doc_confirm: bool,
+    // Go-to-line input (digits only)
+    goto_line_input: String,
+    // Outline state: (line index, level, title) per heading
+    outline: Vec<(usize, usize, String)>,
+    outline_cursor: usize,
     // File menu state
     file_menu_cursor: usize,
     // Export menu state
     export_menu_cursor: usize,
+    export_pending: Option<ExportTarget>,
+    // Outcome of the last on-device export, shown in the export menu
+    export_status: String,
+    // Byte offset an interrupted USB export of this document can resume from
+    export_resume_from: Option<usize>,
+    // Screen a TCP or USB export returns to when it finishes
+    export_origin: AppMode,
+    // QR export: the chunks, and which one is showing
+    qr_chunks: Vec<String>,
+    qr_page: usize,
+    backup_cursor: usize,
+    // Quick note input (appended to today's journal entry)
+    quick_note_input: String,
+    // Journal stats screen (from, to, stats)
+    journal_stats: Option<(String, String, JournalStats)>,
     // Rename input state
     rename_input: String,
+    rename_error: String,
     // F-key menu overlay state
     menu_visible: bool,
     menu_cursor: usize,
     // Mode before help/confirm (to return to)
     prev_mode: AppMode,
+    // Mode the storage locked screen opens once a retry mounts the PDDB
+    locked_target: AppMode,
+    // Transient message over the status bar: (text, expiry on the ticktimer clock)
+    status_msg: Option<(String, u64)>,
 }
 
 impl WriterApp {
@@ -102,9 +218,23 @@ impl WriterApp {
         let content = gam.request_content_canvas(token).expect("couldn't get canvas");
         let screensize = gam.get_canvas_bounds(content).expect("couldn't get dimensions");
 
-        let renderer = Renderer::new(gam, content, screensize);
-        let storage = WriterStorage::new();
-        let export = ExportSystem::new();
+        let modals = modals::Modals::new(xns).expect("can't connect to modals");
+        let renderer = Renderer::new(gam, modals, content, screensize);
+        let cid = xous::connect(sid).expect("can't connect to own server");
+        let tt = ticktimer_server::Ticktimer::new().expect("can't connect to ticktimer");
+        Self::with_backend(Box::new(renderer), WriterStorage::new(), Box::new(ExportSystem::new()), Box::new(tt), cid)
+    }
+
+    /// The app drawing through `renderer`, keeping documents in `storage`,
+    /// and with `cid` for worker threads to report back on. `new` passes
+    /// the GAM, PDDB, USB and ticktimer; tests pass doubles.
+    fn with_backend(
+        renderer: Box<dyn RenderBackend>,
+        storage: WriterStorage,
+        export: Box<dyn ExportBackend + Send>,
+        tt: Box<dyn Clock>,
+        cid: xous::CID,
+    ) -> Self {
 
         // Load saved config (or defaults)
         let config = storage.load_config();
@@ -114,33 +244,87 @@ impl WriterApp {
         // Set initial mode based on config.default_mode
         let initial_mode_cursor = config.default_mode as usize;
 
-        Self {
+        let mut app = Self {
             mode: AppMode::ModeSelect,
             mode_cursor: initial_mode_cursor.min(2), // Clamp to valid range (0-2)
+            settings_cursor: 0,
             allow_redraw: true,
+            needs_redraw: true,
             renderer,
             storage,
-            export,
+            export: Arc::new(Mutex::new(export)),
+            cid,
+            export_cancel: Arc::new(AtomicBool::new(false)),
+            export_progress: (0, 0),
+            export_lines: None,
+            usb_retry_from: 0,
             config,
+            tt,
+            last_key_ms: 0,
             editor: EditorState::new(),
             journal: JournalState::new(),
+            template_buffer: TextBuffer::new(),
             typewriter: TypewriterState::new(),
+            sprint_cursor: 0,
+            sprint_ticking: Arc::new(AtomicBool::new(false)),
             esc_pending: false,
+            clipboard: String::new(),
+            doc_index: Vec::new(),
+            doc_words: Vec::new(),
             doc_list: Vec::new(),
+            doc_folder: String::new(),
             doc_cursor: 0,
+            doc_filter: String::new(),
+            doc_filtering: false,
+            doc_sort: DocSort::Index,
+            goto_doc_input: String::new(),
+            goto_doc_message: String::new(),
+            goto_doc_confirm: false,
+            goto_line_input: String::new(),
+            outline: Vec::new(),
+            outline_cursor: 0,
             file_menu_cursor: 0,
             export_menu_cursor: 0,
+            export_origin: AppMode::ExportMenu,
+            qr_chunks: Vec::new(),
+            qr_page: 0,
+            backup_cursor: 0,
+            export_pending: None,
+            export_status: String::new(),
+            export_resume_from: None,
+            quick_note_input: String::new(),
+            journal_stats: None,
             rename_input: String::new(),
+            rename_error: String::new(),
             menu_visible: false,
             menu_cursor: 0,
             prev_mode: AppMode::ModeSelect,
-        }
+            locked_target: AppMode::ModeSelect,
+            status_msg: None,
+        };
+        app.apply_config();
+        app
     }
 
+    /// Ask for the screen to be drawn. Drawing happens once the current
+    /// message is handled (see `flush_redraw`), so the up to four keys of
+    /// one Rawkeys message cost one draw rather than four.
     pub fn redraw(&mut self) {
+        self.needs_redraw = true;
+    }
+
+    /// Draw the screen if anything asked for it since the last flush.
+    pub fn flush_redraw(&mut self) {
+        if std::mem::take(&mut self.needs_redraw) {
+            self.draw();
+        }
+    }
+
+    fn draw(&mut self) {
         if !self.allow_redraw {
             return;
         }
+        self.renderer.set_toast(visible_status(&self.status_msg, self.tt.elapsed_ms()));
 
         if self.menu_visible {
             self.renderer.draw_menu(self.menu_items(), self.menu_cursor);
@@ -149,42 +333,137 @@ impl WriterApp {
 
         match self.mode {
             AppMode::HelpScreen => {
-                self.renderer.draw_help(self.help_text());
+                self.renderer.draw_help(&self.help_text());
             }
             AppMode::ConfirmExit => {
                 self.renderer.draw_confirm_exit();
             }
+            AppMode::ConfirmRevert => {
+                self.renderer.draw_confirm_revert(&self.editor.doc_name);
+            }
+            AppMode::ConfirmDeleteEntry => {
+                self.renderer.draw_confirm_delete_entry(&self.journal.current_date);
+            }
+            AppMode::StorageLocked => {
+                self.renderer.draw_storage_locked();
+            }
             AppMode::ModeSelect => self.renderer.draw_mode_select(self.mode_cursor),
-            AppMode::DocList => self.renderer.draw_doc_list(&self.doc_list, self.doc_cursor),
+            AppMode::Settings => self.renderer.draw_settings(&self.config, self.settings_cursor),
+            AppMode::TemplateEdit => {
+                fit_viewport(&*self.renderer, BufferView::Template, &mut self.template_buffer);
+                self.renderer.draw_template_editor(&self.template_buffer);
+            }
+            AppMode::QuickNote => self.renderer.draw_quick_note(&self.quick_note_input),
+            AppMode::Backup => self.renderer.draw_backup(&BACKUP_ACTIONS, self.backup_cursor, &self.export_status),
+            AppMode::DocList => {
+                let header = DocListHeader {
+                    folder: &self.doc_folder,
+                    filter: &self.doc_filter,
+                    filtering: self.doc_filtering,
+                    sort: self.doc_sort,
+                };
+                self.renderer.draw_doc_list(&self.doc_list, &self.doc_words, self.doc_cursor, false, header);
+            }
+            AppMode::DocReorder => {
+                let header = DocListHeader { folder: &self.doc_folder, filter: "", filtering: false, sort: DocSort::Index };
+                self.renderer.draw_doc_list(&self.doc_list, &self.doc_words, self.doc_cursor, true, header);
+            }
+            AppMode::GotoDoc => {
+                self.renderer.draw_goto_doc(&self.goto_doc_input, &self.goto_doc_message, self.goto_doc_confirm);
+            }
             AppMode::EditorEdit => {
-                self.renderer.draw_editor(&self.editor.buffer, &self.editor.doc_name, false, self.config.show_line_numbers);
+                fit_viewport(&*self.renderer, BufferView::Editor { preview: false }, &mut self.editor.buffer);
+                self.scroll_editor_horizontally();
+                self.renderer.draw_editor(&self.editor.buffer, &self.editor.doc_name, false, self.config.show_line_numbers, self.config.max_line_length as usize, None);
             }
             AppMode::EditorPreview => {
-                self.renderer.draw_editor(&self.editor.buffer, &self.editor.doc_name, true, self.config.show_line_numbers);
+                fit_viewport(&*self.renderer, BufferView::Editor { preview: true }, &mut self.editor.buffer);
+                self.renderer.draw_editor(&self.editor.buffer, &self.editor.doc_name, true, self.config.show_line_numbers, self.config.max_line_length as usize, None);
+            }
+            AppMode::EditorFind => {
+                fit_viewport(&*self.renderer, BufferView::Editor { preview: false }, &mut self.editor.buffer);
+                self.scroll_editor_horizontally();
+                let find = FindBar {
+                    query: &self.editor.find_query,
+                    position: self.editor.find_position(),
+                    total: self.editor.find_matches.len(),
+                };
+                self.renderer.draw_editor(&self.editor.buffer, &self.editor.doc_name, false, self.config.show_line_numbers, self.config.max_line_length as usize, Some(find));
             }
             AppMode::FileMenu => {
                 self.renderer.draw_file_menu(self.file_menu_cursor);
             }
+            AppMode::DocStats => {
+                self.renderer.draw_doc_stats(&self.editor.buffer, &self.editor.doc_name);
+            }
+            AppMode::GotoLine => {
+                self.renderer.draw_goto_line(&self.goto_line_input, self.editor.buffer.line_count());
+            }
+            AppMode::Outline => {
+                self.renderer.draw_outline(&self.outline, self.outline_cursor);
+            }
             AppMode::RenameDoc => {
-                self.renderer.draw_rename_dialog(&self.rename_input, &self.editor.doc_name);
+                self.renderer.draw_rename_dialog(&self.rename_input, &self.editor.doc_name, &self.rename_error);
             }
             AppMode::ExportMenu => {
-                self.renderer.draw_export_menu(self.export_menu_cursor);
+                self.renderer.draw_export_menu(self.export_menu_cursor, self.config.export_page_size, &self.export_status);
+            }
+            AppMode::ExportConfirm => {
+                if let Some(target) = self.export_pending {
+                    let total = self.export_text().len();
+                    let resume = self.export_resume_from.map(|sent| (sent, total));
+                    self.renderer.draw_export_confirm(target, resume);
+                }
+            }
+            AppMode::ExportWaiting => {
+                let cancelling = self.export_cancel.load(Ordering::Relaxed);
+                self.renderer.draw_export_waiting(self.config.tcp_timeout_secs, cancelling);
+            }
+            AppMode::ExportProgress => {
+                let (typed, total) = self.export_progress;
+                let cancelling = self.export_cancel.load(Ordering::Relaxed);
+                self.renderer.draw_export_progress(typed, total, self.export_lines, cancelling);
+            }
+            AppMode::QrExport => {
+                if let Some(chunk) = self.qr_chunks.get(self.qr_page) {
+                    self.renderer.draw_qr_export(chunk, self.qr_page, self.qr_chunks.len());
+                }
+            }
+            AppMode::UsbNotReady => {
+                self.renderer.draw_usb_not_ready();
             }
             AppMode::JournalDay => {
-                self.renderer.draw_journal(&self.journal.buffer, &self.journal.current_date);
+                fit_viewport(&*self.renderer, BufferView::Journal, &mut self.journal.buffer);
+                self.renderer.draw_journal(&self.journal.buffer, &self.journal.current_date, self.config.journal_date_format, self.journal.streak);
             }
             AppMode::JournalSearch => {
                 self.renderer.draw_journal_search(&self.journal.search_query, &self.journal.search_results, self.journal.search_cursor);
             }
+            AppMode::JournalStats => {
+                if let Some((from, to, stats)) = &self.journal_stats {
+                    self.renderer.draw_journal_stats(from, to, stats);
+                }
+            }
+            AppMode::SprintSelect => self.renderer.draw_sprint_select(&SPRINT_MINUTES, self.sprint_cursor),
             AppMode::TypewriterEdit => {
-                self.renderer.draw_typewriter(&self.typewriter.buffer);
+                fit_viewport(&*self.renderer, BufferView::Typewriter, &mut self.typewriter.buffer);
+                let now = self.tt.elapsed_ms();
+                self.renderer.draw_typewriter(
+                    &self.typewriter.buffer,
+                    self.typewriter.elapsed_secs(now),
+                    self.typewriter.rolling_wpm(now),
+                    self.typewriter.remaining_secs(now),
+                );
             }
             AppMode::TypewriterDone => {
+                // The clock stopped at finish, so any time reads the same
+                let now = self.tt.elapsed_ms();
                 self.renderer.draw_typewriter_done(
-                    self.typewriter.buffer.word_count(),
-                    self.typewriter.buffer.char_count(),
-                    self.typewriter.buffer.line_count(),
+                    &self.typewriter.buffer,
+                    &top_content_words(&self.typewriter.buffer.to_string(), 3, None),
+                    self.typewriter.elapsed_secs(now),
+                    self.typewriter.average_wpm(now),
+                    self.typewriter.sprint_secs,
                 );
             }
             _ => {}
@@ -192,6 +471,8 @@ impl WriterApp {
     }
 
     pub fn handle_key(&mut self, key: char) {
+        self.last_key_ms = self.tt.elapsed_ms();
+
         // F-keys always processed first (clear any pending ESC)
         match key {
             KEY_F1 => { self.esc_pending = false; self.toggle_menu(); return; }
@@ -252,6 +533,39 @@ impl WriterApp {
             return;
         }
 
+        // Confirm revert dialog
+        if self.mode == AppMode::ConfirmRevert {
+            match key {
+                'y' => {
+                    self.revert_current_doc();
+                }
+                'n' => {
+                    self.mode = self.prev_mode;
+                    self.redraw();
+                }
+                _ => {}
+            }
+            return;
+        }
+
+        // Confirm journal entry deletion
+        if self.mode == AppMode::ConfirmDeleteEntry {
+            match key {
+                'y' => {
+                    self.journal.delete_entry(&self.storage);
+                    self.notify(&format!("Deleted entry for {}", self.journal.current_date));
+                    self.mode = AppMode::JournalDay;
+                    self.redraw();
+                }
+                'n' => {
+                    self.mode = AppMode::JournalDay;
+                    self.redraw();
+                }
+                _ => {}
+            }
+            return;
+        }
+
         // Handle escape sequences
         if self.esc_pending {
             self.esc_pending = false;
@@ -267,16 +581,46 @@ impl WriterApp {
 
         match self.mode {
             AppMode::ModeSelect => self.handle_key_mode_select(key),
+            AppMode::Settings => self.handle_key_settings(key),
+            AppMode::TemplateEdit => self.handle_key_template_edit(key),
+            AppMode::Backup => self.handle_key_backup(key),
+            AppMode::QuickNote => self.handle_key_quick_note(key),
             AppMode::DocList => self.handle_key_doc_list(key),
+            AppMode::DocReorder => self.handle_key_doc_reorder(key),
+            AppMode::GotoDoc => self.handle_key_goto_doc(key),
             AppMode::EditorEdit => self.handle_key_editor(key),
             AppMode::EditorPreview => self.handle_key_preview(key),
+            AppMode::EditorFind => self.handle_key_editor_find(key),
+            AppMode::GotoLine => self.handle_key_goto_line(key),
+            AppMode::Outline => self.handle_key_outline(key),
             AppMode::FileMenu => self.handle_key_file_menu(key),
             AppMode::RenameDoc => self.handle_key_rename(key),
             AppMode::ExportMenu => self.handle_key_export_menu(key),
+            AppMode::ExportConfirm => self.handle_key_export_confirm(key),
+            AppMode::ExportWaiting | AppMode::ExportProgress => {} // Only F4 (cancel) does anything
+            AppMode::QrExport => self.handle_key_qr_export(key),
+            AppMode::UsbNotReady => {
+                if key == '\r' || key == '\n' {
+                    self.run_export(ExportTarget::UsbAutotype, self.usb_retry_from);
+                    self.redraw();
+                }
+            }
             AppMode::JournalDay => self.handle_key_journal(key),
             AppMode::JournalSearch => self.handle_key_journal_search(key),
+            AppMode::DocStats => {
+                // Any key returns to the editor
+                self.mode = AppMode::EditorEdit;
+                self.redraw();
+            }
+            AppMode::JournalStats => {
+                // Any key returns to the journal
+                self.mode = AppMode::JournalDay;
+                self.redraw();
+            }
+            AppMode::SprintSelect => self.handle_key_sprint_select(key),
             AppMode::TypewriterEdit => self.handle_key_typewriter(key),
             AppMode::TypewriterDone => self.handle_key_typewriter_done(key),
+            AppMode::StorageLocked => self.handle_key_storage_locked(key),
             _ => {}
         }
     }
@@ -284,27 +628,35 @@ impl WriterApp {
     fn menu_items(&self) -> &'static [&'static str] {
         match self.mode {
             AppMode::EditorEdit | AppMode::EditorPreview => {
-                &["Help", "Save", "Export", "File Menu", "Toggle Preview"]
+                &["Help", "Save", "Export", "File Menu", "Toggle Preview", "Line Numbers", "Document Stats"]
             }
             AppMode::JournalDay => {
-                &["Help", "Prev Day", "Next Day", "Today", "Search"]
+                &["Help", "Prev Day", "Next Day", "Today", "Latest Entry", "Search", "Delete Entry"]
             }
             AppMode::TypewriterEdit => {
                 &["Help", "Done (summary)"]
             }
             AppMode::DocList => &["Help", "New Document", "Back"],
-            AppMode::ModeSelect => &["Help"],
+            AppMode::ModeSelect => &["Help", "Settings", "Quick Note", "Backup"],
+            AppMode::Backup => &["Help", "Back"],
+            AppMode::Settings => &["Help", "Back"],
+            AppMode::TemplateEdit => &["Help", "Save & Back"],
+            AppMode::QuickNote => &["Help", "Cancel"],
             AppMode::TypewriterDone => &["Help", "Save as Doc", "Discard"],
             AppMode::FileMenu => &["Help", "Back to Editor"],
             AppMode::RenameDoc => &["Help", "Cancel"],
+            AppMode::EditorFind | AppMode::GotoLine | AppMode::Outline | AppMode::DocStats => &["Help", "Back to Editor"],
+            AppMode::GotoDoc => &["Help", "Cancel"],
+            AppMode::DocReorder => &["Help", "Done"],
             AppMode::ExportMenu => &["Help", "Back to Editor"],
-            AppMode::JournalSearch => &["Help", "Back to Journal"],
+            AppMode::QrExport => &["Help", "Back to Export"],
+            AppMode::JournalSearch | AppMode::JournalStats => &["Help", "Back to Journal"],
             _ => &["Help"],
         }
     }
 
     fn toggle_menu(&mut self) {
-        if self.mode == AppMode::HelpScreen || self.mode == AppMode::ConfirmExit {
+        if self.is_dialog_mode() {
             return;
         }
         self.menu_visible = !self.menu_visible;
@@ -312,6 +664,11 @@ impl WriterApp {
         self.redraw();
     }
 
+    /// Help and confirmation screens swallow F1/F2/F3.
+    fn is_dialog_mode(&self) -> bool {
+        matches!(self.mode, AppMode::HelpScreen | AppMode::ConfirmExit | AppMode::ConfirmRevert | AppMode::ConfirmDeleteEntry | AppMode::ExportWaiting | AppMode::ExportProgress | AppMode::UsbNotReady)
+    }
+
     fn menu_select_item(&mut self) {
         self.menu_visible = false;
 
@@ -322,9 +679,14 @@ impl WriterApp {
                         self.prev_mode = self.mode;
                         self.mode = AppMode::HelpScreen;
                     }
-                    1 => { self.save_current_doc(); }
+                    1 => {
+                        if self.save_or_ask_name() {
+                            self.notify(&format!("Saved {}", self.editor.doc_name));
+                        }
+                    }
                     2 => {
                         self.export_menu_cursor = 0;
+                        self.export_status.clear();
                         self.mode = AppMode::ExportMenu;
                     }
                     3 => {
@@ -338,6 +700,11 @@ impl WriterApp {
                             AppMode::EditorEdit
                         };
                     }
+                    5 => {
+                        self.config.show_line_numbers = !self.config.show_line_numbers;
+                        self.storage.save_config(&self.config);
+                    }
+                    6 => { self.mode = AppMode::DocStats; }
                     _ => {}
                 }
             }
@@ -361,10 +728,15 @@ impl WriterApp {
                         self.journal.load_entry(&self.storage);
                     }
                     4 => {
+                        self.journal.save_entry(&self.storage);
+                        self.journal.jump_to_latest(&self.storage);
+                    }
+                    5 => {
                         self.journal.search_query.clear();
                         self.journal.search_results.clear();
                         self.mode = AppMode::JournalSearch;
                     }
+                    6 => { self.mode = AppMode::ConfirmDeleteEntry; }
                     _ => {}
                 }
             }
@@ -374,7 +746,7 @@ impl WriterApp {
                         self.prev_mode = self.mode;
                         self.mode = AppMode::HelpScreen;
                     }
-                    1 => { self.mode = AppMode::TypewriterDone; }
+                    1 => self.finish_typewriter(),
                     _ => {}
                 }
             }
@@ -425,7 +797,17 @@ impl WriterApp {
                     _ => {}
                 }
             }
-            AppMode::ExportMenu => {
+            AppMode::GotoDoc | AppMode::DocReorder => {
+                match self.menu_cursor {
+                    0 => {
+                        self.prev_mode = self.mode;
+                        self.mode = AppMode::HelpScreen;
+                    }
+                    1 => { self.mode = AppMode::DocList; } // Cancel
+                    _ => {}
+                }
+            }
+            AppMode::ExportMenu | AppMode::EditorFind | AppMode::GotoLine | AppMode::Outline | AppMode::DocStats => {
                 match self.menu_cursor {
                     0 => {
                         self.prev_mode = self.mode;
@@ -435,7 +817,17 @@ impl WriterApp {
                     _ => {}
                 }
             }
-            AppMode::JournalSearch => {
+            AppMode::QrExport => {
+                match self.menu_cursor {
+                    0 => {
+                        self.prev_mode = self.mode;
+                        self.mode = AppMode::HelpScreen;
+                    }
+                    1 => { self.mode = AppMode::ExportMenu; }
+                    _ => {}
+                }
+            }
+            AppMode::JournalSearch | AppMode::JournalStats => {
                 match self.menu_cursor {
                     0 => {
                         self.prev_mode = self.mode;
@@ -445,6 +837,41 @@ impl WriterApp {
                     _ => {}
                 }
             }
+            AppMode::ModeSelect => {
+                match self.menu_cursor {
+                    0 => {
+                        self.prev_mode = self.mode;
+                        self.mode = AppMode::HelpScreen;
+                    }
+                    1 => {
+                        self.settings_cursor = 0;
+                        self.mode = AppMode::Settings;
+                    }
+                    2 => self.start_quick_note(),
+                    3 => self.open_backup(),
+                    _ => {}
+                }
+            }
+            AppMode::Settings | AppMode::QuickNote | AppMode::Backup => {
+                match self.menu_cursor {
+                    0 => {
+                        self.prev_mode = self.mode;
+                        self.mode = AppMode::HelpScreen;
+                    }
+                    1 => { self.mode = AppMode::ModeSelect; }
+                    _ => {}
+                }
+            }
+            AppMode::TemplateEdit => {
+                match self.menu_cursor {
+                    0 => {
+                        self.prev_mode = self.mode;
+                        self.mode = AppMode::HelpScreen;
+                    }
+                    1 => self.save_template(),
+                    _ => {}
+                }
+            }
             _ => {
                 // Help is always item 0
                 if self.menu_cursor == 0 {
@@ -458,7 +885,7 @@ impl WriterApp {
 
     fn handle_f2(&mut self) {
         if self.menu_visible { self.menu_visible = false; }
-        if self.mode == AppMode::HelpScreen || self.mode == AppMode::ConfirmExit { return; }
+        if self.is_dialog_mode() { return; }
         // F2 = Toggle Preview (in editor modes)
         match self.mode {
             AppMode::EditorEdit => { self.mode = AppMode::EditorPreview; }
@@ -470,14 +897,17 @@ impl WriterApp {
 
     fn handle_f3(&mut self) {
         if self.menu_visible { self.menu_visible = false; }
-        if self.mode == AppMode::HelpScreen || self.mode == AppMode::ConfirmExit { return; }
+        if self.is_dialog_mode() { return; }
         // F3 = Save
         match self.mode {
             AppMode::EditorEdit | AppMode::EditorPreview => {
-                self.save_current_doc();
+                if self.save_or_ask_name() {
+                    self.notify(&format!("Saved {}", self.editor.doc_name));
+                }
             }
             AppMode::JournalDay => {
                 self.journal.save_entry(&self.storage);
+                self.notify(&format!("Saved entry for {}", self.journal.current_date));
             }
             _ => {}
         }
@@ -497,12 +927,29 @@ impl WriterApp {
             self.redraw();
             return;
         }
-        // F4 cancels confirm exit
-        if self.mode == AppMode::ConfirmExit {
+        // F4 cancels confirm exit / revert
+        if self.mode == AppMode::ConfirmExit || self.mode == AppMode::ConfirmRevert {
             self.mode = self.prev_mode;
             self.redraw();
             return;
         }
+        if self.mode == AppMode::ConfirmDeleteEntry {
+            self.mode = AppMode::JournalDay;
+            self.redraw();
+            return;
+        }
+        if self.mode == AppMode::UsbNotReady || self.mode == AppMode::QrExport {
+            self.mode = AppMode::ExportMenu;
+            self.redraw();
+            return;
+        }
+        // F4 stops a running export; the export thread notices within a
+        // poll or chunk and reports back with ExportDone
+        if self.mode == AppMode::ExportWaiting || self.mode == AppMode::ExportProgress {
+            self.export_cancel.store(true, Ordering::Relaxed);
+            self.redraw();
+            return;
+        }
         // F4 = Back/Exit with unsaved changes confirmation
         match self.mode {
             AppMode::EditorEdit | AppMode::EditorPreview => {
@@ -520,48 +967,93 @@ impl WriterApp {
                 self.mode = AppMode::ModeSelect;
                 self.redraw();
             }
-            AppMode::FileMenu | AppMode::RenameDoc | AppMode::ExportMenu => {
+            AppMode::GotoDoc | AppMode::DocReorder => {
+                self.mode = AppMode::DocList;
+                self.redraw();
+            }
+            AppMode::FileMenu | AppMode::RenameDoc | AppMode::ExportMenu | AppMode::EditorFind | AppMode::GotoLine | AppMode::Outline | AppMode::DocStats => {
                 self.mode = AppMode::EditorEdit;
                 self.redraw();
             }
+            AppMode::ExportConfirm => {
+                self.export_pending = None;
+                self.export_resume_from = None;
+                self.mode = AppMode::ExportMenu;
+                self.redraw();
+            }
             AppMode::JournalDay => {
                 self.journal.save_entry(&self.storage);
                 self.mode = AppMode::ModeSelect;
                 self.redraw();
             }
-            AppMode::JournalSearch => {
+            AppMode::JournalSearch | AppMode::JournalStats => {
                 self.mode = AppMode::JournalDay;
                 self.redraw();
             }
             AppMode::TypewriterEdit => {
-                self.mode = AppMode::TypewriterDone;
+                self.finish_typewriter();
                 self.redraw();
             }
-            AppMode::TypewriterDone => {
+            AppMode::TypewriterDone | AppMode::SprintSelect | AppMode::StorageLocked | AppMode::Settings | AppMode::QuickNote | AppMode::Backup => {
                 self.mode = AppMode::ModeSelect;
                 self.redraw();
             }
-            AppMode::ModeSelect => {
-                // Top level - quit
+            AppMode::TemplateEdit => {
+                self.save_template();
+                self.redraw();
             }
+            AppMode::ModeSelect => self.quit(),
             _ => {}
         }
     }
 
-    fn help_text(&self) -> &'static str {
+    fn help_text(&self) -> String {
         match self.prev_mode {
-            AppMode::EditorEdit | AppMode::EditorPreview => {
+            AppMode::EditorEdit | AppMode::EditorPreview => help_text(
                 "EDITOR HELP\n\n\
                  F1     Menu\n\
                  F2     Toggle Preview\n\
                  F3     Save\n\
                  F4     Back to doc list\n\n\
-                 Arrows Move cursor\n\
-                 Esc+p  Toggle Preview\n\
-                 Esc+s  Save\n\
-                 Esc+e  Export menu\n\
-                 Esc+f  File menu\n\
-                 Esc+q  Back to doc list"
+                 Arrows Move cursor",
+                EDITOR_BINDINGS,
+            ),
+            AppMode::JournalDay => help_text(
+                "JOURNAL HELP\n\n\
+                 F1     Menu\n\
+                 F3     Save\n\
+                 F4     Back\n\
+                 Home/End  Line start/end\n\
+                 Del    Delete forward\n",
+                JOURNAL_BINDINGS,
+            ),
+            AppMode::TypewriterEdit => format!(
+                "TYPEWRITER HELP\n\n\
+                 F1     Menu\n\
+                 F4     Done (summary)\n\n\
+                 Type freely!\n\
+                 {}\n\
+                 A sprint ends by itself\n\
+                 when its timer runs out.\n\
+                 No cursor movement.\n\n\
+                 Esc+d  Done (summary)",
+                if self.config.typewriter_allow_backspace { "Backspace: this line only." } else { "No backspace." },
+            ),
+            mode => String::from(Self::fixed_help_text(mode)),
+        }
+    }
+
+    /// Help for screens without an Esc binding table.
+    fn fixed_help_text(mode: AppMode) -> &'static str {
+        match mode {
+            AppMode::DocReorder => {
+                "REORDER HELP\n\n\
+                 F1     Menu\n\
+                 F4     Done\n\n\
+                 Up/Dn  Move document\n\
+                 Enter  Done\n\n\
+                 The order is saved\n\
+                 as you go."
             }
             AppMode::DocList => {
                 "DOCUMENTS HELP\n\n\
@@ -569,44 +1061,73 @@ impl WriterApp {
                  F4     Back\n\n\
                  Enter  Open document\n\
                  n      New document\n\
+                 g      Go to document by name\n\
+                 /      Filter by name\n\
+                 s      Sort: custom/A-Z/Z-A\n\
+                 o      Reorder documents\n\
                  d      Delete document\n\
                  q      Back"
             }
-            AppMode::JournalDay => {
-                "JOURNAL HELP\n\n\
-                 F1     Menu\n\
-                 F3     Save\n\
-                 F4     Back\n\n\
-                 Esc+[  Previous day\n\
-                 Esc+]  Next day\n\
-                 Esc+t  Today\n\
-                 Esc+/  Search\n\
-                 Esc+s  Save\n\
-                 Esc+q  Back"
-            }
-            AppMode::TypewriterEdit => {
-                "TYPEWRITER HELP\n\n\
-                 F1     Menu\n\
-                 F4     Done (summary)\n\n\
-                 Type freely!\n\
-                 No backspace.\n\
-                 No cursor movement.\n\n\
-                 Esc+d  Done (summary)"
-            }
             AppMode::ModeSelect => {
                 "WRITER HELP\n\n\
                  F1     Menu\n\
                  F4     Quit\n\n\
                  Up/Dn  Move cursor\n\
                  Enter  Open mode\n\
+                 s      Settings\n\
+                 n      Quick journal note\n\
                  q      Quit\n\n\
                  -- Settings (any mode) --\n\
                  Esc+A  Toggle autosave\n\
                  Esc+L  Toggle line numbers\n\
+                 Esc+W  Max line length\n\
                  Esc+0  Default: Editor\n\
                  Esc+1  Default: Journal\n\
                  Esc+2  Default: Typewriter"
             }
+            AppMode::Settings => {
+                "SETTINGS HELP\n\n\
+                 F1     Menu\n\
+                 F4     Back\n\n\
+                 Up/Dn  Move cursor\n\
+                 Enter  Change setting\n\
+                 q      Back\n\n\
+                 Changes are saved\n\
+                 immediately."
+            }
+            AppMode::TemplateEdit => {
+                "JOURNAL TEMPLATE HELP\n\n\
+                 F1     Menu\n\
+                 F4     Save & back\n\n\
+                 Days without an entry\n\
+                 start from this text.\n\
+                 {date}     the date\n\
+                 {weekday}  the day's name\n\n\
+                 Clear it to turn the\n\
+                 template off."
+            }
+            AppMode::Backup => {
+                "BACKUP HELP\n\n\
+                 F1     Menu\n\
+                 F4     Back\n\n\
+                 Up/Dn  Move cursor\n\
+                 Enter  Run action\n\n\
+                 A backup holds every\n\
+                 document. Restoring\n\
+                 skips documents you\n\
+                 already have, and keeps\n\
+                 changed ones as copies\n\
+                 named \"... restored\"."
+            }
+            AppMode::QuickNote => {
+                "QUICK NOTE HELP\n\n\
+                 F1     Menu\n\
+                 F4     Cancel\n\n\
+                 Enter  Add note\n\n\
+                 The note is added to\n\
+                 today's journal entry\n\
+                 as \"- HH:MM note\"."
+            }
             AppMode::TypewriterDone => {
                 "SESSION DONE HELP\n\n\
                  F1     Menu\n\
@@ -632,6 +1153,15 @@ impl WriterApp {
                  Enter  Select action\n\
                  q      Back to editor"
             }
+            AppMode::GotoDoc => {
+                "GO TO DOCUMENT HELP\n\n\
+                 F1     Menu\n\
+                 F4     Cancel\n\n\
+                 Type   Name or prefix\n\
+                 Enter  Open match\n\
+                 Bksp   Delete char\n\n\
+                 No match offers to create it."
+            }
             AppMode::RenameDoc => {
                 "RENAME DOC HELP\n\n\
                  F1     Menu\n\
@@ -640,14 +1170,59 @@ impl WriterApp {
                  Enter  Confirm rename\n\
                  Bksp   Delete char"
             }
+            AppMode::EditorFind => {
+                "FIND HELP\n\n\
+                 F1     Menu\n\
+                 F4     Back to editor\n\n\
+                 Type   Search text\n\
+                 Enter  Next match\n\
+                 Bksp   Delete char\n\n\
+                 Case-insensitive."
+            }
+            AppMode::Outline => {
+                "OUTLINE HELP\n\n\
+                 F1     Menu\n\
+                 F4     Back to editor\n\n\
+                 Up/Dn  Move cursor\n\
+                 Enter  Jump to heading\n\n\
+                 Lists # headings,\n\
+                 indented by level."
+            }
+            AppMode::GotoLine => {
+                "GO TO LINE HELP\n\n\
+                 F1     Menu\n\
+                 F4     Back to editor\n\n\
+                 0-9    Line number\n\
+                 Enter  Jump\n\
+                 Bksp   Delete digit\n\n\
+                 Numbers past the end\n\
+                 go to the last line."
+            }
             AppMode::ExportMenu => {
                 "EXPORT MENU HELP\n\n\
                  F1     Menu\n\
                  F4     Back to editor\n\n\
                  Up/Dn  Move cursor\n\
                  Enter  Export\n\
+                 .md/.html Save to\n\
+                        writer.exports\n\
+                 Console Log to serial\n\
+                 QR     Codes to scan\n\
+                 Pages  Enter cycles size\n\
                  q      Back to editor"
             }
+            AppMode::QrExport => {
+                "QR EXPORT HELP\n\n\
+                 F1     Menu\n\
+                 F4     Back to export\n\n\
+                 Lt/Rt  Previous/next\n\
+                 Enter  Show QR code\n\
+                 q      Back to export\n\n\
+                 Each code holds\n\
+                 \"n/total base64\";\n\
+                 join them in order\n\
+                 and decode."
+            }
             _ => {
                 "HELP\n\n\
                  F1     Menu\n\
@@ -676,6 +1251,14 @@ impl WriterApp {
                 self.redraw();
                 return;
             }
+            'W' => {
+                // Cycle max line length warning (Shift+W)
+                self.config.max_line_length = next_line_length_limit(self.config.max_line_length);
+                log::info!("Max line length: {}", self.config.max_line_length);
+                self.storage.save_config(&self.config);
+                self.redraw();
+                return;
+            }
             '0' => {
                 // Set default mode to Editor
                 self.config.default_mode = 0;
@@ -703,31 +1286,8 @@ impl WriterApp {
         // Mode-specific commands
         match self.mode {
             AppMode::EditorEdit => {
-                match key {
-                    'p' => {
-                        self.mode = AppMode::EditorPreview;
-                        self.redraw();
-                    }
-                    's' => {
-                        self.save_current_doc();
-                    }
-                    'e' => {
-                        self.export_menu_cursor = 0;
-                        self.mode = AppMode::ExportMenu;
-                        self.redraw();
-                    }
-                    'f' => {
-                        self.file_menu_cursor = 0;
-                        self.mode = AppMode::FileMenu;
-                        self.redraw();
-                    }
-                    'q' => {
-                        self.save_current_doc();
-                        self.refresh_doc_list();
-                        self.mode = AppMode::DocList;
-                        self.redraw();
-                    }
-                    _ => {}
+                if let Some(action) = lookup(EDITOR_BINDINGS, key) {
+                    self.run_editor_action(action);
                 }
             }
             AppMode::EditorPreview => {
@@ -746,45 +1306,14 @@ impl WriterApp {
                 }
             }
             AppMode::JournalDay => {
-                match key {
-                    '[' => {
-                        self.journal.save_entry(&self.storage);
-                        self.journal.prev_day(&self.storage);
-                        self.redraw();
-                    }
-                    ']' => {
-                        self.journal.save_entry(&self.storage);
-                        self.journal.next_day(&self.storage);
-                        self.redraw();
-                    }
-                    't' => {
-                        self.journal.save_entry(&self.storage);
-                        self.journal.jump_to_today();
-                        self.journal.load_entry(&self.storage);
-                        self.redraw();
-                    }
-                    '/' => {
-                        self.journal.search_query.clear();
-                        self.journal.search_results.clear();
-                        self.mode = AppMode::JournalSearch;
-                        self.redraw();
-                    }
-                    's' => {
-                        self.journal.save_entry(&self.storage);
-                        self.redraw();
-                    }
-                    'q' => {
-                        self.journal.save_entry(&self.storage);
-                        self.mode = AppMode::ModeSelect;
-                        self.redraw();
-                    }
-                    _ => {}
+                if let Some(action) = lookup(JOURNAL_BINDINGS, key) {
+                    self.run_journal_action(action);
                 }
             }
             AppMode::TypewriterEdit => {
                 match key {
                     'd' => {
-                        self.mode = AppMode::TypewriterDone;
+                        self.finish_typewriter();
                         self.redraw();
                     }
                     _ => {}
@@ -794,47 +1323,434 @@ impl WriterApp {
         }
     }
 
-    fn handle_key_mode_select(&mut self, key: char) {
-        match key {
-            '\u{F700}' | '↑' => {
-                if self.mode_cursor > 0 {
-                    self.mode_cursor -= 1;
-                    self.redraw();
-                }
+    /// Run an editor Esc command (see `EDITOR_BINDINGS`).
+    fn run_editor_action(&mut self, action: EditorAction) {
+        match action {
+            EditorAction::WordLeft => {
+                self.editor.buffer.move_word_left();
+                self.redraw();
             }
-            '\u{F701}' | '↓' => {
-                if self.mode_cursor < 2 {
-                    self.mode_cursor += 1;
-                    self.redraw();
+            EditorAction::WordRight => {
+                self.editor.buffer.move_word_right();
+                self.redraw();
+            }
+            EditorAction::Preview => {
+                self.mode = AppMode::EditorPreview;
+                self.redraw();
+            }
+            EditorAction::Save => {
+                self.save_or_ask_name();
+            }
+            EditorAction::ExportMenu => {
+                self.export_menu_cursor = 0;
+                self.export_status.clear();
+                self.mode = AppMode::ExportMenu;
+                self.redraw();
+            }
+            EditorAction::FileMenu => {
+                self.file_menu_cursor = 0;
+                self.mode = AppMode::FileMenu;
+                self.redraw();
+            }
+            EditorAction::ToggleCheckbox => {
+                let line = self.editor.buffer.cursor.line;
+                if self.editor.buffer.toggle_checkbox_at(line) {
+                    self.redraw();
+                }
+            }
+            EditorAction::GotoLine => {
+                self.goto_line_input.clear();
+                self.mode = AppMode::GotoLine;
+                self.redraw();
+            }
+            EditorAction::Outline => {
+                self.open_outline();
+            }
+            EditorAction::Find => {
+                // Find in document (keeps the previous query)
+                self.editor.update_find();
+                self.mode = AppMode::EditorFind;
+                self.redraw();
+            }
+            EditorAction::Undo => {
+                if self.editor.buffer.undo() {
+                    self.redraw();
+                }
+            }
+            EditorAction::Redo => {
+                if self.editor.buffer.redo() {
+                    self.redraw();
+                }
+            }
+            EditorAction::Mark => {
+                // Toggle selection mark at the cursor
+                if self.editor.buffer.selection_anchor.is_some() {
+                    self.editor.buffer.clear_selection();
+                } else {
+                    self.editor.buffer.start_selection();
+                }
+                self.redraw();
+            }
+            EditorAction::Copy => {
+                if let Some(text) = self.editor.buffer.selected_text() {
+                    self.clipboard = text;
+                    self.editor.buffer.clear_selection();
+                    self.redraw();
+                }
+            }
+            EditorAction::Cut => {
+                if let Some(text) = self.editor.buffer.cut_selection() {
+                    self.clipboard = text;
+                    self.redraw();
+                }
+            }
+            EditorAction::Paste => {
+                if !self.clipboard.is_empty() {
+                    self.editor.buffer.paste(&self.clipboard);
+                    self.redraw();
+                }
+            }
+            EditorAction::DeleteLine => {
+                // Kill line (into the clipboard, so Esc+P puts it back)
+                self.clipboard = self.editor.buffer.delete_line();
+                self.redraw();
+            }
+            EditorAction::DuplicateLine => {
+                self.editor.buffer.duplicate_line();
+                self.redraw();
+            }
+            EditorAction::DeleteWordBack => {
+                if self.editor.buffer.delete_word_back() {
+                    self.redraw();
+                }
+            }
+            EditorAction::DeleteToLineEnd => {
+                if self.editor.buffer.delete_to_line_end() {
+                    self.redraw();
+                }
+            }
+            EditorAction::Yank => {
+                // Yank the most recent kill
+                if self.editor.buffer.yank() {
+                    self.redraw();
+                }
+            }
+            EditorAction::YankPop => {
+                // Straight after Esc+u: swap in the previous kill
+                if self.editor.buffer.yank_pop() {
+                    self.redraw();
+                }
+            }
+            EditorAction::YankLine => {
+                // Yank current line
+                self.clipboard = self.editor.buffer.yank_line();
+            }
+            EditorAction::PutLine => {
+                // Put clipboard line(s) below the current line
+                if !self.clipboard.is_empty() {
+                    self.editor.buffer.put_line_below(&self.clipboard);
+                    self.redraw();
+                }
+            }
+            EditorAction::Reflow => {
+                // Reflow lines over the configured max length
+                let max = self.config.max_line_length as usize;
+                if self.editor.buffer.reflow_long_lines(max) {
+                    self.redraw();
+                }
+            }
+            EditorAction::Revert => {
+                // Revert to last saved version (destructive, so confirm)
+                if self.editor.buffer.modified {
+                    self.prev_mode = self.mode;
+                    self.mode = AppMode::ConfirmRevert;
+                    self.redraw();
+                }
+            }
+            EditorAction::Back => {
+                self.save_current_doc();
+                self.refresh_doc_list();
+                self.mode = AppMode::DocList;
+                self.redraw();
+            }
+        }
+    }
+
+    /// Run a journal Esc command (see `JOURNAL_BINDINGS`).
+    fn run_journal_action(&mut self, action: JournalAction) {
+        match action {
+            JournalAction::WordLeft => {
+                self.journal.buffer.move_word_left();
+                self.redraw();
+            }
+            JournalAction::WordRight => {
+                self.journal.buffer.move_word_right();
+                self.redraw();
+            }
+            JournalAction::PrevDay => {
+                self.journal.save_entry(&self.storage);
+                self.journal.prev_day(&self.storage);
+                self.redraw();
+            }
+            JournalAction::NextDay => {
+                self.journal.save_entry(&self.storage);
+                self.journal.next_day(&self.storage);
+                self.redraw();
+            }
+            JournalAction::Today => {
+                self.journal.save_entry(&self.storage);
+                self.journal.jump_to_today();
+                self.journal.load_entry(&self.storage);
+                self.redraw();
+            }
+            JournalAction::Search => {
+                self.journal.search_query.clear();
+                self.journal.search_results.clear();
+                self.mode = AppMode::JournalSearch;
+                self.redraw();
+            }
+            JournalAction::MonthStats => {
+                // Stats for the month so far (up to the day shown)
+                self.journal.save_entry(&self.storage);
+                let to = self.journal.current_date.clone();
+                let from = month_start(&to);
+                let stats = self.storage.journal_stats(&from, &to);
+                self.journal_stats = Some((from, to, stats));
+                self.mode = AppMode::JournalStats;
+                self.redraw();
+            }
+            JournalAction::Save => {
+                self.journal.save_entry(&self.storage);
+                self.redraw();
+            }
+            JournalAction::Back => {
+                self.journal.save_entry(&self.storage);
+                self.mode = AppMode::ModeSelect;
+                self.redraw();
+            }
+        }
+    }
+
+    fn handle_key_mode_select(&mut self, key: char) {
+        match key {
+            '\u{F700}' | '↑' => {
+                if self.mode_cursor > 0 {
+                    self.mode_cursor -= 1;
+                    self.redraw();
+                }
+            }
+            '\u{F701}' | '↓' => {
+                if self.mode_cursor < 2 {
+                    self.mode_cursor += 1;
+                    self.redraw();
                 }
             }
             '\r' | '\n' => {
-                match self.mode_cursor {
-                    0 => {
-                        self.refresh_doc_list();
-                        self.mode = AppMode::DocList;
-                    }
-                    1 => {
-                        self.journal.jump_to_today();
-                        self.journal.load_entry(&self.storage);
-                        self.mode = AppMode::JournalDay;
-                    }
-                    2 => {
-                        self.typewriter = TypewriterState::new();
-                        self.mode = AppMode::TypewriterEdit;
-                    }
-                    _ => {}
+                self.enter_selected_mode();
+                self.redraw();
+            }
+            's' => {
+                self.settings_cursor = 0;
+                self.mode = AppMode::Settings;
+                self.redraw();
+            }
+            'n' => {
+                self.start_quick_note();
+                self.redraw();
+            }
+            'q' => self.quit(),
+            _ => {}
+        }
+    }
+
+    /// Open the mode highlighted on the mode select screen.
+    fn enter_selected_mode(&mut self) {
+        let wanted = match self.mode_cursor {
+            0 => AppMode::DocList,
+            1 => AppMode::JournalDay,
+            _ => AppMode::TypewriterEdit,
+        };
+        self.enter_mode(wanted);
+    }
+
+    /// Switch to `wanted`, setting up its screen, or to the storage locked
+    /// screen if it needs the PDDB and that isn't mounted.
+    fn enter_mode(&mut self, wanted: AppMode) {
+        self.mode = storage_gate(self.storage.is_ready(), wanted);
+        if self.mode == AppMode::StorageLocked {
+            self.locked_target = wanted;
+        }
+        match self.mode {
+            AppMode::DocList => {
+                // Start from the full list each visit, reread from the PDDB
+                self.storage.refresh_index();
+                self.doc_folder.clear();
+                self.doc_filter.clear();
+                self.doc_filtering = false;
+                self.refresh_doc_list();
+            }
+            AppMode::JournalDay => {
+                self.journal.jump_to_today();
+                self.journal.load_entry(&self.storage);
+            }
+            AppMode::TypewriterEdit => {
+                // Pick a sprint length first; the session starts from there
+                self.sprint_cursor = 0;
+                self.mode = AppMode::SprintSelect;
+            }
+            _ => {}
+        }
+    }
+
+    /// Prompt for a one-line note to append to today's journal entry.
+    fn start_quick_note(&mut self) {
+        self.quick_note_input.clear();
+        self.enter_mode(AppMode::QuickNote);
+    }
+
+    /// 'r' retries the mount and, once the PDDB is unlocked, opens what
+    /// the user was after; any other key goes back to mode select.
+    fn handle_key_storage_locked(&mut self, key: char) {
+        match key {
+            'r' | 'R' => {
+                if self.storage.retry_mount() {
+                    // Settings were read while locked, so they were defaults
+                    self.config = self.storage.load_config();
+                    self.apply_config();
+                    self.enter_mode(self.locked_target);
+                } else {
+                    self.notify("Still locked");
                 }
+            }
+            _ => self.mode = AppMode::ModeSelect,
+        }
+        self.redraw();
+    }
+
+    /// Push display settings from the config to the renderer and journal.
+    fn apply_config(&mut self) {
+        self.renderer.set_paragraph_spacing(self.config.paragraph_spacing);
+        self.renderer.set_prose_word_count(self.config.prose_word_count);
+        self.renderer.set_horizontal_scroll(self.config.horizontal_scroll);
+        self.renderer.set_theme(self.config.theme);
+        self.renderer.set_word_goal(self.config.word_goal);
+        self.renderer.set_number_separator(self.config.number_separator.as_char());
+        self.journal.template = self.config.journal_template.clone();
+        self.journal.tz_offset_minutes = self.config.tz_offset_minutes;
+    }
+
+    fn handle_key_quick_note(&mut self, key: char) {
+        match key {
+            '\r' | '\n' => {
+                if !self.quick_note_input.trim().is_empty() {
+                    let date = append_quick_note(&self.storage, &self.quick_note_input, self.config.tz_offset_minutes);
+                    log::info!("Quick note added to {}", date);
+                }
+                self.quick_note_input.clear();
+                self.mode = AppMode::ModeSelect;
+                self.redraw();
+            }
+            '\u{0008}' | '\u{007f}' => {
+                self.quick_note_input.pop();
+                self.redraw();
+            }
+            ch if !ch.is_control() => {
+                self.quick_note_input.push(ch);
+                self.redraw();
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_key_settings(&mut self, key: char) {
+        match key {
+            '\u{F700}' | '↑' => {
+                if self.settings_cursor > 0 {
+                    self.settings_cursor -= 1;
+                    self.redraw();
+                }
+            }
+            '\u{F701}' | '↓' => {
+                if self.settings_cursor + 1 < SETTINGS_LABELS.len() {
+                    self.settings_cursor += 1;
+                    self.redraw();
+                }
+            }
+            '\r' | '\n' | ' ' if self.settings_cursor == TEMPLATE_ROW => self.open_template_editor(),
+            '\r' | '\n' | ' ' => {
+                change_setting(&mut self.config, self.settings_cursor);
+                self.storage.save_config(&self.config);
+                self.apply_config();
                 self.redraw();
             }
             'q' => {
-                // Quit app - could send quit message
+                self.mode = AppMode::ModeSelect;
+                self.redraw();
             }
             _ => {}
         }
     }
 
+    /// Edit the journal template, starting from the default scaffold if
+    /// none is set yet.
+    fn open_template_editor(&mut self) {
+        let template = if self.config.journal_template.is_empty() {
+            DEFAULT_TEMPLATE
+        } else {
+            &self.config.journal_template
+        };
+        self.template_buffer = TextBuffer::from_text(template);
+        self.mode = AppMode::TemplateEdit;
+        self.redraw();
+    }
+
+    /// Store the edited template (blank turns it off) and go back to settings.
+    fn save_template(&mut self) {
+        let text = self.template_buffer.to_string();
+        self.config.journal_template = if text.trim().is_empty() { String::new() } else { text };
+        self.storage.save_config(&self.config);
+        self.journal.template = self.config.journal_template.clone();
+        self.mode = AppMode::Settings;
+    }
+
+    fn handle_key_template_edit(&mut self, key: char) {
+        let buffer = &mut self.template_buffer;
+        match key {
+            '\u{F700}' | '↑' => buffer.move_up(),
+            '\u{F701}' | '↓' => buffer.move_down(),
+            '\u{F702}' | '←' => buffer.move_left(),
+            '\u{F703}' | '→' => buffer.move_right(),
+            '\r' | '\n' => buffer.newline(),
+            '\u{0008}' | '\u{007f}' => buffer.delete_back(),
+            ch if !ch.is_control() => buffer.insert_char(ch),
+            _ => return,
+        }
+        self.redraw();
+    }
+
     fn handle_key_doc_list(&mut self, key: char) {
+        // While typing a filter, text keys edit it; arrows and Enter still work
+        if self.doc_filtering {
+            match key {
+                '\u{F700}' | '↑' | '\u{F701}' | '↓' | '\r' | '\n' => {}
+                '\u{0008}' | '\u{007f}' => {
+                    self.doc_filter.pop();
+                    if self.doc_filter.is_empty() {
+                        self.doc_filtering = false;
+                    }
+                    self.apply_doc_view();
+                    self.redraw();
+                    return;
+                }
+                ch if !ch.is_control() => {
+                    self.doc_filter.push(ch);
+                    self.apply_doc_view();
+                    self.redraw();
+                    return;
+                }
+                _ => return,
+            }
+        }
         match key {
             '\u{F700}' | '↑' => {
                 if self.doc_cursor > 0 {
@@ -848,26 +1764,63 @@ impl WriterApp {
                     self.redraw();
                 }
             }
-            '\r' | '\n' => {
-                if !self.doc_list.is_empty() {
-                    let name = self.doc_list[self.doc_cursor].clone();
-                    self.open_doc(&name);
+            '\r' | '\n' => match self.doc_list.get(self.doc_cursor).cloned() {
+                Some(DocEntry::Folder { path, .. }) => {
+                    self.doc_folder = path;
+                    self.doc_cursor = 0;
+                    self.apply_doc_view();
+                    self.redraw();
                 }
-            }
+                Some(DocEntry::Doc(name)) => self.open_doc(&name),
+                None => {}
+            },
             'n' => {
-                self.new_doc();
+                if self.doc_folder.is_empty() {
+                    self.new_doc();
+                } else {
+                    // New documents go in the folder being shown
+                    let name = self.storage.next_doc_name(&format!("{}/Untitled", self.doc_folder));
+                    self.editor = EditorState::with_name(&name);
+                    self.mode = AppMode::EditorEdit;
+                    self.redraw();
+                }
+            }
+            'g' => {
+                self.goto_doc_input.clear();
+                self.goto_doc_message.clear();
+                self.goto_doc_confirm = false;
+                self.mode = AppMode::GotoDoc;
+                self.redraw();
+            }
+            '/' => {
+                self.doc_filtering = true;
+                self.redraw();
+            }
+            's' => {
+                self.doc_sort = self.doc_sort.next();
+                self.apply_doc_view();
+                self.redraw();
+            }
+            'o' => {
+                // Reordering moves documents within the full index
+                let docs = self.doc_list.iter().filter(|e| matches!(e, DocEntry::Doc(_))).count();
+                if docs > 1 && self.doc_sort == DocSort::Index && self.doc_filter.is_empty() {
+                    self.mode = AppMode::DocReorder;
+                    self.redraw();
+                }
             }
             'd' => {
-                if !self.doc_list.is_empty() {
-                    let name = self.doc_list[self.doc_cursor].clone();
+                if let Some(DocEntry::Doc(name)) = self.doc_list.get(self.doc_cursor).cloned() {
                     self.storage.delete_doc(&name);
+                    self.notify(&format!("Deleted {}", name));
                     self.refresh_doc_list();
-                    if self.doc_cursor >= self.doc_list.len() && self.doc_cursor > 0 {
-                        self.doc_cursor -= 1;
-                    }
                     self.redraw();
                 }
             }
+            '\u{0008}' | '\u{007f}' | 'q' if !self.doc_folder.is_empty() => {
+                self.leave_doc_folder();
+                self.redraw();
+            }
             'q' => {
                 self.mode = AppMode::ModeSelect;
                 self.redraw();
@@ -876,6 +1829,97 @@ impl WriterApp {
         }
     }
 
+    /// Go up from the folder being shown to its parent, with the cursor on
+    /// the folder just left.
+    fn leave_doc_folder(&mut self) {
+        let left = std::mem::take(&mut self.doc_folder);
+        self.doc_folder = split_folder(&left).0.unwrap_or_default();
+        self.apply_doc_view();
+        if let Some(pos) = self.doc_list.iter().position(|e| matches!(e, DocEntry::Folder { path, .. } if *path == left)) {
+            self.doc_cursor = pos;
+        }
+    }
+
+    fn handle_key_doc_reorder(&mut self, key: char) {
+        let delta = match key {
+            '\u{F700}' | '↑' => -1,
+            '\u{F701}' | '↓' => 1,
+            '\r' | '\n' | 'o' | 'q' => {
+                self.mode = AppMode::DocList;
+                self.redraw();
+                return;
+            }
+            _ => return,
+        };
+        // Swap places with the neighbouring document in the view; inside a
+        // folder that can be several places away in the full index
+        let neighbour = self.doc_cursor.checked_add_signed(delta).and_then(|i| self.doc_list.get(i));
+        if let (Some(DocEntry::Doc(name)), Some(DocEntry::Doc(other))) = (self.doc_list.get(self.doc_cursor), neighbour) {
+            let position = |n: &String| self.doc_index.iter().position(|i| i == n);
+            if let (Some(from), Some(to)) = (position(name), position(other)) {
+                let name = name.clone();
+                if self.storage.reorder_doc(&name, to as isize - from as isize).is_some() {
+                    self.refresh_doc_list();
+                    self.redraw();
+                }
+            }
+        }
+    }
+
+    fn handle_key_goto_doc(&mut self, key: char) {
+        if self.goto_doc_confirm {
+            // "Create it?" prompt after a lookup with no match
+            match key {
+                'y' | '\r' | '\n' => {
+                    let name = match sanitize_name(&self.goto_doc_input) {
+                        Some(name) => name,
+                        None => return,
+                    };
+                    // Tidying the name can land on one that's already used
+                    if let NameMatch::Exact(_) = match_doc_name(&self.storage.list_docs(), &name) {
+                        self.goto_doc_message = RenameError::NameTaken.message().to_string();
+                        self.goto_doc_confirm = false;
+                        self.redraw();
+                        return;
+                    }
+                    self.editor = EditorState::with_name(&name);
+                    self.mode = AppMode::EditorEdit;
+                    self.redraw();
+                }
+                'n' => {
+                    self.goto_doc_confirm = false;
+                    self.redraw();
+                }
+                _ => {}
+            }
+            return;
+        }
+        match key {
+            '\r' | '\n' => {
+                if let Some(name) = self.storage.find_doc(&self.goto_doc_input) {
+                    self.open_doc(&name);
+                } else if let NameMatch::Ambiguous(n) = match_doc_name(&self.doc_index, &self.goto_doc_input) {
+                    self.goto_doc_message = format!("{} documents match - keep typing", n);
+                    self.redraw();
+                } else if !self.goto_doc_input.trim().is_empty() {
+                    self.goto_doc_confirm = true;
+                    self.redraw();
+                }
+            }
+            '\u{0008}' | '\u{007f}' => {
+                self.goto_doc_input.pop();
+                self.goto_doc_message.clear();
+                self.redraw();
+            }
+            ch if !ch.is_control() => {
+                self.goto_doc_input.push(ch);
+                self.goto_doc_message.clear();
+                self.redraw();
+            }
+            _ => {}
+        }
+    }
+
     fn handle_key_editor(&mut self, key: char) {
         match key {
             '\u{F700}' | '↑' => {
@@ -894,8 +1938,22 @@ impl WriterApp {
                 self.editor.buffer.move_right();
                 self.redraw();
             }
+            '\u{F72C}' => {
+                // Page Up
+                self.editor.buffer.page_up();
+                self.redraw();
+            }
+            '\u{F72D}' => {
+                // Page Down
+                self.editor.buffer.page_down();
+                self.redraw();
+            }
             '\r' | '\n' => {
-                self.editor.buffer.newline();
+                if self.config.auto_indent {
+                    self.editor.buffer.newline_autoindent();
+                } else {
+                    self.editor.buffer.newline_smart();
+                }
                 self.redraw();
             }
             '\u{0008}' | '\u{007f}' => {
@@ -910,7 +1968,7 @@ impl WriterApp {
             }
             '\u{F729}' => {
                 // Home key
-                self.editor.buffer.move_home();
+                self.editor.buffer.move_home_smart();
                 self.redraw();
             }
             '\u{F72B}' => {
@@ -918,17 +1976,118 @@ impl WriterApp {
                 self.editor.buffer.move_end();
                 self.redraw();
             }
+            '\t' => {
+                self.editor.buffer.insert_str(&tab_text(&self.config));
+                self.redraw();
+            }
+            ch if !ch.is_control() => {
+                if self.config.auto_capitalize {
+                    self.editor.buffer.insert_char_prose(ch);
+                } else {
+                    self.editor.buffer.insert_char(ch);
+                }
+                self.redraw();
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_key_editor_find(&mut self, key: char) {
+        match key {
+            '\r' | '\n' => {
+                if self.editor.find_next() {
+                    self.redraw();
+                }
+            }
+            '\u{0008}' | '\u{007f}' => {
+                self.editor.find_query.pop();
+                self.editor.update_find();
+                self.redraw();
+            }
             ch if !ch.is_control() => {
-                self.editor.buffer.insert_char(ch);
+                self.editor.find_query.push(ch);
+                self.editor.update_find();
+                self.redraw();
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_key_goto_line(&mut self, key: char) {
+        match key {
+            '\r' | '\n' => {
+                if let Ok(n) = self.goto_line_input.parse::<usize>() {
+                    self.editor.buffer.goto_line(n);
+                }
+                self.mode = AppMode::EditorEdit;
+                self.redraw();
+            }
+            '\u{0008}' | '\u{007f}' => {
+                self.goto_line_input.pop();
+                self.redraw();
+            }
+            ch if ch.is_ascii_digit() && self.goto_line_input.len() < 6 => {
+                self.goto_line_input.push(ch);
                 self.redraw();
             }
             _ => {}
         }
     }
 
-    fn handle_key_preview(&mut self, _key: char) {
-        // In preview mode, most keys are ignored
-        // Esc commands handled in handle_esc_command
+    /// List the document's headings, starting at the section the cursor is in.
+    fn open_outline(&mut self) {
+        self.outline = extract_headings(&self.editor.buffer.to_string());
+        let line = self.editor.buffer.cursor.line;
+        self.outline_cursor = self.outline.iter()
+            .rposition(|(at, _, _)| *at <= line)
+            .unwrap_or(0);
+        self.mode = AppMode::Outline;
+        self.redraw();
+    }
+
+    fn handle_key_outline(&mut self, key: char) {
+        match key {
+            '\u{F700}' | '↑' => {
+                if self.outline_cursor > 0 {
+                    self.outline_cursor -= 1;
+                    self.redraw();
+                }
+            }
+            '\u{F701}' | '↓' => {
+                if self.outline_cursor + 1 < self.outline.len() {
+                    self.outline_cursor += 1;
+                    self.redraw();
+                }
+            }
+            '\r' | '\n' => {
+                if let Some((line, _, _)) = self.outline.get(self.outline_cursor) {
+                    self.editor.buffer.goto_line(line + 1);
+                }
+                self.mode = AppMode::EditorEdit;
+                self.redraw();
+            }
+            _ => {}
+        }
+    }
+
+    /// Preview is for reading: arrows and PageUp/PageDown scroll, nothing
+    /// edits. Esc commands are handled in handle_esc_command.
+    fn handle_key_preview(&mut self, key: char) {
+        let buffer = &self.editor.buffer;
+        let delta = match key {
+            '\u{F700}' | '↑' => -1,
+            '\u{F701}' | '↓' => 1,
+            '\u{F72C}' => -(buffer.page_step() as isize),
+            '\u{F72D}' => buffer.page_step() as isize,
+            _ => return,
+        };
+        let view = BufferView::Editor { preview: true };
+        let last_page = self.renderer.last_page_lines(view, buffer);
+        self.editor.buffer.scroll_view(delta, last_page);
+        // Count what fits from the new top, so the draw keeps this view
+        let lines = self.renderer.viewport_lines(view, &self.editor.buffer);
+        self.editor.buffer.fit_cursor_to_view(lines);
+        self.redraw();
     }
 
     fn handle_key_file_menu(&mut self, key: char) {
@@ -940,7 +2099,7 @@ impl WriterApp {
                 }
             }
             '\u{F701}' | '↓' => {
-                if self.file_menu_cursor < 3 {
+                if self.file_menu_cursor < 4 {
                     self.file_menu_cursor += 1;
                     self.redraw();
                 }
@@ -953,23 +2112,37 @@ impl WriterApp {
                         self.new_doc();
                     }
                     1 => {
+                        // Duplicate: save first so the copy has any unsaved
+                        // edits, then continue in the copy
+                        self.save_current_doc();
+                        match self.storage.duplicate_doc(&self.editor.doc_name) {
+                            Some(copy) => self.open_doc(&copy),
+                            None => {
+                                self.mode = AppMode::EditorEdit;
+                                self.redraw();
+                            }
+                        }
+                    }
+                    2 => {
                         // Rename document
                         self.rename_input.clear();
                         self.rename_input.push_str(&self.editor.doc_name);
+                        self.rename_error.clear();
                         self.mode = AppMode::RenameDoc;
                         self.redraw();
                     }
-                    2 => {
+                    3 => {
                         // Delete current
                         let name = self.editor.doc_name.clone();
                         if !name.is_empty() {
                             self.storage.delete_doc(&name);
+                            self.notify(&format!("Deleted {}", name));
                         }
                         self.refresh_doc_list();
                         self.mode = AppMode::DocList;
                         self.redraw();
                     }
-                    3 => {
+                    4 => {
                         // Back to editor
                         self.mode = AppMode::EditorEdit;
                         self.redraw();
@@ -989,91 +2162,365 @@ impl WriterApp {
         match key {
             '\r' | '\n' => {
                 // Confirm rename
-                let new_name = self.rename_input.trim().to_string();
-                if !new_name.is_empty() && new_name != self.editor.doc_name {
-                    let old_name = self.editor.doc_name.clone();
-                    let content = self.editor.buffer.to_string();
-                    // Save with new name
-                    self.storage.save_doc(&new_name, &content);
-                    // Delete old name
-                    if !old_name.is_empty() {
-                        self.storage.delete_doc(&old_name);
+                let new_name = sanitize_name(&self.rename_input).unwrap_or_default();
+                match self.storage.rename_doc(&self.editor.doc_name, &new_name) {
+                    Ok(()) => {
+                        self.editor.doc_name = new_name;
+                        // Unsaved edits (or a never-saved doc) land under the new name
+                        self.save_current_doc();
+                        self.mode = AppMode::EditorEdit;
+                    }
+                    Err(e) => {
+                        self.rename_error = e.message().to_string();
                     }
-                    self.editor.doc_name = new_name;
                 }
-                self.mode = AppMode::EditorEdit;
                 self.redraw();
             }
             '\u{0008}' | '\u{007f}' => {
                 // Backspace
                 self.rename_input.pop();
+                self.rename_error.clear();
                 self.redraw();
             }
-            ch if !ch.is_control() => {
-                // Type character
-                self.rename_input.push(ch);
+            ch if !ch.is_control() => {
+                // Type character
+                self.rename_input.push(ch);
+                self.rename_error.clear();
+                self.redraw();
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_key_export_menu(&mut self, key: char) {
+        match key {
+            '\u{F700}' | '↑' => {
+                if self.export_menu_cursor > 0 {
+                    self.export_menu_cursor -= 1;
+                    self.redraw();
+                }
+            }
+            '\u{F701}' | '↓' => {
+                if self.export_menu_cursor < 6 {
+                    self.export_menu_cursor += 1;
+                    self.redraw();
+                }
+            }
+            '\r' | '\n' if self.export_menu_cursor == 6 => {
+                // Page size option: cycle presets
+                self.config.export_page_size = next_export_page_size(self.config.export_page_size);
+                self.storage.save_config(&self.config);
+                self.redraw();
+            }
+            '\r' | '\n' => {
+                if let Some(target) = ExportTarget::from_menu_index(self.export_menu_cursor) {
+                    self.export_resume_from = match target {
+                        ExportTarget::UsbAutotype => self.saved_export_offset(),
+                        ExportTarget::Tcp | ExportTarget::Pddb | ExportTarget::PddbHtml | ExportTarget::Console | ExportTarget::Qr => None,
+                    };
+                    // A resumable export always asks: continue or start over
+                    if target.needs_confirmation(&self.config) || self.export_resume_from.is_some() {
+                        self.export_pending = Some(target);
+                        self.mode = AppMode::ExportConfirm;
+                        self.redraw();
+                        return;
+                    }
+                    self.run_export(target, 0);
+                    self.redraw();
+                    return;
+                }
+                self.mode = AppMode::EditorEdit;
+                self.redraw();
+            }
+            'q' => {
+                self.mode = AppMode::EditorEdit;
+                self.redraw();
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_key_qr_export(&mut self, key: char) {
+        match key {
+            '\u{F702}' | '←' | '\u{F700}' | '↑' => {
+                if self.qr_page > 0 {
+                    self.qr_page -= 1;
+                    self.redraw();
+                }
+            }
+            '\u{F703}' | '→' | '\u{F701}' | '↓' => {
+                if self.qr_page + 1 < self.qr_chunks.len() {
+                    self.qr_page += 1;
+                    self.redraw();
+                }
+            }
+            '\r' | '\n' => {
+                if let Some(chunk) = self.qr_chunks.get(self.qr_page) {
+                    let caption = format!("{} {} / {}", self.editor.doc_name, self.qr_page + 1, self.qr_chunks.len());
+                    self.renderer.show_qr(&caption, chunk);
+                    self.redraw();
+                }
+            }
+            'q' => {
+                self.mode = AppMode::ExportMenu;
                 self.redraw();
             }
             _ => {}
         }
     }
 
-    fn handle_key_export_menu(&mut self, key: char) {
+    fn open_backup(&mut self) {
+        self.backup_cursor = 0;
+        self.export_status.clear();
+        self.mode = AppMode::Backup;
+    }
+
+    fn handle_key_backup(&mut self, key: char) {
         match key {
             '\u{F700}' | '↑' => {
-                if self.export_menu_cursor > 0 {
-                    self.export_menu_cursor -= 1;
-                    self.redraw();
-                }
+                self.backup_cursor = self.backup_cursor.saturating_sub(1);
             }
             '\u{F701}' | '↓' => {
-                if self.export_menu_cursor < 1 {
-                    self.export_menu_cursor += 1;
-                    self.redraw();
-                }
-            }
-            '\r' | '\n' => {
-                let content = self.editor.buffer.to_string();
-                match self.export_menu_cursor {
-                    0 => {
-                        // TCP export - waits for connection on port 7879
-                        match self.export.export_tcp(&content) {
-                            Ok(bytes) => {
-                                log::info!("TCP export successful: {} bytes", bytes);
-                            }
-                            Err(e) => {
-                                log::error!("TCP export failed: {:?}", e);
-                            }
-                        }
+                self.backup_cursor = (self.backup_cursor + 1).min(BACKUP_ACTIONS.len() - 1);
+            }
+            '\r' | '\n' => match self.backup_cursor {
+                0 => {
+                    // Same wait-for-a-client flow as a TCP export
+                    let (data, _, skipped) = self.storage.export_all();
+                    if skipped > 0 {
+                        self.notify(&format!("Backup: {}", self.skipped_notice(skipped)));
                     }
-                    1 => {
-                        // USB autotype - types document as USB HID keyboard
-                        if !self.export.is_usb_ready() {
-                            log::warn!("USB not connected - cannot autotype");
+                    self.export_origin = AppMode::Backup;
+                    self.export_cancel.store(false, Ordering::Relaxed);
+                    let cancel = self.export_cancel.clone();
+                    let timeout_ms = self.config.tcp_timeout_secs as u64 * 1000;
+                    let cid = self.cid;
+                    std::thread::spawn(move || {
+                        let (failed, value) = match export_tcp_bytes(&data, timeout_ms, &cancel) {
+                            Ok(bytes) => (0, bytes),
+                            Err(e) => (1, e.to_usize().unwrap()),
+                        };
+                        xous::send_message(
+                            cid,
+                            xous::Message::new_scalar(AppOp::ExportDone.to_usize().unwrap(), failed, value, 0, 0),
+                        ).ok();
+                    });
+                    self.export_status.clear();
+                    self.mode = AppMode::ExportWaiting;
+                }
+                1 => {
+                    let (data, packed, skipped) = self.storage.export_all();
+                    self.export_status = if self.storage.save_export(BACKUP_EXPORT_KEY, &data) {
+                        let saved = format!("Saved {} documents to writer.exports/{}", self.number(packed), BACKUP_EXPORT_KEY);
+                        if skipped > 0 {
+                            format!("{}; {}", saved, self.skipped_notice(skipped))
                         } else {
-                            match self.export.export_usb_autotype(&content) {
-                                Ok(chars) => {
-                                    log::info!("USB autotype successful: {} chars", chars);
-                                }
-                                Err(e) => {
-                                    log::error!("USB autotype failed: {:?}", e);
-                                }
-                            }
+                            saved
                         }
-                    }
-                    _ => {}
+                    } else {
+                        String::from("Save failed - is the PDDB mounted?")
+                    };
                 }
-                self.mode = AppMode::EditorEdit;
-                self.redraw();
-            }
-            'q' => {
-                self.mode = AppMode::EditorEdit;
-                self.redraw();
+                _ => {
+                    self.export_status = match self.storage.load_export(BACKUP_EXPORT_KEY) {
+                        Some(data) => {
+                            let restored = self.storage.import_all(&data);
+                            log::info!("Restored {} documents from backup", restored);
+                            format!("Restored {} documents", self.number(restored))
+                        }
+                        None => format!("No writer.exports/{} to restore", BACKUP_EXPORT_KEY),
+                    };
+                }
+            },
+            'q' => self.mode = AppMode::ModeSelect,
+            _ => return,
+        }
+        self.redraw();
+    }
+
+    fn handle_key_export_confirm(&mut self, key: char) {
+        let from = match key {
+            '\r' | '\n' => self.export_resume_from.unwrap_or(0),
+            'r' if self.export_resume_from.is_some() => 0,
+            _ => return,
+        };
+        self.export_resume_from = None;
+        match self.export_pending.take() {
+            Some(target) => self.run_export(target, from),
+            None => self.mode = AppMode::EditorEdit,
+        }
+        self.redraw();
+    }
+
+    /// The pages USB autotype types, newlines as configured.
+    fn autotype_pages(&self) -> Vec<String> {
+        let content = self.editor.buffer.to_string();
+        autotype_pages(&content, self.config.export_page_size as usize, self.config.autotype_newline)
+    }
+
+    /// The autotyped pages joined: what resume offsets are measured against.
+    fn export_text(&self) -> String {
+        self.autotype_pages().concat()
+    }
+
+    /// Where an interrupted USB export of the current document stopped,
+    /// if the document is unchanged since.
+    fn saved_export_offset(&self) -> Option<usize> {
+        self.storage.load_export_resume()?.offset_for(&self.editor.doc_name, &self.export_text())
+    }
+
+    /// Run an export and switch to the screen that follows it; USB
+    /// autotype starts `from` bytes into the export text.
+    fn run_export(&mut self, target: ExportTarget, from: usize) {
+        let content = self.editor.buffer.to_string();
+        let pages = export_pages(&content, self.config.export_page_size as usize);
+        match target {
+            ExportTarget::Pddb | ExportTarget::PddbHtml => {
+                // The whole document, not paginated
+                let name = &self.editor.doc_name;
+                let (key, text) = if target == ExportTarget::Pddb {
+                    (markdown_export_key(name), content)
+                } else {
+                    (html_export_key(name), to_html(&content))
+                };
+                self.export_status = if self.storage.save_export(&key, text.as_bytes()) {
+                    format!("Saved writer.exports/{}", key)
+                } else {
+                    String::from("Save failed - is the PDDB mounted?")
+                };
+                // Stay in the menu to show how it went
+                self.mode = AppMode::ExportMenu;
+            }
+            ExportTarget::Console => {
+                // The whole document, not paginated: records are small anyway
+                let result = self.export().export_console(&content);
+                self.export_status = match result {
+                    Ok(records) => format!("Sent {} records to the console", self.number(records)),
+                    Err(e) => String::from(e.message()),
+                };
+                self.mode = AppMode::ExportMenu;
+            }
+            ExportTarget::Qr => {
+                // The whole document, not paginated: the chunks page it
+                self.qr_chunks = qr_chunks(&content, QR_CHUNK_BYTES);
+                self.qr_page = 0;
+                self.mode = AppMode::QrExport;
+            }
+            ExportTarget::Tcp => {
+                // TCP export - waits for a connection on port 7879 per page.
+                // The wait runs on its own thread so F4 can still cancel it.
+                self.export_origin = AppMode::ExportMenu;
+                self.export_cancel.store(false, Ordering::Relaxed);
+                let cancel = self.export_cancel.clone();
+                let timeout_ms = self.config.tcp_timeout_secs as u64 * 1000;
+                let cid = self.cid;
+                std::thread::spawn(move || {
+                    let (failed, value) = match export_tcp_pages(&pages, timeout_ms, &cancel) {
+                        Ok(bytes) => (0, bytes),
+                        Err(e) => (1, e.to_usize().unwrap()),
+                    };
+                    xous::send_message(
+                        cid,
+                        xous::Message::new_scalar(AppOp::ExportDone.to_usize().unwrap(), failed, value, 0, 0),
+                    ).ok();
+                });
+                self.export_status.clear();
+                self.mode = AppMode::ExportWaiting;
+            }
+            ExportTarget::UsbAutotype => {
+                // USB autotype - types document as USB HID keyboard
+                if !self.export().is_usb_ready() {
+                    // Ask to plug in and retry rather than losing the attempt
+                    log::warn!("USB not connected - cannot autotype");
+                    self.usb_retry_from = from;
+                    self.mode = AppMode::UsbNotReady;
+                    return;
+                }
+                self.export_origin = AppMode::ExportMenu;
+                let pages = self.autotype_pages();
+                let joined = pages.concat();
+                let start = resume_slice(&joined, from).0;
+                self.export_progress = (joined[..start].chars().count(), joined.chars().count());
+                let mut typed = self.export_progress.0;
+                let by_line = self.config.autotype_by_line;
+                self.export_lines = by_line.then(|| {
+                    let total: usize = pages.iter().map(|page| autotype_lines(page).len()).sum();
+                    (autotype_lines(&joined[..start]).len(), total)
+                });
+                let mut line = self.export_lines.map_or(0, |(done, _)| done);
+
+                // Typed on its own thread so F4 can abort between chunks
+                self.export_cancel.store(false, Ordering::Relaxed);
+                let cancel = self.export_cancel.clone();
+                let cid = self.cid;
+                let export = self.export.clone();
+                std::thread::spawn(move || {
+                    let mut reached = start;
+                    let result = {
+                        // Released before ExportDone, so the app can use it again
+                        let mut export = export.lock().unwrap_or_else(|e| e.into_inner());
+                        export.set_by_line(by_line);
+                        export.export_usb_autotype_with_retry(&pages, start, &cancel, &mut |at| {
+                            typed += joined[reached..at].chars().count();
+                            if by_line {
+                                line += autotype_lines(&joined[reached..at]).len();
+                            }
+                            reached = at;
+                            xous::send_message(
+                                cid,
+                                xous::Message::new_scalar(AppOp::ExportProgress.to_usize().unwrap(), at, typed, line, 0),
+                            ).ok();
+                        })
+                    };
+                    let (failed, value) = match result {
+                        Ok(bytes) => (0, bytes),
+                        Err(e) => (1, e.to_usize().unwrap()),
+                    };
+                    xous::send_message(
+                        cid,
+                        xous::Message::new_scalar(AppOp::ExportDone.to_usize().unwrap(), failed, value, reached, 0),
+                    ).ok();
+                });
+                self.export_status.clear();
+                self.mode = AppMode::ExportProgress;
             }
-            _ => {}
         }
     }
 
+    /// An export thread finished: back to the export menu with the result.
+    /// `reached` is how far a USB export got, saved so it can be resumed.
+    fn finish_export(&mut self, result: Result<usize, ExportError>, reached: usize) {
+        let usb = self.mode == AppMode::ExportProgress;
+        self.export_status = match result {
+            Ok(bytes) if usb => {
+                log::info!("USB autotype successful: {} bytes", bytes);
+                self.storage.clear_export_resume();
+                format!("Typed {} characters", self.number(self.export_progress.1))
+            }
+            Ok(bytes) => {
+                log::info!("TCP export successful: {} bytes", bytes);
+                format!("Sent {} bytes", self.number(bytes))
+            }
+            Err(e) if usb => {
+                // Remember how far it got so the next export can continue
+                log::error!("USB autotype stopped at byte {}: {:?}", reached, e);
+                self.storage.save_export_resume(&ExportResume::new(&self.editor.doc_name, &self.export_text(), reached));
+                let (typed, total) = self.export_progress;
+                format!("{} after {}/{} characters", e.message(), self.number(typed), self.number(total))
+            }
+            Err(e) => {
+                log::error!("TCP export failed: {:?}", e);
+                String::from(e.message())
+            }
+        };
+        let status = self.export_status.clone();
+        self.notify(&status);
+        if matches!(self.mode, AppMode::ExportWaiting | AppMode::ExportProgress) {
+            self.mode = self.export_origin;
+        }
+        self.redraw();
+    }
+
     fn handle_key_journal(&mut self, key: char) {
         match key {
             '\u{F700}' | '↑' => {
@@ -1092,6 +2539,16 @@ impl WriterApp {
                 self.journal.buffer.move_right();
                 self.redraw();
             }
+            '\u{F72C}' => {
+                // Page Up
+                self.journal.buffer.page_up();
+                self.redraw();
+            }
+            '\u{F72D}' => {
+                // Page Down
+                self.journal.buffer.page_down();
+                self.redraw();
+            }
             '\r' | '\n' => {
                 self.journal.buffer.newline();
                 self.redraw();
@@ -1100,6 +2557,21 @@ impl WriterApp {
                 self.journal.buffer.delete_back();
                 self.redraw();
             }
+            '\u{F728}' => {
+                // Delete key
+                self.journal.buffer.delete_forward();
+                self.redraw();
+            }
+            '\u{F729}' => {
+                // Home key
+                self.journal.buffer.move_home_smart();
+                self.redraw();
+            }
+            '\u{F72B}' => {
+                // End key
+                self.journal.buffer.move_end();
+                self.redraw();
+            }
             ch if !ch.is_control() => {
                 self.journal.buffer.insert_char(ch);
                 self.redraw();
@@ -1129,7 +2601,7 @@ impl WriterApp {
                     }
                 } else {
                     // Execute search
-                    self.journal.search_entries(&self.storage);
+                    self.journal.search_entries(&self.storage, self.config.journal_search_all_matches);
                     self.redraw();
                 }
             }
@@ -1157,18 +2629,106 @@ impl WriterApp {
         }
     }
 
+    fn handle_key_sprint_select(&mut self, key: char) {
+        match key {
+            '\u{F700}' | '↑' => {
+                if self.sprint_cursor > 0 {
+                    self.sprint_cursor -= 1;
+                    self.redraw();
+                }
+            }
+            '\u{F701}' | '↓' => {
+                if self.sprint_cursor + 1 < SPRINT_MINUTES.len() {
+                    self.sprint_cursor += 1;
+                    self.redraw();
+                }
+            }
+            '\r' | '\n' => {
+                // A sprint left running from an earlier session stops here
+                self.stop_sprint_ticks();
+                self.typewriter = TypewriterState::start(self.tt.elapsed_ms());
+                let minutes = SPRINT_MINUTES[self.sprint_cursor];
+                if minutes > 0 {
+                    self.typewriter.sprint_secs = Some(minutes * 60);
+                    self.start_sprint_ticks();
+                }
+                self.mode = AppMode::TypewriterEdit;
+                self.redraw();
+            }
+            _ => {}
+        }
+    }
+
+    /// Post SprintTick every second until the sprint's session ends.
+    fn start_sprint_ticks(&mut self) {
+        self.stop_sprint_ticks();
+        let running = Arc::new(AtomicBool::new(true));
+        self.sprint_ticking = running.clone();
+        let cid = self.cid;
+        std::thread::spawn(move || {
+            let tt = ticktimer_server::Ticktimer::new().unwrap();
+            while running.load(Ordering::Relaxed) {
+                tt.sleep_ms(SPRINT_TICK_MS).ok();
+                xous::send_message(
+                    cid,
+                    xous::Message::new_scalar(AppOp::SprintTick.to_usize().unwrap(), 0, 0, 0, 0),
+                ).ok();
+            }
+        });
+    }
+
+    fn stop_sprint_ticks(&mut self) {
+        self.sprint_ticking.store(false, Ordering::Relaxed);
+    }
+
+    /// Update the sprint countdown, ending the session when time is up.
+    /// Help and the menu can be open over the typewriter; it ends on the
+    /// first tick back in typewriter mode. Ticks stop once the session has
+    /// ended or the app has gone on to something else.
+    fn sprint_tick(&mut self) {
+        let over_typewriter = self.mode == AppMode::HelpScreen && self.prev_mode == AppMode::TypewriterEdit;
+        if self.typewriter.ended_ms.is_some() || !(self.mode == AppMode::TypewriterEdit || over_typewriter) {
+            self.stop_sprint_ticks();
+            return;
+        }
+        if self.mode != AppMode::TypewriterEdit {
+            return;
+        }
+        if self.typewriter.sprint_over(self.tt.elapsed_ms()) {
+            self.menu_visible = false;
+            self.finish_typewriter();
+        }
+        self.redraw();
+    }
+
+    /// End the typewriter session and show its summary.
+    fn finish_typewriter(&mut self) {
+        self.typewriter.finish(self.tt.elapsed_ms());
+        self.stop_sprint_ticks();
+        self.mode = AppMode::TypewriterDone;
+    }
+
     fn handle_key_typewriter(&mut self, key: char) {
         match key {
             '\r' | '\n' => {
                 self.typewriter.buffer.append_newline();
+                self.typewriter.record(self.last_key_ms);
                 self.redraw();
             }
             ch if !ch.is_control() => {
                 self.typewriter.buffer.append_char(ch);
+                self.typewriter.record(self.last_key_ms);
                 self.redraw();
             }
+            '\u{0008}' | '\u{007f}' if self.config.typewriter_allow_backspace => {
+                // Gentle mode: corrections within the current line only
+                if self.typewriter.buffer.backspace_current_line() {
+                    self.typewriter.record(self.last_key_ms);
+                    self.redraw();
+                }
+            }
             _ => {
-                // No backspace, no cursor movement in typewriter mode
+                // No cursor movement, and no backspace unless enabled
             }
         }
     }
@@ -1195,9 +2755,27 @@ impl WriterApp {
     // Document management helpers
 
     fn refresh_doc_list(&mut self) {
-        self.doc_list = self.storage.list_docs();
-        if self.doc_cursor >= self.doc_list.len() {
-            self.doc_cursor = self.doc_list.len().saturating_sub(1);
+        self.doc_words = self.storage.list_doc_words();
+        self.doc_index = self.doc_words.iter().map(|(name, _)| name.clone()).collect();
+        self.apply_doc_view();
+    }
+
+    /// Rebuild the visible list from the index, filter and sort, keeping
+    /// the cursor on the same document when it is still shown.
+    /// A folder emptied by deleting its last document no longer exists, so
+    /// the view steps up to the nearest folder that still has documents.
+    fn apply_doc_view(&mut self) {
+        let current = self.doc_list.get(self.doc_cursor).cloned();
+        loop {
+            self.doc_list = folder_view(&self.doc_index, &self.doc_folder, &self.doc_filter, self.doc_sort);
+            if !self.doc_list.is_empty() || self.doc_folder.is_empty() || !self.doc_filter.is_empty() {
+                break;
+            }
+            self.doc_folder = split_folder(&self.doc_folder).0.unwrap_or_default();
+        }
+        match current.and_then(|entry| self.doc_list.iter().position(|e| *e == entry)) {
+            Some(pos) => self.doc_cursor = pos,
+            None => self.doc_cursor = self.doc_cursor.min(self.doc_list.len().saturating_sub(1)),
         }
     }
 
@@ -1208,25 +2786,195 @@ impl WriterApp {
         self.redraw();
     }
 
+    /// Open a document in the editor. A damaged one isn't opened at all,
+    /// so nothing can save an empty buffer over what is left of it.
     fn open_doc(&mut self, name: &str) {
-        if let Some(content) = self.storage.load_doc(name) {
-            self.editor = EditorState::with_content(name, &content);
-        } else {
-            self.editor = EditorState::with_name(name);
+        match self.storage.load_doc(name) {
+            Ok(saved) => {
+                self.editor = EditorState::open(name, saved.as_deref());
+                self.mode = AppMode::EditorEdit;
+            }
+            Err(DocError::Corrupt) => {
+                self.notify(&format!("{} is damaged and was not opened", name));
+            }
         }
-        self.mode = AppMode::EditorEdit;
         self.redraw();
     }
 
+    /// Flush unsaved edits, then ask the main loop to exit. Quit is posted
+    /// rather than handled here so the loop can unregister the server.
+    fn quit(&mut self) {
+        self.storage.begin_batch();
+        if self.editor.buffer.modified {
+            self.save_current_doc();
+        }
+        if self.journal.buffer.modified {
+            self.journal.save_entry(&self.storage);
+            self.journal.buffer.modified = false;
+        }
+        self.storage.commit_batch();
+        log::info!("Quitting");
+        xous::send_message(self.cid, quit_message()).ok();
+    }
+
+    /// Discard in-memory edits by reloading the current document from storage.
+    fn revert_current_doc(&mut self) {
+        let name = self.editor.doc_name.clone();
+        log::info!("Reverting '{}' to saved version", name);
+        self.open_doc(&name);
+    }
+
+    /// Periodic tick: save the open document or journal entry once it has
+    /// been idle for the configured interval.
+    fn autosave_tick(&mut self) {
+        let idle_ms = self.tt.elapsed_ms().saturating_sub(self.last_key_ms);
+        let interval = if self.config.autosave { self.config.autosave_interval_secs } else { 0 };
+        match self.mode {
+            AppMode::EditorEdit | AppMode::EditorPreview | AppMode::EditorFind => {
+                if autosave_due(interval, idle_ms, self.editor.buffer.modified) {
+                    self.save_current_doc();
+                    self.redraw();
+                }
+            }
+            AppMode::JournalDay => {
+                if autosave_due(interval, idle_ms, self.journal.buffer.modified) {
+                    self.journal.save_entry(&self.storage);
+                    self.journal.buffer.modified = false;
+                }
+            }
+            // Keep the session timer moving while the writer pauses
+            AppMode::TypewriterEdit => self.redraw(),
+            _ => {}
+        }
+        // Take down a status message once it has had its time
+        if self.status_msg.is_some() && visible_status(&self.status_msg, self.tt.elapsed_ms()).is_none() {
+            self.status_msg = None;
+            self.redraw();
+        }
+    }
+
+    /// The export backend. Only a running USB export holds it elsewhere, and
+    /// nothing else exports until that ends; a panic there leaves it usable.
+    fn export(&self) -> MutexGuard<'_, Box<dyn ExportBackend + Send>> {
+        self.export.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// `n` grouped with the separator chosen in settings, for notices.
+    fn number(&self, n: usize) -> String {
+        format_number_sep(n, self.config.number_separator.as_char())
+    }
+
+    /// What a backup says about the damaged documents it had to leave out.
+    fn skipped_notice(&self, skipped: usize) -> String {
+        format!("{} skipped as damaged", self.number(skipped))
+    }
+
+    /// Show a short status message over the status bar. It replaces any
+    /// message still showing and goes away on its own after a few seconds.
+    fn notify(&mut self, text: &str) {
+        log::info!("{}", text);
+        self.status_msg = Some(status_message(text, self.tt.elapsed_ms()));
+    }
+
+    /// Keep the cursor's column on screen when long lines scroll sideways.
+    fn scroll_editor_horizontally(&mut self) {
+        if self.config.horizontal_scroll {
+            let cols = self.renderer.visible_cols(self.config.show_line_numbers, self.editor.buffer.line_count());
+            self.editor.buffer.ensure_cursor_visible_horizontal(cols);
+        }
+    }
+
+    /// Save the open document. An untitled buffer with edits is saved under
+    /// the next free "Untitled" name, since nobody may be there to ask (quit,
+    /// autosave, going to the background); `save_or_ask_name` asks instead.
     fn save_current_doc(&mut self) {
-        if !self.editor.doc_name.is_empty() {
-            let content = self.editor.buffer.to_string();
-            self.storage.save_doc(&self.editor.doc_name, &content);
-            self.editor.buffer.modified = false;
+        match save_guard(&self.editor.doc_name, self.editor.buffer.modified) {
+            SaveGuard::Write => {}
+            SaveGuard::NeedsName => {
+                self.editor.doc_name = self.storage.next_doc_name("Untitled");
+                self.notify(&format!("Saved as {}", self.editor.doc_name));
+            }
+            SaveGuard::Skip => return,
+        }
+        let content = self.editor.buffer.to_string();
+        self.storage.save_doc(&self.editor.doc_name, &content);
+        self.editor.buffer.modified = false;
+    }
+
+    /// Save the open document when the user asked to: an untitled buffer
+    /// with edits opens the rename dialog to name it first. Returns whether
+    /// the document was saved.
+    fn save_or_ask_name(&mut self) -> bool {
+        match save_guard(&self.editor.doc_name, self.editor.buffer.modified) {
+            SaveGuard::NeedsName => {
+                self.rename_input.clear();
+                self.rename_error.clear();
+                self.mode = AppMode::RenameDoc;
+                self.redraw();
+                false
+            }
+            SaveGuard::Write => {
+                self.save_current_doc();
+                true
+            }
+            SaveGuard::Skip => false,
+        }
+    }
+}
+
+/// Size `buffer`'s viewport to the lines `view` really shows, scrolling
+/// until the cursor's line is among them (a scroll can bring taller
+/// headings into view, so the count is taken again after each one).
+fn fit_viewport(renderer: &dyn RenderBackend, view: BufferView, buffer: &mut TextBuffer) {
+    loop {
+        let top = buffer.viewport_top;
+        buffer.set_viewport_lines(renderer.viewport_lines(view, buffer));
+        if buffer.viewport_top == top {
+            break;
         }
     }
 }
 
+/// Mode to enter when the user picks `wanted`: modes that read from the
+/// PDDB show the locked notice instead while it isn't mounted, rather than
+/// an empty list.
+fn storage_gate(mounted: bool, wanted: AppMode) -> AppMode {
+    match wanted {
+        AppMode::DocList | AppMode::JournalDay if !mounted => AppMode::StorageLocked,
+        _ => wanted,
+    }
+}
+
+/// The message that ends the main loop.
+fn quit_message() -> xous::Message {
+    xous::Message::new_scalar(AppOp::Quit.to_usize().unwrap(), 0, 0, 0, 0)
+}
+
+/// What saving the editor's document should do.
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum SaveGuard {
+    /// Write it under its name
+    Write,
+    /// Untitled with edits: it needs a name before it can be written
+    NeedsName,
+    /// Untitled and untouched: nothing worth keeping
+    Skip,
+}
+
+fn save_guard(doc_name: &str, modified: bool) -> SaveGuard {
+    match (doc_name.is_empty(), modified) {
+        (false, _) => SaveGuard::Write,
+        (true, true) => SaveGuard::NeedsName,
+        (true, false) => SaveGuard::Skip,
+    }
+}
+
+/// Whether an idle buffer should be autosaved. An interval of 0 disables
+/// the timer; unmodified buffers are never rewritten.
+fn autosave_due(interval_secs: u16, idle_ms: u64, modified: bool) -> bool {
+    modified && interval_secs > 0 && idle_ms >= interval_secs as u64 * 1000
+}
+
 fn main() -> ! {
     log_server::init_wait().unwrap();
     log::set_max_level(log::LevelFilter::Info);
@@ -1237,6 +2985,22 @@ fn main() -> ! {
 
     let mut app = WriterApp::new(&xns, sid);
     app.allow_redraw = true;
+    // Launch straight into the configured default mode
+    app.enter_selected_mode();
+    app.flush_redraw();
+
+    // Pump thread for the idle autosave timer
+    let cid = xous::connect(sid).expect("can't connect to own server");
+    std::thread::spawn(move || {
+        let tt = ticktimer_server::Ticktimer::new().unwrap();
+        loop {
+            tt.sleep_ms(AUTOSAVE_TICK_MS).ok();
+            xous::send_message(
+                cid,
+                xous::Message::new_scalar(AppOp::AutosaveTick.to_usize().unwrap(), 0, 0, 0, 0),
+            ).ok();
+        }
+    });
 
     loop {
         let msg = xous::receive_message(sid).unwrap();
@@ -1262,26 +3026,458 @@ fn main() -> ! {
                 match new_state {
                     gam::FocusState::Background => {
                         app.allow_redraw = false;
+                        // Time away doesn't count against a session or sprint
+                        app.typewriter.pause(app.tt.elapsed_ms());
                         // Auto-save on background (if enabled in settings)
                         if app.config.autosave {
+                            app.storage.begin_batch();
                             app.save_current_doc();
                             if app.mode == AppMode::JournalDay {
                                 app.journal.save_entry(&app.storage);
                             }
+                            app.storage.commit_batch();
                         }
                     }
                     gam::FocusState::Foreground => {
+                        app.typewriter.resume(app.tt.elapsed_ms());
                         app.allow_redraw = true;
                         app.redraw();
                     }
                 }
             }),
+            Some(AppOp::AutosaveTick) => {
+                app.autosave_tick();
+            }
+            Some(AppOp::SprintTick) => {
+                app.sprint_tick();
+            }
+            Some(AppOp::ExportProgress) => xous::msg_scalar_unpack!(msg, _, typed, line, _, {
+                app.export_progress.0 = typed;
+                if let Some((done, total)) = &mut app.export_lines {
+                    *done = line.min(*total);
+                }
+                if app.mode == AppMode::ExportProgress {
+                    app.redraw();
+                }
+            }),
+            Some(AppOp::ExportDone) => xous::msg_scalar_unpack!(msg, failed, value, reached, _, {
+                let result = if failed == 0 {
+                    Ok(value)
+                } else {
+                    Err(FromPrimitive::from_usize(value).unwrap_or(ExportError::TcpAcceptFailed))
+                };
+                app.finish_export(result, reached);
+            }),
             Some(AppOp::Quit) => break,
             _ => log::error!("unknown opcode: {:?}", msg),
         }
+        app.flush_redraw();
     }
 
     xns.unregister_server(sid).unwrap();
     xous::destroy_server(sid).unwrap();
     xous::terminate_process(0)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::FakeExport;
+    use crate::render::{DrawLog, RecordingRenderer};
+    use crate::storage::MemoryStore;
+    use crate::ui::{FakeClock, STATUS_MSG_MS};
+    use writer_core::serialize::NumberSeparator;
+
+    /// An app drawing into a `RecordingRenderer`, with documents in memory
+    /// and the clock at zero, the startup draw already flushed, and the
+    /// list of screens drawn since.
+    fn recording_app() -> (WriterApp, DrawLog) {
+        recording_app_with(MemoryStore::default())
+    }
+
+    /// `recording_app` keeping documents in `store`.
+    fn recording_app_with(store: MemoryStore) -> (WriterApp, DrawLog) {
+        let renderer = RecordingRenderer::default();
+        let calls = renderer.calls.clone();
+        let storage = WriterStorage::with_store(Box::new(store));
+        let mut app = WriterApp::with_backend(Box::new(renderer), storage, Box::<FakeExport>::default(), Box::<FakeClock>::default(), 0);
+        app.flush_redraw();
+        calls.borrow_mut().clear();
+        (app, calls)
+    }
+
+    #[test]
+    fn test_draw_fits_viewport_to_screen() {
+        let (mut app, _calls) = recording_app();
+        let text: Vec<String> = (0..50).map(|i| format!("line {}", i)).collect();
+        app.editor.buffer = TextBuffer::from_text(&text.join("\n"));
+        app.editor.buffer.viewport_lines = 5;
+        app.editor.buffer.cursor.line = 40;
+        app.mode = AppMode::EditorEdit;
+        app.redraw();
+        app.flush_redraw();
+        // The recording renderer has room for 13 lines
+        let buffer = &app.editor.buffer;
+        assert_eq!((buffer.viewport_lines, buffer.viewport_top), (13, 28));
+    }
+
+    #[test]
+    fn test_journal_delete_home_end() {
+        let (mut app, calls) = recording_app();
+        app.mode = AppMode::JournalDay;
+        app.journal.buffer = TextBuffer::from_text("  - first\nsecond");
+        app.journal.buffer.cursor.col = 6;
+
+        app.handle_key('\u{F729}');
+        assert_eq!(app.journal.buffer.cursor.col, 2, "Home stops at the text first");
+        app.handle_key('\u{F72B}');
+        assert_eq!(app.journal.buffer.cursor.col, 9);
+        // Delete at the end of a line joins the next one
+        app.handle_key('\u{F728}');
+        assert_eq!(app.journal.buffer.lines, ["  - firstsecond"]);
+        app.handle_key('\u{F728}');
+        assert_eq!(app.journal.buffer.lines, ["  - firstecond"]);
+        assert!(app.journal.buffer.modified);
+        app.flush_redraw();
+        assert_eq!(*calls.borrow(), vec!["draw_journal"]);
+    }
+
+    #[test]
+    fn test_preview_scrolls_without_editing() {
+        let (mut app, calls) = recording_app();
+        let text: Vec<String> = (0..50).map(|i| format!("line {}", i)).collect();
+        app.editor.buffer = TextBuffer::from_text(&text.join("\n"));
+        app.mode = AppMode::EditorPreview;
+
+        app.handle_key('\u{F72D}');
+        app.handle_key('↓');
+        assert_eq!(app.editor.buffer.viewport_top, 13);
+        for _ in 0..10 {
+            app.handle_key('\u{F72D}');
+        }
+        // The recording renderer fits 13 lines on the last page
+        assert_eq!(app.editor.buffer.viewport_top, 37);
+        app.handle_key('\u{F72C}');
+        assert_eq!(app.editor.buffer.viewport_top, 25);
+
+        // Typing does nothing here
+        app.handle_key('x');
+        app.handle_key('\r');
+        assert_eq!(app.editor.buffer.to_string(), text.join("\n"));
+        assert!(!app.editor.buffer.modified);
+        app.flush_redraw();
+        assert_eq!(*calls.borrow(), vec!["draw_editor"]);
+    }
+
+    #[test]
+    fn test_preview_scrolls_up_past_a_taller_heading() {
+        let (mut app, _calls) = recording_app();
+        let mut text: Vec<String> = (0..50).map(|i| format!("line {}", i)).collect();
+        // The recording renderer gives a heading two rows of its 13
+        text[10] = String::from("# Heading");
+        app.editor.buffer = TextBuffer::from_text(&text.join("\n"));
+        app.editor.buffer.viewport_top = 11;
+        app.editor.buffer.cursor.line = 23;
+        app.mode = AppMode::EditorPreview;
+        app.redraw();
+        app.flush_redraw();
+        assert_eq!((app.editor.buffer.viewport_top, app.editor.buffer.viewport_lines), (11, 13));
+
+        // Scrolling the heading in leaves room for 12 lines; the draw keeps
+        // the view rather than scrolling back to the cursor
+        app.handle_key('↑');
+        app.flush_redraw();
+        assert_eq!((app.editor.buffer.viewport_top, app.editor.buffer.viewport_lines), (10, 12));
+        app.handle_key('↑');
+        app.flush_redraw();
+        assert_eq!(app.editor.buffer.viewport_top, 9);
+    }
+
+    #[test]
+    fn test_f1_opens_menu_and_f4_closes_it() {
+        let (mut app, calls) = recording_app();
+        app.mode = AppMode::EditorEdit;
+        app.handle_key(KEY_F1);
+        app.flush_redraw();
+        assert!(app.menu_visible);
+        assert_eq!(*calls.borrow(), vec!["draw_menu"]);
+
+        app.handle_key(KEY_F4);
+        app.flush_redraw();
+        assert!(!app.menu_visible);
+        assert_eq!(app.mode, AppMode::EditorEdit);
+        assert_eq!(*calls.borrow(), vec!["draw_menu", "draw_editor"]);
+    }
+
+    #[test]
+    fn test_key_burst_draws_once() {
+        let (mut app, calls) = recording_app();
+        app.mode = AppMode::EditorEdit;
+        // One Rawkeys message: four keys, then the main loop's flush
+        for key in ['w', 'o', 'r', 'd'] {
+            app.handle_key(key);
+        }
+        app.flush_redraw();
+        assert_eq!(app.editor.buffer.to_string().to_lowercase(), "word");
+        assert_eq!(*calls.borrow(), vec!["draw_editor"]);
+        // Nothing new asked for: no draw
+        app.flush_redraw();
+        assert_eq!(calls.borrow().len(), 1);
+    }
+
+    #[test]
+    fn test_mode_select_opens_doc_list() {
+        let (mut app, calls) = recording_app();
+        app.mode_cursor = 0;
+        app.handle_key('\r');
+        app.flush_redraw();
+        assert_eq!(app.mode, AppMode::DocList);
+        assert_eq!(*calls.borrow(), vec!["draw_doc_list"]);
+
+        // Settings and back
+        app.mode = AppMode::ModeSelect;
+        app.handle_key('s');
+        app.flush_redraw();
+        assert_eq!(app.mode, AppMode::Settings);
+        app.handle_key('q');
+        app.flush_redraw();
+        assert_eq!(app.mode, AppMode::ModeSelect);
+        assert_eq!(*calls.borrow(), vec!["draw_doc_list", "draw_settings", "draw_mode_select"]);
+    }
+
+    #[test]
+    fn test_goto_doc_create_refuses_a_taken_name() {
+        let (mut app, calls) = recording_app();
+        app.storage.save_doc("Notes", "keep me");
+        app.mode = AppMode::GotoDoc;
+        // No match for the typed text, but it tidies to "Notes"
+        for key in "Notes/".chars() {
+            app.handle_key(key);
+        }
+        app.handle_key('\r');
+        assert!(app.goto_doc_confirm);
+        app.handle_key('y');
+        app.flush_redraw();
+        assert_eq!(app.mode, AppMode::GotoDoc);
+        assert_eq!(app.goto_doc_message, "A document with that name exists");
+        assert_eq!(calls.borrow().last(), Some(&"draw_goto_doc"));
+        assert_eq!(app.storage.load_doc("Notes").unwrap().as_deref(), Some("keep me"));
+    }
+
+    #[test]
+    fn test_qr_export_pages_through_chunks() {
+        let (mut app, calls) = recording_app();
+        app.editor.buffer = TextBuffer::from_text(&"word ".repeat(100));
+        app.mode = AppMode::ExportMenu;
+        app.export_menu_cursor = 5;
+        app.handle_key('\r');
+        app.flush_redraw();
+        assert_eq!(app.mode, AppMode::QrExport);
+        let total = app.qr_chunks.len();
+        assert_eq!(total, 4);
+        assert!(app.qr_chunks[0].starts_with("1/4 "));
+
+        // Left stops at the first chunk, right at the last
+        app.handle_key('\u{F702}');
+        assert_eq!(app.qr_page, 0);
+        for _ in 0..total + 2 {
+            app.handle_key('\u{F703}');
+        }
+        assert_eq!(app.qr_page, total - 1);
+        app.handle_key('\u{F702}');
+        assert_eq!(app.qr_page, total - 2);
+
+        app.handle_key('\r');
+        app.flush_redraw();
+        assert_eq!(*calls.borrow(), vec!["draw_qr_export", "show_qr", "draw_qr_export"]);
+
+        app.handle_key(KEY_F4);
+        assert_eq!(app.mode, AppMode::ExportMenu);
+    }
+
+    #[test]
+    fn test_save_guard() {
+        assert_eq!(save_guard("notes", true), SaveGuard::Write);
+        assert_eq!(save_guard("notes", false), SaveGuard::Write);
+        assert_eq!(save_guard("", true), SaveGuard::NeedsName);
+        assert_eq!(save_guard("", false), SaveGuard::Skip);
+    }
+
+    #[test]
+    fn test_saving_untitled_asks_for_a_name() {
+        let (mut app, calls) = recording_app();
+        app.mode = AppMode::EditorEdit;
+        // Untouched: nothing to save, nothing to ask
+        app.handle_key(KEY_F3);
+        app.flush_redraw();
+        assert_eq!(app.mode, AppMode::EditorEdit);
+
+        app.handle_key('x');
+        app.handle_key(KEY_F3);
+        app.flush_redraw();
+        assert_eq!(app.mode, AppMode::RenameDoc);
+        assert!(app.editor.buffer.modified);
+        assert_eq!(calls.borrow().last(), Some(&"draw_rename_dialog"));
+    }
+
+    #[test]
+    fn test_unattended_save_names_untitled() {
+        let (mut app, _calls) = recording_app();
+        app.mode = AppMode::EditorEdit;
+        app.handle_key('x');
+        app.save_current_doc();
+        assert_eq!(app.editor.doc_name, "Untitled");
+        assert!(!app.editor.buffer.modified);
+        // It lands in storage and opens again from there
+        assert_eq!(app.storage.list_docs(), vec!["Untitled"]);
+        app.editor = EditorState::new();
+        app.open_doc("Untitled");
+        assert_eq!(app.editor.buffer.to_string(), "x");
+    }
+
+    #[test]
+    fn test_damaged_doc_is_not_opened() {
+        let store = MemoryStore::default();
+        let (mut app, _calls) = recording_app_with(store.clone());
+        app.storage.save_doc("Notes", "some text");
+        store.damage_doc("Notes");
+        app.mode = AppMode::DocList;
+        app.open_doc("Notes");
+        // Still on the list, with nothing loaded and the user told why
+        assert_eq!(app.mode, AppMode::DocList);
+        assert_eq!(app.editor.doc_name, "");
+        assert_eq!(visible_status(&app.status_msg, 0), Some("Notes is damaged and was not opened"));
+        // The damaged blob is left for recovery
+        assert_eq!(app.storage.list_docs(), vec!["Notes"]);
+    }
+
+    #[test]
+    fn test_backup_reports_skipped_docs() {
+        let store = MemoryStore::default();
+        let (mut app, _calls) = recording_app_with(store.clone());
+        app.storage.save_doc("A", "fine");
+        app.storage.save_doc("B", "broken");
+        store.damage_doc("B");
+        app.open_backup();
+        app.backup_cursor = 1;
+        app.handle_key_backup('\r');
+        assert_eq!(app.export_status, format!("Saved 1 documents to writer.exports/{}; 1 skipped as damaged", BACKUP_EXPORT_KEY));
+    }
+
+    #[test]
+    fn test_export_notice_uses_number_separator() {
+        let (mut app, _calls) = recording_app();
+        app.config.number_separator = NumberSeparator::Period;
+        app.mode = AppMode::ExportWaiting;
+        app.finish_export(Ok(1234567), 0);
+        assert_eq!(app.export_status, "Sent 1.234.567 bytes");
+    }
+
+    #[test]
+    fn test_finished_export_shows_a_status_message() {
+        let (mut app, calls) = recording_app();
+        app.mode = AppMode::ExportWaiting;
+        app.export_origin = AppMode::ExportMenu;
+        app.finish_export(Ok(1204), 0);
+        app.flush_redraw();
+        assert_eq!(visible_status(&app.status_msg, 0), Some("Sent 1,204 bytes"));
+        assert_eq!(*calls.borrow(), vec!["draw_export_menu"]);
+
+        // A failed one says why
+        app.mode = AppMode::ExportWaiting;
+        app.finish_export(Err(ExportError::TcpAcceptFailed), 0);
+        assert_eq!(visible_status(&app.status_msg, 0), Some(ExportError::TcpAcceptFailed.message()));
+    }
+
+    #[test]
+    fn test_console_export_counts_records() {
+        let (mut app, _calls) = recording_app();
+        app.editor.buffer = TextBuffer::from_text("one\ntwo");
+        app.run_export(ExportTarget::Console, 0);
+        // The two lines plus the begin and end markers
+        assert_eq!(app.export_status, "Sent 4 records to the console");
+    }
+
+    #[test]
+    fn test_typewriter_help_follows_backspace_setting() {
+        let (mut app, _calls) = recording_app();
+        app.prev_mode = AppMode::TypewriterEdit;
+        assert!(app.help_text().contains("No backspace."));
+        app.config.typewriter_allow_backspace = true;
+        assert!(app.help_text().contains("Backspace: this line only."));
+    }
+
+    #[test]
+    fn test_sprint_ticks_stop_when_the_sprint_is_left() {
+        let (mut app, _calls) = recording_app();
+        app.mode = AppMode::SprintSelect;
+        app.sprint_cursor = 1;
+        app.handle_key('\r');
+        assert!(app.sprint_ticking.load(Ordering::Relaxed));
+        // Help over the sprint keeps it going
+        app.prev_mode = AppMode::TypewriterEdit;
+        app.mode = AppMode::HelpScreen;
+        app.sprint_tick();
+        assert!(app.sprint_ticking.load(Ordering::Relaxed));
+        // Anywhere else stops it
+        app.mode = AppMode::ModeSelect;
+        app.sprint_tick();
+        assert!(!app.sprint_ticking.load(Ordering::Relaxed));
+
+        // Starting without a timer stops a sprint still ticking
+        app.mode = AppMode::SprintSelect;
+        app.handle_key('\r');
+        let earlier = app.sprint_ticking.clone();
+        app.mode = AppMode::SprintSelect;
+        app.sprint_cursor = 0;
+        app.handle_key('\r');
+        assert_eq!(app.mode, AppMode::TypewriterEdit);
+        assert!(!earlier.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn test_new_status_message_replaces_old() {
+        let (mut app, _calls) = recording_app();
+        let clock = FakeClock::default();
+        let now = clock.now.clone();
+        app.tt = Box::new(clock);
+        app.notify("Saved");
+        now.set(2000);
+        assert_eq!(visible_status(&app.status_msg, now.get()), Some("Saved"));
+        app.notify("Deleted Notes");
+        // The newer message shows, for its own full time
+        assert_eq!(visible_status(&app.status_msg, now.get()), Some("Deleted Notes"));
+        assert_eq!(visible_status(&app.status_msg, STATUS_MSG_MS + 1000), Some("Deleted Notes"));
+        assert_eq!(visible_status(&app.status_msg, STATUS_MSG_MS + 2000), None);
+    }
+
+    #[test]
+    fn test_storage_gate_locked() {
+        assert_eq!(storage_gate(false, AppMode::DocList), AppMode::StorageLocked);
+        assert_eq!(storage_gate(false, AppMode::JournalDay), AppMode::StorageLocked);
+        // Typewriter works without storage
+        assert_eq!(storage_gate(false, AppMode::TypewriterEdit), AppMode::TypewriterEdit);
+    }
+
+    #[test]
+    fn test_quit_message_ends_loop() {
+        let msg = quit_message();
+        assert!(matches!(FromPrimitive::from_usize(msg.id()), Some(AppOp::Quit)));
+    }
+
+    #[test]
+    fn test_autosave_due() {
+        assert!(autosave_due(30, 30_000, true));
+        assert!(!autosave_due(30, 29_999, true));
+        // Unmodified buffers are never rewritten
+        assert!(!autosave_due(30, 60_000, false));
+        // 0 disables the timer
+        assert!(!autosave_due(0, 60_000, true));
+    }
+
+    #[test]
+    fn test_storage_gate_mounted() {
+        assert_eq!(storage_gate(true, AppMode::DocList), AppMode::DocList);
+        assert_eq!(storage_gate(true, AppMode::JournalDay), AppMode::JournalDay);
+    }
+}
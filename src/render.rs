@@ -1,25 +1,253 @@
 use std::fmt::Write;
 use gam::{Gam, GlyphStyle, Gid};
 use gam::menu::*;
-use writer_core::{TextBuffer, LineKind};
-use writer_core::serialize::{date_to_epoch_ms, epoch_ms_to_weekday};
-use crate::ui::{format_number, truncate_str};
+use modals::Modals;
+use writer_core::{BufferStats, TextBuffer, LineKind};
+use writer_core::markdown::{classify_lines, parse_inline, BlockPadding, InlineSpan};
+use writer_core::highlight::{fence_languages, tokenize, Language, Token, TokenKind};
+use writer_core::journal::JournalStats;
+use writer_core::docs::{split_folder, DocEntry, DocSort};
+use writer_core::stats::goal_progress;
+use writer_core::serialize::{format_date, DateFormat, Theme};
+use crate::ui::{format_duration, format_number_sep, list_window, match_window, truncate_str};
+use crate::export::{ExportTarget, EXPORT_PORT};
+use crate::settings::{setting_value, SETTINGS_LABELS};
+use writer_core::serialize::WriterConfig;
 
 const MARGIN_LEFT: isize = 8;
 const MARGIN_RIGHT: isize = 8;
 const STATUS_BAR_HEIGHT: isize = 28;
 const LINE_HEIGHT_REGULAR: isize = 18;
 const LINE_HEIGHT_LARGE: isize = 28;
+const CHAR_WIDTH: isize = 8; // Approximate for Regular/Monospace
+const CHECKBOX_SIZE: isize = 10;
+const PARAGRAPH_GAP: usize = 6; // Extra space above paragraphs in preview
+const READING_WPM: usize = 200;
+const SCROLLBAR_WIDTH: isize = 3;
+const MIN_THUMB_HEIGHT: isize = 8;
+
+/// Query bar shown at the bottom of the editor while finding.
+pub struct FindBar<'a> {
+    pub query: &'a str,
+    pub position: Option<usize>, // 1-based match under the cursor
+    pub total: usize,
+}
+
+/// What the document list is showing: the folder, filter and sort order.
+pub struct DocListHeader<'a> {
+    pub folder: &'a str, // "" = top level
+    pub filter: &'a str,
+    pub filtering: bool, // keys are going into the filter
+    pub sort: DocSort,
+}
+
+/// Screens that show a scrolling `TextBuffer`, for `viewport_lines`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BufferView {
+    Editor { preview: bool },
+    Journal,
+    Typewriter,
+    Template,
+}
+
+/// The screens and settings `WriterApp` draws through. `Renderer` draws
+/// them with the GAM; tests swap in `RecordingRenderer` to check which
+/// screen the app asked for without a display.
+pub trait RenderBackend {
+    fn set_paragraph_spacing(&mut self, on: bool);
+    fn set_prose_word_count(&mut self, on: bool);
+    fn set_horizontal_scroll(&mut self, on: bool);
+    fn set_theme(&mut self, theme: Theme);
+    fn set_word_goal(&mut self, goal: usize);
+    fn set_number_separator(&mut self, sep: char);
+    fn set_toast(&mut self, msg: Option<&str>);
+    fn visible_cols(&self, show_line_numbers: bool, line_count: usize) -> usize;
+    fn viewport_lines(&self, view: BufferView, buffer: &TextBuffer) -> usize;
+    fn last_page_lines(&self, view: BufferView, buffer: &TextBuffer) -> usize;
+    fn draw_menu(&self, items: &[&str], cursor: usize);
+    fn draw_help(&self, help_text: &str);
+    fn draw_confirm_exit(&self);
+    fn draw_confirm_revert(&self, doc_name: &str);
+    fn draw_confirm_delete_entry(&self, date: &str);
+    fn draw_settings(&self, config: &WriterConfig, cursor: usize);
+    fn draw_template_editor(&self, buffer: &TextBuffer);
+    fn draw_storage_locked(&self);
+    fn draw_mode_select(&self, cursor: usize);
+    fn draw_doc_list(&self, docs: &[DocEntry], words: &[(String, Option<u32>)], cursor: usize, reordering: bool, header: DocListHeader);
+    fn draw_editor(&self, buffer: &TextBuffer, doc_name: &str, preview: bool, show_line_numbers: bool, max_line_length: usize, find: Option<FindBar>);
+    fn draw_file_menu(&self, cursor: usize);
+    fn draw_rename_dialog(&self, new_name: &str, old_name: &str, error: &str);
+    fn draw_goto_line(&self, input: &str, line_count: usize);
+    fn draw_outline(&self, headings: &[(usize, usize, String)], cursor: usize);
+    fn draw_goto_doc(&self, input: &str, message: &str, confirm_create: bool);
+    fn draw_quick_note(&self, input: &str);
+    fn draw_export_menu(&self, cursor: usize, page_size: u16, status: &str);
+    fn draw_export_confirm(&self, target: ExportTarget, resume: Option<(usize, usize)>);
+    fn draw_backup(&self, actions: &[&str], cursor: usize, status: &str);
+    fn draw_export_waiting(&self, timeout_secs: u16, cancelling: bool);
+    fn draw_export_progress(&self, typed: usize, total: usize, lines: Option<(usize, usize)>, cancelling: bool);
+    fn draw_usb_not_ready(&self);
+    fn draw_qr_export(&self, chunk: &str, page: usize, total: usize);
+    /// Show `payload` as a QR code until the user dismisses it.
+    fn show_qr(&self, caption: &str, payload: &str);
+    fn draw_journal(&self, buffer: &TextBuffer, date: &str, date_format: DateFormat, streak: usize);
+    fn draw_journal_search(&self, query: &str, results: &[(String, usize, String)], cursor: usize);
+    fn draw_journal_stats(&self, from: &str, to: &str, stats: &JournalStats);
+    fn draw_doc_stats(&self, buffer: &TextBuffer, doc_name: &str);
+    fn draw_sprint_select(&self, minutes: &[u64], cursor: usize);
+    fn draw_typewriter(&self, buffer: &TextBuffer, elapsed_secs: u64, wpm: usize, remaining_secs: Option<u64>);
+    fn draw_typewriter_done(&self, buffer: &TextBuffer, top_words: &[(String, usize)], elapsed_secs: u64, avg_wpm: usize, sprint_secs: Option<u64>);
+}
 
 pub struct Renderer {
     gam: Gam,
+    // Draws QR codes, in a dialog over the canvas
+    modals: Modals,
     content: Gid,
     screensize: Point,
+    block_padding: BlockPadding,
+    prose_word_count: bool,
+    horizontal_scroll: bool,
+    theme: Theme,
+    word_goal: usize,
+    number_separator: char,
+    toast: Option<String>,
+}
+
+/// Colours for a theme. Everything is drawn in `fg` on `bg`; text is
+/// inverted in the dark theme, and highlights invert it back.
+trait Palette {
+    fn fg(&self) -> PixelColor;
+    fn bg(&self) -> PixelColor;
+    fn inverted(&self) -> bool;
+}
+
+impl Palette for Theme {
+    fn fg(&self) -> PixelColor {
+        match self {
+            Theme::Light => PixelColor::Dark,
+            Theme::Dark => PixelColor::Light,
+        }
+    }
+
+    fn bg(&self) -> PixelColor {
+        match self {
+            Theme::Light => PixelColor::Light,
+            Theme::Dark => PixelColor::Dark,
+        }
+    }
+
+    fn inverted(&self) -> bool {
+        *self == Theme::Dark
+    }
 }
 
 impl Renderer {
-    pub fn new(gam: Gam, content: Gid, screensize: Point) -> Self {
-        Self { gam, content, screensize }
+    pub fn new(gam: Gam, modals: Modals, content: Gid, screensize: Point) -> Self {
+        Self { gam, modals, content, screensize, block_padding: BlockPadding::default(), prose_word_count: false, horizontal_scroll: false, theme: Theme::Light, word_goal: 0, number_separator: ',', toast: None }
+    }
+
+    /// Add space above each new paragraph in preview (config.paragraph_spacing).
+    pub fn set_paragraph_spacing(&mut self, on: bool) {
+        self.block_padding.paragraph = if on { PARAGRAPH_GAP } else { 0 };
+    }
+
+    /// Count only prose words in the editor status bar (config.prose_word_count).
+    pub fn set_prose_word_count(&mut self, on: bool) {
+        self.prose_word_count = on;
+    }
+
+    /// Scroll long lines sideways in edit mode instead of wrapping them
+    /// (config.horizontal_scroll).
+    pub fn set_horizontal_scroll(&mut self, on: bool) {
+        self.horizontal_scroll = on;
+    }
+
+    /// Light or dark screen (config.theme).
+    pub fn set_theme(&mut self, theme: Theme) {
+        self.theme = theme;
+    }
+
+    /// Words to aim for, shown against the count in status bars (config.word_goal, 0 = off).
+    pub fn set_word_goal(&mut self, goal: usize) {
+        self.word_goal = goal;
+    }
+
+    /// Digit group separator for counts on stats screens (config.number_separator).
+    pub fn set_number_separator(&mut self, sep: char) {
+        self.number_separator = sep;
+    }
+
+    /// Transient status message drawn over the bottom of every screen
+    /// (None = nothing to show).
+    pub fn set_toast(&mut self, msg: Option<&str>) {
+        self.toast = msg.map(String::from);
+    }
+
+    /// A count with the configured digit grouping.
+    fn num(&self, n: usize) -> String {
+        format_number_sep(n, self.number_separator)
+    }
+
+    /// Approximate number of text columns across the editor, for
+    /// `TextBuffer::ensure_cursor_visible_horizontal`.
+    pub fn visible_cols(&self, show_line_numbers: bool, line_count: usize) -> usize {
+        let gutter = if show_line_numbers { gutter_width(gutter_digits(line_count)) } else { 0 };
+        let width = self.screensize.x - MARGIN_LEFT - gutter - MARGIN_RIGHT;
+        (width / CHAR_WIDTH).max(1) as usize
+    }
+
+    /// Top and bottom y of the area `view` draws its lines in.
+    fn text_area(&self, view: BufferView) -> (isize, isize) {
+        match view {
+            BufferView::Editor { .. } | BufferView::Typewriter => (4, self.screensize.y - STATUS_BAR_HEIGHT),
+            BufferView::Journal => (48, self.screensize.y - STATUS_BAR_HEIGHT),
+            BufferView::Template => (44, self.screensize.y - 40),
+        }
+    }
+
+    /// How many lines of `buffer`, from its viewport top, fit on `view`'s
+    /// screen. The editor's headings are taller and padded, so its count
+    /// depends on which lines are showing; lines past the end of the
+    /// document are counted as plain ones.
+    pub fn viewport_lines(&self, view: BufferView, buffer: &TextBuffer) -> usize {
+        let (top, bottom) = self.text_area(view);
+        let available = (bottom - top).max(0) as usize;
+        let rows = available / LINE_HEIGHT_REGULAR as usize;
+        match view {
+            BufferView::Editor { preview } => {
+                let end = (buffer.viewport_top + rows).min(buffer.lines.len());
+                let mut kinds = editor_line_kinds(buffer, buffer.viewport_top, end).1;
+                kinds.resize(rows.max(kinds.len()), LineKind::Normal);
+                self.editor_padding(preview).fitting_lines(&kinds, |k| line_style(k).1 as usize, available)
+            }
+            BufferView::Journal | BufferView::Typewriter | BufferView::Template => rows.max(1),
+        }
+    }
+
+    /// How many lines fit on `view`'s screen when it is scrolled to the end
+    /// of `buffer`: where scrolling down stops.
+    pub fn last_page_lines(&self, view: BufferView, buffer: &TextBuffer) -> usize {
+        let (top, bottom) = self.text_area(view);
+        let available = (bottom - top).max(0) as usize;
+        let rows = available / LINE_HEIGHT_REGULAR as usize;
+        match view {
+            BufferView::Editor { preview } => {
+                let start = buffer.lines.len().saturating_sub(rows);
+                let kinds = editor_line_kinds(buffer, start, buffer.lines.len()).1;
+                self.editor_padding(preview).fitting_lines_from_end(&kinds, |k| line_style(k).1 as usize, available)
+            }
+            BufferView::Journal | BufferView::Typewriter | BufferView::Template => rows.max(1),
+        }
+    }
+
+    /// Paragraph spacing is a reading aid, so edit mode keeps lines tight.
+    fn editor_padding(&self, preview: bool) -> BlockPadding {
+        if preview {
+            self.block_padding
+        } else {
+            BlockPadding { paragraph: 0, ..self.block_padding }
+        }
     }
 
     fn clear(&self) {
@@ -29,7 +257,7 @@ impl Renderer {
                 Point::new(0, 0),
                 self.screensize,
                 DrawStyle {
-                    fill_color: Some(PixelColor::Light),
+                    fill_color: Some(self.theme.bg()),
                     stroke_color: None,
                     stroke_width: 0,
                 },
@@ -44,14 +272,42 @@ impl Renderer {
         );
         tv.style = style;
         tv.clear_area = true;
+        tv.invert = self.theme.inverted();
         write!(tv.text, "{}", text).unwrap();
         self.gam.post_textview(&mut tv).expect("can't post text");
     }
 
     fn finish(&self) {
+        if let Some(msg) = &self.toast {
+            self.draw_toast(msg);
+        }
         self.gam.redraw().expect("can't redraw");
     }
 
+    /// A boxed one-line message over the status bar area. It covers the
+    /// bar only until it expires; nothing waits on it.
+    fn draw_toast(&self, msg: &str) {
+        let top = self.screensize.y - STATUS_BAR_HEIGHT;
+        self.gam.draw_rectangle(
+            self.content,
+            Rectangle::new_with_style(
+                Point::new(0, top),
+                Point::new(self.screensize.x, self.screensize.y),
+                DrawStyle {
+                    fill_color: Some(self.theme.bg()),
+                    stroke_color: Some(self.theme.fg()),
+                    stroke_width: 2,
+                },
+            ),
+        ).ok();
+        self.post_text(
+            MARGIN_LEFT, top + 4,
+            self.screensize.x - MARGIN_LEFT * 2, STATUS_BAR_HEIGHT - 6,
+            GlyphStyle::Small,
+            msg,
+        );
+    }
+
     // ---- Menu Overlay ----
 
     pub fn draw_menu(&self, items: &[&str], cursor: usize) {
@@ -95,20 +351,22 @@ impl Renderer {
         self.clear();
 
         let line_height = 20;
-        let mut y = 16isize;
+        let body_top = 16 + line_height;
+        let bottom = self.screensize.y - 36;
+        let mut lines = help_text.lines();
 
-        for line in help_text.lines() {
-            if y + line_height > self.screensize.y - 36 {
-                break;
-            }
-            let style = if y == 16 { GlyphStyle::Bold } else { GlyphStyle::Small };
-            self.post_text(
-                16, y,
-                self.screensize.x - 32, line_height - 2,
-                style,
-                line,
-            );
-            y += line_height;
+        if let Some(title) = lines.next() {
+            self.post_text(16, 16, self.screensize.x - 32, line_height - 2, GlyphStyle::Bold, title);
+        }
+        // Long help (the editor's) carries on in a second column
+        let rows = ((bottom - body_top) / line_height).max(1) as usize;
+        let lines: Vec<&str> = lines.collect();
+        let columns = if lines.len() > rows { 2 } else { 1 };
+        let col_width = (self.screensize.x - 32) / columns;
+        for (i, line) in lines.iter().take(rows * columns as usize).enumerate() {
+            let x = 16 + (i / rows) as isize * col_width;
+            let y = body_top + (i % rows) as isize * line_height;
+            self.post_text(x, y, col_width - 4, line_height - 2, GlyphStyle::Small, line);
         }
 
         self.post_text(
@@ -150,6 +408,187 @@ impl Renderer {
         self.finish();
     }
 
+    // ---- Confirm Revert ----
+
+    pub fn draw_confirm_revert(&self, doc_name: &str) {
+        self.clear();
+
+        self.post_text(
+            MARGIN_LEFT, 40,
+            self.screensize.x - MARGIN_LEFT * 2, 30,
+            GlyphStyle::Bold,
+            "Revert Document",
+        );
+
+        let msg = format!("Discard all changes to '{}' since the last save?", doc_name);
+        self.post_text(
+            MARGIN_LEFT, 80,
+            self.screensize.x - MARGIN_LEFT * 2, 40,
+            GlyphStyle::Regular,
+            &msg,
+        );
+
+        self.post_text(
+            20, 140,
+            self.screensize.x - 40, 80,
+            GlyphStyle::Regular,
+            "y = Revert to saved\nn = Keep editing\nF4 = Cancel",
+        );
+
+        self.finish();
+    }
+
+    // ---- Confirm Delete Journal Entry ----
+
+    pub fn draw_confirm_delete_entry(&self, date: &str) {
+        self.clear();
+
+        self.post_text(
+            MARGIN_LEFT, 40,
+            self.screensize.x - MARGIN_LEFT * 2, 30,
+            GlyphStyle::Bold,
+            "Delete Entry",
+        );
+
+        let msg = format!("Delete the journal entry for {}? This cannot be undone.", date);
+        self.post_text(
+            MARGIN_LEFT, 80,
+            self.screensize.x - MARGIN_LEFT * 2, 40,
+            GlyphStyle::Regular,
+            &msg,
+        );
+
+        self.post_text(
+            20, 140,
+            self.screensize.x - 40, 80,
+            GlyphStyle::Regular,
+            "y = Delete entry\nn = Keep it\nF4 = Cancel",
+        );
+
+        self.finish();
+    }
+
+    // ---- Settings ----
+
+    pub fn draw_settings(&self, config: &WriterConfig, cursor: usize) {
+        self.clear();
+
+        self.post_text(
+            MARGIN_LEFT, 8,
+            self.screensize.x - MARGIN_LEFT * 2, 30,
+            GlyphStyle::Bold,
+            "SETTINGS",
+        );
+
+        let list_top = 50;
+        let line_height = 28;
+        let value_x = self.screensize.x * 3 / 5;
+
+        // Scroll the list once the cursor passes the last row that fits
+        let rows = ((self.screensize.y - 40 - list_top) / line_height).max(1) as usize;
+        let first = cursor.saturating_sub(rows - 1);
+
+        for (i, label) in SETTINGS_LABELS.iter().enumerate().skip(first).take(rows) {
+            let y = list_top + ((i - first) as isize) * line_height;
+            let marker = if i == cursor { "> " } else { "  " };
+            self.post_text(
+                16, y,
+                value_x - 16, line_height - 2,
+                GlyphStyle::Regular,
+                &format!("{}{}", marker, label),
+            );
+            self.post_text(
+                value_x, y,
+                self.screensize.x - value_x - MARGIN_RIGHT, line_height - 2,
+                GlyphStyle::Regular,
+                &setting_value(config, i),
+            );
+        }
+
+        self.post_text(
+            MARGIN_LEFT, self.screensize.y - 40,
+            self.screensize.x - MARGIN_LEFT * 2, 30,
+            GlyphStyle::Small,
+            "F4=back  ENTER=change",
+        );
+
+        self.finish();
+    }
+
+    // ---- Journal Template ----
+
+    pub fn draw_template_editor(&self, buffer: &TextBuffer) {
+        self.clear();
+
+        self.post_text(
+            MARGIN_LEFT, 8,
+            self.screensize.x - MARGIN_LEFT * 2, 30,
+            GlyphStyle::Bold,
+            "JOURNAL TEMPLATE",
+        );
+
+        let (content_top, content_bottom) = self.text_area(BufferView::Template);
+        let mut y = content_top;
+        let end_line = (buffer.viewport_top + buffer.viewport_lines).min(buffer.lines.len());
+
+        for line_idx in buffer.viewport_top..end_line {
+            if y + LINE_HEIGHT_REGULAR > content_bottom {
+                break;
+            }
+            let line = &buffer.lines[line_idx];
+            if !line.is_empty() {
+                self.post_text(
+                    MARGIN_LEFT, y,
+                    self.screensize.x - MARGIN_LEFT * 2, LINE_HEIGHT_REGULAR,
+                    GlyphStyle::Regular,
+                    line,
+                );
+            }
+            if line_idx == buffer.cursor.line {
+                self.draw_cursor(MARGIN_LEFT, y, line, buffer.cursor.col, LINE_HEIGHT_REGULAR, GlyphStyle::Regular);
+            }
+            y += LINE_HEIGHT_REGULAR;
+        }
+
+        self.post_text(
+            MARGIN_LEFT, self.screensize.y - 40,
+            self.screensize.x - MARGIN_LEFT * 2, 30,
+            GlyphStyle::Small,
+            "{date} {weekday} are filled in  F4=save",
+        );
+
+        self.finish();
+    }
+
+    // ---- Storage Locked ----
+
+    pub fn draw_storage_locked(&self) {
+        self.clear();
+
+        self.post_text(
+            MARGIN_LEFT, 40,
+            self.screensize.x - MARGIN_LEFT * 2, 30,
+            GlyphStyle::Bold,
+            "Storage locked \u{2014} unlock PDDB",
+        );
+
+        self.post_text(
+            MARGIN_LEFT, 80,
+            self.screensize.x - MARGIN_LEFT * 2, 80,
+            GlyphStyle::Regular,
+            "Documents and journal entries live in the PDDB, which is not mounted. Unlock it, then press R to try again.",
+        );
+
+        self.post_text(
+            20, 180,
+            self.screensize.x - 40, 30,
+            GlyphStyle::Regular,
+            "R = Retry  Any other key = Back",
+        );
+
+        self.finish();
+    }
+
     // ---- Mode Select ----
 
     pub fn draw_mode_select(&self, cursor: usize) {
@@ -185,7 +624,7 @@ impl Renderer {
             MARGIN_LEFT, self.screensize.y - 40,
             self.screensize.x - MARGIN_LEFT * 2, 30,
             GlyphStyle::Small,
-            "F1=menu F4=quit  ENTER=open",
+            "F1=menu F4=quit  ENTER=open  s=settings",
         );
 
         self.finish();
@@ -193,15 +632,29 @@ impl Renderer {
 
     // ---- Document List ----
 
-    pub fn draw_doc_list(&self, docs: &[String], cursor: usize) {
+    /// `docs` is the list as shown: the folder's view, already filtered and
+    /// sorted as `header` says. `words` holds the word counts cached in the
+    /// index; unknown counts are left off.
+    pub fn draw_doc_list(&self, docs: &[DocEntry], words: &[(String, Option<u32>)], cursor: usize, reordering: bool, header: DocListHeader) {
         self.clear();
+        let DocListHeader { folder, filter, filtering, sort } = header;
 
-        // Title
+        // Title, with the folder, sort order and filter when they narrow the index
+        let mut title = String::from(if reordering { "REORDER DOCUMENTS" } else { "DOCUMENTS" });
+        if !folder.is_empty() {
+            write!(title, "  {}/", folder).ok();
+        }
+        if sort != DocSort::Index {
+            write!(title, "  {}", sort.label()).ok();
+        }
+        if filtering || !filter.is_empty() {
+            write!(title, "  /{}{}", filter, if filtering { "_" } else { "" }).ok();
+        }
         self.post_text(
             MARGIN_LEFT, 8,
             self.screensize.x - MARGIN_LEFT * 2, 30,
             GlyphStyle::Bold,
-            "DOCUMENTS",
+            &title,
         );
 
         if docs.is_empty() {
@@ -209,24 +662,44 @@ impl Renderer {
                 20, 60,
                 self.screensize.x - 40, 20,
                 GlyphStyle::Regular,
-                "No documents yet",
+                if !filter.is_empty() {
+                    "No matching documents"
+                } else if !folder.is_empty() {
+                    "No documents in this folder"
+                } else {
+                    "No documents yet"
+                },
             );
         } else {
             let list_top = 50;
             let line_height = 24;
             let max_visible = ((self.screensize.y - list_top - 50) / line_height) as usize;
 
-            // Determine viewport
-            let start = if cursor >= max_visible {
-                cursor - max_visible + 1
-            } else {
-                0
-            };
+            let (start, cursor) = list_window(cursor, docs.len(), max_visible);
 
-            for (i, doc) in docs.iter().enumerate().skip(start).take(max_visible) {
+            // Names are shown relative to the folder
+            let prefix = if folder.is_empty() { String::new() } else { format!("{}/", folder) };
+            for (i, entry) in docs.iter().enumerate().skip(start).take(max_visible) {
                 let y = list_top + ((i - start) as isize) * line_height;
-                let marker = if i == cursor { "> " } else { "  " };
-                let label = format!("{}{}", marker, doc);
+                let marker = match (i == cursor, reordering) {
+                    (true, true) => "= ",
+                    (true, false) => "> ",
+                    _ => "  ",
+                };
+                let label = match entry {
+                    DocEntry::Folder { path, docs } => {
+                        format!("{}{}/  ({})", marker, split_folder(path).1, self.num(*docs))
+                    }
+                    DocEntry::Doc(doc) => {
+                        let shown = doc.strip_prefix(prefix.as_str()).unwrap_or(doc);
+                        let count = words.iter().find(|(name, _)| name == doc).and_then(|&(_, w)| w);
+                        match count {
+                            Some(1) => format!("{}{} \u{2014} 1 word", marker, shown),
+                            Some(n) => format!("{}{} \u{2014} {} words", marker, shown, self.num(n as usize)),
+                            None => format!("{}{}", marker, shown),
+                        }
+                    }
+                };
                 self.post_text(
                     16, y,
                     self.screensize.x - 32, line_height - 2,
@@ -241,7 +714,15 @@ impl Renderer {
             MARGIN_LEFT, self.screensize.y - 40,
             self.screensize.x - MARGIN_LEFT * 2, 30,
             GlyphStyle::Small,
-            "F1=menu F4=back  ENTER=open  n=new  d=del",
+            if reordering {
+                "Up/Dn=move  ENTER=done"
+            } else if filtering {
+                "type to filter  BKSP=erase  ENTER=open"
+            } else if !folder.is_empty() {
+                "F4=back ENTER=open BKSP=up n=new /=filter s=sort o=order d=del"
+            } else {
+                "F4=back ENTER=open n=new g=goto /=filter s=sort o=order d=del"
+            },
         );
 
         self.finish();
@@ -249,47 +730,64 @@ impl Renderer {
 
     // ---- Editor ----
 
-    pub fn draw_editor(&self, buffer: &TextBuffer, doc_name: &str, preview: bool, show_line_numbers: bool) {
+    pub fn draw_editor(&self, buffer: &TextBuffer, doc_name: &str, preview: bool, show_line_numbers: bool, max_line_length: usize, find: Option<FindBar>) {
         self.clear();
 
-        let content_top = 4isize;
-        let content_bottom = self.screensize.y - STATUS_BAR_HEIGHT;
+        let (content_top, content_bottom) = self.text_area(BufferView::Editor { preview });
 
-        // Render visible lines
-        let mut y = content_top;
+        // Lay out visible lines, with extra space around block elements
         let end_line = (buffer.viewport_top + buffer.viewport_lines).min(buffer.lines.len());
+        let (langs, kinds) = editor_line_kinds(buffer, buffer.viewport_top, end_line);
+        let tops = self.editor_padding(preview).layout(
+            &kinds,
+            |k| line_style(k).1 as usize,
+            content_top as usize,
+            content_bottom as usize,
+        );
 
-        for line_idx in buffer.viewport_top..end_line {
-            let line = &buffer.lines[line_idx];
-            let kind = LineKind::classify(line);
-
-            let (style, line_h) = match kind {
-                LineKind::Heading1 => (GlyphStyle::Large, LINE_HEIGHT_LARGE),
-                LineKind::Heading2 | LineKind::Heading3 => (GlyphStyle::Bold, LINE_HEIGHT_REGULAR + 4),
-                LineKind::CodeBlock => (GlyphStyle::Monospace, LINE_HEIGHT_REGULAR),
-                _ => (GlyphStyle::Regular, LINE_HEIGHT_REGULAR),
-            };
-
-            if y + line_h > content_bottom {
-                break;
+        // Line number gutter, sized for the document's last line number
+        let digits = gutter_digits(buffer.lines.len());
+        let gutter = if show_line_numbers { gutter_width(digits) } else { 0 };
+
+        // Horizontal scroll: edit mode shows each line from viewport_col,
+        // cut at the right edge instead of wrapping
+        let scroll = self.horizontal_scroll && !preview;
+        let first_col = if scroll { buffer.viewport_col } else { 0 };
+        let visible_cols = self.visible_cols(show_line_numbers, buffer.lines.len());
+        // Highlight columns relative to the first one shown (None if all scrolled off)
+        let shift = |(from, to): (usize, usize)| {
+            if first_col > 0 && to <= first_col {
+                None
+            } else {
+                Some((from.saturating_sub(first_col), to.saturating_sub(first_col)))
             }
+        };
 
-            // Display text
-            let display_text = if preview {
+        for (i, &top) in tops.iter().enumerate() {
+            let line_idx = buffer.viewport_top + i;
+            let line = &buffer.lines[line_idx];
+            let kind = kinds[i];
+            let (style, line_h) = line_style(kind);
+            let y = top as isize;
+
+            // Display text (fenced code keeps its indentation)
+            let display_text = if preview && langs[i].is_none() {
                 LineKind::strip_prefix(line, kind).to_string()
+            } else if scroll {
+                line.chars().skip(first_col).take(visible_cols).collect()
             } else {
                 line.clone()
             };
 
-            // Draw block quote bar
+            // Draw block quote bar (right of the line number gutter)
             if kind == LineKind::BlockQuote {
                 self.gam.draw_rectangle(
                     self.content,
                     Rectangle::new_with_style(
-                        Point::new(MARGIN_LEFT, y + 2),
-                        Point::new(MARGIN_LEFT + 3, y + line_h - 2),
+                        Point::new(MARGIN_LEFT + gutter, y + 2),
+                        Point::new(MARGIN_LEFT + gutter + 3, y + line_h - 2),
                         DrawStyle {
-                            fill_color: Some(PixelColor::Dark),
+                            fill_color: Some(self.theme.fg()),
                             stroke_color: None,
                             stroke_width: 0,
                         },
@@ -306,39 +804,74 @@ impl Renderer {
                         Point::new(MARGIN_LEFT, rule_y),
                         Point::new(self.screensize.x - MARGIN_RIGHT, rule_y + 1),
                         DrawStyle {
-                            fill_color: Some(PixelColor::Dark),
+                            fill_color: Some(self.theme.fg()),
                             stroke_color: None,
                             stroke_width: 0,
                         },
                     ),
                 ).ok();
-                y += line_h;
                 continue;
             }
 
-            // Line number column width (4 digits + space = ~40px)
-            let line_num_width: isize = if show_line_numbers { 40 } else { 0 };
+            // Text offset for block quotes, checkboxes and line numbers
+            let text_left = text_left(kind, gutter, preview);
 
-            // Text offset for block quotes and line numbers
-            let text_left = if kind == LineKind::BlockQuote {
-                MARGIN_LEFT + line_num_width + 8
-            } else {
-                MARGIN_LEFT + line_num_width
-            };
+            // Draw task list checkbox (preview only; edit mode shows "[ ]")
+            if let (true, LineKind::TaskList { checked }) = (preview, kind) {
+                let box_left = MARGIN_LEFT + gutter;
+                let box_top = y + (line_h - CHECKBOX_SIZE) / 2;
+                self.gam.draw_rectangle(
+                    self.content,
+                    Rectangle::new_with_style(
+                        Point::new(box_left, box_top),
+                        Point::new(box_left + CHECKBOX_SIZE, box_top + CHECKBOX_SIZE),
+                        DrawStyle {
+                            fill_color: if checked { Some(self.theme.fg()) } else { None },
+                            stroke_color: Some(self.theme.fg()),
+                            stroke_width: 1,
+                        },
+                    ),
+                ).ok();
+            }
 
             // Draw line numbers if enabled
             if show_line_numbers {
-                let line_num_str = format!("{:>3} ", line_idx + 1);
+                let line_num_str = format!("{:>width$} ", line_idx + 1, width = digits);
                 self.post_text(
                     MARGIN_LEFT, y,
-                    line_num_width, line_h,
+                    gutter, line_h,
                     GlyphStyle::Monospace,
                     &line_num_str,
                 );
             }
 
-            // Render the text line
-            if !display_text.is_empty() {
+            // Render the text line; preview drops emphasis markers and
+            // styles the spans (headings keep their own style throughout)
+            if let (Some(lang), false) = (langs[i], display_text.is_empty()) {
+                self.draw_code_tokens(text_left, y, line_h, &tokenize(&display_text, lang));
+            } else if preview && kind != LineKind::CodeBlock && !display_text.is_empty() {
+                let spans = parse_inline(&display_text);
+                if matches!(style, GlyphStyle::Regular) {
+                    self.draw_inline(text_left, y, line_h, &spans);
+                } else {
+                    let plain: String = spans.iter().map(|s| s.text.as_str()).collect();
+                    self.post_text(
+                        text_left, y,
+                        self.screensize.x - text_left - MARGIN_RIGHT, line_h,
+                        style,
+                        &plain,
+                    );
+                    // The line keeps its one style, but struck spans still get their line
+                    let mut x = text_left;
+                    for span in &spans {
+                        let end = (x + span.text.chars().count() as isize * CHAR_WIDTH).min(self.screensize.x - MARGIN_RIGHT);
+                        if span.strikethrough {
+                            self.draw_strike(x, end, y, line_h);
+                        }
+                        x = end;
+                    }
+                }
+            } else if !display_text.is_empty() {
                 self.post_text(
                     text_left, y,
                     self.screensize.x - text_left - MARGIN_RIGHT, line_h,
@@ -347,25 +880,124 @@ impl Renderer {
                 );
             }
 
+            // Highlight selected text (edit mode only; columns refer to the raw line)
+            if !preview {
+                if let Some(cols) = buffer.selection_cols(line_idx).and_then(shift) {
+                    self.draw_selection(text_left, y, &display_text, cols, line_h, style);
+                }
+            }
+
+            // Highlight the match under the cursor while finding
+            if let Some(find) = &find {
+                if line_idx == buffer.cursor.line && find.position.is_some() {
+                    let from = buffer.cursor.col;
+                    if let Some(cols) = shift((from, from + find.query.chars().count())) {
+                        self.draw_selection(text_left, y, &display_text, cols, line_h, style);
+                    }
+                }
+            }
+
             // Draw cursor (only in edit mode, after text_left is calculated with line numbers)
             if !preview && line_idx == buffer.cursor.line {
-                self.draw_cursor(text_left, y, &display_text, buffer.cursor.col, line_h, style);
+                self.draw_cursor(text_left, y, &display_text, buffer.cursor.col.saturating_sub(first_col), line_h, style);
             }
-
-            y += line_h;
         }
 
-        // Status bar
-        self.draw_status_bar(buffer, doc_name, preview);
+        self.draw_scrollbar(content_top, content_bottom, buffer);
 
-        self.finish();
+        // Status bar (replaced by the query bar while finding)
+        match find {
+            Some(find) => self.draw_find_bar(&find),
+            None => self.draw_status_bar(buffer, doc_name, preview, max_line_length),
+        }
+
+        self.finish();
+    }
+
+    /// Draw emphasis spans side by side on one line: bold spans in Bold,
+    /// code in Monospace. There is no italic face, so italic text is drawn
+    /// Regular (its markers are still removed); nor a strikethrough one, so
+    /// struck spans get a line drawn through them.
+    fn draw_inline(&self, text_left: isize, y: isize, line_h: isize, spans: &[InlineSpan]) {
+        let right = self.screensize.x - MARGIN_RIGHT;
+        let mut x = text_left;
+        for span in spans {
+            if x >= right {
+                break;
+            }
+            let style = if span.code {
+                GlyphStyle::Monospace
+            } else if span.bold {
+                GlyphStyle::Bold
+            } else {
+                GlyphStyle::Regular
+            };
+            self.post_text(x, y, right - x, line_h, style, &span.text);
+            let end = (x + span.text.chars().count() as isize * CHAR_WIDTH).min(right);
+            if span.strikethrough {
+                self.draw_strike(x, end, y, line_h);
+            }
+            x = end;
+        }
+    }
+
+    /// A thin line through the middle of a text row from `x0` to `x1`.
+    fn draw_strike(&self, x0: isize, x1: isize, y: isize, line_h: isize) {
+        let mid_y = y + line_h / 2;
+        self.gam.draw_rectangle(
+            self.content,
+            Rectangle::new_with_style(
+                Point::new(x0, mid_y),
+                Point::new(x1, mid_y + 1),
+                DrawStyle {
+                    fill_color: Some(self.theme.fg()),
+                    stroke_color: None,
+                    stroke_width: 0,
+                },
+            ),
+        ).ok();
+    }
+
+    /// Draw a highlighted line of code: keywords in Bold, comments in Small,
+    /// everything else Monospace with strings underlined.
+    fn draw_code_tokens(&self, text_left: isize, y: isize, line_h: isize, tokens: &[Token]) {
+        let right = self.screensize.x - MARGIN_RIGHT;
+        let mut x = text_left;
+        for token in tokens {
+            if x >= right {
+                break;
+            }
+            let style = match token.kind {
+                TokenKind::Keyword => GlyphStyle::Bold,
+                TokenKind::Comment => GlyphStyle::Small,
+                TokenKind::String | TokenKind::Normal => GlyphStyle::Monospace,
+            };
+            self.post_text(x, y, right - x, line_h, style, &token.text);
+            let end = (x + token.text.chars().count() as isize * CHAR_WIDTH).min(right);
+            if token.kind == TokenKind::String {
+                self.gam.draw_rectangle(
+                    self.content,
+                    Rectangle::new_with_style(
+                        Point::new(x, y + line_h - 3),
+                        Point::new(end, y + line_h - 2),
+                        DrawStyle {
+                            fill_color: Some(self.theme.fg()),
+                            stroke_color: None,
+                            stroke_width: 0,
+                        },
+                    ),
+                ).ok();
+            }
+            x = end;
+        }
     }
 
-    fn draw_cursor(&self, text_left: isize, y: isize, _line: &str, col: usize, line_h: isize, _style: GlyphStyle) {
+    fn draw_cursor(&self, text_left: isize, y: isize, line: &str, col: usize, line_h: isize, _style: GlyphStyle) {
         // Approximate character width based on style (monospace-like rendering)
-        let char_width: isize = 8; // Approximate for Regular/Monospace
-        let cursor_x = text_left + (col as isize) * char_width;
-        let cursor_w = char_width.min(3);
+        // `col` is a char index, so multibyte characters advance one cell
+        let col = col.min(line.chars().count());
+        let cursor_x = text_left + (col as isize) * CHAR_WIDTH;
+        let cursor_w = CHAR_WIDTH.min(3);
 
         // Draw cursor as a thin dark rectangle
         self.gam.draw_rectangle(
@@ -374,15 +1006,51 @@ impl Renderer {
                 Point::new(cursor_x, y + 1),
                 Point::new(cursor_x + cursor_w, y + line_h - 1),
                 DrawStyle {
-                    fill_color: Some(PixelColor::Dark),
+                    fill_color: Some(self.theme.fg()),
+                    stroke_color: None,
+                    stroke_width: 0,
+                },
+            ),
+        ).ok();
+    }
+
+    /// Draw chars `cols.0..cols.1` of `line` inverted. A selection that only spans
+    /// the line break still gets a one-cell block so it stays visible.
+    fn draw_selection(&self, text_left: isize, y: isize, line: &str, cols: (usize, usize), line_h: isize, style: GlyphStyle) {
+        let (from, to) = cols;
+        let x0 = text_left + (from as isize) * CHAR_WIDTH;
+        let x1 = text_left + (to.max(from + 1) as isize) * CHAR_WIDTH;
+        self.gam.draw_rectangle(
+            self.content,
+            Rectangle::new_with_style(
+                Point::new(x0, y),
+                Point::new(x1.min(self.screensize.x - MARGIN_RIGHT), y + line_h),
+                DrawStyle {
+                    fill_color: Some(self.theme.fg()),
                     stroke_color: None,
                     stroke_width: 0,
                 },
             ),
         ).ok();
+
+        let selected: String = line.chars().skip(from).take(to.saturating_sub(from)).collect();
+        if !selected.is_empty() {
+            let mut tv = TextView::new(
+                self.content,
+                TextBounds::BoundingBox(Rectangle::new_coords(
+                    x0, y,
+                    self.screensize.x - MARGIN_RIGHT, y + line_h,
+                )),
+            );
+            tv.style = style;
+            tv.clear_area = false;
+            tv.invert = !self.theme.inverted();
+            write!(tv.text, "{}", selected).ok();
+            self.gam.post_textview(&mut tv).ok();
+        }
     }
 
-    fn draw_status_bar(&self, buffer: &TextBuffer, doc_name: &str, preview: bool) {
+    fn draw_status_bar(&self, buffer: &TextBuffer, doc_name: &str, preview: bool, max_line_length: usize) {
         let bar_top = self.screensize.y - STATUS_BAR_HEIGHT;
 
         // Separator line
@@ -392,21 +1060,35 @@ impl Renderer {
                 Point::new(0, bar_top),
                 Point::new(self.screensize.x, bar_top + 1),
                 DrawStyle {
-                    fill_color: Some(PixelColor::Dark),
+                    fill_color: Some(self.theme.fg()),
                     stroke_color: None,
                     stroke_width: 0,
                 },
             ),
         ).ok();
 
-        let mode_str = if preview { "PREVIEW" } else { "EDIT" };
+        let mut mode_str = String::from(if preview { "PREVIEW" } else { "EDIT" });
+        if buffer.selection_anchor.is_some() {
+            mode_str.push_str(" MARK");
+        }
+        let stats = buffer.stats();
+        if max_line_length > 0 && stats.longest_line > max_line_length {
+            // Warn that some line is over the configured limit
+            write!(mode_str, " >{}", max_line_length).ok();
+        }
         let modified = if buffer.modified { "*" } else { "" };
+        let words = if self.prose_word_count {
+            buffer.word_count_prose(false)
+        } else {
+            stats.words
+        };
         let status = format!(
             "{}{} {}:{} W:{}",
             doc_name, modified,
             buffer.cursor.line + 1, buffer.cursor.col + 1,
-            buffer.word_count(),
+            self.words_str(words),
         );
+        self.draw_goal_bar(bar_top, words);
 
         self.post_text(
             MARGIN_LEFT, bar_top + 4,
@@ -419,7 +1101,101 @@ impl Renderer {
             self.screensize.x / 2, bar_top + 4,
             self.screensize.x / 2 - MARGIN_RIGHT, STATUS_BAR_HEIGHT - 4,
             GlyphStyle::Small,
-            mode_str,
+            &format!("{}  {}", position_str(buffer), mode_str),
+        );
+    }
+
+    /// Word count for a status bar: "342", or "342/500 (68%)" with a goal set.
+    fn words_str(&self, words: usize) -> String {
+        if self.word_goal == 0 {
+            return words.to_string();
+        }
+        let (percent, _) = goal_progress(words, self.word_goal);
+        format!("{}/{} ({}%)", self.num(words), self.num(self.word_goal), percent)
+    }
+
+    /// With a word goal set, thicken the status bar's separator from the
+    /// left edge in proportion to progress.
+    fn draw_goal_bar(&self, bar_top: isize, words: usize) {
+        if self.word_goal == 0 {
+            return;
+        }
+        let (percent, _) = goal_progress(words, self.word_goal);
+        let width = goal_bar_width(percent, words, self.screensize.x);
+        if width > 0 {
+            self.gam.draw_rectangle(
+                self.content,
+                Rectangle::new_with_style(
+                    Point::new(0, bar_top),
+                    Point::new(width, bar_top + 3),
+                    DrawStyle {
+                        fill_color: Some(self.theme.fg()),
+                        stroke_color: None,
+                        stroke_width: 0,
+                    },
+                ),
+            ).ok();
+        }
+    }
+
+    /// Thin bar at the right edge showing which part of the document is on
+    /// screen. Not drawn when the whole document fits.
+    fn draw_scrollbar(&self, top: isize, bottom: isize, buffer: &TextBuffer) {
+        let thumb = scrollbar_thumb(buffer.viewport_top, buffer.viewport_lines, buffer.lines.len(), bottom - top);
+        if let Some((thumb_top, thumb_h)) = thumb {
+            let x = self.screensize.x - SCROLLBAR_WIDTH - 1;
+            self.gam.draw_rectangle(
+                self.content,
+                Rectangle::new_with_style(
+                    Point::new(x, top + thumb_top),
+                    Point::new(x + SCROLLBAR_WIDTH, top + thumb_top + thumb_h),
+                    DrawStyle {
+                        fill_color: Some(self.theme.fg()),
+                        stroke_color: None,
+                        stroke_width: 0,
+                    },
+                ),
+            ).ok();
+        }
+    }
+
+    fn draw_find_bar(&self, find: &FindBar) {
+        let bar_top = self.screensize.y - STATUS_BAR_HEIGHT;
+
+        self.gam.draw_rectangle(
+            self.content,
+            Rectangle::new_with_style(
+                Point::new(0, bar_top),
+                Point::new(self.screensize.x, bar_top + 1),
+                DrawStyle {
+                    fill_color: Some(self.theme.fg()),
+                    stroke_color: None,
+                    stroke_width: 0,
+                },
+            ),
+        ).ok();
+
+        let query = format!("Find: {}_", find.query);
+        self.post_text(
+            MARGIN_LEFT, bar_top + 4,
+            self.screensize.x * 2 / 3, STATUS_BAR_HEIGHT - 4,
+            GlyphStyle::Small,
+            &query,
+        );
+
+        let count = if find.query.is_empty() {
+            String::new()
+        } else {
+            match find.position {
+                Some(n) => format!("{}/{} matches", n, find.total),
+                None => format!("-/{} matches", find.total),
+            }
+        };
+        self.post_text(
+            self.screensize.x * 2 / 3, bar_top + 4,
+            self.screensize.x / 3 - MARGIN_RIGHT, STATUS_BAR_HEIGHT - 4,
+            GlyphStyle::Small,
+            &count,
         );
     }
 
@@ -435,7 +1211,7 @@ impl Renderer {
             "FILE",
         );
 
-        let items = ["New Document", "Rename", "Delete Current", "Back to Editor"];
+        let items = ["New Document", "Duplicate", "Rename", "Delete Current", "Back to Editor"];
         let list_top = 50;
         let line_height = 32;
 
@@ -461,18 +1237,22 @@ impl Renderer {
         self.finish();
     }
 
-    pub fn draw_rename_dialog(&self, new_name: &str, old_name: &str) {
+    pub fn draw_rename_dialog(&self, new_name: &str, old_name: &str, error: &str) {
         self.clear();
 
         self.post_text(
             MARGIN_LEFT, 8,
             self.screensize.x - MARGIN_LEFT * 2, 30,
             GlyphStyle::Bold,
-            "RENAME DOCUMENT",
+            if old_name.is_empty() { "NAME DOCUMENT" } else { "RENAME DOCUMENT" },
         );
 
         // Show current name
-        let current_label = format!("Current: {}", old_name);
+        let current_label = if old_name.is_empty() {
+            String::from("Current: untitled, not saved yet")
+        } else {
+            format!("Current: {}", old_name)
+        };
         self.post_text(
             MARGIN_LEFT, 60,
             self.screensize.x - MARGIN_LEFT * 2, 20,
@@ -489,6 +1269,15 @@ impl Renderer {
             &input_display,
         );
 
+        if !error.is_empty() {
+            self.post_text(
+                MARGIN_LEFT, 136,
+                self.screensize.x - MARGIN_LEFT * 2, 20,
+                GlyphStyle::Small,
+                error,
+            );
+        }
+
         self.post_text(
             MARGIN_LEFT, self.screensize.y - 40,
             self.screensize.x - MARGIN_LEFT * 2, 30,
@@ -499,30 +1288,79 @@ impl Renderer {
         self.finish();
     }
 
-    // ---- Export Menu ----
+    pub fn draw_goto_line(&self, input: &str, line_count: usize) {
+        self.clear();
+
+        self.post_text(
+            MARGIN_LEFT, 8,
+            self.screensize.x - MARGIN_LEFT * 2, 30,
+            GlyphStyle::Bold,
+            "GO TO LINE",
+        );
+
+        let range_label = format!("Lines 1-{}", line_count);
+        self.post_text(
+            MARGIN_LEFT, 60,
+            self.screensize.x - MARGIN_LEFT * 2, 20,
+            GlyphStyle::Small,
+            &range_label,
+        );
+
+        // Input field with cursor
+        let input_display = format!("Line: {}|", input);
+        self.post_text(
+            MARGIN_LEFT, 100,
+            self.screensize.x - MARGIN_LEFT * 2, 24,
+            GlyphStyle::Regular,
+            &input_display,
+        );
+
+        self.post_text(
+            MARGIN_LEFT, self.screensize.y - 40,
+            self.screensize.x - MARGIN_LEFT * 2, 30,
+            GlyphStyle::Small,
+            "F4=cancel  ENTER=go",
+        );
 
-    pub fn draw_export_menu(&self, cursor: usize) {
+        self.finish();
+    }
+
+    // ---- Outline ----
+
+    /// Headings (line index, level, title) indented by level; the list
+    /// scrolls to keep the highlighted heading on screen.
+    pub fn draw_outline(&self, headings: &[(usize, usize, String)], cursor: usize) {
         self.clear();
 
         self.post_text(
             MARGIN_LEFT, 8,
             self.screensize.x - MARGIN_LEFT * 2, 30,
             GlyphStyle::Bold,
-            "EXPORT",
+            "OUTLINE",
         );
 
-        let items = ["TCP (port 7879)", "USB Keyboard Autotype"];
-        let list_top = 60;
-        let line_height = 32;
+        let list_top = 50;
+        let line_height = 28;
+        if headings.is_empty() {
+            self.post_text(
+                20, list_top,
+                self.screensize.x - 40, 20,
+                GlyphStyle::Small,
+                "No headings. Lines starting with # appear here.",
+            );
+        }
 
-        for (i, item) in items.iter().enumerate() {
-            let y = list_top + (i as isize) * line_height;
-            let marker = if i == cursor { "> " } else { "  " };
-            let label = format!("{}{}", marker, item);
+        let rows = ((self.screensize.y - 50 - list_top) / line_height).max(1) as usize;
+        let first = cursor.saturating_sub(rows - 1);
+        for (row, (line, level, title)) in headings.iter().enumerate().skip(first).take(rows) {
+            let y = list_top + ((row - first) as isize) * line_height;
+            let marker = if row == cursor { "> " } else { "  " };
+            let indent = "  ".repeat(level - 1);
+            let label = format!("{}{}{}  ({})", marker, indent, truncate_str(title, 30), line + 1);
             self.post_text(
                 20, y,
                 self.screensize.x - 40, line_height - 2,
-                GlyphStyle::Regular,
+                if *level == 1 { GlyphStyle::Bold } else { GlyphStyle::Regular },
                 &label,
             );
         }
@@ -531,207 +1369,790 @@ impl Renderer {
             MARGIN_LEFT, self.screensize.y - 40,
             self.screensize.x - MARGIN_LEFT * 2, 30,
             GlyphStyle::Small,
-            "F4=back  ENTER=select",
+            "F4=back  ENTER=jump to heading",
         );
 
         self.finish();
     }
 
-    // ---- Journal ----
-
-    pub fn draw_journal(&self, buffer: &TextBuffer, date: &str) {
+    pub fn draw_goto_doc(&self, input: &str, message: &str, confirm_create: bool) {
         self.clear();
 
-        // Header with date and weekday
-        let weekday = date_to_epoch_ms(date)
-            .map(epoch_ms_to_weekday)
-            .unwrap_or("???");
-        let header = format!("JOURNAL  {} {}", date, weekday);
         self.post_text(
-            MARGIN_LEFT, 4,
-            self.screensize.x - MARGIN_LEFT * 2, 24,
+            MARGIN_LEFT, 8,
+            self.screensize.x - MARGIN_LEFT * 2, 30,
             GlyphStyle::Bold,
-            &header,
+            "GO TO DOCUMENT",
         );
 
-        // Navigation hint
+        // Input field with cursor
+        let input_display = format!("Name: {}|", input);
         self.post_text(
-            MARGIN_LEFT, 26,
-            self.screensize.x - MARGIN_LEFT * 2, 16,
-            GlyphStyle::Small,
-            "F1=menu F3=save F4=back  Esc[/]=nav",
+            MARGIN_LEFT, 60,
+            self.screensize.x - MARGIN_LEFT * 2, 24,
+            GlyphStyle::Regular,
+            &input_display,
         );
 
-        // Separator
-        self.gam.draw_rectangle(
-            self.content,
-            Rectangle::new_with_style(
-                Point::new(MARGIN_LEFT, 44),
-                Point::new(self.screensize.x - MARGIN_RIGHT, 45),
-                DrawStyle {
-                    fill_color: Some(PixelColor::Dark),
-                    stroke_color: None,
-                    stroke_width: 0,
-                },
-            ),
-        ).ok();
-
-        // Content area
-        let content_top = 48isize;
-        let content_bottom = self.screensize.y - STATUS_BAR_HEIGHT;
-
-        let mut y = content_top;
-        let end_line = (buffer.viewport_top + buffer.viewport_lines).min(buffer.lines.len());
-
-        for line_idx in buffer.viewport_top..end_line {
-            if y + LINE_HEIGHT_REGULAR > content_bottom {
-                break;
-            }
-            let line = &buffer.lines[line_idx];
-            if !line.is_empty() {
-                self.post_text(
-                    MARGIN_LEFT, y,
-                    self.screensize.x - MARGIN_LEFT * 2, LINE_HEIGHT_REGULAR,
-                    GlyphStyle::Regular,
-                    line,
-                );
-            }
-
-            // Cursor
-            if line_idx == buffer.cursor.line {
-                self.draw_cursor(MARGIN_LEFT, y, line, buffer.cursor.col, LINE_HEIGHT_REGULAR, GlyphStyle::Regular);
-            }
-
-            y += LINE_HEIGHT_REGULAR;
+        let prompt = if confirm_create {
+            format!("No document '{}'.\ny = Create it\nn = Keep typing", input.trim())
+        } else {
+            message.to_string()
+        };
+        if !prompt.is_empty() {
+            self.post_text(
+                MARGIN_LEFT, 100,
+                self.screensize.x - MARGIN_LEFT * 2, 70,
+                GlyphStyle::Small,
+                &prompt,
+            );
         }
 
-        // Word count in status
-        let status = format!("Words: {}", buffer.word_count());
-        let bar_top = self.screensize.y - STATUS_BAR_HEIGHT;
-        self.gam.draw_rectangle(
-            self.content,
-            Rectangle::new_with_style(
-                Point::new(0, bar_top),
-                Point::new(self.screensize.x, bar_top + 1),
-                DrawStyle {
-                    fill_color: Some(PixelColor::Dark),
-                    stroke_color: None,
-                    stroke_width: 0,
-                },
-            ),
-        ).ok();
         self.post_text(
-            MARGIN_LEFT, bar_top + 4,
-            self.screensize.x - MARGIN_LEFT * 2, STATUS_BAR_HEIGHT - 4,
+            MARGIN_LEFT, self.screensize.y - 40,
+            self.screensize.x - MARGIN_LEFT * 2, 30,
             GlyphStyle::Small,
-            &status,
+            "F4=cancel  ENTER=open",
         );
 
         self.finish();
     }
 
-    // ---- Journal Search ----
+    // ---- Quick Note ----
 
-    pub fn draw_journal_search(&self, query: &str, results: &[(String, String)], cursor: usize) {
+    pub fn draw_quick_note(&self, input: &str) {
         self.clear();
 
         self.post_text(
             MARGIN_LEFT, 8,
-            self.screensize.x - MARGIN_LEFT * 2, 24,
+            self.screensize.x - MARGIN_LEFT * 2, 30,
             GlyphStyle::Bold,
-            "SEARCH JOURNAL",
+            "QUICK NOTE",
         );
 
-        // Search input
-        let input_display = format!("Query: {}|", query);
+        let input_display = format!("Note: {}|", input);
         self.post_text(
-            MARGIN_LEFT, 40,
-            self.screensize.x - MARGIN_LEFT * 2, 20,
+            MARGIN_LEFT, 60,
+            self.screensize.x - MARGIN_LEFT * 2, 24,
             GlyphStyle::Regular,
             &input_display,
         );
 
-        // Results
-        let results_top = 70;
-        let line_height = 28;
+        self.post_text(
+            MARGIN_LEFT, 100,
+            self.screensize.x - MARGIN_LEFT * 2, 40,
+            GlyphStyle::Small,
+            "Added to today's journal entry\nwith the current time.",
+        );
 
-        if results.is_empty() && !query.is_empty() {
-            self.post_text(
-                20, results_top as isize,
-                self.screensize.x - 40, 20,
-                GlyphStyle::Small,
-                "No matches found. Press ENTER to search.",
-            );
-        } else if results.is_empty() {
-            self.post_text(
-                20, results_top as isize,
-                self.screensize.x - 40, 20,
-                GlyphStyle::Small,
+        self.post_text(
+            MARGIN_LEFT, self.screensize.y - 40,
+            self.screensize.x - MARGIN_LEFT * 2, 30,
+            GlyphStyle::Small,
+            "F4=cancel  ENTER=add",
+        );
+
+        self.finish();
+    }
+
+    // ---- Export Menu ----
+
+    /// `status` reports the last on-device export (empty = nothing to say).
+    pub fn draw_export_menu(&self, cursor: usize, page_size: u16, status: &str) {
+        self.clear();
+
+        self.post_text(
+            MARGIN_LEFT, 8,
+            self.screensize.x - MARGIN_LEFT * 2, 30,
+            GlyphStyle::Bold,
+            "EXPORT",
+        );
+
+        let pages = if page_size == 0 {
+            String::from("Pages: off")
+        } else {
+            format!("Pages: {} chars", self.num(page_size as usize))
+        };
+        let items = ["TCP (port 7879)", "USB Keyboard Autotype", "Save .md to PDDB", "Save .html to PDDB", "Serial console", "QR codes on screen", pages.as_str()];
+        let list_top = 60;
+        let line_height = 32;
+
+        for (i, item) in items.iter().enumerate() {
+            let y = list_top + (i as isize) * line_height;
+            let marker = if i == cursor { "> " } else { "  " };
+            let label = format!("{}{}", marker, item);
+            self.post_text(
+                20, y,
+                self.screensize.x - 40, line_height - 2,
+                GlyphStyle::Regular,
+                &label,
+            );
+        }
+
+        self.post_text(
+            MARGIN_LEFT, self.screensize.y - 40,
+            self.screensize.x - MARGIN_LEFT * 2, 30,
+            GlyphStyle::Small,
+            "F4=back  ENTER=select",
+        );
+
+        if !status.is_empty() {
+            self.post_text(
+                MARGIN_LEFT, self.screensize.y - 70,
+                self.screensize.x - MARGIN_LEFT * 2, 24,
+                GlyphStyle::Small,
+                status,
+            );
+        }
+
+        self.finish();
+    }
+
+    /// `resume` is (bytes already typed, total bytes) when an earlier USB
+    /// export of this document was interrupted.
+    pub fn draw_export_confirm(&self, target: ExportTarget, resume: Option<(usize, usize)>) {
+        self.clear();
+
+        self.post_text(
+            MARGIN_LEFT, 40,
+            self.screensize.x - MARGIN_LEFT * 2, 30,
+            GlyphStyle::Bold,
+            "Ready to Export",
+        );
+
+        let msg = match target {
+            ExportTarget::UsbAutotype => {
+                "The document will be typed into whichever window has focus on the host. Focus the target window first."
+            }
+            ExportTarget::Tcp => {
+                "The device will listen on port 7879. Start your client (nc <ip> 7879) after pressing Enter."
+            }
+            ExportTarget::Pddb | ExportTarget::PddbHtml => {
+                "The document will be saved in the writer.exports dictionary."
+            }
+            ExportTarget::Console => {
+                "The document will be written to the debug console. Start capturing the serial port first."
+            }
+            ExportTarget::Qr => {
+                "The document will be shown as a series of QR codes to scan with a phone."
+            }
+        };
+        self.post_text(
+            MARGIN_LEFT, 80,
+            self.screensize.x - MARGIN_LEFT * 2, 80,
+            GlyphStyle::Regular,
+            msg,
+        );
+
+        let keys = match resume {
+            Some((sent, total)) => format!(
+                "Last export stopped at {}%.\nENTER = Continue\nr = Start over\nF4 = Cancel",
+                sent * 100 / total.max(1),
+            ),
+            None => String::from("ENTER = Begin\nF4 = Cancel"),
+        };
+        self.post_text(
+            20, 180,
+            self.screensize.x - 40, 80,
+            GlyphStyle::Regular,
+            &keys,
+        );
+
+        self.finish();
+    }
+
+    // ---- Backup ----
+
+    /// `status` reports the last backup or restore (empty = nothing to say).
+    pub fn draw_backup(&self, actions: &[&str], cursor: usize, status: &str) {
+        self.clear();
+
+        self.post_text(
+            MARGIN_LEFT, 8,
+            self.screensize.x - MARGIN_LEFT * 2, 30,
+            GlyphStyle::Bold,
+            "BACKUP",
+        );
+
+        self.post_text(
+            MARGIN_LEFT, 40,
+            self.screensize.x - MARGIN_LEFT * 2, 24,
+            GlyphStyle::Small,
+            "Every document in one file",
+        );
+
+        let list_top = 76;
+        let line_height = 32;
+        for (i, action) in actions.iter().enumerate() {
+            let y = list_top + (i as isize) * line_height;
+            let marker = if i == cursor { "> " } else { "  " };
+            self.post_text(
+                20, y,
+                self.screensize.x - 40, line_height - 2,
+                GlyphStyle::Regular,
+                &format!("{}{}", marker, action),
+            );
+        }
+
+        if !status.is_empty() {
+            self.post_text(
+                MARGIN_LEFT, self.screensize.y - 70,
+                self.screensize.x - MARGIN_LEFT * 2, 24,
+                GlyphStyle::Small,
+                status,
+            );
+        }
+
+        self.post_text(
+            MARGIN_LEFT, self.screensize.y - 40,
+            self.screensize.x - MARGIN_LEFT * 2, 30,
+            GlyphStyle::Small,
+            "F4=back  ENTER=select",
+        );
+
+        self.finish();
+    }
+
+    // ---- Export Waiting ----
+
+    pub fn draw_export_waiting(&self, timeout_secs: u16, cancelling: bool) {
+        self.clear();
+
+        self.post_text(
+            MARGIN_LEFT, 40,
+            self.screensize.x - MARGIN_LEFT * 2, 30,
+            GlyphStyle::Bold,
+            "Waiting for connection...",
+        );
+
+        let msg = if cancelling {
+            String::from("Cancelling...")
+        } else {
+            format!(
+                "Listening on port {}. Run nc <ip> {} on the host. Gives up after {}s.",
+                EXPORT_PORT, EXPORT_PORT, timeout_secs,
+            )
+        };
+        self.post_text(
+            MARGIN_LEFT, 80,
+            self.screensize.x - MARGIN_LEFT * 2, 80,
+            GlyphStyle::Regular,
+            &msg,
+        );
+
+        self.post_text(
+            MARGIN_LEFT, self.screensize.y - 40,
+            self.screensize.x - MARGIN_LEFT * 2, 30,
+            GlyphStyle::Small,
+            "F4 to cancel",
+        );
+
+        self.finish();
+    }
+
+    // ---- Export Progress ----
+
+    /// `lines` is (lines typed, total lines) when autotyping a line at a time.
+    pub fn draw_export_progress(&self, typed: usize, total: usize, lines: Option<(usize, usize)>, cancelling: bool) {
+        self.clear();
+
+        self.post_text(
+            MARGIN_LEFT, 40,
+            self.screensize.x - MARGIN_LEFT * 2, 30,
+            GlyphStyle::Bold,
+            "Typing via USB...",
+        );
+
+        self.post_text(
+            MARGIN_LEFT, 80,
+            self.screensize.x - MARGIN_LEFT * 2, 30,
+            GlyphStyle::Regular,
+            &match lines {
+                Some((line, lines)) => format!("Line {} / {}", self.num(line), self.num(lines)),
+                None => format!("Typed {} / {} characters", typed, total),
+            },
+        );
+
+        // Progress bar: outline, then the filled fraction
+        let bar_left = MARGIN_LEFT;
+        let bar_right = self.screensize.x - MARGIN_RIGHT;
+        let bar_top = 120;
+        let bar_h = 20;
+        self.gam.draw_rectangle(
+            self.content,
+            Rectangle::new_with_style(
+                Point::new(bar_left, bar_top),
+                Point::new(bar_right, bar_top + bar_h),
+                DrawStyle {
+                    fill_color: None,
+                    stroke_color: Some(self.theme.fg()),
+                    stroke_width: 1,
+                },
+            ),
+        ).ok();
+        let filled = (bar_right - bar_left) * typed.min(total) as isize / total.max(1) as isize;
+        if filled > 0 {
+            self.gam.draw_rectangle(
+                self.content,
+                Rectangle::new_with_style(
+                    Point::new(bar_left, bar_top),
+                    Point::new(bar_left + filled, bar_top + bar_h),
+                    DrawStyle {
+                        fill_color: Some(self.theme.fg()),
+                        stroke_color: None,
+                        stroke_width: 0,
+                    },
+                ),
+            ).ok();
+        }
+
+        if cancelling {
+            self.post_text(
+                MARGIN_LEFT, 160,
+                self.screensize.x - MARGIN_LEFT * 2, 30,
+                GlyphStyle::Regular,
+                "Stopping after this chunk...",
+            );
+        }
+
+        self.post_text(
+            MARGIN_LEFT, self.screensize.y - 40,
+            self.screensize.x - MARGIN_LEFT * 2, 30,
+            GlyphStyle::Small,
+            "F4 to abort (the next export can continue)",
+        );
+
+        self.finish();
+    }
+
+    // ---- USB Not Ready ----
+
+    pub fn draw_usb_not_ready(&self) {
+        self.clear();
+
+        self.post_text(
+            MARGIN_LEFT, 40,
+            self.screensize.x - MARGIN_LEFT * 2, 30,
+            GlyphStyle::Bold,
+            "USB Not Connected",
+        );
+
+        self.post_text(
+            MARGIN_LEFT, 80,
+            self.screensize.x - MARGIN_LEFT * 2, 80,
+            GlyphStyle::Regular,
+            "Connect the device to the host by USB, wait for it to appear as a keyboard, then retry.",
+        );
+
+        self.post_text(
+            20, 180,
+            self.screensize.x - 40, 80,
+            GlyphStyle::Regular,
+            "ENTER = Retry\nF4 = Cancel",
+        );
+
+        self.finish();
+    }
+
+    /// One chunk of a QR export: `page` is 0-based of `total`. The code
+    /// itself is shown by `show_qr`; this screen says where in the
+    /// sequence the user is and shows the payload as text.
+    pub fn draw_qr_export(&self, chunk: &str, page: usize, total: usize) {
+        self.clear();
+
+        self.post_text(
+            MARGIN_LEFT, 8,
+            self.screensize.x - MARGIN_LEFT * 2, 30,
+            GlyphStyle::Bold,
+            &format!("QR EXPORT  {} / {}", page + 1, total),
+        );
+
+        self.post_text(
+            MARGIN_LEFT, 50,
+            self.screensize.x - MARGIN_LEFT * 2, 40,
+            GlyphStyle::Regular,
+            "Scan every code in any order; each starts with its number.",
+        );
+
+        self.post_text(
+            MARGIN_LEFT, 100,
+            self.screensize.x - MARGIN_LEFT * 2, self.screensize.y - 190,
+            GlyphStyle::Monospace,
+            chunk,
+        );
+
+        self.post_text(
+            MARGIN_LEFT, self.screensize.y - 80,
+            self.screensize.x - MARGIN_LEFT * 2, 70,
+            GlyphStyle::Small,
+            "ENTER = show QR code\nLEFT/RIGHT = previous/next\nF4 = back",
+        );
+
+        self.finish();
+    }
+
+    pub fn show_qr(&self, caption: &str, payload: &str) {
+        if let Err(e) = self.modals.show_notification(caption, Some(payload)) {
+            log::error!("Failed to show QR code: {:?}", e);
+        }
+    }
+
+    // ---- Journal ----
+
+    /// `streak` is the run of consecutive days journaled (0 hides it).
+    pub fn draw_journal(&self, buffer: &TextBuffer, date: &str, date_format: DateFormat, streak: usize) {
+        self.clear();
+
+        // Header with date and weekday
+        let mut header = format!("JOURNAL  {}", format_date(date, date_format));
+        if streak > 0 {
+            write!(header, "  Streak: {}", streak).ok();
+        }
+        self.post_text(
+            MARGIN_LEFT, 4,
+            self.screensize.x - MARGIN_LEFT * 2, 24,
+            GlyphStyle::Bold,
+            &header,
+        );
+
+        // Navigation hint
+        self.post_text(
+            MARGIN_LEFT, 26,
+            self.screensize.x - MARGIN_LEFT * 2, 16,
+            GlyphStyle::Small,
+            "F1=menu F3=save F4=back  Esc[/]=nav",
+        );
+
+        // Separator
+        self.gam.draw_rectangle(
+            self.content,
+            Rectangle::new_with_style(
+                Point::new(MARGIN_LEFT, 44),
+                Point::new(self.screensize.x - MARGIN_RIGHT, 45),
+                DrawStyle {
+                    fill_color: Some(self.theme.fg()),
+                    stroke_color: None,
+                    stroke_width: 0,
+                },
+            ),
+        ).ok();
+
+        // Content area
+        let (content_top, content_bottom) = self.text_area(BufferView::Journal);
+
+        let mut y = content_top;
+        let end_line = (buffer.viewport_top + buffer.viewport_lines).min(buffer.lines.len());
+
+        for line_idx in buffer.viewport_top..end_line {
+            if y + LINE_HEIGHT_REGULAR > content_bottom {
+                break;
+            }
+            let line = &buffer.lines[line_idx];
+            if !line.is_empty() {
+                self.post_text(
+                    MARGIN_LEFT, y,
+                    self.screensize.x - MARGIN_LEFT * 2, LINE_HEIGHT_REGULAR,
+                    GlyphStyle::Regular,
+                    line,
+                );
+            }
+
+            // Cursor
+            if line_idx == buffer.cursor.line {
+                self.draw_cursor(MARGIN_LEFT, y, line, buffer.cursor.col, LINE_HEIGHT_REGULAR, GlyphStyle::Regular);
+            }
+
+            y += LINE_HEIGHT_REGULAR;
+        }
+        self.draw_scrollbar(content_top, content_bottom, buffer);
+
+        // Word count and position in status
+        let words = buffer.word_count();
+        let status = format!("Words: {}  {}", self.words_str(words), position_str(buffer));
+        let bar_top = self.screensize.y - STATUS_BAR_HEIGHT;
+        self.gam.draw_rectangle(
+            self.content,
+            Rectangle::new_with_style(
+                Point::new(0, bar_top),
+                Point::new(self.screensize.x, bar_top + 1),
+                DrawStyle {
+                    fill_color: Some(self.theme.fg()),
+                    stroke_color: None,
+                    stroke_width: 0,
+                },
+            ),
+        ).ok();
+        self.draw_goal_bar(bar_top, words);
+        self.post_text(
+            MARGIN_LEFT, bar_top + 4,
+            self.screensize.x - MARGIN_LEFT * 2, STATUS_BAR_HEIGHT - 4,
+            GlyphStyle::Small,
+            &status,
+        );
+
+        self.finish();
+    }
+
+    // ---- Journal Search ----
+
+    pub fn draw_journal_search(&self, query: &str, results: &[(String, usize, String)], cursor: usize) {
+        self.clear();
+
+        self.post_text(
+            MARGIN_LEFT, 8,
+            self.screensize.x - MARGIN_LEFT * 2, 24,
+            GlyphStyle::Bold,
+            "SEARCH JOURNAL",
+        );
+
+        // Search input
+        let input_display = format!("Query: {}|", query);
+        self.post_text(
+            MARGIN_LEFT, 40,
+            self.screensize.x - MARGIN_LEFT * 2, 20,
+            GlyphStyle::Regular,
+            &input_display,
+        );
+
+        // Results
+        let results_top = 70;
+        let line_height = 28;
+
+        if results.is_empty() && !query.is_empty() {
+            self.post_text(
+                20, results_top as isize,
+                self.screensize.x - 40, 20,
+                GlyphStyle::Small,
+                "No matches found. Press ENTER to search.",
+            );
+        } else if results.is_empty() {
+            self.post_text(
+                20, results_top as isize,
+                self.screensize.x - 40, 20,
+                GlyphStyle::Small,
                 "Type query, then ENTER to search",
             );
         } else {
-            for (i, (date, line)) in results.iter().enumerate() {
+            for (i, (date, _, line)) in results.iter().enumerate() {
                 let y = results_top as isize + (i as isize) * line_height;
                 if y + line_height > self.screensize.y - 40 {
                     break;
                 }
 
-                // Highlight selected result
-                if i == cursor {
-                    // Draw selection background
-                    self.gam.draw_rectangle(
-                        self.content,
-                        Rectangle::new_with_style(
-                            Point::new(8, y - 2),
-                            Point::new(self.screensize.x - 8, y + line_height - 4),
-                            DrawStyle {
-                                fill_color: Some(PixelColor::Dark),
-                                stroke_color: None,
-                                stroke_width: 0,
-                            },
-                        ),
-                    ).ok();
-                }
+                // Highlight selected result
+                if i == cursor {
+                    // Draw selection background
+                    self.gam.draw_rectangle(
+                        self.content,
+                        Rectangle::new_with_style(
+                            Point::new(8, y - 2),
+                            Point::new(self.screensize.x - 8, y + line_height - 4),
+                            DrawStyle {
+                                fill_color: Some(self.theme.fg()),
+                                stroke_color: None,
+                                stroke_width: 0,
+                            },
+                        ),
+                    ).ok();
+                }
+
+                // Monospace so the match highlight lines up with the text
+                let (window, hl_start, hl_end) = match_window(line, query, 28);
+                let prefix = format!("{}: ", date);
+                let truncated = format!("{}{}", prefix, window);
+                let match_left = 12 + prefix.chars().count() as isize * CHAR_WIDTH;
+
+                // Create inverted text for selected item
+                if i == cursor {
+                    let mut tv = TextView::new(
+                        self.content,
+                        TextBounds::BoundingBox(Rectangle::new_coords(
+                            12, y,
+                            self.screensize.x - 12, y + line_height - 2,
+                        ))
+                    );
+                    tv.style = GlyphStyle::Monospace;
+                    tv.clear_area = false;
+                    tv.invert = !self.theme.inverted();
+                    write!(tv.text, "{}", truncated).ok();
+                    self.gam.post_textview(&mut tv).ok();
+                    // The row is already inverted: underline the match instead
+                    if hl_end > hl_start {
+                        self.gam.draw_rectangle(
+                            self.content,
+                            Rectangle::new_with_style(
+                                Point::new(match_left + hl_start as isize * CHAR_WIDTH, y + line_height - 8),
+                                Point::new(match_left + hl_end as isize * CHAR_WIDTH, y + line_height - 6),
+                                DrawStyle {
+                                    fill_color: Some(self.theme.bg()),
+                                    stroke_color: None,
+                                    stroke_width: 0,
+                                },
+                            ),
+                        ).ok();
+                    }
+                } else {
+                    self.post_text(
+                        12, y,
+                        self.screensize.x - 24, line_height - 2,
+                        GlyphStyle::Monospace,
+                        &truncated,
+                    );
+                    if hl_end > hl_start {
+                        self.draw_selection(match_left, y, &window, (hl_start, hl_end), line_height - 4, GlyphStyle::Monospace);
+                    }
+                }
+            }
+        }
+
+        // Help text
+        let help_text = if results.is_empty() {
+            "F4=back  ENTER=search"
+        } else {
+            "↑↓=select  ENTER=go  F4=back"
+        };
+        self.post_text(
+            MARGIN_LEFT, self.screensize.y - 36,
+            self.screensize.x - MARGIN_LEFT * 2, 28,
+            GlyphStyle::Small,
+            help_text,
+        );
+
+        self.finish();
+    }
+
+    pub fn draw_journal_stats(&self, from: &str, to: &str, stats: &JournalStats) {
+        self.clear();
+
+        self.post_text(
+            MARGIN_LEFT, 20,
+            self.screensize.x - MARGIN_LEFT * 2, 30,
+            GlyphStyle::Bold,
+            "JOURNAL STATS",
+        );
+
+        let range = if from == to {
+            from.to_string()
+        } else {
+            format!("{} to {}", from, to)
+        };
+        self.post_text(
+            MARGIN_LEFT, 50,
+            self.screensize.x - MARGIN_LEFT * 2, 20,
+            GlyphStyle::Small,
+            &range,
+        );
+
+        let longest = stats.longest_entry_date.as_deref().unwrap_or("-");
+        let lines = [
+            format!("Entries: {}", self.num(stats.entries)),
+            format!("Total words: {}", self.num(stats.total_words)),
+            format!("Average words: {}", self.num(stats.avg_words)),
+            format!("Longest entry: {}", longest),
+        ];
+
+        let stats_top = 90;
+        let line_height = 28;
+
+        for (i, line) in lines.iter().enumerate() {
+            let y = stats_top + (i as isize) * line_height;
+            self.post_text(
+                30, y,
+                self.screensize.x - 60, line_height - 2,
+                GlyphStyle::Regular,
+                line,
+            );
+        }
+
+        self.post_text(
+            MARGIN_LEFT, self.screensize.y - 50,
+            self.screensize.x - MARGIN_LEFT * 2, 40,
+            GlyphStyle::Small,
+            "any key=back",
+        );
+
+        self.finish();
+    }
+
+    // ---- Document Stats ----
+
+    pub fn draw_doc_stats(&self, buffer: &TextBuffer, doc_name: &str) {
+        self.clear();
+
+        self.post_text(
+            MARGIN_LEFT, 20,
+            self.screensize.x - MARGIN_LEFT * 2, 30,
+            GlyphStyle::Bold,
+            "DOCUMENT STATS",
+        );
+
+        self.post_text(
+            MARGIN_LEFT, 50,
+            self.screensize.x - MARGIN_LEFT * 2, 20,
+            GlyphStyle::Small,
+            doc_name,
+        );
+
+        let stats_top = 90;
+        let line_height = 28;
+
+        for (i, line) in self.buffer_stat_lines(buffer, buffer.stats()).iter().enumerate() {
+            let y = stats_top + (i as isize) * line_height;
+            self.post_text(
+                30, y,
+                self.screensize.x - 60, line_height - 2,
+                GlyphStyle::Regular,
+                line,
+            );
+        }
+
+        self.post_text(
+            MARGIN_LEFT, self.screensize.y - 50,
+            self.screensize.x - MARGIN_LEFT * 2, 40,
+            GlyphStyle::Small,
+            "any key=back",
+        );
+
+        self.finish();
+    }
 
-                let truncated = format!("{}: {}", date, truncate_str(line, 28));
+    // ---- Sprint Select ----
 
-                // Create inverted text for selected item
-                if i == cursor {
-                    let mut tv = TextView::new(
-                        self.content,
-                        TextBounds::BoundingBox(Rectangle::new_coords(
-                            12, y,
-                            self.screensize.x - 12, y + line_height - 2,
-                        ))
-                    );
-                    tv.style = GlyphStyle::Small;
-                    tv.clear_area = false;
-                    tv.invert = true;
-                    use std::fmt::Write;
-                    write!(tv.text, "{}", truncated).ok();
-                    self.gam.post_textview(&mut tv).ok();
-                } else {
-                    self.post_text(
-                        12, y,
-                        self.screensize.x - 24, line_height - 2,
-                        GlyphStyle::Small,
-                        &truncated,
-                    );
-                }
-            }
+    /// Sprint length picker shown before a typewriter session (0 = no timer).
+    pub fn draw_sprint_select(&self, minutes: &[u64], cursor: usize) {
+        self.clear();
+
+        self.post_text(
+            MARGIN_LEFT, 8,
+            self.screensize.x - MARGIN_LEFT * 2, 30,
+            GlyphStyle::Bold,
+            "TYPEWRITER SPRINT",
+        );
+
+        let list_top = 50;
+        let line_height = 32;
+
+        for (i, &m) in minutes.iter().enumerate() {
+            let y = list_top + (i as isize) * line_height;
+            let marker = if i == cursor { "> " } else { "  " };
+            let label = if m == 0 {
+                format!("{}No timer", marker)
+            } else {
+                format!("{}{} minutes", marker, m)
+            };
+            self.post_text(
+                20, y,
+                self.screensize.x - 40, line_height - 2,
+                GlyphStyle::Regular,
+                &label,
+            );
         }
 
-        // Help text
-        let help_text = if results.is_empty() {
-            "F4=back  ENTER=search"
-        } else {
-            "↑↓=select  ENTER=go  F4=back"
-        };
         self.post_text(
-            MARGIN_LEFT, self.screensize.y - 36,
-            self.screensize.x - MARGIN_LEFT * 2, 28,
+            MARGIN_LEFT, self.screensize.y - 40,
+            self.screensize.x - MARGIN_LEFT * 2, 30,
             GlyphStyle::Small,
-            help_text,
+            "ENTER=start  F4=back",
         );
 
         self.finish();
@@ -739,11 +2160,12 @@ impl Renderer {
 
     // ---- Typewriter ----
 
-    pub fn draw_typewriter(&self, buffer: &TextBuffer) {
+    /// `remaining_secs` is the sprint countdown, shown instead of the
+    /// elapsed time when a sprint is running.
+    pub fn draw_typewriter(&self, buffer: &TextBuffer, elapsed_secs: u64, wpm: usize, remaining_secs: Option<u64>) {
         self.clear();
 
-        let content_top = 4isize;
-        let content_bottom = self.screensize.y - STATUS_BAR_HEIGHT;
+        let (content_top, content_bottom) = self.text_area(BufferView::Typewriter);
 
         let mut y = content_top;
         let end_line = (buffer.viewport_top + buffer.viewport_lines).min(buffer.lines.len());
@@ -778,14 +2200,21 @@ impl Renderer {
                 Point::new(0, bar_top),
                 Point::new(self.screensize.x, bar_top + 1),
                 DrawStyle {
-                    fill_color: Some(PixelColor::Dark),
+                    fill_color: Some(self.theme.fg()),
                     stroke_color: None,
                     stroke_width: 0,
                 },
             ),
         ).ok();
 
-        let status = format!("TYPEWRITER  W:{}  F1=menu F4=done", buffer.word_count());
+        let clock = match remaining_secs {
+            Some(left) => format!("{} left", format_duration(left)),
+            None => format_duration(elapsed_secs),
+        };
+        let status = format!(
+            "TYPEWRITER  W:{}  {}  {} wpm  F4=done",
+            buffer.word_count(), clock, wpm,
+        );
         self.post_text(
             MARGIN_LEFT, bar_top + 4,
             self.screensize.x - MARGIN_LEFT * 2, STATUS_BAR_HEIGHT - 4,
@@ -798,7 +2227,8 @@ impl Renderer {
 
     // ---- Typewriter Done ----
 
-    pub fn draw_typewriter_done(&self, words: usize, chars: usize, lines: usize) {
+    /// `chars` counts line breaks; `visible_chars` is typed characters only.
+    pub fn draw_typewriter_done(&self, buffer: &TextBuffer, top_words: &[(String, usize)], elapsed_secs: u64, avg_wpm: usize, sprint_secs: Option<u64>) {
         self.clear();
 
         self.post_text(
@@ -808,11 +2238,20 @@ impl Renderer {
             "SESSION COMPLETE",
         );
 
-        let stats = [
-            format!("Words: {}", format_number(words)),
-            format!("Characters: {}", format_number(chars)),
-            format!("Lines: {}", format_number(lines)),
-        ];
+        let counts = buffer.stats();
+        let mut stats = self.buffer_stat_lines(buffer, counts).to_vec();
+        stats.push(format!("Session: {}, {} wpm", format_duration(elapsed_secs), avg_wpm));
+        if let Some(sprint) = sprint_secs {
+            let outcome = if elapsed_secs >= sprint { "complete" } else { "ended early" };
+            stats.push(format!("Sprint of {} min {}", sprint / 60, outcome));
+        }
+        if self.word_goal > 0 {
+            let words = counts.words;
+            stats.push(match goal_progress(words, self.word_goal) {
+                (_, true) => format!("Goal of {} words met", self.num(self.word_goal)),
+                (percent, false) => format!("Goal: {} of {} words ({}%)", self.num(words), self.num(self.word_goal), percent),
+            });
+        }
 
         let stats_top = 70;
         let line_height = 28;
@@ -827,6 +2266,20 @@ impl Renderer {
             );
         }
 
+        // Most used words, skipping stopwords
+        if !top_words.is_empty() {
+            let list: Vec<String> = top_words.iter()
+                .map(|(word, count)| format!("{} ({})", word, count))
+                .collect();
+            let y = stats_top + (stats.len() as isize) * line_height + 12;
+            self.post_text(
+                30, y,
+                self.screensize.x - 60, line_height * 2,
+                GlyphStyle::Small,
+                &format!("Top words: {}", list.join(", ")),
+            );
+        }
+
         self.post_text(
             MARGIN_LEFT, self.screensize.y - 50,
             self.screensize.x - MARGIN_LEFT * 2, 40,
@@ -836,4 +2289,500 @@ impl Renderer {
 
         self.finish();
     }
+
+    /// Count lines shared by the session summary and document stats screens.
+    fn buffer_stat_lines(&self, buffer: &TextBuffer, counts: BufferStats) -> [String; 7] {
+        [
+            format!("Words: {}", self.num(counts.words)),
+            format!("Characters: {}", self.num(counts.chars)),
+            format!("  without line breaks: {}", self.num(counts.chars - counts.lines.saturating_sub(1))),
+            format!("Lines: {}", self.num(counts.lines)),
+            format!("Sentences: {}", self.num(buffer.sentence_count())),
+            format!("Reading time: {} min", self.num(buffer.reading_time_minutes(READING_WPM, true))),
+            format!("  prose only: {} min", self.num(buffer.reading_time_minutes(READING_WPM, false))),
+        ]
+    }
+}
+
+impl RenderBackend for Renderer {
+    fn set_paragraph_spacing(&mut self, on: bool) {
+        Renderer::set_paragraph_spacing(self, on)
+    }
+
+    fn set_prose_word_count(&mut self, on: bool) {
+        Renderer::set_prose_word_count(self, on)
+    }
+
+    fn set_horizontal_scroll(&mut self, on: bool) {
+        Renderer::set_horizontal_scroll(self, on)
+    }
+
+    fn set_theme(&mut self, theme: Theme) {
+        Renderer::set_theme(self, theme)
+    }
+
+    fn set_word_goal(&mut self, goal: usize) {
+        Renderer::set_word_goal(self, goal)
+    }
+
+    fn set_number_separator(&mut self, sep: char) {
+        Renderer::set_number_separator(self, sep)
+    }
+
+    fn set_toast(&mut self, msg: Option<&str>) {
+        Renderer::set_toast(self, msg)
+    }
+
+    fn visible_cols(&self, show_line_numbers: bool, line_count: usize) -> usize {
+        Renderer::visible_cols(self, show_line_numbers, line_count)
+    }
+    fn viewport_lines(&self, view: BufferView, buffer: &TextBuffer) -> usize {
+        Renderer::viewport_lines(self, view, buffer)
+    }
+    fn last_page_lines(&self, view: BufferView, buffer: &TextBuffer) -> usize {
+        Renderer::last_page_lines(self, view, buffer)
+    }
+
+    fn draw_menu(&self, items: &[&str], cursor: usize) {
+        Renderer::draw_menu(self, items, cursor)
+    }
+
+    fn draw_help(&self, help_text: &str) {
+        Renderer::draw_help(self, help_text)
+    }
+
+    fn draw_confirm_exit(&self) {
+        Renderer::draw_confirm_exit(self)
+    }
+
+    fn draw_confirm_revert(&self, doc_name: &str) {
+        Renderer::draw_confirm_revert(self, doc_name)
+    }
+
+    fn draw_confirm_delete_entry(&self, date: &str) {
+        Renderer::draw_confirm_delete_entry(self, date)
+    }
+
+    fn draw_settings(&self, config: &WriterConfig, cursor: usize) {
+        Renderer::draw_settings(self, config, cursor)
+    }
+
+    fn draw_template_editor(&self, buffer: &TextBuffer) {
+        Renderer::draw_template_editor(self, buffer)
+    }
+
+    fn draw_storage_locked(&self) {
+        Renderer::draw_storage_locked(self)
+    }
+
+    fn draw_mode_select(&self, cursor: usize) {
+        Renderer::draw_mode_select(self, cursor)
+    }
+
+    fn draw_doc_list(&self, docs: &[DocEntry], words: &[(String, Option<u32>)], cursor: usize, reordering: bool, header: DocListHeader) {
+        Renderer::draw_doc_list(self, docs, words, cursor, reordering, header)
+    }
+
+    fn draw_editor(&self, buffer: &TextBuffer, doc_name: &str, preview: bool, show_line_numbers: bool, max_line_length: usize, find: Option<FindBar>) {
+        Renderer::draw_editor(self, buffer, doc_name, preview, show_line_numbers, max_line_length, find)
+    }
+
+    fn draw_file_menu(&self, cursor: usize) {
+        Renderer::draw_file_menu(self, cursor)
+    }
+
+    fn draw_rename_dialog(&self, new_name: &str, old_name: &str, error: &str) {
+        Renderer::draw_rename_dialog(self, new_name, old_name, error)
+    }
+
+    fn draw_goto_line(&self, input: &str, line_count: usize) {
+        Renderer::draw_goto_line(self, input, line_count)
+    }
+
+    fn draw_outline(&self, headings: &[(usize, usize, String)], cursor: usize) {
+        Renderer::draw_outline(self, headings, cursor)
+    }
+
+    fn draw_goto_doc(&self, input: &str, message: &str, confirm_create: bool) {
+        Renderer::draw_goto_doc(self, input, message, confirm_create)
+    }
+
+    fn draw_quick_note(&self, input: &str) {
+        Renderer::draw_quick_note(self, input)
+    }
+
+    fn draw_export_menu(&self, cursor: usize, page_size: u16, status: &str) {
+        Renderer::draw_export_menu(self, cursor, page_size, status)
+    }
+
+    fn draw_export_confirm(&self, target: ExportTarget, resume: Option<(usize, usize)>) {
+        Renderer::draw_export_confirm(self, target, resume)
+    }
+
+    fn draw_backup(&self, actions: &[&str], cursor: usize, status: &str) {
+        Renderer::draw_backup(self, actions, cursor, status)
+    }
+
+    fn draw_export_waiting(&self, timeout_secs: u16, cancelling: bool) {
+        Renderer::draw_export_waiting(self, timeout_secs, cancelling)
+    }
+
+    fn draw_export_progress(&self, typed: usize, total: usize, lines: Option<(usize, usize)>, cancelling: bool) {
+        Renderer::draw_export_progress(self, typed, total, lines, cancelling)
+    }
+
+    fn draw_usb_not_ready(&self) {
+        Renderer::draw_usb_not_ready(self)
+    }
+
+    fn draw_qr_export(&self, chunk: &str, page: usize, total: usize) {
+        Renderer::draw_qr_export(self, chunk, page, total)
+    }
+
+    fn show_qr(&self, caption: &str, payload: &str) {
+        Renderer::show_qr(self, caption, payload)
+    }
+
+    fn draw_journal(&self, buffer: &TextBuffer, date: &str, date_format: DateFormat, streak: usize) {
+        Renderer::draw_journal(self, buffer, date, date_format, streak)
+    }
+
+    fn draw_journal_search(&self, query: &str, results: &[(String, usize, String)], cursor: usize) {
+        Renderer::draw_journal_search(self, query, results, cursor)
+    }
+
+    fn draw_journal_stats(&self, from: &str, to: &str, stats: &JournalStats) {
+        Renderer::draw_journal_stats(self, from, to, stats)
+    }
+
+    fn draw_doc_stats(&self, buffer: &TextBuffer, doc_name: &str) {
+        Renderer::draw_doc_stats(self, buffer, doc_name)
+    }
+
+    fn draw_sprint_select(&self, minutes: &[u64], cursor: usize) {
+        Renderer::draw_sprint_select(self, minutes, cursor)
+    }
+
+    fn draw_typewriter(&self, buffer: &TextBuffer, elapsed_secs: u64, wpm: usize, remaining_secs: Option<u64>) {
+        Renderer::draw_typewriter(self, buffer, elapsed_secs, wpm, remaining_secs)
+    }
+
+    fn draw_typewriter_done(&self, buffer: &TextBuffer, top_words: &[(String, usize)], elapsed_secs: u64, avg_wpm: usize, sprint_secs: Option<u64>) {
+        Renderer::draw_typewriter_done(self, buffer, top_words, elapsed_secs, avg_wpm, sprint_secs)
+    }
+}
+
+/// Test double that draws nothing and records the name of each screen
+/// drawn, in order. Clone `calls` before boxing it to read them back.
+#[cfg(test)]
+#[derive(Default)]
+pub struct RecordingRenderer {
+    pub calls: DrawLog,
+}
+
+/// Lines of `lines` that fit the recording renderer's 13 rows, where a
+/// `# ` heading takes two (so tests can see the count change with what
+/// is showing). Past the end of the document, rows hold plain lines.
+#[cfg(test)]
+fn recorded_fit<'a>(lines: impl Iterator<Item = &'a String>) -> usize {
+    let mut rows = 0;
+    let mut count = 0;
+    for line in lines {
+        rows += if line.starts_with("# ") { 2 } else { 1 };
+        if rows > 13 {
+            return count.max(1);
+        }
+        count += 1;
+    }
+    count + (13 - rows)
+}
+
+/// Names of the screens a `RecordingRenderer` drew, shared with the test.
+#[cfg(test)]
+pub type DrawLog = std::rc::Rc<std::cell::RefCell<Vec<&'static str>>>;
+
+#[cfg(test)]
+impl RenderBackend for RecordingRenderer {
+    fn set_paragraph_spacing(&mut self, _on: bool) {}
+    fn set_prose_word_count(&mut self, _on: bool) {}
+    fn set_horizontal_scroll(&mut self, _on: bool) {}
+    fn set_theme(&mut self, _theme: Theme) {}
+    fn set_word_goal(&mut self, _goal: usize) {}
+    fn set_number_separator(&mut self, _sep: char) {}
+    fn set_toast(&mut self, _msg: Option<&str>) {}
+    fn visible_cols(&self, _show_line_numbers: bool, _line_count: usize) -> usize {
+        40
+    }
+    fn viewport_lines(&self, _view: BufferView, buffer: &TextBuffer) -> usize {
+        recorded_fit(buffer.lines.iter().skip(buffer.viewport_top))
+    }
+    fn last_page_lines(&self, _view: BufferView, buffer: &TextBuffer) -> usize {
+        recorded_fit(buffer.lines.iter().rev())
+    }
+    fn draw_menu(&self, _items: &[&str], _cursor: usize) {
+        self.calls.borrow_mut().push("draw_menu");
+    }
+    fn draw_help(&self, _help_text: &str) {
+        self.calls.borrow_mut().push("draw_help");
+    }
+    fn draw_confirm_exit(&self) {
+        self.calls.borrow_mut().push("draw_confirm_exit");
+    }
+    fn draw_confirm_revert(&self, _doc_name: &str) {
+        self.calls.borrow_mut().push("draw_confirm_revert");
+    }
+    fn draw_confirm_delete_entry(&self, _date: &str) {
+        self.calls.borrow_mut().push("draw_confirm_delete_entry");
+    }
+    fn draw_settings(&self, _config: &WriterConfig, _cursor: usize) {
+        self.calls.borrow_mut().push("draw_settings");
+    }
+    fn draw_template_editor(&self, _buffer: &TextBuffer) {
+        self.calls.borrow_mut().push("draw_template_editor");
+    }
+    fn draw_storage_locked(&self) {
+        self.calls.borrow_mut().push("draw_storage_locked");
+    }
+    fn draw_mode_select(&self, _cursor: usize) {
+        self.calls.borrow_mut().push("draw_mode_select");
+    }
+    fn draw_doc_list(&self, _docs: &[DocEntry], _words: &[(String, Option<u32>)], _cursor: usize, _reordering: bool, _header: DocListHeader) {
+        self.calls.borrow_mut().push("draw_doc_list");
+    }
+    fn draw_editor(&self, _buffer: &TextBuffer, _doc_name: &str, _preview: bool, _show_line_numbers: bool, _max_line_length: usize, _find: Option<FindBar>) {
+        self.calls.borrow_mut().push("draw_editor");
+    }
+    fn draw_file_menu(&self, _cursor: usize) {
+        self.calls.borrow_mut().push("draw_file_menu");
+    }
+    fn draw_rename_dialog(&self, _new_name: &str, _old_name: &str, _error: &str) {
+        self.calls.borrow_mut().push("draw_rename_dialog");
+    }
+    fn draw_goto_line(&self, _input: &str, _line_count: usize) {
+        self.calls.borrow_mut().push("draw_goto_line");
+    }
+    fn draw_outline(&self, _headings: &[(usize, usize, String)], _cursor: usize) {
+        self.calls.borrow_mut().push("draw_outline");
+    }
+    fn draw_goto_doc(&self, _input: &str, _message: &str, _confirm_create: bool) {
+        self.calls.borrow_mut().push("draw_goto_doc");
+    }
+    fn draw_quick_note(&self, _input: &str) {
+        self.calls.borrow_mut().push("draw_quick_note");
+    }
+    fn draw_export_menu(&self, _cursor: usize, _page_size: u16, _status: &str) {
+        self.calls.borrow_mut().push("draw_export_menu");
+    }
+    fn draw_export_confirm(&self, _target: ExportTarget, _resume: Option<(usize, usize)>) {
+        self.calls.borrow_mut().push("draw_export_confirm");
+    }
+    fn draw_backup(&self, _actions: &[&str], _cursor: usize, _status: &str) {
+        self.calls.borrow_mut().push("draw_backup");
+    }
+    fn draw_export_waiting(&self, _timeout_secs: u16, _cancelling: bool) {
+        self.calls.borrow_mut().push("draw_export_waiting");
+    }
+    fn draw_export_progress(&self, _typed: usize, _total: usize, _lines: Option<(usize, usize)>, _cancelling: bool) {
+        self.calls.borrow_mut().push("draw_export_progress");
+    }
+    fn draw_usb_not_ready(&self) {
+        self.calls.borrow_mut().push("draw_usb_not_ready");
+    }
+    fn draw_qr_export(&self, _chunk: &str, _page: usize, _total: usize) {
+        self.calls.borrow_mut().push("draw_qr_export");
+    }
+    fn show_qr(&self, _caption: &str, _payload: &str) {
+        self.calls.borrow_mut().push("show_qr");
+    }
+    fn draw_journal(&self, _buffer: &TextBuffer, _date: &str, _date_format: DateFormat, _streak: usize) {
+        self.calls.borrow_mut().push("draw_journal");
+    }
+    fn draw_journal_search(&self, _query: &str, _results: &[(String, usize, String)], _cursor: usize) {
+        self.calls.borrow_mut().push("draw_journal_search");
+    }
+    fn draw_journal_stats(&self, _from: &str, _to: &str, _stats: &JournalStats) {
+        self.calls.borrow_mut().push("draw_journal_stats");
+    }
+    fn draw_doc_stats(&self, _buffer: &TextBuffer, _doc_name: &str) {
+        self.calls.borrow_mut().push("draw_doc_stats");
+    }
+    fn draw_sprint_select(&self, _minutes: &[u64], _cursor: usize) {
+        self.calls.borrow_mut().push("draw_sprint_select");
+    }
+    fn draw_typewriter(&self, _buffer: &TextBuffer, _elapsed_secs: u64, _wpm: usize, _remaining_secs: Option<u64>) {
+        self.calls.borrow_mut().push("draw_typewriter");
+    }
+    fn draw_typewriter_done(&self, _buffer: &TextBuffer, _top_words: &[(String, usize)], _elapsed_secs: u64, _avg_wpm: usize, _sprint_secs: Option<u64>) {
+        self.calls.borrow_mut().push("draw_typewriter_done");
+    }
+}
+
+/// Fence language and kind of each editor line from `start` up to `end`.
+/// Fences are tracked from the top of the document, so a line inside a
+/// fenced block is code whatever it looks like on its own.
+fn editor_line_kinds(buffer: &TextBuffer, start: usize, end: usize) -> (Vec<Option<Language>>, Vec<LineKind>) {
+    let langs = fence_languages(&buffer.lines, start, end);
+    let kinds = classify_lines(&buffer.lines[..end]).split_off(start);
+    (langs, kinds)
+}
+
+/// Glyph style and line height for a markdown line in the editor.
+fn line_style(kind: LineKind) -> (GlyphStyle, isize) {
+    match kind {
+        LineKind::Heading1 => (GlyphStyle::Large, LINE_HEIGHT_LARGE),
+        LineKind::Heading2 | LineKind::Heading3 => (GlyphStyle::Bold, LINE_HEIGHT_REGULAR + 4),
+        LineKind::CodeBlock => (GlyphStyle::Monospace, LINE_HEIGHT_REGULAR),
+        _ => (GlyphStyle::Regular, LINE_HEIGHT_REGULAR),
+    }
+}
+
+/// Digits shown in the line number gutter: enough for the last line, at least 3.
+fn gutter_digits(line_count: usize) -> usize {
+    line_count.to_string().len().max(3)
+}
+
+/// Pixel width of the line number gutter (digits, a space, and padding).
+fn gutter_width(digits: usize) -> isize {
+    (digits as isize + 1) * CHAR_WIDTH + 8
+}
+
+/// Scrollbar thumb (top, height) in a track `track_h` pixels tall, for a
+/// view of `visible` lines from `top` in a `total`-line document. None
+/// when the whole document fits on screen.
+fn scrollbar_thumb(top: usize, visible: usize, total: usize, track_h: isize) -> Option<(isize, isize)> {
+    if visible == 0 || total <= visible {
+        return None;
+    }
+    let height = (track_h * visible as isize / total as isize).clamp(MIN_THUMB_HEIGHT.min(track_h), track_h);
+    let max_top = total - visible;
+    let offset = (track_h - height) * top.min(max_top) as isize / max_top as isize;
+    Some((offset, height))
+}
+
+/// Filled width of a goal bar `full` pixels wide. Any words at all show
+/// a sliver, even while the percentage still rounds down to 0.
+fn goal_bar_width(percent: u8, words: usize, full: isize) -> isize {
+    let width = full * percent.min(100) as isize / 100;
+    if words > 0 {
+        width.max(2)
+    } else {
+        width
+    }
+}
+
+/// How far down the document the view is, reaching 100 once the last line
+/// is on screen.
+fn scroll_percent(top: usize, visible: usize, total: usize) -> usize {
+    if top + visible >= total {
+        100
+    } else {
+        top * 100 / (total - visible)
+    }
+}
+
+/// "ln X of Y (Z%)" for the status bar: cursor line, line count, and how
+/// far down the view is.
+fn position_str(buffer: &TextBuffer) -> String {
+    let total = buffer.lines.len();
+    format!(
+        "ln {} of {} ({}%)",
+        buffer.cursor.line + 1, total,
+        scroll_percent(buffer.viewport_top, buffer.viewport_lines, total),
+    )
+}
+
+/// Left edge of a line's text. Block quotes are indented past their bar and
+/// previewed task items past their checkbox; the cursor and selection are
+/// positioned from the same edge.
+fn text_left(kind: LineKind, gutter: isize, preview: bool) -> isize {
+    match kind {
+        LineKind::BlockQuote => MARGIN_LEFT + gutter + 8,
+        LineKind::TaskList { .. } if preview => MARGIN_LEFT + gutter + CHECKBOX_SIZE + 6,
+        _ => MARGIN_LEFT + gutter,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scrollbar_hidden_when_document_fits() {
+        assert_eq!(scrollbar_thumb(0, 13, 5, 400), None);
+        assert_eq!(scrollbar_thumb(0, 13, 13, 400), None);
+        assert_eq!(scroll_percent(0, 13, 5), 100);
+    }
+
+    #[test]
+    fn test_scrollbar_thumb_tracks_viewport() {
+        // A quarter of the document visible: a quarter-height thumb
+        assert_eq!(scrollbar_thumb(0, 10, 40, 400), Some((0, 100)));
+        // Scrolled to the end: the thumb sits at the bottom of the track
+        assert_eq!(scrollbar_thumb(30, 10, 40, 400), Some((300, 100)));
+        assert_eq!(scrollbar_thumb(15, 10, 40, 400), Some((150, 100)));
+    }
+
+    #[test]
+    fn test_scrollbar_thumb_has_minimum_height() {
+        let (top, height) = scrollbar_thumb(9990, 10, 10_000, 400).unwrap();
+        assert_eq!(height, MIN_THUMB_HEIGHT);
+        assert_eq!(top + height, 400);
+    }
+
+    #[test]
+    fn test_scroll_percent_reaches_100_at_end() {
+        assert_eq!(scroll_percent(0, 10, 40), 0);
+        assert_eq!(scroll_percent(15, 10, 40), 50);
+        assert_eq!(scroll_percent(29, 10, 40), 96);
+        // Last line visible
+        assert_eq!(scroll_percent(30, 10, 40), 100);
+    }
+
+    #[test]
+    fn test_goal_bar_width() {
+        assert_eq!(goal_bar_width(0, 0, 336), 0);
+        // One word of a big goal is still visible
+        assert_eq!(goal_bar_width(0, 1, 336), 2);
+        assert_eq!(goal_bar_width(50, 250, 336), 168);
+        assert_eq!(goal_bar_width(100, 900, 336), 336);
+    }
+
+    #[test]
+    fn test_themes_swap_colours() {
+        // Highlights (cursor, selection) are drawn in fg, so fg must never match bg
+        for theme in [Theme::Light, Theme::Dark] {
+            assert!(theme.fg() != theme.bg());
+        }
+        assert!(Theme::Light.fg() == Theme::Dark.bg());
+        assert!(!Theme::Light.inverted());
+        assert!(Theme::Dark.inverted());
+    }
+
+    #[test]
+    fn test_gutter_grows_with_line_count() {
+        assert_eq!(gutter_digits(1), 3);
+        assert_eq!(gutter_digits(999), 3);
+        assert_eq!(gutter_digits(1000), 4);
+        assert_eq!(gutter_width(3), 40);
+        assert!(gutter_width(4) > gutter_width(3));
+    }
+
+    #[test]
+    fn test_text_left_with_line_numbers() {
+        let gutter = gutter_width(3);
+        assert_eq!(text_left(LineKind::Normal, 0, false), MARGIN_LEFT);
+        assert_eq!(text_left(LineKind::Normal, gutter, false), MARGIN_LEFT + gutter);
+        // Block quotes clear both the gutter and the quote bar
+        let quote_left = text_left(LineKind::BlockQuote, gutter, false);
+        assert_eq!(quote_left, MARGIN_LEFT + gutter + 8);
+        assert!(quote_left > MARGIN_LEFT + gutter + 3);
+    }
+
+    #[test]
+    fn test_text_left_clears_checkbox_in_preview() {
+        let task = LineKind::TaskList { checked: true };
+        assert!(text_left(task, 0, true) > MARGIN_LEFT + CHECKBOX_SIZE);
+        // Edit mode shows the raw "- [x]" text, so no box to clear
+        assert_eq!(text_left(task, 0, false), MARGIN_LEFT);
+    }
 }
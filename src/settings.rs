@@ -0,0 +1,280 @@
+// Settings screen rows: labels, current values, and how Enter changes each one
+
+use writer_core::serialize::{AutotypeNewline, DateFormat, NumberSeparator, Theme, WriterConfig};
+
+/// Rows on the settings screen, in display order.
+pub const SETTINGS_LABELS: [&str; 26] = [
+    "Default mode",
+    "Autosave",
+    "Autosave after idle",
+    "Line numbers",
+    "Max line length",
+    "Confirm USB export",
+    "Confirm TCP export",
+    "Export page size",
+    "Auto-capitalize",
+    "Paragraph spacing",
+    "Journal date",
+    "Prose word count",
+    "TCP export wait",
+    "Long lines",
+    "Theme",
+    "Word goal",
+    "Typewriter backspace",
+    "Tab key inserts",
+    "Tab width",
+    "Auto-indent",
+    "Number format",
+    "Journal search",
+    "Journal template",
+    "Clock correction",
+    "Autotype newline",
+    "Autotype by",
+];
+
+/// The journal template row, which Enter opens for editing rather than
+/// cycling.
+pub const TEMPLATE_ROW: usize = 22;
+
+fn on_off(flag: bool) -> String {
+    String::from(if flag { "on" } else { "off" })
+}
+
+fn or_off(n: usize, unit: &str) -> String {
+    if n == 0 {
+        String::from("off")
+    } else {
+        format!("{}{}", n, unit)
+    }
+}
+
+/// Display value for settings row `index`.
+pub fn setting_value(config: &WriterConfig, index: usize) -> String {
+    match index {
+        0 => String::from(match config.default_mode {
+            1 => "Journal",
+            2 => "Typewriter",
+            _ => "Editor",
+        }),
+        1 => on_off(config.autosave),
+        2 => or_off(config.autosave_interval_secs.into(), "s"),
+        3 => on_off(config.show_line_numbers),
+        4 => or_off(config.max_line_length.into(), ""),
+        5 => on_off(config.confirm_usb_export),
+        6 => on_off(config.confirm_tcp_export),
+        7 => or_off(config.export_page_size.into(), " chars"),
+        8 => on_off(config.auto_capitalize),
+        9 => on_off(config.paragraph_spacing),
+        10 => String::from(match config.journal_date_format {
+            DateFormat::Iso => "YYYY-MM-DD",
+            DateFormat::Long => "Long",
+            DateFormat::DayMonth => "D Mon YYYY",
+        }),
+        11 => on_off(config.prose_word_count),
+        12 => format!("{}s", config.tcp_timeout_secs),
+        13 => String::from(if config.horizontal_scroll { "scroll" } else { "wrap" }),
+        14 => String::from(match config.theme {
+            Theme::Light => "light",
+            Theme::Dark => "dark",
+        }),
+        15 => or_off(config.word_goal, " words"),
+        16 => String::from(if config.typewriter_allow_backspace { "this line" } else { "off" }),
+        17 => String::from(if config.expand_tabs { "spaces" } else { "tab" }),
+        18 => config.tab_width.to_string(),
+        19 => on_off(config.auto_indent),
+        20 => String::from(match config.number_separator {
+            NumberSeparator::Comma => "1,000",
+            NumberSeparator::Period => "1.000",
+            NumberSeparator::Space => "1 000",
+        }),
+        21 => String::from(if config.journal_search_all_matches { "all matches" } else { "first per day" }),
+        22 => or_off(config.journal_template.lines().count(), " lines"),
+        23 => clock_correction(config.tz_offset_minutes),
+        24 => String::from(match config.autotype_newline {
+            AutotypeNewline::Lf => "LF (as is)",
+            AutotypeNewline::CrLf => "CR LF",
+            AutotypeNewline::Cr => "CR",
+            AutotypeNewline::Space => "space",
+        }),
+        25 => String::from(if config.autotype_by_line { "line" } else { "chunk" }),
+        _ => String::new(),
+    }
+}
+
+/// Toggle or cycle settings row `index` to its next value (the template
+/// row has its own editor and is left alone).
+pub fn change_setting(config: &mut WriterConfig, index: usize) {
+    match index {
+        0 => config.default_mode = (config.default_mode + 1) % 3,
+        1 => config.autosave = !config.autosave,
+        2 => config.autosave_interval_secs = next_autosave_interval(config.autosave_interval_secs),
+        3 => config.show_line_numbers = !config.show_line_numbers,
+        4 => config.max_line_length = next_line_length_limit(config.max_line_length),
+        5 => config.confirm_usb_export = !config.confirm_usb_export,
+        6 => config.confirm_tcp_export = !config.confirm_tcp_export,
+        7 => config.export_page_size = next_export_page_size(config.export_page_size),
+        8 => config.auto_capitalize = !config.auto_capitalize,
+        9 => config.paragraph_spacing = !config.paragraph_spacing,
+        10 => config.journal_date_format = config.journal_date_format.next(),
+        11 => config.prose_word_count = !config.prose_word_count,
+        12 => config.tcp_timeout_secs = next_tcp_timeout(config.tcp_timeout_secs),
+        13 => config.horizontal_scroll = !config.horizontal_scroll,
+        14 => config.theme = config.theme.toggled(),
+        15 => config.word_goal = next_word_goal(config.word_goal),
+        16 => config.typewriter_allow_backspace = !config.typewriter_allow_backspace,
+        17 => config.expand_tabs = !config.expand_tabs,
+        18 => config.tab_width = next_tab_width(config.tab_width),
+        19 => config.auto_indent = !config.auto_indent,
+        20 => config.number_separator = config.number_separator.next(),
+        21 => config.journal_search_all_matches = !config.journal_search_all_matches,
+        23 => config.tz_offset_minutes = next_tz_offset(config.tz_offset_minutes),
+        24 => config.autotype_newline = config.autotype_newline.next(),
+        25 => config.autotype_by_line = !config.autotype_by_line,
+        _ => {}
+    }
+}
+
+/// Presets cycled by Esc+W: off, then common line-length limits.
+pub fn next_line_length_limit(current: u16) -> u16 {
+    match current {
+        0 => 72,
+        72 => 80,
+        80 => 100,
+        _ => 0,
+    }
+}
+
+/// Presets cycled by the export menu's page size option (0 = one page).
+pub fn next_export_page_size(current: u16) -> u16 {
+    match current {
+        0 => 1000,
+        1000 => 2000,
+        2000 => 4000,
+        _ => 0,
+    }
+}
+
+/// Idle autosave presets in seconds (0 = timer off).
+fn next_autosave_interval(current: u16) -> u16 {
+    match current {
+        0 => 15,
+        15 => 30,
+        30 => 60,
+        60 => 120,
+        _ => 0,
+    }
+}
+
+/// How long TCP export waits for a client to connect, in seconds.
+fn next_tcp_timeout(current: u16) -> u16 {
+    match current {
+        30 => 60,
+        60 => 120,
+        120 => 300,
+        _ => 30,
+    }
+}
+
+/// Spaces per Tab when tabs are expanded.
+fn next_tab_width(current: u8) -> u8 {
+    match current {
+        2 => 4,
+        4 => 8,
+        _ => 2,
+    }
+}
+
+/// What the Tab key inserts: `tab_width` spaces, or a tab character.
+pub fn tab_text(config: &WriterConfig) -> String {
+    if config.expand_tabs {
+        " ".repeat(config.tab_width as usize)
+    } else {
+        String::from("\t")
+    }
+}
+
+/// Corrections to the device clock in half-hour steps from -12:00 to
+/// +14:00, wrapping.
+fn next_tz_offset(current: i16) -> i16 {
+    let next = (current.div_euclid(30) + 1) * 30;
+    if next > 14 * 60 { -12 * 60 } else { next }
+}
+
+/// "off", "+5:30", "-8:00".
+fn clock_correction(minutes: i16) -> String {
+    if minutes == 0 {
+        return String::from("off");
+    }
+    let sign = if minutes < 0 { '-' } else { '+' };
+    format!("{}{}:{:02}", sign, minutes.unsigned_abs() / 60, minutes.unsigned_abs() % 60)
+}
+
+/// Word goal presets (0 = no goal).
+fn next_word_goal(current: usize) -> usize {
+    match current {
+        0 => 250,
+        250 => 500,
+        500 => 750,
+        750 => 1000,
+        1000 => 2000,
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use writer_core::serialize::{deserialize_config, serialize_config};
+
+    #[test]
+    fn test_every_row_changes_and_round_trips() {
+        // The template row opens an editor instead of cycling
+        for index in (0..SETTINGS_LABELS.len()).filter(|&i| i != TEMPLATE_ROW) {
+            let mut config = WriterConfig::default();
+            let before = setting_value(&config, index);
+            change_setting(&mut config, index);
+            assert_ne!(setting_value(&config, index), before, "row {}", index);
+
+            // What the settings screen saves is what the next launch loads
+            let restored = deserialize_config(&serialize_config(&config)).unwrap();
+            assert_eq!(restored, config);
+        }
+    }
+
+    #[test]
+    fn test_tab_text() {
+        let mut config = WriterConfig::default();
+        assert_eq!(tab_text(&config), "    ");
+        change_setting(&mut config, 18);
+        assert_eq!(tab_text(&config), "        ");
+        change_setting(&mut config, 17);
+        assert_eq!(tab_text(&config), "\t");
+    }
+
+    #[test]
+    fn test_clock_correction_cycles() {
+        let mut config = WriterConfig::default();
+        assert_eq!(setting_value(&config, 23), "off");
+        change_setting(&mut config, 23);
+        assert_eq!(setting_value(&config, 23), "+0:30");
+        config.tz_offset_minutes = 14 * 60;
+        change_setting(&mut config, 23);
+        assert_eq!(setting_value(&config, 23), "-12:00");
+        // Off-grid offsets land back on the half hour
+        config.tz_offset_minutes = 345;
+        change_setting(&mut config, 23);
+        assert_eq!(setting_value(&config, 23), "+6:00");
+        config.tz_offset_minutes = -210;
+        assert_eq!(setting_value(&config, 23), "-3:30");
+    }
+
+    #[test]
+    fn test_default_mode_cycles() {
+        let mut config = WriterConfig::default();
+        change_setting(&mut config, 0);
+        assert_eq!(setting_value(&config, 0), "Journal");
+        change_setting(&mut config, 0);
+        change_setting(&mut config, 0);
+        assert_eq!(setting_value(&config, 0), "Editor");
+    }
+}
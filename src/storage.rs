@@ -1,157 +1,378 @@
+use std::cell::{Cell, RefCell};
 use std::io::{Read, Write, Seek, SeekFrom};
 use writer_core::serialize::{
     serialize_document, deserialize_document,
-    serialize_index, deserialize_index,
+    serialize_index_words, deserialize_index_words,
     serialize_config, deserialize_config,
+    serialize_export_resume, deserialize_export_resume,
     WriterConfig,
 };
+use writer_core::backup;
+use writer_core::export::ExportResume;
+use writer_core::docs::{index_with_counts, match_doc_name, move_in_index, next_free_name_by, rename_in_index, sanitize_name, NameMatch, RenameError};
+use writer_core::journal::{self, JournalStats};
 
 const DICT_DOCS: &str = "writer.docs";
 const DICT_JOURNAL: &str = "writer.journal";
 const DICT_SETTINGS: &str = "writer.settings";
+const DICT_EXPORTS: &str = "writer.exports";
 const INDEX_KEY: &str = "_index";
 const CONFIG_KEY: &str = "config";
+const EXPORT_RESUME_KEY: &str = "export_resume";
+
+/// Why a stored document couldn't be read.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DocError {
+    /// The blob is truncated or fails its checksum
+    Corrupt,
+}
+
+/// Document names in index order with their cached word counts.
+type DocIndex = Vec<(String, Option<u32>)>;
+
+/// The key-value calls storage makes. `PddbStore` makes them on the PDDB;
+/// tests use `MemoryStore` so the app runs without a device.
+pub trait KeyStore {
+    /// A key's bytes, or None if the key (or its dictionary) doesn't exist
+    /// or can't be read.
+    fn read(&self, dict: &str, key: &str) -> Option<Vec<u8>>;
+    /// Write a key, creating it and its dictionary as needed.
+    fn write(&self, dict: &str, key: &str, data: &[u8]) -> std::io::Result<()>;
+    fn delete(&self, dict: &str, key: &str) -> std::io::Result<()>;
+    fn sync(&self);
+    /// Whether the store is mounted now, trying to mount it if not.
+    fn try_mount(&self) -> bool;
+}
+
+struct PddbStore(pddb::Pddb);
+
+impl KeyStore for PddbStore {
+    fn read(&self, dict: &str, key: &str) -> Option<Vec<u8>> {
+        let mut key = self.0.get(dict, key, None, false, false, None, None::<fn()>).ok()?;
+        let mut data = Vec::new();
+        key.seek(SeekFrom::Start(0)).ok();
+        key.read_to_end(&mut data).ok()?;
+        Some(data)
+    }
+    fn write(&self, dict: &str, key: &str, data: &[u8]) -> std::io::Result<()> {
+        let mut key = self.0.get(dict, key, None, true, true, Some(data.len()), None::<fn()>)?;
+        key.seek(SeekFrom::Start(0)).ok();
+        key.write_all(data)
+    }
+    fn delete(&self, dict: &str, key: &str) -> std::io::Result<()> {
+        self.0.delete_key(dict, key, None)
+    }
+    fn sync(&self) {
+        self.0.sync().ok();
+    }
+    fn try_mount(&self) -> bool {
+        self.0.try_mount()
+    }
+}
+
+/// Keys held in memory, for tests. Always mounted. Clones share the
+/// keys, so a test can keep one to look at what the app stored.
+#[cfg(test)]
+#[derive(Clone, Default)]
+pub struct MemoryStore {
+    keys: std::rc::Rc<RefCell<MemoryKeys>>,
+}
+
+/// A `MemoryStore`'s keys, by (dictionary, key).
+#[cfg(test)]
+type MemoryKeys = std::collections::BTreeMap<(String, String), Vec<u8>>;
+
+#[cfg(test)]
+impl MemoryStore {
+    /// Cut the last byte off a saved document, as a crash mid-write would.
+    pub fn damage_doc(&self, name: &str) {
+        if let Some(data) = self.keys.borrow_mut().get_mut(&(DICT_DOCS.to_string(), format!("doc_{}", name))) {
+            data.pop();
+        }
+    }
+}
+
+#[cfg(test)]
+impl KeyStore for MemoryStore {
+    fn read(&self, dict: &str, key: &str) -> Option<Vec<u8>> {
+        self.keys.borrow().get(&(dict.to_string(), key.to_string())).cloned()
+    }
+    fn write(&self, dict: &str, key: &str, data: &[u8]) -> std::io::Result<()> {
+        self.keys.borrow_mut().insert((dict.to_string(), key.to_string()), data.to_vec());
+        Ok(())
+    }
+    fn delete(&self, dict: &str, key: &str) -> std::io::Result<()> {
+        self.keys.borrow_mut().remove(&(dict.to_string(), key.to_string()));
+        Ok(())
+    }
+    fn sync(&self) {}
+    fn try_mount(&self) -> bool {
+        true
+    }
+}
 
 pub struct WriterStorage {
-    pddb: pddb::Pddb,
+    store: Box<dyn KeyStore>,
+    mounted: bool,
+    batch: SyncBatch,
+    // Document index as last read or written; None until next needed
+    index: RefCell<Option<DocIndex>>,
 }
 
 impl WriterStorage {
     pub fn new() -> Self {
-        let pddb = pddb::Pddb::new();
-        pddb.try_mount();
-        Self { pddb }
+        Self::with_store(Box::new(PddbStore(pddb::Pddb::new())))
+    }
+
+    /// Storage kept in `store`; `new` passes the PDDB.
+    pub fn with_store(store: Box<dyn KeyStore>) -> Self {
+        let mounted = store.try_mount();
+        if !mounted {
+            log::warn!("PDDB not mounted; documents and journal unavailable");
+        }
+        Self { store, mounted, batch: SyncBatch::default(), index: RefCell::new(None) }
+    }
+
+    /// Whether the PDDB is mounted. While it isn't (still locked), every
+    /// read comes back empty and saves fail, so callers should say so.
+    pub fn is_ready(&self) -> bool {
+        self.mounted
+    }
+
+    /// Try mounting the PDDB again, say after the user unlocked it.
+    /// Returns whether storage is ready now.
+    pub fn retry_mount(&mut self) -> bool {
+        self.mounted = mount_if_needed(self.mounted, || self.store.try_mount());
+        self.refresh_index();
+        if !self.mounted {
+            log::warn!("PDDB still not mounted");
+        }
+        self.mounted
     }
 
     // ---- Document Operations ----
 
     pub fn list_docs(&self) -> Vec<String> {
-        match self.pddb.get(DICT_DOCS, INDEX_KEY, None, false, false, None, None::<fn()>) {
-            Ok(mut key) => {
-                let mut data = Vec::new();
-                key.seek(SeekFrom::Start(0)).ok();
-                if key.read_to_end(&mut data).is_ok() && data.len() >= 4 {
-                    deserialize_index(&data)
-                } else {
-                    Vec::new()
-                }
+        self.list_doc_words().into_iter().map(|(name, _)| name).collect()
+    }
+
+    /// Document names in index order, each with the word count cached when
+    /// it was last saved (None until a document saved before counts were
+    /// kept is saved again).
+    pub fn list_doc_words(&self) -> Vec<(String, Option<u32>)> {
+        self.with_index(|index| index.to_vec())
+    }
+
+    /// Forget the cached index so the next use reads it from the PDDB
+    /// again, for when something other than this app may have changed it.
+    pub fn refresh_index(&self) {
+        *self.index.borrow_mut() = None;
+    }
+
+    /// Run `f` on the document index, reading it from the PDDB only when it
+    /// isn't cached. Nothing is cached while the PDDB is locked, since the
+    /// empty index read then isn't the real one.
+    fn with_index<R, F: FnOnce(&[(String, Option<u32>)]) -> R>(&self, f: F) -> R {
+        if self.index.borrow().is_none() {
+            let index = deserialize_index_words(&self.read_doc_index());
+            if !self.mounted {
+                return f(&index);
             }
-            Err(_) => Vec::new(),
+            *self.index.borrow_mut() = Some(index);
         }
+        f(self.index.borrow().as_deref().unwrap_or(&[]))
+    }
+
+    /// Raw bytes of the document index (empty if there isn't one yet).
+    fn read_doc_index(&self) -> Vec<u8> {
+        self.store.read(DICT_DOCS, INDEX_KEY).unwrap_or_default()
     }
 
     pub fn save_doc(&self, name: &str, content: &str) {
-        let key_name = format!("doc_{}", name);
-        let data = serialize_document(name, content);
+        save_doc_in(self, name, content);
+    }
 
-        match self.pddb.get(DICT_DOCS, &key_name, None, true, true, Some(data.len()), None::<fn()>) {
-            Ok(mut key) => {
-                key.seek(SeekFrom::Start(0)).ok();
-                key.write_all(&data).ok();
-            }
-            Err(e) => {
-                log::error!("Failed to save doc '{}': {:?}", name, e);
-                return;
-            }
-        }
+    /// Hold off syncing until `commit_batch`, so several saves in a row
+    /// (a document and a journal entry on focus loss, say) cost one sync.
+    /// Batches nest; only the outermost commit syncs.
+    pub fn begin_batch(&self) {
+        self.batch.begin();
+    }
 
-        // Update index
-        let mut names = self.list_docs();
-        if !names.iter().any(|n| n == name) {
-            names.push(name.to_string());
-            self.write_doc_index(&names);
-        }
+    /// Close a batch, syncing once if anything was written in it.
+    pub fn commit_batch(&self) {
+        self.batch.commit(|| self.flush());
+    }
+
+    /// Sync now, or at the end of the open batch.
+    fn sync(&self) {
+        self.batch.sync(|| self.flush());
+    }
 
-        self.pddb.sync().ok();
+    fn flush(&self) {
+        self.store.sync();
     }
 
-    pub fn load_doc(&self, name: &str) -> Option<String> {
+    /// A document's saved content: None if it was never saved, an error if
+    /// the stored blob is there but fails its checks. The damaged blob is
+    /// left alone so it can still be recovered.
+    pub fn load_doc(&self, name: &str) -> Result<Option<String>, DocError> {
         let key_name = format!("doc_{}", name);
-        match self.pddb.get(DICT_DOCS, &key_name, None, false, false, None, None::<fn()>) {
-            Ok(mut key) => {
-                let mut data = Vec::new();
-                key.seek(SeekFrom::Start(0)).ok();
-                if key.read_to_end(&mut data).is_ok() && !data.is_empty() {
-                    deserialize_document(&data).map(|(_, content)| content)
-                } else {
-                    None
+        match self.store.read(DICT_DOCS, &key_name) {
+            Some(data) if !data.is_empty() => match deserialize_document(&data) {
+                Some((_, content)) => Ok(Some(content)),
+                None => {
+                    log::warn!("Document '{}' is corrupt or truncated; not loading it", name);
+                    Err(DocError::Corrupt)
                 }
-            }
-            Err(_) => None,
+            },
+            _ => Ok(None),
         }
     }
 
+    /// Rename a document along with everything kept under its name (see
+    /// `rename_doc_in`). The index entry is renamed in place, so list order
+    /// is kept. A document that was never saved just gets its new name
+    /// added to the index.
+    pub fn rename_doc(&self, old: &str, new: &str) -> Result<(), RenameError> {
+        rename_doc_in(self, old, new)
+    }
+
+    /// Move a document `delta` places in the index, which is the manual
+    /// order shown in the doc list. Returns its new position.
+    pub fn reorder_doc(&self, name: &str, delta: isize) -> Option<usize> {
+        let mut names = self.list_docs();
+        let pos = move_in_index(&mut names, name, delta)?;
+        self.write_doc_index(&names, &[]);
+        self.sync();
+        Some(pos)
+    }
+
     pub fn delete_doc(&self, name: &str) {
         let key_name = format!("doc_{}", name);
-        self.pddb.delete_key(DICT_DOCS, &key_name, None).ok();
+        self.store.delete(DICT_DOCS, &key_name).ok();
 
         // Update index
         let mut names = self.list_docs();
         names.retain(|n| n != name);
-        self.write_doc_index(&names);
+        self.write_doc_index(&names, &[]);
 
-        self.pddb.sync().ok();
+        self.sync();
     }
 
+    /// Resolve a typed name to an existing document (exact or unique prefix).
+    pub fn find_doc(&self, name: &str) -> Option<String> {
+        match match_doc_name(&self.list_docs(), name) {
+            NameMatch::Exact(found) | NameMatch::Prefix(found) => Some(found),
+            NameMatch::Ambiguous(_) | NameMatch::NoMatch => None,
+        }
+    }
+
+    /// First free name of the form "prefix", "prefix 2", "prefix 3", ...
     pub fn next_doc_name(&self, prefix: &str) -> String {
-        let existing = self.list_docs();
-        let mut n = 1u32;
-        loop {
-            let candidate = if n == 1 {
-                prefix.to_string()
-            } else {
-                format!("{} {}", prefix, n)
-            };
-            if !existing.iter().any(|name| name == &candidate) {
-                return candidate;
-            }
-            n += 1;
-            if n > 999 {
-                return format!("{} {}", prefix, n);
+        let prefix = sanitize_name(prefix).unwrap_or_else(|| String::from("Untitled"));
+        self.with_index(|index| next_free_name_by(&prefix, |name| index.iter().any(|(n, _)| n == name)))
+    }
+
+    /// Save a copy of a document as "{name} copy" (or "copy 2", ...),
+    /// added to the end of the index. Returns the copy's name, or None if
+    /// `name` has no saved content (or it is damaged).
+    pub fn duplicate_doc(&self, name: &str) -> Option<String> {
+        let content = self.load_doc(name).ok()??;
+        let copy = self.next_doc_name(&format!("{} copy", name));
+        self.save_doc(&copy, &content);
+        Some(copy)
+    }
+
+    /// Every document, in index order, packed into one backup blob, with
+    /// how many documents went in and how many were left out as damaged.
+    pub fn export_all(&self) -> (Vec<u8>, usize, usize) {
+        let mut docs = Vec::new();
+        let mut skipped = 0;
+        for name in self.list_docs() {
+            match self.load_doc(&name) {
+                Ok(Some(content)) => docs.push((name, content)),
+                Ok(None) => {}
+                Err(DocError::Corrupt) => skipped += 1,
             }
         }
+        (backup::pack(&docs), docs.len(), skipped)
     }
 
-    fn write_doc_index(&self, names: &[String]) {
-        let data = serialize_index(names);
-        match self.pddb.get(DICT_DOCS, INDEX_KEY, None, true, true, Some(data.len()), None::<fn()>) {
-            Ok(mut key) => {
-                key.seek(SeekFrom::Start(0)).ok();
-                key.write_all(&data).ok();
+    /// Restore the documents in a backup blob, appending them to the index.
+    /// Documents already stored with the same text are skipped; a name
+    /// taken by a different document gets a "restored" copy instead. A
+    /// truncated blob restores what arrived whole. Returns how many
+    /// documents were written.
+    pub fn import_all(&self, data: &[u8]) -> usize {
+        let mut names = self.list_docs();
+        // A damaged document counts as different text, so the backup's copy
+        // is restored next to it
+        let writes = backup::plan_restore(&names, backup::unpack(data), |name| self.load_doc(name).ok().flatten());
+        let mut counted = Vec::new();
+        for (name, content) in writes {
+            if self.write_doc(&name, &content) {
+                names.push(name.clone());
+                counted.push((name, word_count(&content)));
             }
-            Err(e) => log::error!("Failed to write doc index: {:?}", e),
         }
+        if !counted.is_empty() {
+            self.write_doc_index(&names, &counted);
+            self.sync();
+        }
+        counted.len()
     }
 
-    // ---- Journal Operations ----
+    /// Write a document's key without touching the index.
+    fn write_doc(&self, name: &str, content: &str) -> bool {
+        let key_name = format!("doc_{}", name);
+        let data = serialize_document(name, content);
 
-    pub fn load_journal_entry(&self, date: &str) -> Option<String> {
-        match self.pddb.get(DICT_JOURNAL, date, None, false, false, None, None::<fn()>) {
-            Ok(mut key) => {
-                let mut content = String::new();
-                key.seek(SeekFrom::Start(0)).ok();
-                if key.read_to_string(&mut content).is_ok() && !content.is_empty() {
-                    Some(content)
-                } else {
-                    None
-                }
+        match self.store.write(DICT_DOCS, &key_name, &data) {
+            Ok(()) => true,
+            Err(e) => {
+                log::error!("Failed to save doc '{}': {:?}", name, e);
+                false
             }
-            Err(_) => None,
         }
     }
 
-    pub fn save_journal_entry(&self, date: &str, content: &str) {
-        let data = content.as_bytes();
-        match self.pddb.get(DICT_JOURNAL, date, None, true, true, Some(data.len()), None::<fn()>) {
-            Ok(mut key) => {
-                key.seek(SeekFrom::Start(0)).ok();
-                key.write_all(data).ok();
+    /// Write the index as `names`, in order. Word counts come from
+    /// `counted` for documents just written, and otherwise carry over from
+    /// the stored index. An index that comes out the same isn't rewritten.
+    fn write_doc_index(&self, names: &[String], counted: &[(String, u32)]) {
+        let entries = self.with_index(|stored| index_with_counts(stored, names, counted));
+        if self.index.borrow().as_ref() == Some(&entries) {
+            return;
+        }
+        let data = serialize_index_words(&entries);
+        match self.store.write(DICT_DOCS, INDEX_KEY, &data) {
+            Ok(()) => {
+                *self.index.borrow_mut() = Some(entries);
             }
             Err(e) => {
-                log::error!("Failed to save journal entry for {}: {:?}", date, e);
-                return;
+                log::error!("Failed to write doc index: {:?}", e);
+                self.refresh_index();
             }
         }
+    }
+
+    // ---- Journal Operations ----
+
+    pub fn load_journal_entry(&self, date: &str) -> Option<String> {
+        let content = String::from_utf8(self.store.read(DICT_JOURNAL, date)?).ok()?;
+        if content.is_empty() {
+            None
+        } else {
+            Some(content)
+        }
+    }
+
+    pub fn save_journal_entry(&self, date: &str, content: &str) {
+        if let Err(e) = self.store.write(DICT_JOURNAL, date, content.as_bytes()) {
+            log::error!("Failed to save journal entry for {}: {:?}", date, e);
+            return;
+        }
 
         // Update journal index
         let mut dates = self.list_journal_dates();
@@ -161,35 +382,36 @@ impl WriterStorage {
             self.write_journal_index(&dates);
         }
 
-        self.pddb.sync().ok();
+        self.sync();
     }
 
     pub fn list_journal_dates(&self) -> Vec<String> {
-        match self.pddb.get(DICT_JOURNAL, INDEX_KEY, None, false, false, None, None::<fn()>) {
-            Ok(mut key) => {
-                let mut data = String::new();
-                key.seek(SeekFrom::Start(0)).ok();
-                if key.read_to_string(&mut data).is_ok() {
-                    data.lines()
-                        .filter(|l| !l.is_empty())
-                        .map(|l| l.to_string())
-                        .collect()
-                } else {
-                    Vec::new()
-                }
-            }
-            Err(_) => Vec::new(),
+        match self.store.read(DICT_JOURNAL, INDEX_KEY).map(String::from_utf8) {
+            Some(Ok(data)) => journal::parse_index(&data),
+            _ => Vec::new(),
         }
     }
 
+    /// Delete the entry for `date` and drop it from the index. Deleting a
+    /// date that was never saved changes nothing.
+    pub fn delete_journal_entry(&self, date: &str) {
+        self.store.delete(DICT_JOURNAL, date).ok();
+        let mut dates = self.list_journal_dates();
+        if journal::remove_date(&mut dates, date) {
+            self.write_journal_index(&dates);
+        }
+        self.sync();
+    }
+
+    /// Word statistics for journal entries dated `from..=to` (YYYY-MM-DD).
+    pub fn journal_stats(&self, from: &str, to: &str) -> JournalStats {
+        journal::journal_stats(from, to, |date| self.load_journal_entry(date))
+    }
+
     fn write_journal_index(&self, dates: &[String]) {
         let data = dates.join("\n");
-        match self.pddb.get(DICT_JOURNAL, INDEX_KEY, None, true, true, Some(data.len()), None::<fn()>) {
-            Ok(mut key) => {
-                key.seek(SeekFrom::Start(0)).ok();
-                key.write_all(data.as_bytes()).ok();
-            }
-            Err(e) => log::error!("Failed to write journal index: {:?}", e),
+        if let Err(e) = self.store.write(DICT_JOURNAL, INDEX_KEY, data.as_bytes()) {
+            log::error!("Failed to write journal index: {:?}", e);
         }
     }
 
@@ -197,37 +419,419 @@ impl WriterStorage {
 
     /// Load app configuration. Returns default config if not found.
     pub fn load_config(&self) -> WriterConfig {
-        match self.pddb.get(DICT_SETTINGS, CONFIG_KEY, None, false, false, None, None::<fn()>) {
-            Ok(mut key) => {
-                let mut data = Vec::new();
-                key.seek(SeekFrom::Start(0)).ok();
-                if key.read_to_end(&mut data).is_ok() && data.len() >= 3 {
-                    deserialize_config(&data).unwrap_or_else(WriterConfig::default)
-                } else {
-                    WriterConfig::default()
-                }
-            }
-            Err(_) => WriterConfig::default(),
+        match self.store.read(DICT_SETTINGS, CONFIG_KEY) {
+            Some(data) if data.len() >= 3 => deserialize_config(&data).unwrap_or_default(),
+            _ => WriterConfig::default(),
         }
     }
 
     /// Save app configuration.
     pub fn save_config(&self, config: &WriterConfig) {
         let data = serialize_config(config);
-        match self.pddb.get(DICT_SETTINGS, CONFIG_KEY, None, true, true, Some(data.len()), None::<fn()>) {
-            Ok(mut key) => {
-                key.seek(SeekFrom::Start(0)).ok();
-                if let Err(e) = key.write_all(&data) {
-                    log::error!("Failed to write config: {:?}", e);
-                    return;
-                }
-            }
+        if let Err(e) = self.store.write(DICT_SETTINGS, CONFIG_KEY, &data) {
+            log::error!("Failed to write config: {:?}", e);
+            return;
+        }
+        self.sync();
+        log::info!("Settings saved");
+    }
+
+    // ---- On-device Export ----
+
+    /// Save an exported copy (`{name}.md`, `{name}.html`) in
+    /// `writer.exports`, replacing any earlier one. Returns false if the
+    /// write failed.
+    pub fn save_export(&self, key_name: &str, data: &[u8]) -> bool {
+        if let Err(e) = self.store.write(DICT_EXPORTS, key_name, data) {
+            log::error!("Failed to write {}: {:?}", key_name, e);
+            return false;
+        }
+        self.sync();
+        true
+    }
+
+    /// Read back a file saved in `writer.exports`.
+    pub fn load_export(&self, key_name: &str) -> Option<Vec<u8>> {
+        self.store.read(DICT_EXPORTS, key_name)
+    }
+
+    // ---- Export Resume ----
+
+    /// Where the last interrupted USB autotype stopped, if any.
+    pub fn load_export_resume(&self) -> Option<ExportResume> {
+        deserialize_export_resume(&self.store.read(DICT_SETTINGS, EXPORT_RESUME_KEY)?)
+    }
+
+    /// Remember how far an interrupted USB autotype got.
+    pub fn save_export_resume(&self, resume: &ExportResume) {
+        if self.write_export_resume(resume) {
+            self.sync();
+        }
+    }
+
+    fn write_export_resume(&self, resume: &ExportResume) -> bool {
+        let data = serialize_export_resume(resume);
+        match self.store.write(DICT_SETTINGS, EXPORT_RESUME_KEY, &data) {
+            Ok(()) => true,
             Err(e) => {
-                log::error!("Failed to open config key: {:?}", e);
-                return;
+                log::error!("Failed to write export resume point: {:?}", e);
+                false
             }
         }
-        self.pddb.sync().ok();
-        log::info!("Settings saved");
+    }
+
+    /// Forget the resume point once an export completes.
+    pub fn clear_export_resume(&self) {
+        if self.store.delete(DICT_SETTINGS, EXPORT_RESUME_KEY).is_ok() {
+            self.sync();
+        }
+    }
+}
+
+/// The steps a document save takes, apart from the PDDB so their order
+/// can be checked against a fake.
+trait DocWrites {
+    fn names(&self) -> Vec<String>;
+    fn put_doc(&self, name: &str, content: &str) -> bool;
+    fn put_index(&self, names: &[String], counted: &[(String, u32)]);
+    fn sync(&self);
+}
+
+impl DocWrites for WriterStorage {
+    fn names(&self) -> Vec<String> {
+        self.list_docs()
+    }
+    fn put_doc(&self, name: &str, content: &str) -> bool {
+        self.write_doc(name, content)
+    }
+    fn put_index(&self, names: &[String], counted: &[(String, u32)]) {
+        self.write_doc_index(names, counted)
+    }
+    fn sync(&self) {
+        WriterStorage::sync(self)
+    }
+}
+
+/// The extra reads and writes a rename needs: the document's content and
+/// the side data kept under its name.
+trait DocRenames: DocWrites {
+    fn get_doc(&self, name: &str) -> Result<Option<String>, DocError>;
+    fn remove_doc(&self, name: &str);
+    /// Cached word count from the index
+    fn cached_words(&self, name: &str) -> Option<u32>;
+    fn resume(&self) -> Option<ExportResume>;
+    fn put_resume(&self, resume: &ExportResume);
+}
+
+impl DocRenames for WriterStorage {
+    fn get_doc(&self, name: &str) -> Result<Option<String>, DocError> {
+        self.load_doc(name)
+    }
+    fn remove_doc(&self, name: &str) {
+        self.store.delete(DICT_DOCS, &format!("doc_{}", name)).ok();
+    }
+    fn cached_words(&self, name: &str) -> Option<u32> {
+        self.with_index(|index| index.iter().find(|(n, _)| n == name).and_then(|(_, words)| *words))
+    }
+    fn resume(&self) -> Option<ExportResume> {
+        self.load_export_resume()
+    }
+    fn put_resume(&self, resume: &ExportResume) {
+        self.write_export_resume(resume);
+    }
+}
+
+/// Rename a document and everything kept under its name: the content
+/// moves to the new key, the cached word count and index entry follow,
+/// and an export resume point for it is rewritten to the new name. Side
+/// data that isn't there is skipped. All of it goes out in one sync, so a
+/// crash can't leave metadata behind under the old name.
+fn rename_doc_in<S: DocRenames>(store: &S, old: &str, new: &str) -> Result<(), RenameError> {
+    let new = sanitize_name(new).ok_or(RenameError::EmptyName)?;
+    let new = new.as_str();
+    let mut names = store.names();
+    rename_in_index(&mut names, old, new)?;
+    if new == old {
+        return Ok(());
+    }
+
+    let mut counted = Vec::new();
+    // A damaged document stays where it is rather than being left behind
+    // under a name the index no longer has
+    match store.get_doc(old).map_err(|_| RenameError::Unreadable)? {
+        Some(content) => {
+            if !store.put_doc(new, &content) {
+                return Err(RenameError::WriteFailed);
+            }
+            store.remove_doc(old);
+            counted.push((new.to_string(), word_count(&content)));
+        }
+        None => {
+            if let Some(words) = store.cached_words(old) {
+                counted.push((new.to_string(), words));
+            }
+        }
+    }
+    if let Some(resume) = store.resume().filter(|r| r.doc == old) {
+        store.put_resume(&ExportResume { doc: new.to_string(), ..resume });
+    }
+    store.put_index(&names, &counted);
+    store.sync();
+    Ok(())
+}
+
+/// Save one document: its key first, then the index with its new word
+/// count, then one sync. Writing the document before the index means a
+/// crash in between leaves at worst an unlisted key, never an index entry
+/// without a document behind it.
+fn save_doc_in<S: DocWrites>(store: &S, name: &str, content: &str) {
+    if !store.put_doc(name, content) {
+        return;
+    }
+    let mut names = store.names();
+    if !names.iter().any(|n| n == name) {
+        names.push(name.to_string());
+    }
+    store.put_index(&names, &[(name.to_string(), word_count(content))]);
+    store.sync();
+}
+
+/// Defers syncs while a batch is open. `sync` and `commit` take the actual
+/// flush so the bookkeeping doesn't need a PDDB.
+#[derive(Default)]
+struct SyncBatch {
+    depth: Cell<u32>,
+    pending: Cell<bool>,
+}
+
+impl SyncBatch {
+    fn begin(&self) {
+        self.depth.set(self.depth.get() + 1);
+    }
+
+    fn sync<F: FnOnce()>(&self, flush: F) {
+        if self.depth.get() == 0 {
+            flush();
+        } else {
+            self.pending.set(true);
+        }
+    }
+
+    fn commit<F: FnOnce()>(&self, flush: F) {
+        self.depth.set(self.depth.get().saturating_sub(1));
+        if self.depth.get() == 0 && self.pending.replace(false) {
+            flush();
+        }
+    }
+}
+
+/// Word count cached in the index; the same count the editor's status bar shows.
+fn word_count(content: &str) -> u32 {
+    content.split_whitespace().count() as u32
+}
+
+/// Mount state after a retry: an already-mounted PDDB is left alone,
+/// otherwise `try_mount` decides.
+fn mount_if_needed<F: FnOnce() -> bool>(mounted: bool, try_mount: F) -> bool {
+    mounted || try_mount()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Records each step a save takes, against an in-memory index.
+    #[derive(Default)]
+    struct FakeStore {
+        index: RefCell<Vec<String>>,
+        log: RefCell<Vec<String>>,
+        batch: SyncBatch,
+        fail_doc_writes: bool,
+        docs: RefCell<Vec<(String, String)>>,
+        words: RefCell<Vec<(String, u32)>>,
+        resume: RefCell<Option<ExportResume>>,
+        corrupt: RefCell<Vec<String>>,
+    }
+
+    impl DocWrites for FakeStore {
+        fn names(&self) -> Vec<String> {
+            self.index.borrow().clone()
+        }
+        fn put_doc(&self, name: &str, content: &str) -> bool {
+            self.log.borrow_mut().push(format!("doc {}", name));
+            if !self.fail_doc_writes {
+                self.docs.borrow_mut().push((name.to_string(), content.to_string()));
+            }
+            !self.fail_doc_writes
+        }
+        fn put_index(&self, names: &[String], counted: &[(String, u32)]) {
+            self.log.borrow_mut().push(format!("index {}", names.join(",")));
+            *self.index.borrow_mut() = names.to_vec();
+            self.words.borrow_mut().extend_from_slice(counted);
+        }
+        fn sync(&self) {
+            self.batch.sync(|| self.log.borrow_mut().push(String::from("sync")));
+        }
+    }
+
+    impl DocRenames for FakeStore {
+        fn get_doc(&self, name: &str) -> Result<Option<String>, DocError> {
+            if self.corrupt.borrow().iter().any(|n| n == name) {
+                return Err(DocError::Corrupt);
+            }
+            Ok(self.docs.borrow().iter().find(|(n, _)| n == name).map(|(_, c)| c.clone()))
+        }
+        fn remove_doc(&self, name: &str) {
+            self.log.borrow_mut().push(format!("remove {}", name));
+            self.docs.borrow_mut().retain(|(n, _)| n != name);
+        }
+        fn cached_words(&self, name: &str) -> Option<u32> {
+            self.words.borrow().iter().find(|(n, _)| n == name).map(|(_, w)| *w)
+        }
+        fn resume(&self) -> Option<ExportResume> {
+            self.resume.borrow().clone()
+        }
+        fn put_resume(&self, resume: &ExportResume) {
+            self.log.borrow_mut().push(format!("resume {}", resume.doc));
+            *self.resume.borrow_mut() = Some(resume.clone());
+        }
+    }
+
+    fn store_with(names: &[&str]) -> FakeStore {
+        let store = FakeStore::default();
+        *store.index.borrow_mut() = names.iter().map(|n| n.to_string()).collect();
+        store
+    }
+
+    #[test]
+    fn test_rename_moves_side_data() {
+        let store = store_with(&["Draft", "Other"]);
+        store.docs.borrow_mut().push((String::from("Draft"), String::from("three words here")));
+        *store.resume.borrow_mut() = Some(ExportResume { doc: String::from("Draft"), offset: 5, total: 16, crc: 7 });
+        assert_eq!(rename_doc_in(&store, "Draft", "Final"), Ok(()));
+
+        assert_eq!(store.get_doc("Final"), Ok(Some(String::from("three words here"))));
+        assert_eq!(store.get_doc("Draft"), Ok(None));
+        assert_eq!(*store.index.borrow(), vec!["Final", "Other"]);
+        assert_eq!(store.cached_words("Final"), Some(3));
+        assert_eq!(*store.resume.borrow(), Some(ExportResume { doc: String::from("Final"), offset: 5, total: 16, crc: 7 }));
+        // Everything lands before the one sync
+        assert_eq!(*store.log.borrow(), vec!["doc Final", "remove Draft", "resume Final", "index Final,Other", "sync"]);
+    }
+
+    #[test]
+    fn test_rename_skips_missing_side_data() {
+        // No content, a cached count, and a resume point for another doc
+        let store = store_with(&["Draft"]);
+        store.words.borrow_mut().push((String::from("Draft"), 42));
+        *store.resume.borrow_mut() = Some(ExportResume { doc: String::from("Other"), offset: 1, total: 9, crc: 7 });
+        assert_eq!(rename_doc_in(&store, "Draft", "Final"), Ok(()));
+        assert_eq!(store.cached_words("Final"), Some(42));
+        assert_eq!(store.resume.borrow().as_ref().map(|r| r.doc.as_str()), Some("Other"));
+        assert_eq!(*store.log.borrow(), vec!["index Final", "sync"]);
+
+        // Nothing at all under the old name
+        let store = store_with(&[]);
+        assert_eq!(rename_doc_in(&store, "", "Named"), Ok(()));
+        assert_eq!(*store.index.borrow(), vec!["Named"]);
+        assert_eq!(store.cached_words("Named"), None);
+
+        // A taken name changes nothing
+        let store = store_with(&["A", "B"]);
+        assert_eq!(rename_doc_in(&store, "A", "B"), Err(RenameError::NameTaken));
+        assert!(store.log.borrow().is_empty());
+
+        // A failed write keeps the old name and the index as they were
+        let store = FakeStore { fail_doc_writes: true, ..store_with(&["A"]) };
+        store.docs.borrow_mut().push((String::from("A"), String::from("text")));
+        assert_eq!(rename_doc_in(&store, "A", "C"), Err(RenameError::WriteFailed));
+        assert_eq!(*store.log.borrow(), vec!["doc C"]);
+        assert_eq!(*store.index.borrow(), vec!["A"]);
+        assert_eq!(store.get_doc("A"), Ok(Some(String::from("text"))));
+
+        // Nor does a damaged document: it keeps its name and its blob
+        let store = store_with(&["A"]);
+        store.corrupt.borrow_mut().push(String::from("A"));
+        assert_eq!(rename_doc_in(&store, "A", "C"), Err(RenameError::Unreadable));
+        assert!(store.log.borrow().is_empty());
+        assert_eq!(*store.index.borrow(), vec!["A"]);
+    }
+
+    #[test]
+    fn test_save_writes_doc_before_index() {
+        let store = FakeStore::default();
+        save_doc_in(&store, "Notes", "hello");
+        assert_eq!(*store.log.borrow(), vec!["doc Notes", "index Notes", "sync"]);
+
+        // A failed document write leaves the index alone
+        let store = FakeStore { fail_doc_writes: true, ..FakeStore::default() };
+        save_doc_in(&store, "Notes", "hello");
+        assert_eq!(*store.log.borrow(), vec!["doc Notes"]);
+        assert!(store.index.borrow().is_empty());
+    }
+
+    #[test]
+    fn test_batch_syncs_once_at_commit() {
+        let store = FakeStore::default();
+        store.batch.begin();
+        save_doc_in(&store, "A", "one");
+        store.batch.begin(); // nested: the inner commit doesn't sync
+        save_doc_in(&store, "B", "two");
+        store.batch.commit(|| store.log.borrow_mut().push(String::from("sync")));
+        assert!(!store.log.borrow().contains(&String::from("sync")));
+        store.batch.commit(|| store.log.borrow_mut().push(String::from("sync")));
+        assert_eq!(*store.log.borrow(), vec!["doc A", "index A", "doc B", "index A,B", "sync"]);
+
+        // A batch with nothing written doesn't sync
+        let quiet = SyncBatch::default();
+        quiet.begin();
+        quiet.commit(|| panic!("nothing to sync"));
+    }
+
+    fn memory_storage() -> WriterStorage {
+        WriterStorage::with_store(Box::<MemoryStore>::default())
+    }
+
+    #[test]
+    fn test_backup_restores_into_a_fresh_store() {
+        let from = memory_storage();
+        from.save_doc("Notes", "three words here");
+        from.save_doc("Draft", "");
+        from.save_doc("Work/Plan", "one\ntwo lines");
+        let (data, packed, skipped) = from.export_all();
+        assert_eq!((packed, skipped), (3, 0));
+
+        let to = memory_storage();
+        assert_eq!(to.import_all(&data), 3);
+        // Same names in the same order, same text, and the index counts rebuilt
+        assert_eq!(to.list_docs(), from.list_docs());
+        for name in from.list_docs() {
+            assert_eq!(to.load_doc(&name), from.load_doc(&name), "{}", name);
+        }
+        assert_eq!(to.list_doc_words(), from.list_doc_words());
+        assert_eq!(to.list_doc_words(), vec![
+            (String::from("Notes"), Some(3)),
+            (String::from("Draft"), Some(0)),
+            (String::from("Work/Plan"), Some(3)),
+        ]);
+    }
+
+    #[test]
+    fn test_backup_of_an_empty_store() {
+        let (data, packed, skipped) = memory_storage().export_all();
+        assert_eq!((packed, skipped), (0, 0));
+        let to = memory_storage();
+        assert_eq!(to.import_all(&data), 0);
+        assert!(to.list_docs().is_empty());
+        assert!(to.list_doc_words().is_empty());
+    }
+
+    #[test]
+    fn test_retry_mounts_only_when_locked() {
+        // Still locked, then unlocked
+        assert!(!mount_if_needed(false, || false));
+        assert!(mount_if_needed(false, || true));
+        // Already mounted: no second attempt
+        assert!(mount_if_needed(true, || panic!("mounted twice")));
     }
 }
@@ -1,14 +1,200 @@
+use std::collections::VecDeque;
+use writer_core::stats::wpm;
 use writer_core::TextBuffer;
 
+/// Span the live pace is measured over.
+const ROLLING_WPM_WINDOW_MS: u64 = 60_000;
+
+/// Sprint lengths offered before a session starts, in minutes (0 = no timer).
+pub const SPRINT_MINUTES: [u64; 4] = [0, 5, 10, 25];
+
 #[derive(Clone, Debug)]
 pub struct TypewriterState {
     pub buffer: TextBuffer,
+    // Session clock (ticktimer ms): when writing started, and when it ended
+    pub started_ms: u64,
+    pub ended_ms: Option<u64>,
+    // Timed sprint length, if one was picked
+    pub sprint_secs: Option<u64>,
+    // Time spent in the background, which doesn't count against the session
+    paused_ms: u64,
+    paused_at: Option<u64>,
+    // (active ms, word count) after recent keystrokes, oldest first
+    recent: VecDeque<(u64, usize)>,
 }
 
 impl TypewriterState {
     pub fn new() -> Self {
+        Self::start(0)
+    }
+
+    /// A fresh session whose clock starts at `now_ms`.
+    pub fn start(now_ms: u64) -> Self {
+        let mut recent = VecDeque::new();
+        recent.push_back((0, 0));
         Self {
             buffer: TextBuffer::new(),
+            started_ms: now_ms,
+            ended_ms: None,
+            sprint_secs: None,
+            paused_ms: 0,
+            paused_at: None,
+            recent,
+        }
+    }
+
+    /// Note the word count after a keystroke, for the rolling pace. Samples
+    /// are timed on the session clock, so time in the background is skipped.
+    pub fn record(&mut self, now_ms: u64) {
+        let active = self.active_ms(now_ms);
+        self.recent.push_back((active, self.buffer.word_count()));
+        // Keep the newest sample at or before the window start as the baseline
+        let window_start = active.saturating_sub(ROLLING_WPM_WINDOW_MS);
+        while self.recent.len() > 1 && self.recent[1].0 <= window_start {
+            self.recent.pop_front();
+        }
+    }
+
+    /// Stop the session clock (leaving typewriter mode for the summary).
+    pub fn finish(&mut self, now_ms: u64) {
+        self.ended_ms.get_or_insert(now_ms);
+    }
+
+    /// Stop the clock while the app is in the background.
+    pub fn pause(&mut self, now_ms: u64) {
+        if self.ended_ms.is_none() && self.paused_at.is_none() {
+            self.paused_at = Some(now_ms);
+        }
+    }
+
+    /// Restart the clock on returning to the foreground.
+    pub fn resume(&mut self, now_ms: u64) {
+        if let Some(at) = self.paused_at.take() {
+            self.paused_ms += now_ms.saturating_sub(at);
         }
     }
+
+    /// Milliseconds written for: since the start, up to when the session
+    /// ended, less any time in the background.
+    fn active_ms(&self, now_ms: u64) -> u64 {
+        let end = self.ended_ms.unwrap_or(now_ms);
+        let paused = self.paused_ms + self.paused_at.map_or(0, |at| end.saturating_sub(at));
+        end.saturating_sub(self.started_ms).saturating_sub(paused)
+    }
+
+    /// Seconds since the session started, up to when it ended.
+    pub fn elapsed_secs(&self, now_ms: u64) -> u64 {
+        self.active_ms(now_ms) / 1000
+    }
+
+    /// Seconds left in a timed sprint, rounded up so the countdown reads
+    /// 0:00 only once time is up. None without a sprint.
+    pub fn remaining_secs(&self, now_ms: u64) -> Option<u64> {
+        let sprint_ms = self.sprint_secs? * 1000;
+        Some(sprint_ms.saturating_sub(self.active_ms(now_ms)).div_ceil(1000))
+    }
+
+    /// Whether a timed sprint has run out.
+    pub fn sprint_over(&self, now_ms: u64) -> bool {
+        self.remaining_secs(now_ms) == Some(0)
+    }
+
+    /// Pace over roughly the last minute of writing, not counting time in
+    /// the background.
+    pub fn rolling_wpm(&self, now_ms: u64) -> usize {
+        let (since_ms, words_then) = self.recent.front().copied().unwrap_or((0, 0));
+        let words = self.buffer.word_count().saturating_sub(words_then);
+        wpm(words, self.active_ms(now_ms).saturating_sub(since_ms) / 1000)
+    }
+
+    /// Pace over the whole session.
+    pub fn average_wpm(&self, now_ms: u64) -> usize {
+        wpm(self.buffer.word_count(), self.elapsed_secs(now_ms))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn type_words(tw: &mut TypewriterState, words: usize, now_ms: u64) {
+        for _ in 0..words {
+            for ch in "word ".chars() {
+                tw.buffer.append_char(ch);
+            }
+        }
+        tw.record(now_ms);
+    }
+
+    #[test]
+    fn test_session_clock_stops_at_finish() {
+        let mut tw = TypewriterState::start(10_000);
+        assert_eq!(tw.elapsed_secs(10_000), 0);
+        assert_eq!(tw.average_wpm(10_000), 0);
+        type_words(&mut tw, 30, 70_000);
+        assert_eq!(tw.elapsed_secs(70_000), 60);
+        assert_eq!(tw.average_wpm(70_000), 30);
+
+        tw.finish(100_000);
+        // The done screen keeps showing the session as it ended
+        assert_eq!(tw.elapsed_secs(500_000), 90);
+        assert_eq!(tw.average_wpm(500_000), 20);
+    }
+
+    #[test]
+    fn test_sprint_counts_down() {
+        let mut tw = TypewriterState::start(1_000);
+        assert_eq!(tw.remaining_secs(1_000), None);
+        tw.sprint_secs = Some(300);
+        assert_eq!(tw.remaining_secs(1_000), Some(300));
+        assert_eq!(tw.remaining_secs(1_500), Some(300));
+        assert_eq!(tw.remaining_secs(2_000), Some(299));
+        assert!(!tw.sprint_over(300_999));
+        assert!(tw.sprint_over(301_000));
+        assert_eq!(tw.remaining_secs(900_000), Some(0));
+    }
+
+    #[test]
+    fn test_background_time_is_not_counted() {
+        let mut tw = TypewriterState::start(0);
+        tw.sprint_secs = Some(600);
+        tw.pause(60_000);
+        // Frozen while in the background
+        assert_eq!(tw.remaining_secs(200_000), Some(540));
+        tw.resume(300_000);
+        assert_eq!(tw.elapsed_secs(330_000), 90);
+        assert_eq!(tw.remaining_secs(330_000), Some(510));
+        // A second focus change before resuming doesn't restart the pause
+        tw.pause(400_000);
+        tw.pause(450_000);
+        tw.resume(500_000);
+        assert_eq!(tw.elapsed_secs(500_000), 160);
+    }
+
+    #[test]
+    fn test_rolling_wpm_forgets_old_bursts() {
+        let mut tw = TypewriterState::start(0);
+        // A fast first minute...
+        type_words(&mut tw, 60, 60_000);
+        assert_eq!(tw.rolling_wpm(60_000), 60);
+        // ...then a slow two minutes
+        type_words(&mut tw, 10, 120_000);
+        type_words(&mut tw, 10, 180_000);
+        assert_eq!(tw.rolling_wpm(180_000), 10);
+        assert_eq!(tw.average_wpm(180_000), 26);
+    }
+
+    #[test]
+    fn test_rolling_wpm_skips_background_time() {
+        let mut tw = TypewriterState::start(0);
+        type_words(&mut tw, 15, 30_000);
+        // Ten minutes away doesn't drag the pace down...
+        tw.pause(30_000);
+        tw.resume(630_000);
+        type_words(&mut tw, 15, 660_000);
+        assert_eq!(tw.rolling_wpm(660_000), 30);
+        // ...nor push the earlier words out of the window
+        type_words(&mut tw, 0, 670_000);
+        assert_eq!(tw.rolling_wpm(670_000), 25);
+    }
 }
@@ -1,18 +1,73 @@
 // Shared UI constants and helpers for the Writer app
 
-/// Truncate a string to fit within a character limit, adding "..." if needed
+/// Truncate a string to fit within a character limit, adding "..." if needed.
+/// Counts and cuts by character, so multibyte text is never split.
 pub fn truncate_str(s: &str, max_chars: usize) -> String {
-    if s.len() <= max_chars {
-        s.to_string()
-    } else if max_chars > 3 {
-        format!("{}...", &s[..max_chars - 3])
+    if s.chars().count() <= max_chars {
+        return s.to_string();
+    }
+    let keep = if max_chars > 3 { max_chars - 3 } else { max_chars };
+    let end = s.char_indices().nth(keep).map_or(s.len(), |(i, _)| i);
+    if max_chars > 3 {
+        format!("{}...", &s[..end])
     } else {
-        s[..max_chars].to_string()
+        s[..end].to_string()
+    }
+}
+
+/// Fit `line` into `width` characters with the first case-insensitive match
+/// of `query` in view. Returns the text to show and the character range of
+/// the match within it (empty when there's no match). Lines that don't fit
+/// are cut with "..." at the head, the tail, or both, around the match.
+pub fn match_window(line: &str, query: &str, width: usize) -> (String, usize, usize) {
+    let chars: Vec<char> = line.chars().collect();
+    let (start, end) = match find_ignore_case(&chars, query) {
+        Some(range) => range,
+        None => return (truncate_str(line, width), 0, 0),
+    };
+    let n = chars.len();
+    if n <= width {
+        return (line.to_string(), start, end);
+    }
+    if end + 3 <= width || width <= 6 {
+        // Visible after the usual tail cut
+        let shown = truncate_str(line, width);
+        let limit = shown.chars().count();
+        return (shown, start.min(limit), end.min(limit));
+    }
+    let collect = |from: usize, to: usize| chars[from..to].iter().collect::<String>();
+    // Cut the head too, centring the match in the room between the ellipses
+    let room = width - 6;
+    let from = start.saturating_sub(room.saturating_sub(end - start) / 2);
+    if from + width - 3 >= n {
+        // Near the end of the line: no tail cut needed
+        let from = n - (width - 3);
+        return (format!("...{}", collect(from, n)), start - from + 3, end - from + 3);
+    }
+    let to = from + room;
+    (format!("...{}...", collect(from, to)), start - from + 3, end.min(to) - from + 3)
+}
+
+/// Character range of the first case-insensitive match of `query` in `chars`.
+fn find_ignore_case(chars: &[char], query: &str) -> Option<(usize, usize)> {
+    let query: Vec<char> = query.chars().collect();
+    if query.is_empty() || query.len() > chars.len() {
+        return None;
     }
+    let same = |a: char, b: char| a == b || a.to_lowercase().eq(b.to_lowercase());
+    (0..=chars.len() - query.len())
+        .find(|&i| query.iter().enumerate().all(|(j, &q)| same(chars[i + j], q)))
+        .map(|i| (i, i + query.len()))
 }
 
 /// Format a number with comma separators (for display)
+#[allow(dead_code)]
 pub fn format_number(n: usize) -> String {
+    format_number_sep(n, ',')
+}
+
+/// Format a number with `sep` between groups of three digits
+pub fn format_number_sep(n: usize, sep: char) -> String {
     if n < 1000 {
         return n.to_string();
     }
@@ -20,13 +75,76 @@ pub fn format_number(n: usize) -> String {
     let mut result = String::new();
     for (i, ch) in s.chars().rev().enumerate() {
         if i > 0 && i % 3 == 0 {
-            result.push(',');
+            result.push(sep);
         }
         result.push(ch);
     }
     result.chars().rev().collect()
 }
 
+/// Format a duration as "m:ss", or "h:mm:ss" from an hour up
+pub fn format_duration(secs: u64) -> String {
+    let (h, m, s) = (secs / 3600, secs / 60 % 60, secs % 60);
+    if h > 0 {
+        format!("{}:{:02}:{:02}", h, m, s)
+    } else {
+        format!("{}:{:02}", m, s)
+    }
+}
+
+/// How long a status message stays on screen, in ms.
+pub const STATUS_MSG_MS: u64 = 3000;
+
+/// A status message (text, expiry in ms) with its expiry set from `now_ms`.
+pub fn status_message(text: &str, now_ms: u64) -> (String, u64) {
+    (text.to_string(), now_ms + STATUS_MSG_MS)
+}
+
+/// The status message's text while it is still showing at `now_ms`.
+pub fn visible_status(status: &Option<(String, u64)>, now_ms: u64) -> Option<&str> {
+    match status {
+        Some((text, expires_ms)) if now_ms < *expires_ms => Some(text),
+        _ => None,
+    }
+}
+
+/// The millisecond clock status messages, idle autosave and sprints are
+/// timed on: the ticktimer on the device, `FakeClock` in tests.
+pub trait Clock {
+    fn elapsed_ms(&self) -> u64;
+}
+
+impl Clock for ticktimer_server::Ticktimer {
+    fn elapsed_ms(&self) -> u64 {
+        ticktimer_server::Ticktimer::elapsed_ms(self)
+    }
+}
+
+/// A clock that stands still until the test moves it through `now`.
+#[cfg(test)]
+#[derive(Default)]
+pub struct FakeClock {
+    pub now: std::rc::Rc<std::cell::Cell<u64>>,
+}
+
+#[cfg(test)]
+impl Clock for FakeClock {
+    fn elapsed_ms(&self) -> u64 {
+        self.now.get()
+    }
+}
+
+/// Which rows of a `len`-long list to show in `visible` rows. Returns the
+/// first row shown and the cursor clamped into the list: a cursor left past
+/// the end (after a filter or delete shrank the list) lands on the last row,
+/// and the window never scrolls past the end, so the screen stays filled
+/// with the cursor in view.
+pub fn list_window(cursor: usize, len: usize, visible: usize) -> (usize, usize) {
+    let cursor = cursor.min(len.saturating_sub(1));
+    let start = (cursor + 1).saturating_sub(visible).min(len.saturating_sub(visible));
+    (start, cursor)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -38,6 +156,55 @@ mod tests {
         assert_eq!(truncate_str("hi", 2), "hi");
     }
 
+    #[test]
+    fn test_truncate_str_multibyte() {
+        // Keeping 4 bytes before the "..." would cut 'é' in half
+        assert_eq!(truncate_str("café au lait", 7), "café...");
+        // Six bytes, but four characters fit without truncating
+        assert_eq!(truncate_str("déjà", 4), "déjà");
+        assert_eq!(truncate_str("🙂🙂🙂🙂🙂", 4), "🙂...");
+        assert_eq!(truncate_str("日本語です", 2), "日本");
+    }
+
+    #[test]
+    fn test_match_window_short_line() {
+        assert_eq!(match_window("Met Alice for tea", "alice", 28), ("Met Alice for tea".into(), 4, 9));
+        // No match: plain truncation, nothing highlighted
+        assert_eq!(match_window("Met Alice for tea", "bob", 10), ("Met Ali...".into(), 0, 0));
+    }
+
+    #[test]
+    fn test_match_window_match_near_start() {
+        let line = "Alice and I walked all the way to the harbour and back";
+        assert_eq!(match_window(line, "ALICE", 20), ("Alice and I walke...".into(), 0, 5));
+    }
+
+    #[test]
+    fn test_match_window_match_at_end_of_long_line() {
+        let line = "We walked all the way down to the harbour and met Alice";
+        let (text, start, end) = match_window(line, "alice", 20);
+        assert_eq!(text, "...our and met Alice");
+        assert_eq!(text.chars().count(), 20);
+        assert_eq!(text.chars().skip(start).take(end - start).collect::<String>(), "Alice");
+    }
+
+    #[test]
+    fn test_match_window_match_in_middle() {
+        let line = "We walked all the way down to meet Alice at the harbour and then back home";
+        let (text, start, end) = match_window(line, "alice", 20);
+        assert!(text.starts_with("...") && text.ends_with("..."));
+        assert_eq!(text.chars().count(), 20);
+        assert_eq!(text.chars().skip(start).take(end - start).collect::<String>(), "Alice");
+    }
+
+    #[test]
+    fn test_match_window_multibyte() {
+        let line = "Déjà vu: un café très très très long près de la gare";
+        let (text, start, end) = match_window(line, "GARE", 16);
+        assert_eq!(text.chars().skip(start).take(end - start).collect::<String>(), "gare");
+        assert_eq!(text.chars().count(), 16);
+    }
+
     #[test]
     fn test_format_number() {
         assert_eq!(format_number(42), "42");
@@ -46,4 +213,53 @@ mod tests {
         assert_eq!(format_number(1000000), "1,000,000");
     }
 
+    #[test]
+    fn test_format_number_sep() {
+        assert_eq!(format_number_sep(1000, ' '), "1 000");
+        assert_eq!(format_number_sep(1234567, '.'), "1.234.567");
+        // Under 1000 there's nothing to group
+        assert_eq!(format_number_sep(999, '.'), "999");
+        assert_eq!(format_number_sep(0, ' '), "0");
+        assert_eq!(format_number_sep(u32::MAX as usize, '.'), "4.294.967.295");
+        assert_eq!(
+            format_number_sep(usize::MAX, ','),
+            if cfg!(target_pointer_width = "64") { "18,446,744,073,709,551,615" } else { "4,294,967,295" }
+        );
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(0), "0:00");
+        assert_eq!(format_duration(5), "0:05");
+        assert_eq!(format_duration(754), "12:34");
+        assert_eq!(format_duration(25 * 60), "25:00");
+        assert_eq!(format_duration(3600), "1:00:00");
+        assert_eq!(format_duration(3723), "1:02:03");
+    }
+
+    #[test]
+    fn test_status_message_expires() {
+        let status = Some(status_message("Saved", 10_000));
+        assert_eq!(visible_status(&status, 10_000), Some("Saved"));
+        assert_eq!(visible_status(&status, 10_000 + STATUS_MSG_MS - 1), Some("Saved"));
+        assert_eq!(visible_status(&status, 10_000 + STATUS_MSG_MS), None);
+        assert_eq!(visible_status(&None, 0), None);
+    }
+
+    #[test]
+    fn test_list_window_cursor_at_end() {
+        assert_eq!(list_window(0, 20, 8), (0, 0));
+        assert_eq!(list_window(7, 20, 8), (0, 7));
+        assert_eq!(list_window(8, 20, 8), (1, 8));
+        assert_eq!(list_window(19, 20, 8), (12, 19));
+        // A cursor left past the end of a shrunken list shows its last page
+        assert_eq!(list_window(19, 10, 8), (2, 9));
+    }
+
+    #[test]
+    fn test_list_window_short_list() {
+        assert_eq!(list_window(15, 3, 8), (0, 2));
+        assert_eq!(list_window(2, 3, 8), (0, 2));
+        assert_eq!(list_window(5, 0, 8), (0, 0));
+    }
 }
@@ -0,0 +1,159 @@
+use alloc::{string::String, vec::Vec, format};
+use crate::docs::next_free_name;
+use crate::serialize::{deserialize_document, deserialize_index, serialize_document, serialize_index};
+
+/// Magic bytes at the start of a backup blob.
+pub const BACKUP_MAGIC: &[u8; 4] = b"WBAK";
+
+/// Key in the `writer.exports` dict that on-device backups are saved as.
+pub const BACKUP_EXPORT_KEY: &str = "backup.wbak";
+
+/// Pack every document into one blob:
+/// [b"WBAK"][u32 index_len][index][u32 doc_len][document]...
+/// The index section is `serialize_index` and each document section is
+/// `serialize_document`, titled with the document's name, in index order.
+pub fn pack(docs: &[(String, String)]) -> Vec<u8> {
+    let names: Vec<String> = docs.iter().map(|(name, _)| name.clone()).collect();
+    let index = serialize_index(&names);
+
+    let mut data = Vec::new();
+    data.extend_from_slice(BACKUP_MAGIC);
+    data.extend_from_slice(&(index.len() as u32).to_le_bytes());
+    data.extend_from_slice(&index);
+    for (name, content) in docs {
+        let doc = serialize_document(name, content);
+        data.extend_from_slice(&(doc.len() as u32).to_le_bytes());
+        data.extend_from_slice(&doc);
+    }
+    data
+}
+
+/// Unpack a backup into (name, content) pairs in the backed-up index order.
+/// A blob cut short gives back every document that arrived whole; a
+/// section that fails to parse (or its checksum) is skipped. Anything that
+/// isn't a backup gives nothing.
+pub fn unpack(bytes: &[u8]) -> Vec<(String, String)> {
+    let Some(rest) = bytes.strip_prefix(BACKUP_MAGIC) else {
+        return Vec::new();
+    };
+    let mut sections = Sections(rest);
+    let index = sections.next().map(deserialize_index).unwrap_or_default();
+
+    let mut docs: Vec<(String, String)> = sections.filter_map(deserialize_document).collect();
+    // Documents the index lists come first, in its order
+    docs.sort_by_key(|(name, _)| index.iter().position(|n| n == name).unwrap_or(usize::MAX));
+    docs
+}
+
+/// Length-prefixed sections ([u32 len][bytes]) until the data runs out.
+struct Sections<'a>(&'a [u8]);
+
+impl<'a> Iterator for Sections<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<&'a [u8]> {
+        let len = u32::from_le_bytes(self.0.get(..4)?.try_into().ok()?) as usize;
+        let end = len.checked_add(4)?;
+        let section = self.0.get(4..end)?;
+        self.0 = &self.0[end..];
+        Some(section)
+    }
+}
+
+/// Decide where each backed-up document goes, given the documents already
+/// stored (`existing`, with `load` reading one). A document whose name is
+/// free is restored as is. Otherwise it is skipped if the stored document
+/// (or an earlier restored copy of it) has the same text, and restored
+/// beside it as "name restored" (then "name restored 2", ...) if not.
+/// Returns the (name, content) pairs to write, in order.
+pub fn plan_restore<F>(existing: &[String], docs: Vec<(String, String)>, mut load: F) -> Vec<(String, String)>
+where
+    F: FnMut(&str) -> Option<String>,
+{
+    let mut taken = existing.to_vec();
+    let mut writes = Vec::new();
+    for (name, content) in docs {
+        let copy_prefix = format!("{} restored", name);
+        let name = if !taken.contains(&name) {
+            name
+        } else if existing.iter()
+            .filter(|n| **n == name || n.starts_with(&copy_prefix))
+            .any(|n| load(n).as_deref() == Some(content.as_str()))
+        {
+            continue;
+        } else {
+            next_free_name(&taken, &copy_prefix)
+        };
+        taken.push(name.clone());
+        writes.push((name, content));
+    }
+    writes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    fn docs(list: &[(&str, &str)]) -> Vec<(String, String)> {
+        list.iter().map(|(n, c)| (n.to_string(), c.to_string())).collect()
+    }
+
+    /// In-memory stand-in for the document store: restore into it the
+    /// way `WriterStorage::import_all` does.
+    fn restore_into(store: &mut Vec<(String, String)>, blob: &[u8]) -> usize {
+        let names: Vec<String> = store.iter().map(|(n, _)| n.clone()).collect();
+        let current = store.clone();
+        let writes = plan_restore(&names, unpack(blob), |name| {
+            current.iter().find(|(n, _)| n == name).map(|(_, c)| c.clone())
+        });
+        let count = writes.len();
+        store.extend(writes);
+        count
+    }
+
+    #[test]
+    fn test_round_trip_into_empty_store() {
+        let original = docs(&[("Notes", "one\ntwo"), ("Draft", ""), ("Café", "naïve")]);
+        let blob = pack(&original);
+        let mut store = Vec::new();
+        assert_eq!(restore_into(&mut store, &blob), 3);
+        assert_eq!(store, original);
+    }
+
+    #[test]
+    fn test_empty_store_round_trip() {
+        let blob = pack(&[]);
+        assert!(unpack(&blob).is_empty());
+        let mut store = docs(&[("Kept", "x")]);
+        assert_eq!(restore_into(&mut store, &blob), 0);
+        assert_eq!(store, docs(&[("Kept", "x")]));
+    }
+
+    #[test]
+    fn test_restore_skips_identical_and_renames_changed() {
+        let blob = pack(&docs(&[("Same", "a"), ("Changed", "new"), ("Fresh", "f")]));
+        let mut store = docs(&[("Same", "a"), ("Changed", "old")]);
+        assert_eq!(restore_into(&mut store, &blob), 2);
+        assert_eq!(
+            store,
+            docs(&[("Same", "a"), ("Changed", "old"), ("Changed restored", "new"), ("Fresh", "f")])
+        );
+        // Restoring twice doesn't pile up copies
+        assert_eq!(restore_into(&mut store, &blob), 0);
+    }
+
+    #[test]
+    fn test_truncated_backup_restores_whole_documents() {
+        let original = docs(&[("A", "first"), ("B", "second"), ("C", "third")]);
+        let blob = pack(&original);
+        // Cut into the last document: the first two survive
+        let cut = &blob[..blob.len() - 3];
+        assert_eq!(unpack(cut), docs(&[("A", "first"), ("B", "second")]));
+        // Every prefix parses without panicking
+        for len in 0..blob.len() {
+            assert!(unpack(&blob[..len]).len() <= 3);
+        }
+        assert!(unpack(b"not a backup").is_empty());
+    }
+}
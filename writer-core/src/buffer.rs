@@ -1,7 +1,20 @@
-#[derive(Clone, Debug)]
+use alloc::{string::{String, ToString}, vec::Vec, format, vec};
+use core::fmt;
+use crate::markdown::{classify_lines, list_marker, LineKind};
+
+/// Maximum number of undo steps kept per buffer.
+const UNDO_LIMIT: usize = 100;
+
+/// Number of deleted spans remembered for yank / yank-pop.
+const KILL_RING_SIZE: usize = 8;
+
+/// Abbreviations whose period doesn't end a sentence, lowercase.
+const ABBREVIATIONS: [&str; 4] = ["e.g.", "i.e.", "cf.", "vs."];
+
+#[derive(Clone, Debug, PartialEq)]
 pub struct Cursor {
     pub line: usize,
-    pub col: usize,
+    pub col: usize, // char index within the line, not a byte offset
 }
 
 impl Cursor {
@@ -10,15 +23,70 @@ impl Cursor {
     }
 }
 
+impl Default for Cursor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct TextBuffer {
     pub lines: Vec<String>,
     pub cursor: Cursor,
     pub viewport_top: usize,
     pub viewport_lines: usize,
+    // First column shown when long lines scroll instead of wrapping
+    pub viewport_col: usize,
+    pub modified: bool,
+    pub selection_anchor: Option<Cursor>,
+    undo_stack: Vec<BufferSnapshot>,
+    redo_stack: Vec<BufferSnapshot>,
+    // Cursor position where the next insert_char continues the current undo step
+    coalesce_at: Option<Cursor>,
+    // Recently deleted spans, newest last
+    kill_ring: Vec<String>,
+    // Span inserted by the last yank and its kill-ring index, for yank_pop
+    last_yank: Option<(Cursor, Cursor, usize)>,
+    // Where the last Up/Down left the cursor and the row column it aims for,
+    // so a short row in between doesn't lose the column
+    desired_col: Option<(Cursor, usize)>,
+}
+
+/// Size figures for a buffer, gathered in one pass over its lines.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct BufferStats {
+    /// Whitespace-separated words, as `word_count`
+    pub words: usize,
+    /// Characters including line breaks, as `char_count`
+    pub chars: usize,
+    pub lines: usize,
+    /// Length in characters of the longest line, as `longest_line_len`
+    pub longest_line: usize,
+}
+
+/// Everything needed to put a buffer back the way it was: used for undo
+/// steps, and serialized (see `serialize::serialize_snapshot`) to persist
+/// an editing session.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BufferSnapshot {
+    pub lines: Vec<String>,
+    pub cursor: Cursor,
+    pub viewport_top: usize,
     pub modified: bool,
 }
 
+impl Default for TextBuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Display for TextBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.lines.join("\n"))
+    }
+}
+
 impl TextBuffer {
     pub fn new() -> Self {
         Self {
@@ -26,49 +94,83 @@ impl TextBuffer {
             cursor: Cursor::new(),
             viewport_top: 0,
             viewport_lines: 13,
+            viewport_col: 0,
             modified: false,
+            selection_anchor: None,
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            coalesce_at: None,
+            kill_ring: Vec::new(),
+            last_yank: None,
+            desired_col: None,
         }
     }
 
+    /// Buffer holding `text`, one line per `\n` (a `\r` before it is
+    /// dropped). A trailing newline becomes a final empty line, so
+    /// `to_string` gives back exactly the text that was loaded.
     pub fn from_text(text: &str) -> Self {
-        let lines: Vec<String> = if text.is_empty() {
-            vec![String::new()]
-        } else {
-            text.lines().map(|l| l.to_string()).collect()
-        };
-        // Ensure at least one line
-        let lines = if lines.is_empty() { vec![String::new()] } else { lines };
+        let lines: Vec<String> = text
+            .split('\n')
+            .map(|l| l.strip_suffix('\r').unwrap_or(l).to_string())
+            .collect();
         Self {
             lines,
             cursor: Cursor::new(),
             viewport_top: 0,
             viewport_lines: 13,
+            viewport_col: 0,
             modified: false,
+            selection_anchor: None,
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            coalesce_at: None,
+            kill_ring: Vec::new(),
+            last_yank: None,
+            desired_col: None,
         }
     }
 
+    /// Length of a line in characters (cursor columns are char indices).
+    pub fn line_len(&self, line: usize) -> usize {
+        self.lines[line].chars().count()
+    }
+
+    /// Byte offset of char column `col` in `line`, clamped to the line end.
+    pub fn byte_offset(&self, line: usize, col: usize) -> usize {
+        let text = &self.lines[line];
+        text.char_indices()
+            .nth(col)
+            .map(|(i, _)| i)
+            .unwrap_or(text.len())
+    }
+
     pub fn insert_char(&mut self, ch: char) {
-        let line = &mut self.lines[self.cursor.line];
-        if self.cursor.col >= line.len() {
-            line.push(ch);
-        } else {
-            line.insert(self.cursor.col, ch);
+        // A run of typed characters is a single undo step
+        if self.coalesce_at.as_ref() != Some(&self.cursor) {
+            self.push_undo();
         }
+        let at = self.byte_offset(self.cursor.line, self.cursor.col);
+        self.lines[self.cursor.line].insert(at, ch);
         self.cursor.col += 1;
         self.modified = true;
+        self.coalesce_at = Some(self.cursor.clone());
     }
 
     pub fn delete_back(&mut self) {
+        if self.cursor.col > 0 || self.cursor.line > 0 {
+            self.push_undo();
+        }
         if self.cursor.col > 0 {
-            let line = &mut self.lines[self.cursor.line];
             self.cursor.col -= 1;
-            line.remove(self.cursor.col);
+            let at = self.byte_offset(self.cursor.line, self.cursor.col);
+            self.lines[self.cursor.line].remove(at);
             self.modified = true;
         } else if self.cursor.line > 0 {
             // Merge with previous line
             let current = self.lines.remove(self.cursor.line);
             self.cursor.line -= 1;
-            self.cursor.col = self.lines[self.cursor.line].len();
+            self.cursor.col = self.line_len(self.cursor.line);
             self.lines[self.cursor.line].push_str(&current);
             self.modified = true;
         }
@@ -76,9 +178,13 @@ impl TextBuffer {
     }
 
     pub fn delete_forward(&mut self) {
-        let line_len = self.lines[self.cursor.line].len();
+        let line_len = self.line_len(self.cursor.line);
+        if self.cursor.col < line_len || self.cursor.line + 1 < self.lines.len() {
+            self.push_undo();
+        }
         if self.cursor.col < line_len {
-            self.lines[self.cursor.line].remove(self.cursor.col);
+            let at = self.byte_offset(self.cursor.line, self.cursor.col);
+            self.lines[self.cursor.line].remove(at);
             self.modified = true;
         } else if self.cursor.line + 1 < self.lines.len() {
             // Merge next line into current
@@ -88,10 +194,48 @@ impl TextBuffer {
         }
     }
 
-    pub fn newline(&mut self) {
+    /// Insert `ch` as prose: a lowercase letter typed at the start of a
+    /// sentence (line start, after a list marker, or after ". ", "! ", "? ")
+    /// is capitalized. Code blocks and the word after "e.g." and the like
+    /// are left alone.
+    pub fn insert_char_prose(&mut self, ch: char) {
+        let mut upper = ch.to_uppercase();
+        let ch = match (upper.next(), upper.next()) {
+            (Some(u), None) if ch.is_lowercase() && self.at_sentence_start() && !self.in_code_block() => u,
+            _ => ch,
+        };
+        self.insert_char(ch);
+    }
+
+    /// Whether the cursor sits where a new sentence begins.
+    fn at_sentence_start(&self) -> bool {
         let line = &self.lines[self.cursor.line];
-        let remainder = line[self.cursor.col..].to_string();
-        self.lines[self.cursor.line].truncate(self.cursor.col);
+        let before = &line[..self.byte_offset(self.cursor.line, self.cursor.col)];
+        if before.trim().is_empty() {
+            return true;
+        }
+        if list_marker(line).is_some_and(|(len, _)| len == before.len()) {
+            return true;
+        }
+        let text = before.trim_end();
+        let last_word = text.rsplit(char::is_whitespace).next().unwrap_or("").to_lowercase();
+        text.len() < before.len() && text.ends_with(['.', '!', '?']) && !ABBREVIATIONS.contains(&last_word.as_str())
+    }
+
+    /// Whether the cursor line is code: an indented or fence line, or any
+    /// line between an opening ``` and its closing fence.
+    fn in_code_block(&self) -> bool {
+        let line = self.cursor.line;
+        let text = &self.lines[line];
+        // A blank line classifies as Empty, so check the indent directly
+        text.starts_with("    ") || text.starts_with('\t')
+            || classify_lines(&self.lines[..=line])[line] == LineKind::CodeBlock
+    }
+
+    pub fn newline(&mut self) {
+        self.push_undo();
+        let at = self.byte_offset(self.cursor.line, self.cursor.col);
+        let remainder = self.lines[self.cursor.line].split_off(at);
         self.cursor.line += 1;
         self.cursor.col = 0;
         self.lines.insert(self.cursor.line, remainder);
@@ -99,26 +243,102 @@ impl TextBuffer {
         self.ensure_cursor_visible();
     }
 
-    pub fn move_up(&mut self) {
-        if self.cursor.line > 0 {
-            self.cursor.line -= 1;
-            let line_len = self.lines[self.cursor.line].len();
-            if self.cursor.col > line_len {
-                self.cursor.col = line_len;
+    /// Enter for the markdown editor: continues a list with the same marker
+    /// ("- ", "- [ ] ", or the next number), and on an item with no text
+    /// removes the marker instead, ending the list. Text moved down by a
+    /// mid-item split loses its leading spaces, so the new item has one
+    /// space after its marker.
+    pub fn newline_smart(&mut self) {
+        let line = &self.lines[self.cursor.line];
+        let at = self.byte_offset(self.cursor.line, self.cursor.col);
+        match list_marker(line) {
+            Some((len, _)) if line[len.min(line.len())..].trim().is_empty() => {
+                self.push_undo();
+                self.lines[self.cursor.line].clear();
+                self.cursor.col = 0;
+                self.modified = true;
             }
-            self.ensure_cursor_visible();
+            // Enter before or inside the marker just splits the line
+            Some((len, next)) if at >= len => {
+                self.newline();
+                let moved = &self.lines[self.cursor.line];
+                let moved = moved[leading_whitespace(moved).len()..].to_string();
+                self.lines[self.cursor.line] = next.clone() + &moved;
+                self.cursor.col = next.chars().count();
+            }
+            _ => self.newline(),
+        }
+    }
+
+    /// Enter that keeps indentation: the new line starts with the current
+    /// line's leading spaces and tabs, and the cursor goes after them. Text
+    /// moved down by a mid-line split loses its own leading whitespace so it
+    /// lines up with that indentation. List items continue as with
+    /// newline_smart.
+    pub fn newline_autoindent(&mut self) {
+        let line = &self.lines[self.cursor.line];
+        if list_marker(line).is_some() {
+            self.newline_smart();
+            return;
         }
+        let indent = leading_whitespace(line).to_string();
+        self.newline();
+        let moved = &self.lines[self.cursor.line];
+        let moved = moved[leading_whitespace(moved).len()..].to_string();
+        self.lines[self.cursor.line] = indent.clone() + &moved;
+        self.cursor.col = indent.chars().count();
+    }
+
+    pub fn move_up(&mut self) {
+        self.move_up_wrapped(0);
     }
 
     pub fn move_down(&mut self) {
-        if self.cursor.line + 1 < self.lines.len() {
-            self.cursor.line += 1;
-            let line_len = self.lines[self.cursor.line].len();
-            if self.cursor.col > line_len {
-                self.cursor.col = line_len;
+        self.move_down_wrapped(0);
+    }
+
+    /// Move to the screen row above with lines soft-wrapped at `width` chars
+    /// (0 = no wrapping), which may be in the same line. Repeated moves keep
+    /// aiming for the column they started from.
+    pub fn move_up_wrapped(&mut self, width: usize) {
+        self.move_vertical(false, width);
+    }
+
+    /// Move to the screen row below; see `move_up_wrapped`.
+    pub fn move_down_wrapped(&mut self, width: usize) {
+        self.move_vertical(true, width);
+    }
+
+    fn move_vertical(&mut self, down: bool, width: usize) {
+        let line = self.cursor.line;
+        let starts = wrap_starts(&self.lines[line], width);
+        let row = starts.iter().rposition(|&s| s <= self.cursor.col).unwrap_or(0);
+        let goal = match &self.desired_col {
+            Some((at, col)) if *at == self.cursor => *col,
+            _ => self.cursor.col - starts[row],
+        };
+
+        let (line, row) = if down {
+            if row + 1 < starts.len() {
+                (line, row + 1)
+            } else if line + 1 < self.lines.len() {
+                (line + 1, 0)
+            } else {
+                return;
             }
-            self.ensure_cursor_visible();
-        }
+        } else if row > 0 {
+            (line, row - 1)
+        } else if line > 0 {
+            (line - 1, self.visual_rows(line - 1, width) - 1)
+        } else {
+            return;
+        };
+
+        let starts = wrap_starts(&self.lines[line], width);
+        let col = starts[row] + goal.min(self.row_len(line, &starts, row));
+        self.cursor = Cursor { line, col };
+        self.desired_col = Some((self.cursor.clone(), goal));
+        self.ensure_cursor_visible();
     }
 
     pub fn move_left(&mut self) {
@@ -126,13 +346,13 @@ impl TextBuffer {
             self.cursor.col -= 1;
         } else if self.cursor.line > 0 {
             self.cursor.line -= 1;
-            self.cursor.col = self.lines[self.cursor.line].len();
+            self.cursor.col = self.line_len(self.cursor.line);
             self.ensure_cursor_visible();
         }
     }
 
     pub fn move_right(&mut self) {
-        let line_len = self.lines[self.cursor.line].len();
+        let line_len = self.line_len(self.cursor.line);
         if self.cursor.col < line_len {
             self.cursor.col += 1;
         } else if self.cursor.line + 1 < self.lines.len() {
@@ -142,16 +362,75 @@ impl TextBuffer {
         }
     }
 
+    /// Move to the start of the next word. Runs of word characters and runs
+    /// of punctuation are each one stop; whitespace between them is skipped
+    /// as a group. At end of line, continues onto the next line.
+    pub fn move_word_right(&mut self) {
+        let chars: Vec<char> = self.lines[self.cursor.line].chars().collect();
+        let mut col = self.cursor.col.min(chars.len());
+        if col == chars.len() {
+            if self.cursor.line + 1 >= self.lines.len() {
+                return;
+            }
+            self.cursor.line += 1;
+            let next: Vec<char> = self.lines[self.cursor.line].chars().collect();
+            self.cursor.col = next.iter().take_while(|c| c.is_whitespace()).count();
+            self.ensure_cursor_visible();
+            return;
+        }
+        let class = char_class(chars[col]);
+        if class != CharClass::Space {
+            while col < chars.len() && char_class(chars[col]) == class {
+                col += 1;
+            }
+        }
+        while col < chars.len() && chars[col].is_whitespace() {
+            col += 1;
+        }
+        self.cursor.col = col;
+    }
+
+    /// Move to the start of the previous word (mirror of `move_word_right`).
+    /// At column 0, moves to the end of the previous line.
+    pub fn move_word_left(&mut self) {
+        if self.cursor.col == 0 {
+            self.move_left();
+            return;
+        }
+        let chars: Vec<char> = self.lines[self.cursor.line].chars().collect();
+        let mut col = self.cursor.col.min(chars.len());
+        while col > 0 && chars[col - 1].is_whitespace() {
+            col -= 1;
+        }
+        if col > 0 {
+            let class = char_class(chars[col - 1]);
+            while col > 0 && char_class(chars[col - 1]) == class {
+                col -= 1;
+            }
+        }
+        self.cursor.col = col;
+    }
+
     pub fn move_home(&mut self) {
         self.cursor.col = 0;
+        self.viewport_col = 0;
     }
 
-    pub fn move_end(&mut self) {
-        self.cursor.col = self.lines[self.cursor.line].len();
+    /// Home that respects indentation: jumps to the first non-whitespace
+    /// character, or to column 0 if already there. A blank line has no
+    /// text to jump to, so it always goes to column 0.
+    pub fn move_home_smart(&mut self) {
+        let line = &self.lines[self.cursor.line];
+        let indent = if line.trim().is_empty() {
+            0
+        } else {
+            line.chars().take_while(|c| c.is_whitespace()).count()
+        };
+        self.cursor.col = if self.cursor.col == indent { 0 } else { indent };
     }
 
-    pub fn to_string(&self) -> String {
-        self.lines.join("\n")
+    pub fn move_end(&mut self) {
+        self.cursor.col = self.line_len(self.cursor.line);
     }
 
     pub fn line_count(&self) -> usize {
@@ -164,198 +443,2137 @@ impl TextBuffer {
             .count()
     }
 
+    /// Words of prose only: heading, list and quote markers are stripped,
+    /// and rules count nothing. Code blocks (fenced or indented) count
+    /// their words only with `include_code`; code spans within a line are
+    /// prose either way.
+    pub fn word_count_prose(&self, include_code: bool) -> usize {
+        self.lines
+            .iter()
+            .zip(classify_lines(&self.lines))
+            .filter(|(_, kind)| include_code || *kind != LineKind::CodeBlock)
+            .map(|(line, kind)| LineKind::strip_prefix(line, kind).split_whitespace().count())
+            .sum()
+    }
+
+    /// Estimated minutes to read the document at `wpm` words per minute,
+    /// rounded up (any text at all takes at least a minute). Words are
+    /// counted as `word_count_prose`, so markdown markers aren't read and
+    /// code blocks only count with `include_code`.
+    pub fn reading_time_minutes(&self, wpm: usize, include_code: bool) -> usize {
+        if wpm == 0 {
+            return 0;
+        }
+        self.word_count_prose(include_code).div_ceil(wpm)
+    }
+
+    /// Number of sentences: each run of `.`, `!` or `?` after some words
+    /// ends one ("..." counts once), and trailing words without a
+    /// terminator count as a final sentence. A period followed directly by
+    /// a letter or digit ("3.14") doesn't end a sentence.
+    pub fn sentence_count(&self) -> usize {
+        let text = self.to_string();
+        let mut chars = text.chars().peekable();
+        let mut count = 0;
+        let mut in_sentence = false;
+        while let Some(ch) = chars.next() {
+            if ch.is_alphanumeric() {
+                in_sentence = true;
+            } else if matches!(ch, '.' | '!' | '?') && in_sentence {
+                let next = chars.peek().copied();
+                if !next.is_some_and(char::is_alphanumeric) {
+                    count += 1;
+                    in_sentence = false;
+                }
+            }
+        }
+        if in_sentence {
+            count += 1;
+        }
+        count
+    }
+
+    /// Characters in the document, line breaks included. Counts Unicode
+    /// scalar values, so "é" is one character, not two bytes.
     pub fn char_count(&self) -> usize {
-        self.lines.iter()
-            .map(|l| l.len())
-            .sum::<usize>()
+        self.char_count_visible()
             + self.lines.len().saturating_sub(1) // count newlines
     }
 
-    pub fn ensure_cursor_visible(&mut self) {
-        if self.cursor.line < self.viewport_top {
-            self.viewport_top = self.cursor.line;
-        } else if self.cursor.line >= self.viewport_top + self.viewport_lines {
-            self.viewport_top = self.cursor.line - self.viewport_lines + 1;
+    /// Characters actually typed, without the line breaks `char_count`
+    /// adds between lines (so trailing blank lines count for nothing).
+    pub fn char_count_visible(&self) -> usize {
+        self.lines.iter().map(|l| l.chars().count()).sum()
+    }
+
+    /// Word, character, line and longest-line counts in one pass, for
+    /// screens that show several of them on every redraw.
+    pub fn stats(&self) -> BufferStats {
+        let mut stats = BufferStats {
+            lines: self.lines.len(),
+            chars: self.lines.len().saturating_sub(1), // newlines
+            ..BufferStats::default()
+        };
+        for line in &self.lines {
+            let len = line.chars().count();
+            stats.chars += len;
+            stats.longest_line = stats.longest_line.max(len);
+            stats.words += line.split_whitespace().count();
         }
+        stats
     }
 
-    /// Append a character at the end of the buffer (for typewriter mode)
-    pub fn append_char(&mut self, ch: char) {
-        let last = self.lines.len() - 1;
-        self.lines[last].push(ch);
-        self.cursor.line = last;
-        self.cursor.col = self.lines[last].len();
+    /// Length in characters of the longest line.
+    pub fn longest_line_len(&self) -> usize {
+        self.lines.iter()
+            .map(|l| l.chars().count())
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// True if any line is longer than `max` characters (0 = no limit).
+    pub fn exceeds_line_length(&self, max: usize) -> bool {
+        max > 0 && self.longest_line_len() > max
+    }
+
+    /// Re-wrap only the lines longer than `max` characters, breaking at spaces
+    /// where possible. Shorter lines are left untouched, and so are code,
+    /// list and heading lines, which a break would change the meaning of.
+    /// Wrapped block quote lines keep their `> ` on every piece. Returns true
+    /// if anything changed.
+    pub fn reflow_long_lines(&mut self, max: usize) -> bool {
+        if !self.exceeds_line_length(max) {
+            return false;
+        }
+        let kinds = classify_lines(&self.lines);
+        let wraps: Vec<bool> = kinds
+            .iter()
+            .map(|kind| !matches!(kind,
+                LineKind::CodeBlock | LineKind::UnorderedList | LineKind::OrderedList | LineKind::TaskList { .. }
+                | LineKind::Heading1 | LineKind::Heading2 | LineKind::Heading3))
+            .collect();
+        if !self.lines.iter().zip(&wraps).any(|(line, &wraps)| wraps && line.chars().count() > max) {
+            return false;
+        }
+        self.push_undo();
+        let cursor_line = self.cursor.line;
+        let cursor_char = self.cursor.col;
+
+        let mut new_lines = Vec::with_capacity(self.lines.len());
+        for (idx, line) in self.lines.iter().enumerate() {
+            let pieces = if !wraps[idx] {
+                vec![(0, 0, line.clone())]
+            } else if kinds[idx] == LineKind::BlockQuote {
+                wrap_quote(line, max)
+            } else {
+                wrap_line(line, max).into_iter().map(|(start, piece)| (start, 0, piece)).collect()
+            };
+            if idx == cursor_line {
+                // Keep the cursor on the same character it was on
+                let (piece_idx, (start, lead, piece)) = pieces.iter()
+                    .enumerate()
+                    .rev()
+                    .find(|(_, (start, _, _))| *start <= cursor_char)
+                    .unwrap_or((0, &pieces[0]));
+                let offset = (lead + cursor_char - start).min(piece.chars().count());
+                self.cursor.line = new_lines.len() + piece_idx;
+                self.cursor.col = offset;
+            }
+            new_lines.extend(pieces.into_iter().map(|(_, _, piece)| piece));
+        }
+        self.lines = new_lines;
         self.modified = true;
         self.ensure_cursor_visible();
+        true
     }
 
-    /// Append a newline at the end (for typewriter mode)
-    pub fn append_newline(&mut self) {
-        self.lines.push(String::new());
-        self.cursor.line = self.lines.len() - 1;
+    /// Copy of the current line (for line-wise yank).
+    pub fn yank_line(&self) -> String {
+        self.lines[self.cursor.line].clone()
+    }
+
+    /// Insert `text` as whole line(s) below the cursor line and move the
+    /// cursor to the start of the first inserted line.
+    pub fn put_line_below(&mut self, text: &str) {
+        self.push_undo();
+        let at = self.cursor.line + 1;
+        for (i, line) in text.split('\n').enumerate() {
+            self.lines.insert(at + i, line.to_string());
+        }
+        self.cursor.line = at;
         self.cursor.col = 0;
         self.modified = true;
         self.ensure_cursor_visible();
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Remove the cursor line entirely and return its text (it also goes
+    /// on the kill ring). Deleting the only line leaves a single empty line.
+    pub fn delete_line(&mut self) -> String {
+        self.push_undo();
+        let removed = self.lines.remove(self.cursor.line);
+        self.kill(removed.clone());
+        if self.lines.is_empty() {
+            self.lines.push(String::new());
+        }
+        self.cursor.line = self.cursor.line.min(self.lines.len() - 1);
+        self.clamp_cursor_col();
+        self.modified = true;
+        self.ensure_cursor_visible();
+        removed
+    }
 
-    #[test]
-    fn test_new_buffer() {
-        let buf = TextBuffer::new();
-        assert_eq!(buf.lines.len(), 1);
-        assert_eq!(buf.cursor.line, 0);
-        assert_eq!(buf.cursor.col, 0);
-        assert!(!buf.modified);
+    /// Insert a copy of the cursor line below it and move onto the copy.
+    pub fn duplicate_line(&mut self) {
+        self.push_undo();
+        let copy = self.lines[self.cursor.line].clone();
+        self.lines.insert(self.cursor.line + 1, copy);
+        self.cursor.line += 1;
+        self.modified = true;
+        self.ensure_cursor_visible();
     }
 
-    #[test]
-    fn test_from_text() {
-        let buf = TextBuffer::from_text("hello\nworld");
-        assert_eq!(buf.lines.len(), 2);
-        assert_eq!(buf.lines[0], "hello");
-        assert_eq!(buf.lines[1], "world");
+    /// Flip a task list checkbox between "[ ]" and "[x]" on `line`.
+    /// Returns false (and changes nothing) if the line isn't a task item.
+    pub fn toggle_checkbox_at(&mut self, line: usize) -> bool {
+        let checked = match self.lines.get(line).map(|l| LineKind::classify(l)) {
+            Some(LineKind::TaskList { checked }) => checked,
+            _ => return false,
+        };
+        self.push_undo();
+        let text = &mut self.lines[line];
+        // Indent, then the two-byte bullet, then "[" — the mark follows it
+        let mark = text.len() - text.trim_start().len() + 3;
+        text.replace_range(mark..mark + 1, if checked { " " } else { "x" });
+        self.modified = true;
+        true
     }
 
-    #[test]
-    fn test_insert_char() {
-        let mut buf = TextBuffer::new();
-        buf.insert_char('h');
-        buf.insert_char('i');
-        assert_eq!(buf.lines[0], "hi");
-        assert_eq!(buf.cursor.col, 2);
-        assert!(buf.modified);
+    // ---- Selection & clipboard ----
+
+    /// Anchor a selection at the cursor; moving the cursor extends it.
+    pub fn start_selection(&mut self) {
+        self.selection_anchor = Some(self.cursor.clone());
     }
 
-    #[test]
-    fn test_delete_back() {
-        let mut buf = TextBuffer::from_text("hello");
-        buf.cursor.col = 5;
-        buf.delete_back();
-        assert_eq!(buf.lines[0], "hell");
-        assert_eq!(buf.cursor.col, 4);
+    pub fn clear_selection(&mut self) {
+        self.selection_anchor = None;
     }
 
-    #[test]
-    fn test_delete_back_merge_lines() {
-        let mut buf = TextBuffer::from_text("hello\nworld");
-        buf.cursor.line = 1;
-        buf.cursor.col = 0;
-        buf.delete_back();
-        assert_eq!(buf.lines.len(), 1);
-        assert_eq!(buf.lines[0], "helloworld");
-        assert_eq!(buf.cursor.line, 0);
-        assert_eq!(buf.cursor.col, 5);
+    /// Ordered (start, end) of the selection, or None if nothing is selected.
+    pub fn selection_range(&self) -> Option<(Cursor, Cursor)> {
+        let anchor = self.selection_anchor.clone()?;
+        let cursor = self.cursor.clone();
+        let (start, end) = if (anchor.line, anchor.col) <= (cursor.line, cursor.col) {
+            (anchor, cursor)
+        } else {
+            (cursor, anchor)
+        };
+        if start == end {
+            None
+        } else {
+            Some((start, end))
+        }
     }
 
-    #[test]
-    fn test_newline() {
-        let mut buf = TextBuffer::from_text("hello");
-        buf.cursor.col = 3;
-        buf.newline();
-        assert_eq!(buf.lines.len(), 2);
-        assert_eq!(buf.lines[0], "hel");
-        assert_eq!(buf.lines[1], "lo");
-        assert_eq!(buf.cursor.line, 1);
-        assert_eq!(buf.cursor.col, 0);
+    /// Selected char columns on one line, for highlighting.
+    pub fn selection_cols(&self, line: usize) -> Option<(usize, usize)> {
+        let (start, end) = self.selection_range()?;
+        if line < start.line || line > end.line {
+            return None;
+        }
+        let from = if line == start.line { start.col } else { 0 };
+        let to = if line == end.line { end.col } else { self.line_len(line) };
+        Some((from, to))
     }
 
-    #[test]
-    fn test_cursor_movement() {
-        let mut buf = TextBuffer::from_text("hello\nworld");
-        buf.cursor.col = 2;
-        buf.move_down();
-        assert_eq!(buf.cursor.line, 1);
-        assert_eq!(buf.cursor.col, 2);
-        buf.move_up();
-        assert_eq!(buf.cursor.line, 0);
-        buf.move_end();
-        assert_eq!(buf.cursor.col, 5);
-        buf.move_home();
-        assert_eq!(buf.cursor.col, 0);
+    pub fn selected_text(&self) -> Option<String> {
+        let (start, end) = self.selection_range()?;
+        Some(self.text_between(&start, &end))
     }
 
-    #[test]
-    fn test_word_count() {
-        let buf = TextBuffer::from_text("hello world\nfoo bar baz");
-        assert_eq!(buf.word_count(), 5);
+    /// Remove the selected text and return it (it also goes on the kill ring).
+    pub fn cut_selection(&mut self) -> Option<String> {
+        let (start, end) = self.selection_range()?;
+        let text = self.text_between(&start, &end);
+        self.push_undo();
+        self.remove_range(start, end);
+        self.modified = true;
+        self.ensure_cursor_visible();
+        self.kill(text.clone());
+        Some(text)
     }
 
-    #[test]
-    fn test_char_count() {
-        let buf = TextBuffer::from_text("hi\nbye");
-        // "hi" (2) + "\n" (1) + "bye" (3) = 6
-        assert_eq!(buf.char_count(), 6);
+    // ---- Kill ring ----
+
+    /// Delete from the start of the previous word to the cursor.
+    pub fn delete_word_back(&mut self) -> bool {
+        let end = self.cursor.clone();
+        self.move_word_left();
+        let start = core::mem::replace(&mut self.cursor, end.clone());
+        self.kill_range(start, end)
     }
 
-    #[test]
-    fn test_viewport_scrolling() {
-        let mut buf = TextBuffer::new();
-        buf.viewport_lines = 3;
-        for i in 0..10 {
-            buf.lines.push(format!("line {}", i));
+    /// Delete from the cursor to the end of the line; at the end of a
+    /// line, delete the line break instead (joining the next line).
+    pub fn delete_to_line_end(&mut self) -> bool {
+        let line = self.cursor.line;
+        let end = if self.cursor.col < self.line_len(line) {
+            Cursor { line, col: self.line_len(line) }
+        } else if line + 1 < self.lines.len() {
+            Cursor { line: line + 1, col: 0 }
+        } else {
+            return false;
+        };
+        self.kill_range(self.cursor.clone(), end)
+    }
+
+    /// Insert the most recent kill at the cursor.
+    pub fn yank(&mut self) -> bool {
+        let Some(index) = self.kill_ring.len().checked_sub(1) else {
+            return false;
+        };
+        self.push_undo();
+        self.insert_yank(index);
+        true
+    }
+
+    /// Straight after a yank, replace the yanked text with the next older
+    /// kill, cycling round to the newest after the oldest.
+    pub fn yank_pop(&mut self) -> bool {
+        let Some((start, end, index)) = self.last_yank.clone() else {
+            return false;
+        };
+        if self.cursor != end {
+            return false;
         }
-        buf.cursor.line = 5;
-        buf.ensure_cursor_visible();
-        assert_eq!(buf.viewport_top, 3);
+        self.push_undo();
+        self.remove_range(start, end);
+        let len = self.kill_ring.len();
+        self.insert_yank((index + len - 1) % len);
+        true
     }
 
-    #[test]
-    fn test_delete_forward() {
-        let mut buf = TextBuffer::from_text("hello");
-        buf.cursor.col = 2;
-        buf.delete_forward();
-        assert_eq!(buf.lines[0], "helo");
+    /// Deleted spans on the kill ring, newest last.
+    pub fn kill_ring(&self) -> &[String] {
+        &self.kill_ring
     }
 
-    #[test]
-    fn test_delete_forward_merge() {
-        let mut buf = TextBuffer::from_text("hello\nworld");
-        buf.cursor.col = 5;
-        buf.delete_forward();
-        assert_eq!(buf.lines.len(), 1);
-        assert_eq!(buf.lines[0], "helloworld");
+    fn kill(&mut self, text: String) {
+        if text.is_empty() {
+            return;
+        }
+        if self.kill_ring.len() >= KILL_RING_SIZE {
+            self.kill_ring.remove(0);
+        }
+        self.kill_ring.push(text);
     }
 
-    #[test]
-    fn test_append_char() {
+    fn kill_range(&mut self, start: Cursor, end: Cursor) -> bool {
+        if start == end {
+            return false;
+        }
+        let text = self.text_between(&start, &end);
+        self.push_undo();
+        self.remove_range(start, end);
+        self.modified = true;
+        self.ensure_cursor_visible();
+        self.kill(text);
+        true
+    }
+
+    fn insert_yank(&mut self, index: usize) {
+        let start = self.cursor.clone();
+        let text = self.kill_ring[index].clone();
+        self.insert_text(&text);
+        self.last_yank = Some((start, self.cursor.clone(), index));
+    }
+
+    /// Insert `text` at the cursor (replacing any selection) as one undo
+    /// step. Newlines in `text` split the line; the cursor ends up after
+    /// the inserted text.
+    pub fn insert_str(&mut self, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+        let selection = self.selection_range();
+        self.push_undo();
+        if let Some((start, end)) = selection {
+            self.remove_range(start, end);
+        }
+        self.insert_text(text);
+    }
+
+    /// Paste clipboard `text` at the cursor, replacing any selection.
+    pub fn paste(&mut self, text: &str) {
+        self.insert_str(text);
+    }
+
+    /// Insert `text` at the cursor without recording an undo step.
+    fn insert_text(&mut self, text: &str) {
+        let at = self.byte_offset(self.cursor.line, self.cursor.col);
+        let tail = self.lines[self.cursor.line].split_off(at);
+        let mut parts = text.split('\n');
+        if let Some(first) = parts.next() {
+            self.lines[self.cursor.line].push_str(first);
+        }
+        for part in parts {
+            self.cursor.line += 1;
+            self.lines.insert(self.cursor.line, part.to_string());
+        }
+        self.cursor.col = self.line_len(self.cursor.line);
+        self.lines[self.cursor.line].push_str(&tail);
+        self.modified = true;
+        self.ensure_cursor_visible();
+    }
+
+    fn text_between(&self, start: &Cursor, end: &Cursor) -> String {
+        let from = self.byte_offset(start.line, start.col);
+        let to = self.byte_offset(end.line, end.col);
+        if start.line == end.line {
+            return self.lines[start.line][from..to].to_string();
+        }
+        let mut text = self.lines[start.line][from..].to_string();
+        for line in &self.lines[start.line + 1..end.line] {
+            text.push('\n');
+            text.push_str(line);
+        }
+        text.push('\n');
+        text.push_str(&self.lines[end.line][..to]);
+        text
+    }
+
+    fn remove_range(&mut self, start: Cursor, end: Cursor) {
+        let to = self.byte_offset(end.line, end.col);
+        let tail = self.lines[end.line][to..].to_string();
+        let from = self.byte_offset(start.line, start.col);
+        self.lines[start.line].truncate(from);
+        self.lines[start.line].push_str(&tail);
+        self.lines.drain(start.line + 1..=end.line);
+        self.cursor = start;
+    }
+
+    /// Record the current state as an undo step and invalidate redo history.
+    fn push_undo(&mut self) {
+        if self.undo_stack.len() >= UNDO_LIMIT {
+            self.undo_stack.remove(0);
+        }
+        self.undo_stack.push(self.snapshot());
+        self.redo_stack.clear();
+        self.coalesce_at = None;
+        self.last_yank = None;
+        self.desired_col = None;
+        // Edits may invalidate the anchor position
+        self.selection_anchor = None;
+    }
+
+    fn apply_undo(&mut self, state: BufferSnapshot) {
+        self.restore(&state);
+        self.modified = true;
+    }
+
+    /// Capture lines, cursor, viewport and modified state.
+    pub fn snapshot(&self) -> BufferSnapshot {
+        BufferSnapshot {
+            lines: self.lines.clone(),
+            cursor: self.cursor.clone(),
+            viewport_top: self.viewport_top,
+            modified: self.modified,
+        }
+    }
+
+    /// Put the buffer back to a snapshot. Undo history is kept; the
+    /// selection is dropped. A cursor outside the text (e.g. from a
+    /// damaged snapshot) is clamped.
+    pub fn restore(&mut self, snapshot: &BufferSnapshot) {
+        self.lines = snapshot.lines.clone();
+        if self.lines.is_empty() {
+            self.lines.push(String::new());
+        }
+        self.cursor = snapshot.cursor.clone();
+        self.cursor.line = self.cursor.line.min(self.lines.len() - 1);
+        self.clamp_cursor_col();
+        self.viewport_top = snapshot.viewport_top.min(self.lines.len() - 1);
+        self.modified = snapshot.modified;
+        self.coalesce_at = None;
+        // The yanked span may not be there any more
+        self.last_yank = None;
+        self.desired_col = None;
+        self.selection_anchor = None;
+        self.ensure_cursor_visible();
+    }
+
+    /// Undo the last edit. Returns false if there is nothing to undo.
+    pub fn undo(&mut self) -> bool {
+        match self.undo_stack.pop() {
+            Some(state) => {
+                self.redo_stack.push(self.snapshot());
+                self.apply_undo(state);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Redo the last undone edit. Returns false if there is nothing to redo.
+    pub fn redo(&mut self) -> bool {
+        match self.redo_stack.pop() {
+            Some(state) => {
+                self.undo_stack.push(self.snapshot());
+                self.apply_undo(state);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // ---- Visual (wrapped) coordinates ----
+
+    /// Number of screen rows `line` takes when wrapped at `width` chars.
+    pub fn visual_rows(&self, line: usize, width: usize) -> usize {
+        wrap_starts(&self.lines[line], width).len()
+    }
+
+    /// Map a logical cursor to (visual_row, visual_col), where rows count
+    /// from the top of the document with every line soft-wrapped at `width`
+    /// chars (0 = no wrapping).
+    pub fn to_visual(&self, cursor: &Cursor, width: usize) -> (usize, usize) {
+        let rows_above: usize = (0..cursor.line)
+            .map(|l| self.visual_rows(l, width))
+            .sum();
+        let starts = wrap_starts(&self.lines[cursor.line], width);
+        let seg = starts.iter().rposition(|&s| s <= cursor.col).unwrap_or(0);
+        (rows_above + seg, cursor.col - starts[seg])
+    }
+
+    /// Map (visual_row, visual_col) back to a logical cursor. Positions past
+    /// the end of a row or of the document clamp to the nearest valid spot.
+    pub fn to_logical(&self, row: usize, col: usize, width: usize) -> Cursor {
+        let mut remaining = row;
+        for (line_idx, line) in self.lines.iter().enumerate() {
+            let starts = wrap_starts(line, width);
+            let is_last_line = line_idx + 1 == self.lines.len();
+            if remaining < starts.len() || is_last_line {
+                let seg = remaining.min(starts.len() - 1);
+                let max_col = self.row_len(line_idx, &starts, seg);
+                return Cursor { line: line_idx, col: starts[seg] + col.min(max_col) };
+            }
+            remaining -= starts.len();
+        }
+        Cursor::new()
+    }
+
+    /// Last column the cursor can take on row `seg` of `line`, given its
+    /// `wrap_starts`.
+    fn row_len(&self, line: usize, starts: &[usize], seg: usize) -> usize {
+        match starts.get(seg + 1) {
+            // Column at a wrap point belongs to the next row
+            Some(&next) => next - starts[seg] - 1,
+            None => self.line_len(line) - starts[seg],
+        }
+    }
+
+    // ---- Find ----
+
+    /// Start position of every case-insensitive match of `query`, in document
+    /// order. Matches may overlap; a query never spans a line break.
+    pub fn find_all(&self, query: &str) -> Vec<Cursor> {
+        let needle: Vec<char> = query.chars().collect();
+        let mut matches = Vec::new();
+        if needle.is_empty() {
+            return matches;
+        }
+        for (line_idx, line) in self.lines.iter().enumerate() {
+            let hay: Vec<char> = line.chars().collect();
+            if hay.len() < needle.len() {
+                continue;
+            }
+            for col in 0..=hay.len() - needle.len() {
+                let hit = needle.iter().zip(&hay[col..])
+                    .all(|(a, b)| a.to_lowercase().eq(b.to_lowercase()));
+                if hit {
+                    matches.push(Cursor { line: line_idx, col });
+                }
+            }
+        }
+        matches
+    }
+
+    /// Jump to 1-based line `n`, clamped to the first and last lines.
+    /// The cursor lands at the start of the line.
+    pub fn goto_line(&mut self, n: usize) {
+        self.cursor.line = n.clamp(1, self.lines.len()) - 1;
+        self.cursor.col = 0;
+        self.ensure_cursor_visible();
+    }
+
+    /// Scroll a screen up (less one line of overlap), moving the cursor with it.
+    pub fn page_up(&mut self) {
+        let step = self.page_step();
+        self.viewport_top = self.viewport_top.saturating_sub(step);
+        self.cursor.line = self.cursor.line.saturating_sub(step);
+        self.clamp_cursor_col();
+        self.ensure_cursor_visible();
+    }
+
+    /// Scroll a screen down (less one line of overlap), moving the cursor with it.
+    pub fn page_down(&mut self) {
+        let step = self.page_step();
+        let last = self.lines.len() - 1;
+        let max_top = self.lines.len().saturating_sub(self.viewport_lines);
+        self.viewport_top = (self.viewport_top + step).min(max_top);
+        self.cursor.line = (self.cursor.line + step).min(last);
+        self.clamp_cursor_col();
+        self.ensure_cursor_visible();
+    }
+
+    /// Scroll the view `delta` lines (negative is up) without editing, for
+    /// reading a rendered document. The view stops once the last line is at
+    /// the bottom, where `last_page_lines` lines fit; the cursor, hidden
+    /// while reading, is carried along so it stays in view.
+    pub fn scroll_view(&mut self, delta: isize, last_page_lines: usize) {
+        let max_top = self.lines.len().saturating_sub(last_page_lines.max(1));
+        let top = self.viewport_top.saturating_add_signed(delta).min(max_top);
+        self.viewport_top = top;
+        let bottom = (top + self.viewport_lines.max(1) - 1).min(self.lines.len() - 1);
+        self.cursor.line = self.cursor.line.clamp(top, bottom);
+        self.clamp_cursor_col();
+    }
+
+    /// Take `lines` as the count that fits from the current viewport top
+    /// and bring the cursor inside it, leaving the view where it is. After
+    /// `scroll_view`, the count for the new top can differ from the old
+    /// one (a taller heading scrolled in), and a cursor left below it
+    /// would pull the view straight back on the next draw.
+    pub fn fit_cursor_to_view(&mut self, lines: usize) {
+        self.viewport_lines = lines.max(1);
+        let bottom = (self.viewport_top + self.viewport_lines - 1).min(self.lines.len() - 1);
+        self.cursor.line = self.cursor.line.clamp(self.viewport_top.min(bottom), bottom);
+        self.clamp_cursor_col();
+    }
+
+    /// Lines moved by a page up or down: a screen less one line of overlap.
+    pub fn page_step(&self) -> usize {
+        self.viewport_lines.saturating_sub(1).max(1)
+    }
+
+    fn clamp_cursor_col(&mut self) {
+        let line_len = self.line_len(self.cursor.line);
+        if self.cursor.col > line_len {
+            self.cursor.col = line_len;
+        }
+    }
+
+    /// Set how many lines the screen shows (at least one) and scroll to
+    /// keep the cursor among them.
+    pub fn set_viewport_lines(&mut self, lines: usize) {
+        self.viewport_lines = lines.max(1);
+        self.ensure_cursor_visible();
+    }
+
+    pub fn ensure_cursor_visible(&mut self) {
+        if self.cursor.line < self.viewport_top {
+            self.viewport_top = self.cursor.line;
+        } else if self.cursor.line >= self.viewport_top + self.viewport_lines {
+            self.viewport_top = self.cursor.line - self.viewport_lines + 1;
+        }
+    }
+
+    /// Scroll the column viewport so the cursor is one of the `visible_cols`
+    /// columns shown. Moving left past the edge (or onto a shorter line)
+    /// jumps back half a screen, so the text before the cursor stays in view.
+    pub fn ensure_cursor_visible_horizontal(&mut self, visible_cols: usize) {
+        let visible_cols = visible_cols.max(1);
+        let col = self.cursor.col;
+        if col < self.viewport_col {
+            self.viewport_col = col.saturating_sub(visible_cols / 2);
+        } else if col >= self.viewport_col + visible_cols {
+            self.viewport_col = col + 1 - visible_cols;
+        }
+    }
+
+    /// Append a character at the end of the buffer (for typewriter mode)
+    pub fn append_char(&mut self, ch: char) {
+        let last = self.lines.len() - 1;
+        self.lines[last].push(ch);
+        self.cursor.line = last;
+        self.cursor.col = self.line_len(last);
+        self.modified = true;
+        self.ensure_cursor_visible();
+    }
+
+    /// Append a newline at the end (for typewriter mode)
+    pub fn append_newline(&mut self) {
+        self.lines.push(String::new());
+        self.cursor.line = self.lines.len() - 1;
+        self.cursor.col = 0;
+        self.modified = true;
+        self.ensure_cursor_visible();
+    }
+
+    /// Delete the character before the cursor, but never past the start of
+    /// the cursor line (for typewriter mode's gentle backspace). Returns
+    /// false, changing nothing, at column 0.
+    pub fn backspace_current_line(&mut self) -> bool {
+        if self.cursor.col == 0 {
+            return false;
+        }
+        self.push_undo();
+        self.cursor.col -= 1;
+        let at = self.byte_offset(self.cursor.line, self.cursor.col);
+        self.lines[self.cursor.line].remove(at);
+        self.modified = true;
+        true
+    }
+}
+
+/// The run of spaces and tabs at the start of `line`.
+fn leading_whitespace(line: &str) -> &str {
+    let rest = line.trim_start_matches([' ', '\t']);
+    &line[..line.len() - rest.len()]
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum CharClass {
+    Space,
+    Word,
+    Punct,
+}
+
+fn char_class(ch: char) -> CharClass {
+    if ch.is_whitespace() {
+        CharClass::Space
+    } else if ch.is_alphanumeric() || ch == '_' || ch == '\'' {
+        CharClass::Word
+    } else {
+        CharClass::Punct
+    }
+}
+
+/// Split a line into pieces of at most `max` characters, breaking at the last
+/// space that fits (the space itself is dropped) or hard-breaking long words.
+/// Each piece is paired with its starting char offset in the original line.
+fn wrap_line(line: &str, max: usize) -> Vec<(usize, String)> {
+    let chars: Vec<char> = line.chars().collect();
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    while max > 0 && chars.len() - start > max {
+        let window = &chars[start..start + max + 1];
+        match window.iter().rposition(|&c| c == ' ') {
+            Some(pos) if pos > 0 => {
+                pieces.push((start, chars[start..start + pos].iter().collect()));
+                start += pos + 1;
+            }
+            _ => {
+                pieces.push((start, chars[start..start + max].iter().collect()));
+                start += max;
+            }
+        }
+    }
+    pieces.push((start, chars[start..].iter().collect()));
+    pieces
+}
+
+/// `wrap_line` for a block quote: wraps the text after the `> ` marker and
+/// starts every piece with the marker again. Each piece comes with its
+/// starting char offset in the original line and how many marker chars
+/// were put in front of it.
+fn wrap_quote(line: &str, max: usize) -> Vec<(usize, usize, String)> {
+    let text = LineKind::strip_prefix(line, LineKind::BlockQuote);
+    let marker_len = line.chars().count() - text.chars().count();
+    let marker: String = line.chars().take(marker_len).collect();
+    wrap_line(text, max.saturating_sub(marker_len))
+        .into_iter()
+        .enumerate()
+        .map(|(i, (start, piece))| {
+            // The first piece still has its own marker in the original line
+            let lead = if i == 0 { 0 } else { marker_len };
+            (if i == 0 { 0 } else { marker_len + start }, lead, format!("{}{}", marker, piece))
+        })
+        .collect()
+}
+
+/// Char offset where each screen row of `line` starts when soft-wrapped at
+/// `width`. Rows break after the last space that fits (the space stays at
+/// the end of its row) or mid-word when there is none.
+fn wrap_starts(line: &str, width: usize) -> Vec<usize> {
+    let chars: Vec<char> = line.chars().collect();
+    let mut starts = vec![0];
+    let mut start = 0;
+    while width > 0 && chars.len() - start > width {
+        let window = &chars[start..start + width];
+        start += match window.iter().rposition(|&c| c == ' ') {
+            Some(pos) if pos > 0 => pos + 1,
+            _ => width,
+        };
+        starts.push(start);
+    }
+    starts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_new_buffer() {
+        let buf = TextBuffer::new();
+        assert_eq!(buf.lines.len(), 1);
+        assert_eq!(buf.cursor.line, 0);
+        assert_eq!(buf.cursor.col, 0);
+        assert!(!buf.modified);
+    }
+
+    #[test]
+    fn test_from_text() {
+        let buf = TextBuffer::from_text("hello\nworld");
+        assert_eq!(buf.lines.len(), 2);
+        assert_eq!(buf.lines[0], "hello");
+        assert_eq!(buf.lines[1], "world");
+    }
+
+    #[test]
+    fn test_round_trip_keeps_trailing_newline() {
+        let with = TextBuffer::from_text("a\nb\n");
+        assert_eq!(with.lines, vec!["a", "b", ""]);
+        assert_eq!(with.to_string(), "a\nb\n");
+        let without = TextBuffer::from_text("a\nb");
+        assert_eq!(without.lines, vec!["a", "b"]);
+        assert_eq!(without.to_string(), "a\nb");
+        // save -> load -> save changes nothing
+        for text in ["a\nb\n", "a\nb", "", "\n", "a\n\n"] {
+            let saved = TextBuffer::from_text(text).to_string();
+            assert_eq!(saved, text);
+            assert_eq!(TextBuffer::from_text(&saved).to_string(), saved);
+        }
+        assert_eq!(TextBuffer::from_text("a\r\nb\r\n").to_string(), "a\nb\n");
+    }
+
+    #[test]
+    fn test_insert_char() {
+        let mut buf = TextBuffer::new();
+        buf.insert_char('h');
+        buf.insert_char('i');
+        assert_eq!(buf.lines[0], "hi");
+        assert_eq!(buf.cursor.col, 2);
+        assert!(buf.modified);
+    }
+
+    #[test]
+    fn test_delete_back() {
+        let mut buf = TextBuffer::from_text("hello");
+        buf.cursor.col = 5;
+        buf.delete_back();
+        assert_eq!(buf.lines[0], "hell");
+        assert_eq!(buf.cursor.col, 4);
+    }
+
+    #[test]
+    fn test_delete_back_merge_lines() {
+        let mut buf = TextBuffer::from_text("hello\nworld");
+        buf.cursor.line = 1;
+        buf.cursor.col = 0;
+        buf.delete_back();
+        assert_eq!(buf.lines.len(), 1);
+        assert_eq!(buf.lines[0], "helloworld");
+        assert_eq!(buf.cursor.line, 0);
+        assert_eq!(buf.cursor.col, 5);
+    }
+
+    #[test]
+    fn test_newline() {
+        let mut buf = TextBuffer::from_text("hello");
+        buf.cursor.col = 3;
+        buf.newline();
+        assert_eq!(buf.lines.len(), 2);
+        assert_eq!(buf.lines[0], "hel");
+        assert_eq!(buf.lines[1], "lo");
+        assert_eq!(buf.cursor.line, 1);
+        assert_eq!(buf.cursor.col, 0);
+    }
+
+    #[test]
+    fn test_cursor_movement() {
+        let mut buf = TextBuffer::from_text("hello\nworld");
+        buf.cursor.col = 2;
+        buf.move_down();
+        assert_eq!(buf.cursor.line, 1);
+        assert_eq!(buf.cursor.col, 2);
+        buf.move_up();
+        assert_eq!(buf.cursor.line, 0);
+        buf.move_end();
+        assert_eq!(buf.cursor.col, 5);
+        buf.move_home();
+        assert_eq!(buf.cursor.col, 0);
+    }
+
+    #[test]
+    fn test_word_count() {
+        let buf = TextBuffer::from_text("hello world\nfoo bar baz");
+        assert_eq!(buf.word_count(), 5);
+    }
+
+    #[test]
+    fn test_word_count_prose_strips_markdown() {
+        let buf = TextBuffer::from_text("# Hello World\n> quoted words\n- one item\n- [x] done task\n1. first\n---");
+        assert_eq!(buf.word_count(), 16);
+        assert_eq!(buf.word_count_prose(false), 9);
+        assert_eq!(TextBuffer::from_text("# Hello World").word_count_prose(false), 2);
+    }
+
+    #[test]
+    fn test_word_count_prose_skips_code() {
+        let text = "Intro text here.\n```\nlet x = 1;\n# not a heading\n```\n    indented code\nOutro.";
+        let buf = TextBuffer::from_text(text);
+        assert_eq!(buf.word_count(), 16);
+        assert_eq!(buf.word_count_prose(false), 4);
+        // A lone fence line is no words at all
+        assert_eq!(TextBuffer::from_text("```").word_count_prose(false), 0);
+        assert_eq!(TextBuffer::from_text("```rust").word_count_prose(false), 0);
+    }
+
+    #[test]
+    fn test_char_count() {
+        let buf = TextBuffer::from_text("hi\nbye");
+        // "hi" (2) + "\n" (1) + "bye" (3) = 6
+        assert_eq!(buf.char_count(), 6);
+    }
+
+    #[test]
+    fn test_reading_time() {
+        assert_eq!(TextBuffer::new().reading_time_minutes(200, true), 0);
+        let buf = TextBuffer::from_text("one two three");
+        assert_eq!(buf.reading_time_minutes(200, true), 1);
+        let words = vec!["word"; 401].join(" ");
+        let buf = TextBuffer::from_text(&words);
+        assert_eq!(buf.reading_time_minutes(200, true), 3);
+        assert_eq!(buf.reading_time_minutes(401, true), 1);
+        assert_eq!(buf.reading_time_minutes(0, true), 0);
+    }
+
+    #[test]
+    fn test_reading_time_with_and_without_code() {
+        let code = vec!["let value = compute(input);"; 100].join("\n");
+        let text = format!("# Setup\nRun the `build` step first.\n```rust\n{}\n```\nThat's all.", code);
+        let buf = TextBuffer::from_text(&text);
+        // 400 words of code, 8 of prose (the code span among them)
+        assert_eq!(buf.word_count_prose(true), 408);
+        assert_eq!(buf.word_count_prose(false), 8);
+        assert_eq!(buf.reading_time_minutes(200, true), 3);
+        assert_eq!(buf.reading_time_minutes(200, false), 1);
+        // An unclosed fence leaves the rest out too
+        let buf = TextBuffer::from_text("Intro words\n```\nsome code\nmore code");
+        assert_eq!(buf.word_count_prose(false), 2);
+        assert_eq!(buf.word_count_prose(true), 6);
+    }
+
+    #[test]
+    fn test_sentence_count() {
+        assert_eq!(TextBuffer::new().sentence_count(), 0);
+        assert_eq!(TextBuffer::from_text("   \n").sentence_count(), 0);
+        let buf = TextBuffer::from_text("It rained. Then what?! Wait...\nAnd on");
+        assert_eq!(buf.sentence_count(), 4);
+        // No terminator at all is still one sentence
+        assert_eq!(TextBuffer::from_text("just some words").sentence_count(), 1);
+        // Decimals don't split
+        assert_eq!(TextBuffer::from_text("Pi is 3.14 or so.").sentence_count(), 1);
+        // Stray punctuation with no words isn't a sentence
+        assert_eq!(TextBuffer::from_text("... !").sentence_count(), 0);
+    }
+
+    #[test]
+    fn test_char_count_unicode() {
+        let buf = TextBuffer::from_text("café\nüber");
+        // 4 + newline + 4 displayed characters (13 bytes)
+        assert_eq!(buf.char_count(), 9);
+        assert_eq!(buf.char_count_visible(), 8);
+        let buf = TextBuffer::from_text("日本語");
+        assert_eq!(buf.char_count(), 3);
+    }
+
+    #[test]
+    fn test_viewport_scrolling() {
+        let mut buf = TextBuffer::new();
+        buf.viewport_lines = 3;
+        for i in 0..10 {
+            buf.lines.push(format!("line {}", i));
+        }
+        buf.cursor.line = 5;
+        buf.ensure_cursor_visible();
+        assert_eq!(buf.viewport_top, 3);
+
+        // A smaller screen scrolls further; zero lines still shows one
+        buf.set_viewport_lines(2);
+        assert_eq!(buf.viewport_top, 4);
+        buf.set_viewport_lines(0);
+        assert_eq!((buf.viewport_lines, buf.viewport_top), (1, 5));
+    }
+
+    #[test]
+    fn test_delete_forward() {
+        let mut buf = TextBuffer::from_text("hello");
+        buf.cursor.col = 2;
+        buf.delete_forward();
+        assert_eq!(buf.lines[0], "helo");
+    }
+
+    #[test]
+    fn test_delete_forward_merge() {
+        let mut buf = TextBuffer::from_text("hello\nworld");
+        buf.cursor.col = 5;
+        buf.delete_forward();
+        assert_eq!(buf.lines.len(), 1);
+        assert_eq!(buf.lines[0], "helloworld");
+    }
+
+    #[test]
+    fn test_append_char() {
+        let mut buf = TextBuffer::new();
+        buf.append_char('a');
+        buf.append_char('b');
+        assert_eq!(buf.lines[0], "ab");
+        assert_eq!(buf.cursor.col, 2);
+    }
+
+    #[test]
+    fn test_backspace_current_line_deletes_within_line() {
+        let mut buf = TextBuffer::new();
+        for ch in "héllo".chars() {
+            buf.append_char(ch);
+        }
+        assert!(buf.backspace_current_line());
+        assert!(buf.backspace_current_line());
+        assert_eq!(buf.lines[0], "hél");
+        assert_eq!(buf.cursor.col, 3);
+    }
+
+    #[test]
+    fn test_backspace_current_line_stops_at_column_zero() {
         let mut buf = TextBuffer::new();
         buf.append_char('a');
+        buf.append_newline();
+        buf.append_char('b');
+        assert!(buf.backspace_current_line());
+        // Start of the line: no merge with the line above
+        assert!(!buf.backspace_current_line());
+        assert_eq!(buf.lines, vec!["a".to_string(), String::new()]);
+        assert_eq!((buf.cursor.line, buf.cursor.col), (1, 0));
+    }
+
+    #[test]
+    fn test_append_newline() {
+        let mut buf = TextBuffer::new();
+        buf.append_char('a');
+        buf.append_newline();
         buf.append_char('b');
+        assert_eq!(buf.lines.len(), 2);
+        assert_eq!(buf.lines[0], "a");
+        assert_eq!(buf.lines[1], "b");
+    }
+
+    #[test]
+    fn test_move_right_wraps() {
+        let mut buf = TextBuffer::from_text("ab\ncd");
+        buf.cursor.col = 2; // end of first line
+        buf.move_right();
+        assert_eq!(buf.cursor.line, 1);
+        assert_eq!(buf.cursor.col, 0);
+    }
+
+    #[test]
+    fn test_move_left_wraps() {
+        let mut buf = TextBuffer::from_text("ab\ncd");
+        buf.cursor.line = 1;
+        buf.cursor.col = 0;
+        buf.move_left();
+        assert_eq!(buf.cursor.line, 0);
+        assert_eq!(buf.cursor.col, 2);
+    }
+
+    #[test]
+    fn test_longest_line_len() {
+        assert_eq!(TextBuffer::new().longest_line_len(), 0);
+        let buf = TextBuffer::from_text("short\na much longer line\nmid");
+        assert_eq!(buf.longest_line_len(), 18);
+        // Counted in characters, not bytes
+        let buf = TextBuffer::from_text("café");
+        assert_eq!(buf.longest_line_len(), 4);
+    }
+
+    #[test]
+    fn test_exceeds_line_length() {
+        let buf = TextBuffer::from_text("12345\n1234567890");
+        assert!(buf.exceeds_line_length(8));
+        assert!(!buf.exceeds_line_length(10));
+        // 0 disables the check
+        assert!(!buf.exceeds_line_length(0));
+    }
+
+    #[test]
+    fn test_reflow_long_lines() {
+        let mut buf = TextBuffer::from_text("ok\nthe quick brown fox jumps\nfine");
+        assert!(buf.reflow_long_lines(10));
+        assert_eq!(buf.lines, vec!["ok", "the quick", "brown fox", "jumps", "fine"]);
+        assert!(!buf.exceeds_line_length(10));
+        assert!(buf.modified);
+        // Nothing left to do
+        assert!(!buf.reflow_long_lines(10));
+    }
+
+    #[test]
+    fn test_reflow_leaves_code_and_lists() {
+        let text = "```\nlet total = first + second;\n```\n- an item that runs long\n1. a numbered one too\nplain prose that wraps";
+        let mut buf = TextBuffer::from_text(text);
+        assert!(buf.reflow_long_lines(12));
+        assert_eq!(&buf.lines[..5], &TextBuffer::from_text(text).lines[..5]);
+        assert_eq!(&buf.lines[5..], ["plain prose", "that wraps"]);
+        // Only code and lists over the limit: nothing to do
+        let mut buf = TextBuffer::from_text("    indented code runs long\n- [ ] a task that runs long");
+        assert!(!buf.reflow_long_lines(12));
+        assert!(!buf.modified);
+    }
+
+    #[test]
+    fn test_reflow_leaves_headings() {
+        let mut buf = TextBuffer::from_text("## A heading that runs long
+### Another long heading");
+        assert!(!buf.reflow_long_lines(12));
+        assert_eq!(buf.lines, vec!["## A heading that runs long", "### Another long heading"]);
+        assert!(!buf.modified);
+    }
+
+    #[test]
+    fn test_reflow_keeps_quotes_quoted() {
+        let mut buf = TextBuffer::from_text("> the quick brown fox jumps");
+        buf.cursor.col = 14; // the 'o' in "brown"
+        assert!(buf.reflow_long_lines(12));
+        assert_eq!(buf.lines, vec!["> the quick", "> brown fox", "> jumps"]);
+        assert_eq!((buf.cursor.line, buf.cursor.col), (1, 4));
+        assert_eq!(&buf.lines[1][buf.cursor.col..], "own fox");
+    }
+
+    #[test]
+    fn test_reflow_hard_breaks_long_words() {
+        let mut buf = TextBuffer::from_text("abcdefghij");
+        buf.reflow_long_lines(4);
+        assert_eq!(buf.lines, vec!["abcd", "efgh", "ij"]);
+    }
+
+    #[test]
+    fn test_reflow_keeps_cursor_on_same_char() {
+        let mut buf = TextBuffer::from_text("the quick brown fox");
+        buf.cursor.col = 12; // the 'o' in "brown"
+        buf.reflow_long_lines(10);
+        assert_eq!(buf.cursor.line, 1);
+        assert_eq!(buf.cursor.col, 2);
+        assert_eq!(&buf.lines[1][buf.cursor.col..], "own fox");
+    }
+
+    #[test]
+    fn test_insert_multibyte_chars() {
+        let mut buf = TextBuffer::new();
+        for ch in "café — 🙂".chars() {
+            buf.insert_char(ch);
+        }
+        assert_eq!(buf.lines[0], "café — 🙂");
+        assert_eq!(buf.cursor.col, 8);
+        // Insert in the middle, right after the accented char
+        buf.cursor.col = 4;
+        buf.insert_char('s');
+        assert_eq!(buf.lines[0], "cafés — 🙂");
+    }
+
+    #[test]
+    fn test_move_through_multibyte_chars() {
+        let mut buf = TextBuffer::from_text("é🙂a\nb");
+        buf.move_end();
+        assert_eq!(buf.cursor.col, 3);
+        buf.move_left();
+        buf.move_left();
+        assert_eq!(buf.cursor.col, 1);
+        buf.move_right();
+        assert_eq!(buf.cursor.col, 2);
+        buf.move_right();
+        buf.move_right();
+        assert_eq!(buf.cursor.line, 1);
+        buf.move_left();
+        assert_eq!((buf.cursor.line, buf.cursor.col), (0, 3));
+    }
+
+    #[test]
+    fn test_delete_multibyte_chars() {
+        let mut buf = TextBuffer::from_text("aé—🙂");
+        buf.move_end();
+        buf.delete_back();
+        assert_eq!(buf.lines[0], "aé—");
+        buf.delete_back();
+        assert_eq!(buf.lines[0], "aé");
+        buf.cursor.col = 1;
+        buf.delete_forward();
+        assert_eq!(buf.lines[0], "a");
+        assert_eq!(buf.cursor.col, 1);
+    }
+
+    #[test]
+    fn test_newline_and_merge_multibyte() {
+        let mut buf = TextBuffer::from_text("naïve🙂end");
+        buf.cursor.col = 6;
+        buf.newline();
+        assert_eq!(buf.lines, vec!["naïve🙂", "end"]);
+        buf.delete_back();
+        assert_eq!(buf.lines[0], "naïve🙂end");
+        assert_eq!(buf.cursor.col, 6);
+    }
+
+    #[test]
+    fn test_undo_insert_coalesces_typing() {
+        let mut buf = TextBuffer::from_text("ab");
+        buf.move_end();
+        for ch in "cde".chars() {
+            buf.insert_char(ch);
+        }
+        assert_eq!(buf.lines[0], "abcde");
+        assert!(buf.undo());
+        assert_eq!(buf.lines[0], "ab");
+        assert_eq!(buf.cursor.col, 2);
+        assert!(!buf.undo());
+    }
+
+    #[test]
+    fn test_undo_typing_after_cursor_move_is_separate_step() {
+        let mut buf = TextBuffer::new();
+        buf.insert_char('a');
+        buf.insert_char('b');
+        buf.move_left();
+        buf.insert_char('x');
+        assert_eq!(buf.lines[0], "axb");
+        buf.undo();
+        assert_eq!(buf.lines[0], "ab");
+        buf.undo();
+        assert_eq!(buf.lines[0], "");
+    }
+
+    #[test]
+    fn test_undo_delete() {
+        let mut buf = TextBuffer::from_text("hello\nworld");
+        buf.cursor.line = 1;
+        buf.delete_back();
+        assert_eq!(buf.lines, vec!["helloworld"]);
+        buf.delete_forward();
+        assert_eq!(buf.lines, vec!["helloorld"]);
+        assert!(buf.undo());
+        assert_eq!(buf.lines, vec!["helloworld"]);
+        assert!(buf.undo());
+        assert_eq!(buf.lines, vec!["hello", "world"]);
+        assert_eq!((buf.cursor.line, buf.cursor.col), (1, 0));
+    }
+
+    #[test]
+    fn test_redo_and_new_edit_clears_redo() {
+        let mut buf = TextBuffer::new();
+        buf.insert_char('a');
+        buf.newline();
+        assert!(buf.undo());
+        assert_eq!(buf.lines, vec!["a"]);
+        assert!(buf.redo());
+        assert_eq!(buf.lines, vec!["a", ""]);
+        assert!(buf.undo());
+        // A fresh edit discards the redo history
+        buf.insert_char('b');
+        assert!(!buf.redo());
+        assert_eq!(buf.lines, vec!["ab"]);
+    }
+
+    #[test]
+    fn test_undo_history_is_bounded() {
+        let mut buf = TextBuffer::new();
+        for _ in 0..(UNDO_LIMIT + 20) {
+            buf.newline();
+        }
+        let mut steps = 0;
+        while buf.undo() {
+            steps += 1;
+        }
+        assert_eq!(steps, UNDO_LIMIT);
+    }
+
+    #[test]
+    fn test_yank_line() {
+        let mut buf = TextBuffer::from_text("one\ntwo\nthree");
+        buf.cursor.line = 1;
+        buf.cursor.col = 2;
+        assert_eq!(buf.yank_line(), "two");
+        assert!(!buf.modified);
+    }
+
+    #[test]
+    fn test_put_line_below() {
+        let mut buf = TextBuffer::from_text("one\ntwo\nthree");
+        buf.cursor.line = 1;
+        let yanked = buf.yank_line();
+        buf.put_line_below(&yanked);
+        assert_eq!(buf.lines, vec!["one", "two", "two", "three"]);
+        assert_eq!((buf.cursor.line, buf.cursor.col), (2, 0));
+        assert!(buf.modified);
+
+        // Putting below the last line appends
+        buf.cursor.line = 3;
+        buf.put_line_below("end");
+        assert_eq!(buf.lines.last().unwrap(), "end");
+        assert_eq!(buf.cursor.line, 4);
+    }
+
+    #[test]
+    fn test_put_multiple_lines_and_undo() {
+        let mut buf = TextBuffer::from_text("a\nd");
+        buf.put_line_below("b\nc");
+        assert_eq!(buf.lines, vec!["a", "b", "c", "d"]);
+        buf.undo();
+        assert_eq!(buf.lines, vec!["a", "d"]);
+    }
+
+    #[test]
+    fn test_move_word_right() {
+        let mut buf = TextBuffer::from_text("foo,   bar baz");
+        buf.move_word_right();
+        assert_eq!(buf.cursor.col, 3); // the comma
+        buf.move_word_right();
+        assert_eq!(buf.cursor.col, 7); // spaces skipped as a group
+        buf.move_word_right();
+        assert_eq!(buf.cursor.col, 11);
+        buf.move_word_right();
+        assert_eq!(buf.cursor.col, 14); // end of line
+        // End of buffer is a no-op
+        buf.move_word_right();
+        assert_eq!((buf.cursor.line, buf.cursor.col), (0, 14));
+    }
+
+    #[test]
+    fn test_move_word_left() {
+        let mut buf = TextBuffer::from_text("foo,   bar baz");
+        buf.move_end();
+        buf.move_word_left();
+        assert_eq!(buf.cursor.col, 11);
+        buf.move_word_left();
+        assert_eq!(buf.cursor.col, 7);
+        buf.move_word_left();
+        assert_eq!(buf.cursor.col, 3);
+        buf.move_word_left();
+        assert_eq!(buf.cursor.col, 0);
+    }
+
+    #[test]
+    fn test_move_word_across_lines() {
+        let mut buf = TextBuffer::from_text("end.\n  next line");
+        buf.move_end();
+        buf.move_word_right();
+        assert_eq!((buf.cursor.line, buf.cursor.col), (1, 2));
+        buf.move_home();
+        buf.move_word_left();
+        assert_eq!((buf.cursor.line, buf.cursor.col), (0, 4));
+    }
+
+    #[test]
+    fn test_move_word_keeps_contractions_together() {
+        let mut buf = TextBuffer::from_text("don't stop");
+        buf.move_word_right();
+        assert_eq!(buf.cursor.col, 6);
+    }
+
+    #[test]
+    fn test_select_single_line() {
+        let mut buf = TextBuffer::from_text("hello world");
+        buf.cursor.col = 6;
+        buf.start_selection();
+        buf.move_end();
+        assert_eq!(buf.selected_text().as_deref(), Some("world"));
+        assert_eq!(buf.selection_cols(0), Some((6, 11)));
+        // Selecting backwards gives the same text
+        buf.cursor.col = 0;
+        assert_eq!(buf.selected_text().as_deref(), Some("hello "));
+    }
+
+    #[test]
+    fn test_select_multi_line() {
+        let mut buf = TextBuffer::from_text("one\ntwo\nthree");
+        buf.cursor.col = 1;
+        buf.start_selection();
+        buf.cursor.line = 2;
+        buf.cursor.col = 2;
+        assert_eq!(buf.selected_text().as_deref(), Some("ne\ntwo\nth"));
+        assert_eq!(buf.selection_cols(0), Some((1, 3)));
+        assert_eq!(buf.selection_cols(1), Some((0, 3)));
+        assert_eq!(buf.selection_cols(2), Some((0, 2)));
+    }
+
+    #[test]
+    fn test_empty_selection() {
+        let mut buf = TextBuffer::from_text("abc");
+        buf.start_selection();
+        assert_eq!(buf.selected_text(), None);
+        assert_eq!(buf.cut_selection(), None);
+        assert!(!buf.modified);
+    }
+
+    #[test]
+    fn test_cut_and_paste_multi_line() {
+        let mut buf = TextBuffer::from_text("one\ntwo\nthree");
+        buf.cursor.col = 1;
+        buf.start_selection();
+        buf.cursor.line = 1;
+        buf.cursor.col = 3;
+        let cut = buf.cut_selection().unwrap();
+        assert_eq!(cut, "ne\ntwo");
+        assert_eq!(buf.lines, vec!["o", "three"]);
+        assert_eq!((buf.cursor.line, buf.cursor.col), (0, 1));
+        assert!(buf.selection_anchor.is_none());
+
+        // Paste it back where it came from
+        buf.paste(&cut);
+        assert_eq!(buf.lines, vec!["one", "two", "three"]);
+        assert_eq!((buf.cursor.line, buf.cursor.col), (1, 3));
+    }
+
+    #[test]
+    fn test_paste_single_line_mid_line() {
+        let mut buf = TextBuffer::from_text("héllo");
+        buf.cursor.col = 2;
+        buf.paste("XY");
+        assert_eq!(buf.lines[0], "héXYllo");
+        assert_eq!(buf.cursor.col, 4);
+        buf.undo();
+        assert_eq!(buf.lines[0], "héllo");
+    }
+
+    #[test]
+    fn test_paste_replaces_selection() {
+        let mut buf = TextBuffer::from_text("cat dog");
+        buf.start_selection();
+        buf.cursor.col = 3;
+        buf.paste("bird");
+        assert_eq!(buf.lines[0], "bird dog");
+    }
+
+    #[test]
+    fn test_insert_str_expanded_tab() {
+        let mut buf = TextBuffer::from_text("ab");
+        buf.cursor.col = 1;
+        buf.insert_str("    ");
+        assert_eq!(buf.lines[0], "a    b");
+        assert_eq!(buf.cursor.col, 5);
+        // One undo step for the whole run of spaces
+        buf.undo();
         assert_eq!(buf.lines[0], "ab");
+    }
+
+    #[test]
+    fn test_insert_str_literal_tab() {
+        let mut buf = TextBuffer::from_text("ab");
+        buf.cursor.col = 1;
+        buf.insert_str("\t");
+        assert_eq!(buf.lines[0], "a\tb");
         assert_eq!(buf.cursor.col, 2);
+        assert_eq!(buf.line_count(), 1);
     }
 
     #[test]
-    fn test_append_newline() {
-        let mut buf = TextBuffer::new();
-        buf.append_char('a');
+    fn test_find_all_case_insensitive() {
+        let buf = TextBuffer::from_text("The cat\nthe end");
+        let hits = buf.find_all("THE");
+        assert_eq!(hits, vec![Cursor { line: 0, col: 0 }, Cursor { line: 1, col: 0 }]);
+        assert!(buf.find_all("dog").is_empty());
+        assert!(buf.find_all("").is_empty());
+    }
+
+    #[test]
+    fn test_find_all_overlapping() {
+        let buf = TextBuffer::from_text("aaaa");
+        let cols: Vec<usize> = buf.find_all("aa").iter().map(|c| c.col).collect();
+        assert_eq!(cols, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn test_find_all_multi_line_char_columns() {
+        let buf = TextBuffer::from_text("café au lait\nno match\n\nlait café lait");
+        let hits = buf.find_all("lait");
+        assert_eq!(hits, vec![
+            Cursor { line: 0, col: 8 },
+            Cursor { line: 3, col: 0 },
+            Cursor { line: 3, col: 10 },
+        ]);
+        // Columns are char indices, so "é" counts once
+        assert_eq!(buf.find_all("É au"), vec![Cursor { line: 0, col: 3 }]);
+    }
+
+    fn all_cursors(buf: &TextBuffer) -> Vec<Cursor> {
+        let mut cursors = Vec::new();
+        for line in 0..buf.lines.len() {
+            for col in 0..=buf.line_len(line) {
+                cursors.push(Cursor { line, col });
+            }
+        }
+        cursors
+    }
+
+    #[test]
+    fn test_visual_unwrapped_matches_logical() {
+        let buf = TextBuffer::from_text("one\n\nthree");
+        assert_eq!(buf.to_visual(&Cursor { line: 2, col: 4 }, 0), (2, 4));
+        assert_eq!(buf.to_visual(&Cursor { line: 1, col: 0 }, 80), (1, 0));
+        for c in all_cursors(&buf) {
+            let (row, col) = buf.to_visual(&c, 80);
+            assert_eq!(buf.to_logical(row, col, 80), c);
+        }
+    }
+
+    #[test]
+    fn test_visual_wrapped_rows() {
+        // "the quick " | "brown fox" at width 10
+        let buf = TextBuffer::from_text("the quick brown fox\nend");
+        assert_eq!(buf.visual_rows(0, 10), 2);
+        assert_eq!(buf.to_visual(&Cursor { line: 0, col: 4 }, 10), (0, 4));
+        assert_eq!(buf.to_visual(&Cursor { line: 0, col: 10 }, 10), (1, 0));
+        assert_eq!(buf.to_visual(&Cursor { line: 0, col: 19 }, 10), (1, 9));
+        assert_eq!(buf.to_visual(&Cursor { line: 1, col: 2 }, 10), (2, 2));
+        assert_eq!(buf.to_logical(1, 3, 10), Cursor { line: 0, col: 13 });
+    }
+
+    #[test]
+    fn test_visual_hard_wrap_long_word() {
+        let buf = TextBuffer::from_text("abcdefghijkl");
+        assert_eq!(buf.visual_rows(0, 5), 3);
+        assert_eq!(buf.to_visual(&Cursor { line: 0, col: 11 }, 5), (2, 1));
+        assert_eq!(buf.to_logical(1, 0, 5), Cursor { line: 0, col: 5 });
+    }
+
+    #[test]
+    fn test_visual_round_trip_wrapped() {
+        let buf = TextBuffer::from_text(
+            "the quick brown fox jumps over\n\nhéllo wörld ünïcode text\nabcdefghijklmnop",
+        );
+        for width in [1, 4, 7, 10, 0] {
+            for c in all_cursors(&buf) {
+                let (row, col) = buf.to_visual(&c, width);
+                assert_eq!(buf.to_logical(row, col, width), c, "width {}", width);
+            }
+        }
+    }
+
+    #[test]
+    fn test_move_wrapped_by_rows() {
+        // "the quick " | "brown fox" | "end" at width 10
+        let mut buf = TextBuffer::from_text("the quick brown fox\nend");
+        buf.cursor = Cursor { line: 0, col: 6 };
+        buf.move_down_wrapped(10);
+        assert_eq!(buf.cursor, Cursor { line: 0, col: 16 });
+        // The short row clamps the column, and moving on doesn't forget it
+        buf.move_down_wrapped(10);
+        assert_eq!(buf.cursor, Cursor { line: 1, col: 3 });
+        buf.move_up_wrapped(10);
+        assert_eq!(buf.cursor, Cursor { line: 0, col: 16 });
+        buf.move_up_wrapped(10);
+        assert_eq!(buf.cursor, Cursor { line: 0, col: 6 });
+        // First row of the first line, last row of the last line: no-ops
+        buf.move_up_wrapped(10);
+        assert_eq!(buf.cursor, Cursor { line: 0, col: 6 });
+        buf.cursor = Cursor { line: 1, col: 1 };
+        buf.move_down_wrapped(10);
+        assert_eq!(buf.cursor, Cursor { line: 1, col: 1 });
+        // Up into a wrapped line lands on its last row
+        buf.move_up_wrapped(10);
+        assert_eq!(buf.cursor, Cursor { line: 0, col: 11 });
+    }
+
+    #[test]
+    fn test_move_wrapped_matches_visual_rows() {
+        let buf = TextBuffer::from_text("the quick brown fox jumps over\n\nabcdefghijklmnop\nhi");
+        for width in [4, 7, 10] {
+            for start in all_cursors(&buf) {
+                let (row, col) = buf.to_visual(&start, width);
+                let mut down = buf.clone();
+                down.cursor = start.clone();
+                down.move_down_wrapped(width);
+                let mut up = buf.clone();
+                up.cursor = start.clone();
+                up.move_up_wrapped(width);
+                assert_eq!(down.cursor, buf.to_logical(row + 1, col, width), "{:?} w{}", start, width);
+                let above = if row == 0 { start.clone() } else { buf.to_logical(row - 1, col, width) };
+                assert_eq!(up.cursor, above, "{:?} w{}", start, width);
+            }
+        }
+    }
+
+    #[test]
+    fn test_move_up_down_keeps_column() {
+        let mut buf = TextBuffer::from_text("a longer line\nab\nanother long one");
+        buf.cursor.col = 9;
+        buf.move_down();
+        assert_eq!(buf.cursor, Cursor { line: 1, col: 2 });
+        buf.move_down();
+        assert_eq!(buf.cursor, Cursor { line: 2, col: 9 });
+        // Any other move starts from where the cursor is
+        buf.move_up();
+        buf.move_left();
+        buf.move_down();
+        assert_eq!(buf.cursor, Cursor { line: 2, col: 1 });
+    }
+
+    #[test]
+    fn test_to_logical_clamps() {
+        let buf = TextBuffer::from_text("the quick brown fox\nend");
+        // Past the end of a wrapped row: last char of that row
+        assert_eq!(buf.to_logical(0, 50, 10), Cursor { line: 0, col: 9 });
+        // Past the end of the document: end of the last line
+        assert_eq!(buf.to_logical(99, 99, 10), Cursor { line: 1, col: 3 });
+    }
+
+    #[test]
+    fn test_char_count_visible_ignores_line_breaks() {
+        let buf = TextBuffer::from_text("ab cd\nef");
+        assert_eq!(buf.char_count(), 8);
+        assert_eq!(buf.char_count_visible(), 7);
+
+        // Trailing blank lines add line breaks but no visible characters
+        let mut buf = TextBuffer::from_text("ab cd\nef");
         buf.append_newline();
-        buf.append_char('b');
-        assert_eq!(buf.lines.len(), 2);
-        assert_eq!(buf.lines[0], "a");
-        assert_eq!(buf.lines[1], "b");
+        buf.append_newline();
+        assert_eq!(buf.char_count(), 10);
+        assert_eq!(buf.char_count_visible(), 7);
     }
 
     #[test]
-    fn test_move_right_wraps() {
-        let mut buf = TextBuffer::from_text("ab\ncd");
-        buf.cursor.col = 2; // end of first line
-        buf.move_right();
-        assert_eq!(buf.cursor.line, 1);
+    fn test_toggle_checkbox_round_trip() {
+        let mut buf = TextBuffer::from_text("  - [ ] write tests\n- [X] done");
+        assert!(buf.toggle_checkbox_at(0));
+        assert_eq!(buf.lines[0], "  - [x] write tests");
+        assert!(buf.toggle_checkbox_at(0));
+        assert_eq!(buf.lines[0], "  - [ ] write tests");
+        assert!(buf.toggle_checkbox_at(1));
+        assert_eq!(buf.lines[1], "- [ ] done");
+        assert!(buf.modified);
+        buf.undo();
+        assert_eq!(buf.lines[1], "- [X] done");
+    }
+
+    #[test]
+    fn test_toggle_checkbox_not_a_task() {
+        let mut buf = TextBuffer::from_text("- plain item\ntext [ ] here");
+        assert!(!buf.toggle_checkbox_at(0));
+        assert!(!buf.toggle_checkbox_at(1));
+        assert!(!buf.toggle_checkbox_at(5));
+        assert_eq!(buf.lines, vec!["- plain item", "text [ ] here"]);
+        assert!(!buf.modified);
+    }
+
+    fn enter_at_end(text: &str) -> TextBuffer {
+        let mut buf = TextBuffer::from_text(text);
+        buf.cursor.line = buf.lines.len() - 1;
+        buf.move_end();
+        buf.newline_smart();
+        buf
+    }
+
+    #[test]
+    fn test_newline_autoindent_keeps_indentation() {
+        let mut buf = TextBuffer::from_text("    let x = 1;");
+        buf.move_end();
+        buf.newline_autoindent();
+        assert_eq!(buf.lines, vec!["    let x = 1;", "    "]);
+        assert_eq!((buf.cursor.line, buf.cursor.col), (1, 4));
+        // Tabs are carried over as tabs
+        let mut buf = TextBuffer::from_text("\tfoo");
+        buf.move_end();
+        buf.newline_autoindent();
+        assert_eq!(buf.lines[1], "\t");
+        assert_eq!(buf.cursor.col, 1);
+        // Unindented lines split as usual
+        let mut buf = TextBuffer::from_text("prose");
+        buf.move_end();
+        buf.newline_autoindent();
+        assert_eq!(buf.lines, vec!["prose", ""]);
+    }
+
+    #[test]
+    fn test_newline_autoindent_mid_line_split() {
+        let mut buf = TextBuffer::from_text("  call(a,  b)");
+        buf.cursor.col = 9;
+        buf.newline_autoindent();
+        assert_eq!(buf.lines, vec!["  call(a,", "  b)"]);
+        assert_eq!((buf.cursor.line, buf.cursor.col), (1, 2));
+        // Splitting inside the indentation moves the text down, still indented
+        let mut buf = TextBuffer::from_text("    x");
+        buf.cursor.col = 2;
+        buf.newline_autoindent();
+        assert_eq!(buf.lines, vec!["  ", "    x"]);
+        buf.undo();
+        assert_eq!(buf.lines, vec!["    x"]);
+    }
+
+    #[test]
+    fn test_newline_autoindent_continues_lists() {
+        let mut buf = TextBuffer::from_text("  - milk");
+        buf.move_end();
+        buf.newline_autoindent();
+        assert_eq!(buf.lines, vec!["  - milk", "  - "]);
+    }
+
+    #[test]
+    fn test_newline_smart_unordered() {
+        let buf = enter_at_end("- milk");
+        assert_eq!(buf.lines, vec!["- milk", "- "]);
+        assert_eq!((buf.cursor.line, buf.cursor.col), (1, 2));
+        let buf = enter_at_end("  * eggs");
+        assert_eq!(buf.lines, vec!["  * eggs", "  * "]);
+    }
+
+    #[test]
+    fn test_newline_smart_ordered_increments() {
+        let buf = enter_at_end("1. first");
+        assert_eq!(buf.lines, vec!["1. first", "2. "]);
+        assert_eq!(buf.cursor.col, 3);
+        let buf = enter_at_end("9. ninth");
+        assert_eq!(buf.lines[1], "10. ");
+    }
+
+    #[test]
+    fn test_newline_smart_task_list() {
+        let buf = enter_at_end("- [x] done");
+        assert_eq!(buf.lines, vec!["- [x] done", "- [ ] "]);
+        assert_eq!(buf.cursor.col, 6);
+    }
+
+    #[test]
+    fn test_newline_smart_empty_item_ends_list() {
+        for text in ["- milk\n- ", "1. first\n2. ", "- [ ] "] {
+            let mut buf = enter_at_end(text);
+            let last = buf.lines.len() - 1;
+            assert_eq!(buf.lines[last], "", "{:?}", text);
+            assert_eq!(buf.cursor.col, 0);
+            // One undo step brings the marker back
+            buf.undo();
+            assert_eq!(buf.to_string(), text);
+        }
+    }
+
+    #[test]
+    fn test_newline_smart_splits_item_and_plain_lines() {
+        let mut buf = TextBuffer::from_text("- milk eggs");
+        buf.cursor.col = 6;
+        buf.newline_smart();
+        assert_eq!(buf.lines, vec!["- milk", "- eggs"]);
+        assert_eq!(buf.cursor.col, 2);
+        // Extra spaces after an indented marker don't carry over either
+        let mut buf = TextBuffer::from_text("  -   milk   eggs");
+        buf.cursor.col = 10;
+        buf.newline_smart();
+        assert_eq!(buf.lines, vec!["  -   milk", "  - eggs"]);
+        assert_eq!(buf.cursor.col, 4);
+        let buf = enter_at_end("plain text");
+        assert_eq!(buf.lines, vec!["plain text", ""]);
+        // Enter at column 0 doesn't duplicate the marker
+        let mut buf = TextBuffer::from_text("- item");
+        buf.newline_smart();
+        assert_eq!(buf.lines, vec!["", "- item"]);
+    }
+
+    #[test]
+    fn test_move_home_smart_indented() {
+        let mut buf = TextBuffer::from_text("    let x = 1;");
+        buf.move_end();
+        buf.move_home_smart();
+        assert_eq!(buf.cursor.col, 4);
+        buf.move_home_smart();
         assert_eq!(buf.cursor.col, 0);
+        // And back to the indent again
+        buf.move_home_smart();
+        assert_eq!(buf.cursor.col, 4);
     }
 
     #[test]
-    fn test_move_left_wraps() {
-        let mut buf = TextBuffer::from_text("ab\ncd");
+    fn test_move_home_smart_unindented_and_blank() {
+        let mut buf = TextBuffer::from_text("plain\n   ");
+        buf.move_end();
+        buf.move_home_smart();
+        assert_eq!(buf.cursor.col, 0);
+        buf.move_home_smart();
+        assert_eq!(buf.cursor.col, 0);
+
         buf.cursor.line = 1;
-        buf.cursor.col = 0;
-        buf.move_left();
+        buf.move_end();
+        buf.move_home_smart();
+        assert_eq!(buf.cursor.col, 0);
+    }
+
+    fn type_prose(buf: &mut TextBuffer, text: &str) {
+        for ch in text.chars() {
+            buf.insert_char_prose(ch);
+        }
+    }
+
+    #[test]
+    fn test_prose_capitalizes_after_sentence_end() {
+        let mut buf = TextBuffer::new();
+        type_prose(&mut buf, "it rained. then it stopped! why? no idea");
+        assert_eq!(buf.lines[0], "It rained. Then it stopped! Why? No idea");
+        // No space after the period, or an abbreviation: not a sentence break
+        let mut buf = TextBuffer::new();
+        type_prose(&mut buf, "see e.g. pi is 3.14, i.e. close. right");
+        assert_eq!(buf.lines[0], "See e.g. pi is 3.14, i.e. close. Right");
+    }
+
+    #[test]
+    fn test_prose_capitalizes_line_start() {
+        let mut buf = TextBuffer::from_text("first line");
+        buf.move_end();
+        buf.newline();
+        type_prose(&mut buf, "  second");
+        assert_eq!(buf.lines[1], "  Second");
+        let mut buf = TextBuffer::from_text("- ");
+        buf.move_end();
+        type_prose(&mut buf, "milk");
+        assert_eq!(buf.lines[0], "- Milk");
+    }
+
+    #[test]
+    fn test_prose_leaves_code_blocks_alone() {
+        let mut buf = TextBuffer::from_text("```\n\n```\nafter");
+        buf.cursor.line = 1;
+        type_prose(&mut buf, "let x = 1. y");
+        assert_eq!(buf.lines[1], "let x = 1. y");
+        // Indented code
+        let mut buf = TextBuffer::from_text("    ");
+        buf.move_end();
+        type_prose(&mut buf, "fn main");
+        assert_eq!(buf.lines[0], "    fn main");
+        // Past the closing fence it's prose again
+        buf = TextBuffer::from_text("```\ncode\n```");
+        buf.cursor.line = 2;
+        buf.move_end();
+        buf.newline();
+        type_prose(&mut buf, "done");
+        assert_eq!(buf.lines[3], "Done");
+    }
+
+    fn fifty_lines() -> TextBuffer {
+        let text: Vec<String> = (0..50).map(|i| format!("line number {}", i)).collect();
+        TextBuffer::from_text(&text.join("\n"))
+    }
+
+    #[test]
+    fn test_page_down_to_end() {
+        let mut buf = fifty_lines();
+        buf.cursor.col = 12;
+        buf.page_down();
+        assert_eq!((buf.viewport_top, buf.cursor.line), (12, 12));
+        buf.page_down();
+        buf.page_down();
+        assert_eq!((buf.viewport_top, buf.cursor.line), (36, 36));
+        // Viewport stops with the last line at the bottom; cursor stops on it
+        buf.page_down();
+        assert_eq!((buf.viewport_top, buf.cursor.line), (37, 48));
+        buf.page_down();
+        assert_eq!((buf.viewport_top, buf.cursor.line), (37, 49));
+        assert_eq!(buf.cursor.col, 12);
+    }
+
+    #[test]
+    fn test_page_up_to_start() {
+        let mut buf = fifty_lines();
+        buf.cursor.line = 49;
+        buf.ensure_cursor_visible();
+        assert_eq!(buf.viewport_top, 37);
+        buf.page_up();
+        assert_eq!((buf.viewport_top, buf.cursor.line), (25, 37));
+        for _ in 0..5 {
+            buf.page_up();
+        }
+        assert_eq!((buf.viewport_top, buf.cursor.line), (0, 0));
+    }
+
+    #[test]
+    fn test_scroll_view() {
+        let mut buf = fifty_lines();
+        buf.viewport_lines = 10;
+        buf.cursor = Cursor { line: 3, col: 4 };
+        buf.scroll_view(1, 10);
+        assert_eq!((buf.viewport_top, buf.cursor.line), (1, 3));
+        // The cursor is carried down with the view, and back up
+        buf.scroll_view(20, 10);
+        assert_eq!((buf.viewport_top, buf.cursor.line), (21, 21));
+        buf.scroll_view(-20, 10);
+        assert_eq!((buf.viewport_top, buf.cursor.line), (1, 10));
+        buf.scroll_view(-5, 10);
+        assert_eq!(buf.viewport_top, 0);
+
+        // Stops with the last line at the bottom, which taller lines make
+        // later than a plain page would
+        buf.scroll_view(100, 10);
+        assert_eq!((buf.viewport_top, buf.cursor.line), (40, 40));
+        buf.scroll_view(100, 7);
+        assert_eq!(buf.viewport_top, 43);
+        assert!(!buf.modified);
+    }
+
+    #[test]
+    fn test_fit_cursor_to_view() {
+        let mut buf = fifty_lines();
+        buf.viewport_lines = 10;
+        buf.viewport_top = 5;
+        buf.cursor = Cursor { line: 14, col: 0 };
+        // Fewer lines fit from this top: the cursor moves up, the view stays
+        buf.fit_cursor_to_view(8);
+        assert_eq!((buf.viewport_top, buf.viewport_lines, buf.cursor.line), (5, 8, 12));
+        buf.cursor.line = 2;
+        buf.fit_cursor_to_view(8);
+        assert_eq!((buf.viewport_top, buf.cursor.line), (5, 5));
+        // Near the end the last line bounds it
+        buf.viewport_top = 48;
+        buf.fit_cursor_to_view(8);
+        assert_eq!(buf.cursor.line, 48);
+    }
+
+    #[test]
+    fn test_page_clamps_cursor_col() {
+        let mut buf = TextBuffer::from_text("a much longer first line\nshort");
+        buf.move_end();
+        buf.page_down();
+        assert_eq!((buf.cursor.line, buf.cursor.col), (1, 5));
+    }
+
+    #[test]
+    fn test_goto_line() {
+        let mut buf = fifty_lines();
+        buf.cursor.col = 5;
+        buf.goto_line(30);
+        assert_eq!((buf.cursor.line, buf.cursor.col), (29, 0));
+        assert!(buf.viewport_top <= 29 && 29 < buf.viewport_top + buf.viewport_lines);
+        buf.goto_line(1);
+        assert_eq!((buf.cursor.line, buf.viewport_top), (0, 0));
+    }
+
+    #[test]
+    fn test_goto_line_clamps() {
+        let mut buf = fifty_lines();
+        buf.goto_line(0);
         assert_eq!(buf.cursor.line, 0);
-        assert_eq!(buf.cursor.col, 2);
+        buf.goto_line(999);
+        assert_eq!(buf.cursor.line, 49);
+        assert_eq!(buf.viewport_top, 37);
+        let mut buf = TextBuffer::new();
+        buf.goto_line(5);
+        assert_eq!(buf.cursor.line, 0);
+    }
+
+    #[test]
+    fn test_snapshot_restore() {
+        let mut buf = fifty_lines();
+        buf.goto_line(40);
+        buf.insert_char('x');
+        let snap = buf.snapshot();
+        assert!(snap.modified);
+
+        let mut other = TextBuffer::new();
+        other.restore(&snap);
+        assert_eq!(other.lines, buf.lines);
+        assert_eq!(other.cursor, buf.cursor);
+        assert_eq!(other.viewport_top, buf.viewport_top);
+        assert!(other.modified);
+    }
+
+    #[test]
+    fn test_restore_clamps_cursor() {
+        let snap = BufferSnapshot {
+            lines: vec![String::from("short")],
+            cursor: Cursor { line: 9, col: 40 },
+            viewport_top: 7,
+            modified: false,
+        };
+        let mut buf = TextBuffer::new();
+        buf.restore(&snap);
+        assert_eq!((buf.cursor.line, buf.cursor.col), (0, 5));
+        assert_eq!(buf.viewport_top, 0);
+    }
+
+    #[test]
+    fn test_delete_line() {
+        let mut buf = TextBuffer::from_text("one\nlonger two\nsix");
+        buf.cursor.line = 1;
+        buf.move_end();
+        assert_eq!(buf.delete_line(), "longer two");
+        assert_eq!(buf.lines, vec!["one", "six"]);
+        // Cursor stays on the line that moved up, column clamped
+        assert_eq!((buf.cursor.line, buf.cursor.col), (1, 3));
+        buf.delete_line();
+        assert_eq!(buf.lines, vec!["one"]);
+        assert_eq!(buf.cursor.line, 0);
+        assert!(buf.modified);
+    }
+
+    #[test]
+    fn test_delete_only_line_leaves_empty_line() {
+        let mut buf = TextBuffer::from_text("only");
+        buf.delete_line();
+        assert_eq!(buf.lines, vec![""]);
+        assert_eq!((buf.cursor.line, buf.cursor.col), (0, 0));
+        buf.undo();
+        assert_eq!(buf.lines, vec!["only"]);
+    }
+
+    #[test]
+    fn test_duplicate_line() {
+        let mut buf = TextBuffer::from_text("alpha\nbeta");
+        buf.cursor.col = 2;
+        buf.duplicate_line();
+        assert_eq!(buf.lines, vec!["alpha", "alpha", "beta"]);
+        assert_eq!((buf.cursor.line, buf.cursor.col), (1, 2));
+        assert!(buf.modified);
+        // At the bottom of the viewport the copy scrolls into view
+        let mut buf = fifty_lines();
+        buf.goto_line(13);
+        buf.duplicate_line();
+        assert_eq!(buf.cursor.line, 13);
+        assert_eq!(buf.viewport_top, 1);
+    }
+
+    #[test]
+    fn test_kills_accumulate() {
+        let mut buf = TextBuffer::from_text("alpha beta gamma\nnext");
+        buf.move_end();
+        assert!(buf.delete_word_back());
+        assert_eq!(buf.lines[0], "alpha beta ");
+        buf.cursor.col = 6;
+        assert!(buf.delete_to_line_end());
+        assert_eq!(buf.lines[0], "alpha ");
+        // At the end of the line the line break is killed
+        assert!(buf.delete_to_line_end());
+        assert_eq!(buf.lines, vec!["alpha next"]);
+        assert_eq!(buf.kill_ring(), ["gamma", "beta ", "\n"]);
+        // Nothing to delete: no kill
+        buf.move_end();
+        assert!(!buf.delete_to_line_end());
+        assert_eq!(buf.kill_ring().len(), 3);
+    }
+
+    #[test]
+    fn test_yank_pop_cycles() {
+        let mut buf = TextBuffer::from_text("one two three");
+        buf.move_end();
+        buf.delete_word_back();
+        buf.delete_word_back();
+        buf.delete_word_back();
+        assert_eq!(buf.lines[0], "");
+        assert!(!buf.yank_pop()); // not after a yank
+
+        assert!(buf.yank());
+        assert_eq!(buf.lines[0], "one ");
+        assert!(buf.yank_pop());
+        assert_eq!(buf.lines[0], "two ");
+        assert!(buf.yank_pop());
+        assert_eq!(buf.lines[0], "three");
+        // Wraps back round to the newest
+        assert!(buf.yank_pop());
+        assert_eq!(buf.lines[0], "one ");
+        // Each pop is undoable on its own
+        buf.undo();
+        assert_eq!(buf.lines[0], "three");
+    }
+
+    #[test]
+    fn test_yank_pop_needs_fresh_yank() {
+        let mut buf = TextBuffer::from_text("a b");
+        buf.move_end();
+        buf.delete_word_back();
+        assert!(buf.yank());
+        buf.insert_char('!');
+        assert!(!buf.yank_pop());
+        assert!(!TextBuffer::new().yank());
+
+        // Undoing the yank forgets it, even with the cursor back where the
+        // yank ended
+        let mut buf = TextBuffer::from_text("ab xyz");
+        buf.move_right();
+        buf.move_right();
+        buf.delete_word_back();
+        assert!(buf.yank());
+        assert_eq!(buf.lines[0], "ab xyz");
+        buf.undo();
+        assert_eq!(buf.lines[0], " xyz");
+        buf.move_right();
+        buf.move_right();
+        assert!(!buf.yank_pop());
+        assert_eq!(buf.lines[0], " xyz");
+    }
+
+    #[test]
+    fn test_kill_ring_is_bounded() {
+        let mut buf = TextBuffer::new();
+        for i in 0..(KILL_RING_SIZE + 3) {
+            buf.insert_char(char::from(b'a' + i as u8));
+            buf.delete_word_back();
+        }
+        assert_eq!(buf.kill_ring().len(), KILL_RING_SIZE);
+        assert_eq!(buf.kill_ring()[0], "d");
+    }
+
+    #[test]
+    fn test_horizontal_scroll_follows_cursor_right() {
+        let mut buf = TextBuffer::from_text(&"x".repeat(100));
+        buf.ensure_cursor_visible_horizontal(40);
+        assert_eq!(buf.viewport_col, 0);
+        buf.cursor.col = 39;
+        buf.ensure_cursor_visible_horizontal(40);
+        assert_eq!(buf.viewport_col, 0);
+        // One past the right edge scrolls by one
+        buf.cursor.col = 40;
+        buf.ensure_cursor_visible_horizontal(40);
+        assert_eq!(buf.viewport_col, 1);
+        // End of line: the cell after the last char is the rightmost column
+        buf.move_end();
+        buf.ensure_cursor_visible_horizontal(40);
+        assert_eq!(buf.viewport_col, 61);
+    }
+
+    #[test]
+    fn test_horizontal_scroll_back_left_keeps_context() {
+        let mut buf = TextBuffer::from_text(&"x".repeat(100));
+        buf.viewport_col = 60;
+        buf.cursor.col = 59;
+        buf.ensure_cursor_visible_horizontal(40);
+        assert_eq!(buf.viewport_col, 39);
+        // Still on screen: no scroll
+        buf.cursor.col = 45;
+        buf.ensure_cursor_visible_horizontal(40);
+        assert_eq!(buf.viewport_col, 39);
+    }
+
+    #[test]
+    fn test_horizontal_scroll_short_line_and_home() {
+        let mut buf = TextBuffer::from_text(&format!("{}\nshort", "x".repeat(100)));
+        buf.move_end();
+        buf.ensure_cursor_visible_horizontal(40);
+        assert_eq!(buf.viewport_col, 61);
+        // Moving down clamps the cursor to the short line, which is then shown from the start
+        buf.move_down();
+        buf.ensure_cursor_visible_horizontal(40);
+        assert_eq!(buf.cursor.col, 5);
+        assert_eq!(buf.viewport_col, 0);
+
+        buf.move_up();
+        buf.move_end();
+        buf.ensure_cursor_visible_horizontal(40);
+        buf.move_home();
+        assert_eq!(buf.viewport_col, 0);
+    }
+
+    #[test]
+    fn test_stats_match_individual_counts() {
+        let buf = TextBuffer::from_text("# Café notes\n\nnaïve résumé — 日本語 text\n  indented line here  \n");
+        let stats = buf.stats();
+        assert_eq!(stats.words, buf.word_count());
+        assert_eq!(stats.chars, buf.char_count());
+        assert_eq!(stats.lines, buf.line_count());
+        assert_eq!(stats.longest_line, buf.longest_line_len());
+        // Characters, not bytes: the longest line is 23 chars but 34 bytes
+        assert_eq!(stats.longest_line, 23);
+        assert_eq!(TextBuffer::new().stats(), BufferStats { lines: 1, ..BufferStats::default() });
     }
 }
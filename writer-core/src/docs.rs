@@ -0,0 +1,485 @@
+use alloc::{string::{String, ToString}, vec::Vec, format};
+
+/// Result of resolving a typed name against the document list.
+#[derive(Clone, Debug, PartialEq)]
+pub enum NameMatch {
+    /// A document with exactly this name exists
+    Exact(String),
+    /// Exactly one document name starts with the query
+    Prefix(String),
+    /// Several documents start with the query (count)
+    Ambiguous(usize),
+    NoMatch,
+}
+
+/// Resolve a typed name to a document: an exact match wins, otherwise a
+/// unique case-insensitive prefix match. Several prefix matches are ambiguous.
+pub fn match_doc_name(names: &[String], query: &str) -> NameMatch {
+    let query = query.trim();
+    if query.is_empty() {
+        return NameMatch::NoMatch;
+    }
+    if let Some(name) = names.iter().find(|n| n.as_str() == query) {
+        return NameMatch::Exact(name.clone());
+    }
+    let lower = query.to_lowercase();
+    if let Some(name) = names.iter().find(|n| n.to_lowercase() == lower) {
+        return NameMatch::Exact(name.clone());
+    }
+    let prefixed: Vec<&String> = names.iter()
+        .filter(|n| n.to_lowercase().starts_with(&lower))
+        .collect();
+    match prefixed.len() {
+        0 => NameMatch::NoMatch,
+        1 => NameMatch::Prefix(prefixed[0].clone()),
+        n => NameMatch::Ambiguous(n),
+    }
+}
+
+/// Clean up a document name typed by the user: control characters are
+/// dropped and surrounding whitespace trimmed. The length-prefixed `WIDX`
+/// index would store control characters as is, but the document list
+/// can't show them and nobody could type the name back to find it.
+/// Folder separators are tidied too: spaces around a `/` and empty
+/// segments (`//`, a leading or trailing `/`) are removed. None if nothing
+/// is left.
+pub fn sanitize_name(name: &str) -> Option<String> {
+    let cleaned: String = name.chars().filter(|c| !c.is_control()).collect();
+    let segments: Vec<&str> = cleaned.split('/')
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .collect();
+    if segments.is_empty() {
+        None
+    } else {
+        Some(segments.join("/"))
+    }
+}
+
+/// Split a document name into its folder and its own name: "Work/Q1/Plan"
+/// is in folder "Work/Q1" and named "Plan". A name without a `/` (or with
+/// nothing on one side of its last `/`) is in no folder.
+pub fn split_folder(name: &str) -> (Option<String>, String) {
+    match name.rsplit_once('/') {
+        Some((folder, leaf)) if !folder.is_empty() && !leaf.is_empty() => {
+            (Some(folder.to_string()), leaf.to_string())
+        }
+        _ => (None, name.to_string()),
+    }
+}
+
+/// One row of the document list.
+#[derive(Clone, Debug, PartialEq)]
+pub enum DocEntry {
+    /// A folder directly inside the one shown: its full path and how many
+    /// documents it holds, counting subfolders
+    Folder { path: String, docs: usize },
+    /// A document, by full name
+    Doc(String),
+}
+
+/// The document list for `folder` ("" for the top level): its subfolders,
+/// then the documents directly in it, each group in `sort` order. Folders
+/// only exist while some document's name is inside them. A non-empty
+/// `query` instead lists every document anywhere under `folder` whose name
+/// contains it, without folders.
+pub fn folder_view(names: &[String], folder: &str, query: &str, sort: DocSort) -> Vec<DocEntry> {
+    let prefix = if folder.is_empty() { String::new() } else { format!("{}/", folder) };
+    let inside: Vec<String> = names.iter().filter(|n| n.starts_with(&prefix)).cloned().collect();
+    if !query.is_empty() {
+        return filter_and_sort(&inside, query, sort).into_iter().map(DocEntry::Doc).collect();
+    }
+
+    let mut folders: Vec<(String, usize)> = Vec::new();
+    let mut docs: Vec<String> = Vec::new();
+    for name in &inside {
+        match name[prefix.len()..].split_once('/') {
+            Some((sub, rest)) if !sub.is_empty() && !rest.is_empty() => {
+                let path = format!("{}{}", prefix, sub);
+                match folders.iter_mut().find(|(p, _)| *p == path) {
+                    Some((_, count)) => *count += 1,
+                    None => folders.push((path, 1)),
+                }
+            }
+            _ => docs.push(name.clone()),
+        }
+    }
+
+    let paths: Vec<String> = folders.iter().map(|(p, _)| p.clone()).collect();
+    let mut view: Vec<DocEntry> = filter_and_sort(&paths, "", sort)
+        .into_iter()
+        .map(|path| {
+            let docs = folders.iter().find(|(p, _)| *p == path).map_or(0, |&(_, n)| n);
+            DocEntry::Folder { path, docs }
+        })
+        .collect();
+    view.extend(filter_and_sort(&docs, "", sort).into_iter().map(DocEntry::Doc));
+    view
+}
+
+/// First name of the form "prefix", "prefix 2", "prefix 3", ... that isn't
+/// in `existing`. Gives up looking after 999 and returns "prefix 1000".
+pub fn next_free_name(existing: &[String], prefix: &str) -> String {
+    next_free_name_by(prefix, |name| existing.iter().any(|n| n == name))
+}
+
+/// `next_free_name` with `taken` answering whether a name is in use, for
+/// callers that can check a name without listing them all.
+pub fn next_free_name_by<F>(prefix: &str, mut taken: F) -> String
+where
+    F: FnMut(&str) -> bool,
+{
+    for n in 1..1000u32 {
+        let candidate = if n == 1 {
+            prefix.to_string()
+        } else {
+            format!("{} {}", prefix, n)
+        };
+        if !taken(&candidate) {
+            return candidate;
+        }
+    }
+    format!("{} 1000", prefix)
+}
+
+/// Why a document rename was refused.
+#[derive(Clone, Debug, PartialEq)]
+pub enum RenameError {
+    EmptyName,
+    NameTaken,
+    /// The stored document is damaged and can't be moved
+    Unreadable,
+    /// The document couldn't be written under its new name
+    WriteFailed,
+}
+
+impl RenameError {
+    /// One-line explanation for the rename dialog.
+    pub fn message(&self) -> &'static str {
+        match self {
+            RenameError::EmptyName => "Name cannot be empty",
+            RenameError::NameTaken => "A document with that name exists",
+            RenameError::Unreadable => "The document is damaged",
+            RenameError::WriteFailed => "Could not save under the new name",
+        }
+    }
+}
+
+/// Rename `old` to `new` in the document index, keeping its position.
+/// A document that isn't in the index yet (never saved) is appended.
+pub fn rename_in_index(names: &mut Vec<String>, old: &str, new: &str) -> Result<(), RenameError> {
+    let new = sanitize_name(new).ok_or(RenameError::EmptyName)?;
+    if new != old && names.contains(&new) {
+        return Err(RenameError::NameTaken);
+    }
+    match names.iter_mut().find(|n| n.as_str() == old) {
+        Some(slot) => *slot = new,
+        None => names.push(new),
+    }
+    Ok(())
+}
+
+/// Move `name` by `delta` places in the document index (negative = up),
+/// clamped to the ends. Returns its new position, or None if not found.
+pub fn move_in_index(names: &mut [String], name: &str, delta: isize) -> Option<usize> {
+    let from = names.iter().position(|n| n == name)?;
+    let to = (from as isize + delta).clamp(0, names.len() as isize - 1) as usize;
+    if to < from {
+        names[to..=from].rotate_right(1);
+    } else {
+        names[from..=to].rotate_left(1);
+    }
+    Some(to)
+}
+
+/// Index entries for `names`, in order. Word counts come from `counted`
+/// for documents just written and otherwise carry over from `stored`, so
+/// renamed documents must appear in `counted` under their new name.
+pub fn index_with_counts(
+    stored: &[(String, Option<u32>)],
+    names: &[String],
+    counted: &[(String, u32)],
+) -> Vec<(String, Option<u32>)> {
+    names.iter()
+        .map(|name| {
+            let words = counted.iter().find(|(n, _)| n == name).map(|&(_, w)| w)
+                .or_else(|| stored.iter().find(|(n, _)| n == name).and_then(|&(_, w)| w));
+            (name.clone(), words)
+        })
+        .collect()
+}
+
+/// Order of the document list.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DocSort {
+    /// The index order, as arranged with reorder
+    Index,
+    NameAsc,
+    NameDesc,
+}
+
+impl DocSort {
+    /// The next order, for cycling with a key.
+    pub fn next(self) -> Self {
+        match self {
+            DocSort::Index => DocSort::NameAsc,
+            DocSort::NameAsc => DocSort::NameDesc,
+            DocSort::NameDesc => DocSort::Index,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            DocSort::Index => "custom order",
+            DocSort::NameAsc => "A-Z",
+            DocSort::NameDesc => "Z-A",
+        }
+    }
+}
+
+/// Documents whose names contain `query` (case-insensitive; an empty query
+/// keeps them all), in `sort` order.
+pub fn filter_and_sort(names: &[String], query: &str, sort: DocSort) -> Vec<String> {
+    let query = query.to_lowercase();
+    let mut view: Vec<String> = names.iter()
+        .filter(|n| n.to_lowercase().contains(&query))
+        .cloned()
+        .collect();
+    match sort {
+        DocSort::Index => {}
+        DocSort::NameAsc => view.sort_by_key(|n| n.to_lowercase()),
+        DocSort::NameDesc => {
+            view.sort_by_key(|n| n.to_lowercase());
+            view.reverse();
+        }
+    }
+    view
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    fn names() -> Vec<String> {
+        ["Notes", "Notes 2", "Novel draft", "Shopping"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect()
+    }
+
+    fn index_of(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_exact_match() {
+        assert_eq!(match_doc_name(&names(), "Notes"), NameMatch::Exact("Notes".into()));
+        // Exact match wins even though it is also a prefix of "Notes 2"
+        assert_eq!(match_doc_name(&names(), "notes"), NameMatch::Exact("Notes".into()));
+    }
+
+    #[test]
+    fn test_unique_prefix() {
+        assert_eq!(match_doc_name(&names(), "Sho"), NameMatch::Prefix("Shopping".into()));
+        assert_eq!(match_doc_name(&names(), "nov"), NameMatch::Prefix("Novel draft".into()));
+    }
+
+    #[test]
+    fn test_ambiguous_prefix() {
+        assert_eq!(match_doc_name(&names(), "No"), NameMatch::Ambiguous(3));
+        assert_eq!(match_doc_name(&names(), "Notes "), NameMatch::Exact("Notes".into()));
+    }
+
+    #[test]
+    fn test_no_match() {
+        assert_eq!(match_doc_name(&names(), "Recipes"), NameMatch::NoMatch);
+        assert_eq!(match_doc_name(&names(), "   "), NameMatch::NoMatch);
+        assert_eq!(match_doc_name(&[], "Notes"), NameMatch::NoMatch);
+    }
+
+    #[test]
+    fn test_sanitize_name_trims() {
+        assert_eq!(sanitize_name("  Notes  "), Some("Notes".into()));
+        assert_eq!(sanitize_name("Notes 2\t"), Some("Notes 2".into()));
+        // Inner spaces are kept, control characters dropped
+        assert_eq!(sanitize_name("My\nNovel  draft"), Some("MyNovel  draft".into()));
+    }
+
+    #[test]
+    fn test_sanitize_name_rejects_blank() {
+        assert_eq!(sanitize_name(""), None);
+        assert_eq!(sanitize_name("   "), None);
+        assert_eq!(sanitize_name(" \t\n "), None);
+    }
+
+    #[test]
+    fn test_next_free_name_numbers_copies() {
+        let mut index = index_of(&["Notes", "Shopping"]);
+        assert_eq!(next_free_name(&index, "Untitled"), "Untitled");
+        // Duplicating "Notes" repeatedly: "Notes copy", "Notes copy 2", ...
+        for expected in ["Notes copy", "Notes copy 2", "Notes copy 3"] {
+            let name = next_free_name(&index, "Notes copy");
+            assert_eq!(name, expected);
+            index.push(name);
+        }
+    }
+
+    #[test]
+    fn test_rename_preserves_index_order() {
+        let mut index = index_of(&["Alpha", "Beta", "Gamma"]);
+        assert_eq!(rename_in_index(&mut index, "Beta", " Delta "), Ok(()));
+        assert_eq!(index, index_of(&["Alpha", "Delta", "Gamma"]));
+    }
+
+    #[test]
+    fn test_rename_rejects_collision_and_empty() {
+        let mut index = index_of(&["Alpha", "Beta"]);
+        assert_eq!(rename_in_index(&mut index, "Alpha", "Beta"), Err(RenameError::NameTaken));
+        assert_eq!(rename_in_index(&mut index, "Alpha", "   "), Err(RenameError::EmptyName));
+        assert_eq!(index, index_of(&["Alpha", "Beta"]));
+        // Renaming to the same name is a no-op, not a collision
+        assert_eq!(rename_in_index(&mut index, "Alpha", "Alpha"), Ok(()));
+        assert_eq!(index, index_of(&["Alpha", "Beta"]));
+    }
+
+    #[test]
+    fn test_rename_unsaved_doc_appends() {
+        let mut index = index_of(&["Alpha"]);
+        assert_eq!(rename_in_index(&mut index, "Untitled", "Notes"), Ok(()));
+        assert_eq!(index, index_of(&["Alpha", "Notes"]));
+    }
+
+    #[test]
+    fn test_move_doc_up_and_down() {
+        let mut index = index_of(&["A", "B", "C", "D"]);
+        assert_eq!(move_in_index(&mut index, "C", -1), Some(1));
+        assert_eq!(index, index_of(&["A", "C", "B", "D"]));
+        assert_eq!(move_in_index(&mut index, "A", 2), Some(2));
+        assert_eq!(index, index_of(&["C", "B", "A", "D"]));
+    }
+
+    #[test]
+    fn test_move_doc_clamps_at_ends() {
+        let mut index = index_of(&["A", "B", "C"]);
+        assert_eq!(move_in_index(&mut index, "A", -1), Some(0));
+        assert_eq!(move_in_index(&mut index, "B", 10), Some(2));
+        assert_eq!(index, index_of(&["A", "C", "B"]));
+        assert_eq!(move_in_index(&mut index, "Z", 1), None);
+    }
+
+    #[test]
+    fn test_filter_is_case_insensitive_substring() {
+        let view = filter_and_sort(&names(), "OT", DocSort::Index);
+        assert_eq!(view, index_of(&["Notes", "Notes 2"]));
+        assert_eq!(filter_and_sort(&names(), "draft", DocSort::Index), index_of(&["Novel draft"]));
+        assert!(filter_and_sort(&names(), "xyz", DocSort::Index).is_empty());
+        // Cleared filter: the full list, in index order
+        assert_eq!(filter_and_sort(&names(), "", DocSort::Index), names());
+    }
+
+    #[test]
+    fn test_sort_by_name() {
+        let index = index_of(&["beta", "Alpha", "gamma"]);
+        assert_eq!(filter_and_sort(&index, "", DocSort::NameAsc), index_of(&["Alpha", "beta", "gamma"]));
+        assert_eq!(filter_and_sort(&index, "", DocSort::NameDesc), index_of(&["gamma", "beta", "Alpha"]));
+        assert_eq!(filter_and_sort(&index, "a", DocSort::Index), index);
+        assert_eq!(DocSort::NameDesc.next(), DocSort::Index);
+    }
+
+    #[test]
+    fn test_sanitize_name_tidies_folders() {
+        assert_eq!(sanitize_name(" Work / Notes "), Some("Work/Notes".into()));
+        assert_eq!(sanitize_name("/Work//2026/Plan/"), Some("Work/2026/Plan".into()));
+        assert_eq!(sanitize_name(" / "), None);
+    }
+
+    #[test]
+    fn test_split_folder() {
+        assert_eq!(split_folder("Notes"), (None, "Notes".into()));
+        assert_eq!(split_folder("Work/Notes"), (Some("Work".into()), "Notes".into()));
+        // Nested folders: everything before the last slash
+        assert_eq!(split_folder("Work/2026/Q1 plan"), (Some("Work/2026".into()), "Q1 plan".into()));
+        // A slash with nothing on one side isn't a folder
+        assert_eq!(split_folder("Work/"), (None, "Work/".into()));
+        assert_eq!(split_folder("/Notes"), (None, "/Notes".into()));
+    }
+
+    #[test]
+    fn test_folder_view_groups_top_level() {
+        let index = index_of(&["Work/Plan", "Todo", "Ideas/App", "Work/2026/Q1", "Work/Notes", "Apple"]);
+        assert_eq!(
+            folder_view(&index, "", "", DocSort::Index),
+            vec![
+                DocEntry::Folder { path: "Work".into(), docs: 3 },
+                DocEntry::Folder { path: "Ideas".into(), docs: 1 },
+                DocEntry::Doc("Todo".into()),
+                DocEntry::Doc("Apple".into()),
+            ]
+        );
+        assert_eq!(
+            folder_view(&index, "", "", DocSort::NameAsc),
+            vec![
+                DocEntry::Folder { path: "Ideas".into(), docs: 1 },
+                DocEntry::Folder { path: "Work".into(), docs: 3 },
+                DocEntry::Doc("Apple".into()),
+                DocEntry::Doc("Todo".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_folder_view_inside_folder() {
+        let index = index_of(&["Work/Plan", "Work/2026/Q1", "Work/2026/Q2", "Workshop", "Work/Notes"]);
+        assert_eq!(
+            folder_view(&index, "Work", "", DocSort::Index),
+            vec![
+                DocEntry::Folder { path: "Work/2026".into(), docs: 2 },
+                DocEntry::Doc("Work/Plan".into()),
+                DocEntry::Doc("Work/Notes".into()),
+            ]
+        );
+        assert_eq!(
+            folder_view(&index, "Work/2026", "", DocSort::Index),
+            vec![DocEntry::Doc("Work/2026/Q1".into()), DocEntry::Doc("Work/2026/Q2".into())]
+        );
+        // Filtering searches the whole folder, subfolders included
+        assert_eq!(
+            folder_view(&index, "Work", "q", DocSort::Index),
+            vec![DocEntry::Doc("Work/2026/Q1".into()), DocEntry::Doc("Work/2026/Q2".into())]
+        );
+    }
+
+    #[test]
+    fn test_folder_disappears_with_last_doc() {
+        let mut index = index_of(&["Ideas/App", "Todo"]);
+        index.retain(|n| n != "Ideas/App");
+        assert_eq!(folder_view(&index, "", "", DocSort::Index), vec![DocEntry::Doc("Todo".into())]);
+        assert!(folder_view(&index, "Ideas", "", DocSort::Index).is_empty());
+    }
+
+    #[test]
+    fn test_moved_order_persists_in_index() {
+        use crate::serialize::{deserialize_index, serialize_index};
+        let mut index = index_of(&["A", "B", "C"]);
+        move_in_index(&mut index, "C", -2);
+        let restored = deserialize_index(&serialize_index(&index));
+        assert_eq!(restored, index_of(&["C", "A", "B"]));
+    }
+
+    #[test]
+    fn test_index_with_counts_tracks_saves_deletes_renames() {
+        let stored = vec![("A".to_string(), Some(10)), ("B".to_string(), None), ("C".to_string(), Some(3))];
+        // Saving B gives it a count; the others carry over
+        let saved = index_with_counts(&stored, &index_of(&["A", "B", "C"]), &[("B".to_string(), 7)]);
+        assert_eq!(saved, vec![("A".to_string(), Some(10)), ("B".to_string(), Some(7)), ("C".to_string(), Some(3))]);
+        // Deleting drops the entry and its count
+        let deleted = index_with_counts(&saved, &index_of(&["A", "C"]), &[]);
+        assert_eq!(deleted, vec![("A".to_string(), Some(10)), ("C".to_string(), Some(3))]);
+        // A rename passes the count under the new name; the old one is gone
+        let renamed = index_with_counts(&deleted, &index_of(&["Z", "C"]), &[("Z".to_string(), 10)]);
+        assert_eq!(renamed, vec![("Z".to_string(), Some(10)), ("C".to_string(), Some(3))]);
+        // Saving with an unchanged count leaves the index as it was
+        assert_eq!(index_with_counts(&renamed, &index_of(&["Z", "C"]), &[("C".to_string(), 3)]), renamed);
+    }
+}
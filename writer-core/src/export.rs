@@ -0,0 +1,474 @@
+use alloc::{string::{String, ToString}, vec::Vec, format, vec};
+use crate::serialize::{crc32, AutotypeNewline};
+
+/// Split `content` into pages of at most `size` characters, breaking after
+/// a newline where possible. A line longer than `size` is split mid-line.
+/// Concatenating the pages gives back `content`. A size of 0 means one page.
+pub fn paginate(content: &str, size: usize) -> Vec<String> {
+    if size == 0 || content.chars().count() <= size {
+        return vec![content.to_string()];
+    }
+
+    let mut pages = Vec::new();
+    let mut page = String::new();
+    let mut page_len = 0;
+    for line in content.split_inclusive('\n') {
+        let line_len = line.chars().count();
+        if page_len + line_len > size && page_len > 0 {
+            pages.push(core::mem::take(&mut page));
+            page_len = 0;
+        }
+        if line_len <= size {
+            page.push_str(line);
+            page_len += line_len;
+            continue;
+        }
+        // Hard-split an overlong line; its tail starts the next page
+        let chars: Vec<char> = line.chars().collect();
+        for chunk in chars.chunks(size) {
+            if chunk.len() == size {
+                pages.push(chunk.iter().collect());
+            } else {
+                page = chunk.iter().collect();
+                page_len = chunk.len();
+            }
+        }
+    }
+    if !page.is_empty() {
+        pages.push(page);
+    }
+    pages
+}
+
+/// Separator line sent before each page of a paginated export.
+pub fn page_separator(page: usize, total: usize) -> String {
+    format!("--- page {}/{} ---\n", page, total)
+}
+
+/// Pages ready to send: unchanged content when it fits in one page,
+/// otherwise each page prefixed with its "--- page N/M ---" separator.
+pub fn export_pages(content: &str, size: usize) -> Vec<String> {
+    let pages = paginate(content, size);
+    if pages.len() == 1 {
+        return pages;
+    }
+    let total = pages.len();
+    pages.into_iter()
+        .enumerate()
+        .map(|(i, page)| page_separator(i + 1, total) + &page)
+        .collect()
+}
+
+/// Key a document's markdown copy is saved under in the exports dict.
+pub fn markdown_export_key(name: &str) -> String {
+    format!("{}.md", name)
+}
+
+/// Key a document's HTML rendering is saved under in the exports dict.
+pub fn html_export_key(name: &str) -> String {
+    format!("{}.html", name)
+}
+
+/// Where an interrupted USB autotype stopped, so the next export of the
+/// same document can pick up from there.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExportResume {
+    pub doc: String,
+    /// Bytes of the export text already typed
+    pub offset: usize,
+    /// Byte length of the export text
+    pub total: usize,
+    /// CRC-32 of the export text; text that no longer matches it means
+    /// the document changed and the offset no longer applies, even when
+    /// an edit kept the length the same
+    pub crc: u32,
+}
+
+impl ExportResume {
+    /// A resume point `offset` bytes into `text`, the export of `doc`.
+    pub fn new(doc: &str, text: &str, offset: usize) -> Self {
+        Self { doc: doc.to_string(), offset, total: text.len(), crc: crc32(text.as_bytes()) }
+    }
+
+    /// The saved offset if it still applies to `doc` with export text
+    /// `text` and there is something left to send.
+    pub fn offset_for(&self, doc: &str, text: &str) -> Option<usize> {
+        let unchanged = self.total == text.len() && self.crc == crc32(text.as_bytes());
+        if self.doc == doc && unchanged && self.offset > 0 && self.offset < self.total {
+            Some(self.offset)
+        } else {
+            None
+        }
+    }
+}
+
+/// The rest of `content` from byte offset `from`, which is moved back to
+/// the start of the character it falls in (so a half-sent character is
+/// sent again rather than skipped). Returns the adjusted offset too.
+pub fn resume_slice(content: &str, from: usize) -> (usize, &str) {
+    let mut at = from.min(content.len());
+    while !content.is_char_boundary(at) {
+        at -= 1;
+    }
+    (at, &content[at..])
+}
+
+/// Split `content` into pieces of at most `max_bytes` bytes without
+/// cutting a character in half (a piece may be longer only when a single
+/// character is wider than `max_bytes`).
+pub fn char_chunks(content: &str, max_bytes: usize) -> Vec<&str> {
+    let mut chunks = Vec::new();
+    let mut rest = content;
+    while !rest.is_empty() {
+        let mut end = max_bytes.min(rest.len());
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        if end == 0 {
+            end = rest.chars().next().map_or(rest.len(), char::len_utf8);
+        }
+        let (chunk, tail) = rest.split_at(end);
+        chunks.push(chunk);
+        rest = tail;
+    }
+    chunks
+}
+
+/// Pages for USB autotype: `export_pages`, with every line break typed as
+/// `newline` says. Other than `Lf`, trailing line breaks are dropped, so
+/// the export doesn't end by pressing Enter on an empty line (which
+/// submits a chat box, say).
+pub fn autotype_pages(content: &str, size: usize, newline: AutotypeNewline) -> Vec<String> {
+    let content = match newline {
+        AutotypeNewline::Lf => content,
+        _ => content.trim_end_matches('\n'),
+    };
+    export_pages(content, size)
+        .iter()
+        .map(|page| autotype_newlines(page, newline))
+        .collect()
+}
+
+/// `text` with each '\n' replaced by what `newline` types for it.
+pub fn autotype_newlines(text: &str, newline: AutotypeNewline) -> String {
+    text.replace('\n', newline.as_str())
+}
+
+/// `text` cut after each line break ("\n", "\r\n" or a lone "\r", as
+/// `autotype_newlines` leaves them), for typing a line at a time. Each
+/// piece keeps its break, so an empty line is still a break of its own and
+/// the pieces join back into `text`.
+pub fn autotype_lines(text: &str) -> Vec<&str> {
+    let bytes = text.as_bytes();
+    let mut lines = Vec::new();
+    let mut start = 0;
+    for (i, &b) in bytes.iter().enumerate() {
+        let ends = b == b'\n' || (b == b'\r' && bytes.get(i + 1) != Some(&b'\n'));
+        if ends {
+            lines.push(&text[start..=i]);
+            start = i + 1;
+        }
+    }
+    if start < text.len() {
+        lines.push(&text[start..]);
+    }
+    lines
+}
+
+/// First line of a console export.
+pub const CONSOLE_BEGIN: &str = "---BEGIN WRITER EXPORT---";
+/// Last line of a console export.
+pub const CONSOLE_END: &str = "---END WRITER EXPORT---";
+
+/// Log records for a console export: `CONSOLE_BEGIN`, then each line of
+/// `content` as "> text", then `CONSOLE_END`. A line longer than
+/// `max_bytes` is split, its later pieces sent as "+ text" to be joined
+/// back onto the line before. A host reading the serial port rebuilds the
+/// document from what follows each "> " or "+ " between the markers.
+pub fn console_records(content: &str, max_bytes: usize) -> Vec<String> {
+    let mut records = vec![CONSOLE_BEGIN.to_string()];
+    for line in content.split('\n') {
+        let chunks = char_chunks(line, max_bytes);
+        if chunks.is_empty() {
+            records.push(String::from("> "));
+        }
+        for (i, chunk) in chunks.iter().enumerate() {
+            records.push(format!("{} {}", if i == 0 { '>' } else { '+' }, chunk));
+        }
+    }
+    records.push(CONSOLE_END.to_string());
+    records
+}
+
+const BASE64_ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Standard base64 (RFC 4648, with `=` padding).
+pub fn base64(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for group in bytes.chunks(3) {
+        let n = group.iter().enumerate().fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= group.len() {
+                out.push(BASE64_ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// Payloads for a QR code export: `content` in base64, cut into chunks of
+/// at most `max_bytes` each including an "idx/total " header (1-based),
+/// so a phone scanning them in any order can put the base64 back together
+/// and decode it. A document that fits yields the single chunk "1/1 ...";
+/// an empty one still yields "1/1 ".
+pub fn qr_chunks(content: &str, max_bytes: usize) -> Vec<String> {
+    let encoded = base64(content.as_bytes());
+    // The header grows with the chunk count, which depends on the room
+    // left after the header: widen it until the count stops changing
+    let mut total = 1;
+    let room = loop {
+        let header = format!("{0}/{0} ", total).len();
+        let room = max_bytes.saturating_sub(header).max(1);
+        let needed = encoded.len().div_ceil(room).max(1);
+        if needed <= total {
+            break room;
+        }
+        total = needed;
+    };
+    let pieces: Vec<&str> = if encoded.is_empty() {
+        vec![""]
+    } else {
+        // base64 is ASCII, so any byte offset is a char boundary
+        encoded.as_bytes().chunks(room).map(|c| core::str::from_utf8(c).unwrap_or_default()).collect()
+    };
+    let total = pieces.len();
+    pieces.iter().enumerate().map(|(i, piece)| format!("{}/{} {}", i + 1, total, piece)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_paginate_fits_in_one_page() {
+        assert_eq!(paginate("short\ntext", 100), vec!["short\ntext"]);
+        assert_eq!(paginate("anything", 0), vec!["anything"]);
+    }
+
+    #[test]
+    fn test_paginate_breaks_at_lines() {
+        let content = "aaaa\nbbbb\ncccc\ndddd";
+        let pages = paginate(content, 10);
+        assert_eq!(pages, vec!["aaaa\nbbbb\n", "cccc\ndddd"]);
+        assert_eq!(pages.concat(), content);
+    }
+
+    #[test]
+    fn test_paginate_splits_long_line() {
+        let content = "ab\nccccccccccc\nd";
+        let pages = paginate(content, 5);
+        assert_eq!(pages, vec!["ab\n", "ccccc", "ccccc", "c\nd"]);
+        assert_eq!(pages.concat(), content);
+        assert!(pages.iter().all(|p| p.chars().count() <= 5));
+    }
+
+    #[test]
+    fn test_paginate_counts_chars_not_bytes() {
+        let pages = paginate("ééé\nüüü", 4);
+        assert_eq!(pages, vec!["ééé\n", "üüü"]);
+    }
+
+    #[test]
+    fn test_export_pages_separators() {
+        assert_eq!(export_pages("one page", 100), vec!["one page"]);
+        let pages = export_pages("aaaa\nbbbb", 5);
+        assert_eq!(pages, vec![
+            "--- page 1/2 ---\naaaa\n",
+            "--- page 2/2 ---\nbbbb",
+        ]);
+    }
+
+    #[test]
+    fn test_markdown_export_key() {
+        assert_eq!(markdown_export_key("Notes"), "Notes.md");
+        assert_eq!(markdown_export_key("Novel draft 2"), "Novel draft 2.md");
+        assert_eq!(html_export_key("Notes"), "Notes.html");
+    }
+
+    #[test]
+    fn test_resume_slice_from_saved_offset() {
+        let content = "hello world";
+        assert_eq!(resume_slice(content, 6), (6, "world"));
+        assert_eq!(resume_slice(content, 0), (0, "hello world"));
+        // Past the end leaves nothing to send
+        assert_eq!(resume_slice(content, 99), (11, ""));
+    }
+
+    #[test]
+    fn test_resume_slice_backs_up_to_char_boundary() {
+        // 'é' is bytes 1..3; an offset inside it resends the whole character
+        let content = "café au lait";
+        assert_eq!(resume_slice(content, 4), (3, "é au lait"));
+        assert_eq!(resume_slice("日本語", 4), (3, "本語"));
+        assert_eq!(resume_slice("日本語", 5), (3, "本語"));
+    }
+
+    #[test]
+    fn test_resume_offset_only_for_same_document() {
+        let text = "x".repeat(100);
+        let saved = ExportResume::new("Notes", &text, 40);
+        assert_eq!(saved.offset_for("Notes", &text), Some(40));
+        assert_eq!(saved.offset_for("Other", &text), None);
+        // Edited since: the offset no longer lines up
+        assert_eq!(saved.offset_for("Notes", &"x".repeat(101)), None);
+        // Even when the edit kept the length
+        let same_length = format!("y{}", &text[1..]);
+        assert_eq!(saved.offset_for("Notes", &same_length), None);
+        let done = ExportResume { offset: 100, ..saved };
+        assert_eq!(done.offset_for("Notes", &text), None);
+    }
+
+    #[test]
+    fn test_char_chunks_keep_characters_whole() {
+        let content = "aéb日c";
+        let chunks = char_chunks(content, 2);
+        assert_eq!(chunks, vec!["a", "é", "b", "日", "c"]);
+        assert_eq!(chunks.concat(), content);
+        assert_eq!(char_chunks("abcde", 2), vec!["ab", "cd", "e"]);
+        assert!(char_chunks("", 4).is_empty());
+    }
+
+    #[test]
+    fn test_autotype_newlines() {
+        let text = "one\ntwo\n\nthree";
+        assert_eq!(autotype_newlines(text, AutotypeNewline::Lf), text);
+        assert_eq!(autotype_newlines(text, AutotypeNewline::CrLf), "one\r\ntwo\r\n\r\nthree");
+        assert_eq!(autotype_newlines(text, AutotypeNewline::Cr), "one\rtwo\r\rthree");
+        assert_eq!(autotype_newlines(text, AutotypeNewline::Space), "one two  three");
+        assert_eq!(autotype_newlines("", AutotypeNewline::CrLf), "");
+    }
+
+    #[test]
+    fn test_autotype_pages_trailing_newline() {
+        // As is: the text is typed exactly
+        assert_eq!(autotype_pages("a\nb\n", 0, AutotypeNewline::Lf), vec!["a\nb\n"]);
+        // Otherwise no final Enter on an empty line
+        assert_eq!(autotype_pages("a\nb\n\n", 0, AutotypeNewline::Cr), vec!["a\rb"]);
+        assert_eq!(autotype_pages("a\nb\n", 0, AutotypeNewline::Space), vec!["a b"]);
+        assert_eq!(autotype_pages("\n\n", 0, AutotypeNewline::CrLf), vec![""]);
+    }
+
+    #[test]
+    fn test_autotype_pages_transform_separators() {
+        let pages = autotype_pages("aaaa\nbbbb\n", 5, AutotypeNewline::CrLf);
+        assert_eq!(pages, vec!["--- page 1/2 ---\r\naaaa\r\n", "--- page 2/2 ---\r\nbbbb"]);
+        assert!(pages.iter().all(|p| !p.replace("\r\n", "").contains('\n')));
+    }
+
+    #[test]
+    fn test_autotype_lines() {
+        assert_eq!(autotype_lines("one\n\ntwo"), vec!["one\n", "\n", "two"]);
+        assert_eq!(autotype_lines("a\r\nb\r\n"), vec!["a\r\n", "b\r\n"]);
+        assert_eq!(autotype_lines("a\r\rb"), vec!["a\r", "\r", "b"]);
+        assert_eq!(autotype_lines("日本\n語"), vec!["日本\n", "語"]);
+        assert!(autotype_lines("").is_empty());
+        let text = "x\n\r\ny\rz";
+        assert_eq!(autotype_lines(text).concat(), text);
+    }
+
+    // What a host does with the captured records
+    fn from_console(records: &[String]) -> String {
+        let mut text = String::new();
+        for (i, record) in records.iter().enumerate() {
+            if let Some(line) = record.strip_prefix("> ") {
+                if i > 1 {
+                    text.push('\n');
+                }
+                text.push_str(line);
+            } else if let Some(piece) = record.strip_prefix("+ ") {
+                text.push_str(piece);
+            }
+        }
+        text
+    }
+
+    #[test]
+    fn test_base64() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+        assert_eq!(base64("é\n".as_bytes()), "w6kK");
+    }
+
+    // What a phone does with the scanned chunks, in whatever order
+    fn from_qr(chunks: &[String]) -> String {
+        let mut parts: Vec<(usize, usize, &str)> = chunks
+            .iter()
+            .map(|c| {
+                let (header, payload) = c.split_once(' ').unwrap();
+                let (idx, total) = header.split_once('/').unwrap();
+                (idx.parse().unwrap(), total.parse().unwrap(), payload)
+            })
+            .collect();
+        parts.sort();
+        assert!(parts.iter().enumerate().all(|(i, &(idx, total, _))| idx == i + 1 && total == chunks.len()));
+        parts.iter().map(|&(_, _, payload)| payload).collect()
+    }
+
+    #[test]
+    fn test_qr_chunks_small_document_is_one_chunk() {
+        assert_eq!(qr_chunks("hi", 100), vec!["1/1 aGk="]);
+        assert_eq!(qr_chunks("", 100), vec!["1/1 "]);
+    }
+
+    #[test]
+    fn test_qr_chunks_index_and_size() {
+        let content = "# Notes\n\nSome text, with ünïcode — and more.\n".repeat(20);
+        let chunks = qr_chunks(&content, 64);
+        assert!(chunks.len() > 9);
+        assert!(chunks.iter().all(|c| c.len() <= 64));
+        assert!(chunks[0].starts_with(&format!("1/{} ", chunks.len())));
+        let last = format!("{0}/{0} ", chunks.len());
+        assert!(chunks.last().unwrap().starts_with(&last));
+        // Reversed, the chunks still reassemble
+        let mut reversed = chunks.clone();
+        reversed.reverse();
+        assert_eq!(from_qr(&reversed), base64(content.as_bytes()));
+    }
+
+    #[test]
+    fn test_qr_chunks_header_growth() {
+        // 12 base64 chars: "1/1 " leaves 4 per chunk, so 3 chunks
+        assert_eq!(qr_chunks("abcdefghi", 8), vec!["1/3 YWJj", "2/3 ZGVm", "3/3 Z2hp"]);
+        // Ten chunks need a wider header, which leaves less room per chunk
+        let chunks = qr_chunks(&"x".repeat(60), 9);
+        assert_eq!(chunks.len(), 27);
+        assert_eq!(chunks[26], "27/27 h4");
+        assert!(chunks.iter().all(|c| c.len() <= 9));
+        // Too small for any header still makes progress
+        assert_eq!(qr_chunks("a", 2), vec!["1/4 Y", "2/4 Q", "3/4 =", "4/4 ="]);
+    }
+
+    #[test]
+    fn test_console_records_framing() {
+        let records = console_records("# Title\n\nbody", 64);
+        assert_eq!(records, vec![CONSOLE_BEGIN, "> # Title", "> ", "> body", CONSOLE_END]);
+        assert_eq!(console_records("", 64), vec![CONSOLE_BEGIN, "> ", CONSOLE_END]);
+    }
+
+    #[test]
+    fn test_console_records_chunk_long_lines() {
+        let long = "word ".repeat(100) + "日本語";
+        let content = format!("short\n{}\n\nend\n", long);
+        let records = console_records(&content, 32);
+        for record in &records[1..records.len() - 1] {
+            assert!(record.len() <= 2 + 32, "{:?}", record);
+        }
+        assert!(records.iter().filter(|r| r.starts_with("+ ")).count() > 10);
+        assert_eq!(from_console(&records), content);
+    }
+}
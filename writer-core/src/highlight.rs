@@ -0,0 +1,315 @@
+use alloc::{string::String, vec::Vec};
+use crate::markdown::is_fence;
+
+/// Languages highlighted inside fenced code blocks.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Language {
+    Rust,
+    Python,
+}
+
+impl Language {
+    /// Language named by a fence's info string ("rust" in ```` ```rust ````).
+    pub fn from_fence_info(info: &str) -> Option<Self> {
+        let name = info.split_whitespace().next()?.to_lowercase();
+        match name.as_str() {
+            "rust" | "rs" => Some(Language::Rust),
+            "python" | "py" => Some(Language::Python),
+            _ => None,
+        }
+    }
+
+    fn syntax(&self) -> &'static Syntax {
+        match self {
+            Language::Rust => &RUST,
+            Language::Python => &PYTHON,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TokenKind {
+    Normal,
+    Keyword,
+    String,
+    Comment,
+}
+
+/// A run of code with one highlighting class.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub text: String,
+}
+
+struct Syntax {
+    keywords: &'static [&'static str],
+    line_comment: &'static str,
+    block_comment: Option<(&'static str, &'static str)>,
+    quotes: &'static [char],
+    // 'a' is a char literal but 'a on its own is a lifetime
+    char_literals: bool,
+}
+
+const RUST: Syntax = Syntax {
+    keywords: &[
+        "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum",
+        "extern", "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod",
+        "move", "mut", "pub", "ref", "return", "self", "Self", "static", "struct", "super",
+        "trait", "true", "type", "unsafe", "use", "where", "while",
+    ],
+    line_comment: "//",
+    block_comment: Some(("/*", "*/")),
+    quotes: &['"'],
+    char_literals: true,
+};
+
+const PYTHON: Syntax = Syntax {
+    keywords: &[
+        "False", "None", "True", "and", "as", "assert", "async", "await", "break", "class",
+        "continue", "def", "del", "elif", "else", "except", "finally", "for", "from", "global",
+        "if", "import", "in", "is", "lambda", "nonlocal", "not", "or", "pass", "raise",
+        "return", "try", "while", "with", "yield",
+    ],
+    line_comment: "#",
+    block_comment: None,
+    quotes: &['"', '\''],
+    char_literals: false,
+};
+
+/// Longest fenced block body that gets highlighted. An unterminated fence
+/// would otherwise highlight everything after it.
+pub const MAX_FENCE_LINES: usize = 200;
+
+/// Language to highlight each of `lines[from..to]` in: Some for the body of
+/// a fence opened with a known language, None for everything else (prose,
+/// the fence lines themselves, unknown languages, and body lines more than
+/// MAX_FENCE_LINES past the opening fence). Fence state is tracked from the
+/// top of the document, so a block scrolled half off screen still counts.
+pub fn fence_languages(lines: &[String], from: usize, to: usize) -> Vec<Option<Language>> {
+    let to = to.min(lines.len());
+    let mut result = Vec::with_capacity(to.saturating_sub(from));
+    // (language, line of the opening fence) while inside a fence
+    let mut open: Option<(Option<Language>, usize)> = None;
+    for (i, line) in lines.iter().enumerate().take(to) {
+        let lang = if is_fence(line) {
+            open = match open {
+                Some(_) => None,
+                None => Some((Language::from_fence_info(&line.trim()[3..]), i)),
+            };
+            None
+        } else {
+            match open {
+                Some((lang, start)) if i - start <= MAX_FENCE_LINES => lang,
+                _ => None,
+            }
+        };
+        if i >= from {
+            result.push(lang);
+        }
+    }
+    result
+}
+
+/// Split one line of code into highlighted runs. Lines are tokenized on
+/// their own: a block comment or string left open runs to the end of its
+/// line, not onto the next.
+pub fn tokenize(line: &str, lang: Language) -> Vec<Token> {
+    let syntax = lang.syntax();
+    let chars: Vec<char> = line.chars().collect();
+    let mut tokens: Vec<Token> = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let rest: String = chars[i..].iter().collect();
+        let (kind, len) = if rest.starts_with(syntax.line_comment) {
+            (TokenKind::Comment, chars.len() - i)
+        } else if let Some((open, close)) = syntax.block_comment.filter(|(open, _)| rest.starts_with(open)) {
+            let body = &rest[open.len()..];
+            let len = match body.find(close) {
+                Some(end) => open.len() + end + close.len(),
+                None => rest.len(),
+            };
+            (TokenKind::Comment, rest[..len].chars().count())
+        } else if syntax.quotes.contains(&chars[i]) {
+            (TokenKind::String, string_len(&chars[i..]))
+        } else if let Some(len) = char_literal_len(&chars[i..]).filter(|_| syntax.char_literals) {
+            (TokenKind::String, len)
+        } else if is_ident_char(chars[i]) {
+            let len = chars[i..].iter().take_while(|&&c| is_ident_char(c)).count();
+            let word: String = chars[i..i + len].iter().collect();
+            let kind = if syntax.keywords.contains(&word.as_str()) {
+                TokenKind::Keyword
+            } else {
+                TokenKind::Normal
+            };
+            (kind, len)
+        } else {
+            (TokenKind::Normal, 1)
+        };
+
+        let text: String = chars[i..i + len].iter().collect();
+        match tokens.last_mut() {
+            // Merge neighbouring runs of the same class
+            Some(last) if last.kind == kind && kind != TokenKind::Keyword => last.text.push_str(&text),
+            _ => tokens.push(Token { kind, text }),
+        }
+        i += len;
+    }
+    tokens
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Length of the string literal opening at `chars[0]`, through its closing
+/// quote (or the end of the line). Backslash escapes are skipped.
+fn string_len(chars: &[char]) -> usize {
+    let quote = chars[0];
+    let mut i = 1;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 2,
+            c if c == quote => return i + 1,
+            _ => i += 1,
+        }
+    }
+    chars.len()
+}
+
+/// Length of a char literal like 'a' or '\n' at `chars[0]`, or None if the
+/// quote starts a lifetime.
+fn char_literal_len(chars: &[char]) -> Option<usize> {
+    match chars {
+        ['\'', '\\', _, '\'', ..] => Some(4),
+        ['\'', c, '\'', ..] if *c != '\\' => Some(3),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::{string::ToString, vec};
+
+    fn tokens(expected: &[(TokenKind, &str)]) -> Vec<Token> {
+        expected.iter().map(|&(kind, text)| Token { kind, text: text.to_string() }).collect()
+    }
+
+    fn lines(text: &str) -> Vec<String> {
+        text.lines().map(|l| l.to_string()).collect()
+    }
+
+    #[test]
+    fn test_fence_info_names_language() {
+        assert_eq!(Language::from_fence_info("rust"), Some(Language::Rust));
+        assert_eq!(Language::from_fence_info(" RS ignore"), Some(Language::Rust));
+        assert_eq!(Language::from_fence_info("py"), Some(Language::Python));
+        assert_eq!(Language::from_fence_info("haskell"), None);
+        assert_eq!(Language::from_fence_info(""), None);
+    }
+
+    #[test]
+    fn test_tokenize_rust_snippet() {
+        use TokenKind::*;
+        assert_eq!(
+            tokenize("let s = \"hi \\\"there\\\"\"; // greet", Language::Rust),
+            tokens(&[
+                (Keyword, "let"),
+                (Normal, " s = "),
+                (String, "\"hi \\\"there\\\"\""),
+                (Normal, "; "),
+                (Comment, "// greet"),
+            ])
+        );
+        assert_eq!(
+            tokenize("pub fn new() -> Self {", Language::Rust),
+            tokens(&[
+                (Keyword, "pub"),
+                (Normal, " "),
+                (Keyword, "fn"),
+                (Normal, " new() -> "),
+                (Keyword, "Self"),
+                (Normal, " {"),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_tokenize_rust_identifiers_are_not_keywords() {
+        // Keywords only match whole words
+        assert_eq!(
+            tokenize("let letter = format;", Language::Rust),
+            tokens(&[(TokenKind::Keyword, "let"), (TokenKind::Normal, " letter = format;")])
+        );
+    }
+
+    #[test]
+    fn test_tokenize_rust_chars_and_lifetimes() {
+        use TokenKind::*;
+        assert_eq!(
+            tokenize("fn f<'a>(c: char) { c == 'x' || c == '\\n' }", Language::Rust),
+            tokens(&[
+                (Keyword, "fn"),
+                (Normal, " f<'a>(c: char) { c == "),
+                (String, "'x'"),
+                (Normal, " || c == "),
+                (String, "'\\n'"),
+                (Normal, " }"),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_tokenize_block_comment() {
+        use TokenKind::*;
+        assert_eq!(
+            tokenize("x /* note */ + y", Language::Rust),
+            tokens(&[(Normal, "x "), (Comment, "/* note */"), (Normal, " + y")])
+        );
+        // Left open: runs to the end of this line only
+        assert_eq!(tokenize("x /* open", Language::Rust), tokens(&[(Normal, "x "), (Comment, "/* open")]));
+    }
+
+    #[test]
+    fn test_tokenize_python() {
+        use TokenKind::*;
+        assert_eq!(
+            tokenize("def f(): return 'a#b' # done", Language::Python),
+            tokens(&[
+                (Keyword, "def"),
+                (Normal, " f(): "),
+                (Keyword, "return"),
+                (Normal, " "),
+                (String, "'a#b'"),
+                (Normal, " "),
+                (Comment, "# done"),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_fence_languages_tracks_blocks() {
+        let doc = lines("text\n```rust\nlet x = 1;\n```\n```\nplain code\n```\nmore");
+        let langs = fence_languages(&doc, 0, doc.len());
+        assert_eq!(
+            langs,
+            vec![None, None, Some(Language::Rust), None, None, None, None, None]
+        );
+        // A window starting inside the block still knows its language
+        assert_eq!(fence_languages(&doc, 2, 4), vec![Some(Language::Rust), None]);
+    }
+
+    #[test]
+    fn test_unterminated_fence_is_bounded() {
+        let mut doc = lines("```rust");
+        for _ in 0..MAX_FENCE_LINES + 50 {
+            doc.push(String::from("let x = 1;"));
+        }
+        let langs = fence_languages(&doc, 0, doc.len());
+        assert_eq!(langs[MAX_FENCE_LINES], Some(Language::Rust));
+        assert_eq!(langs[MAX_FENCE_LINES + 1], None);
+        assert!(langs[MAX_FENCE_LINES + 1..].iter().all(|l| l.is_none()));
+    }
+}
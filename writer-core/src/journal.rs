@@ -0,0 +1,358 @@
+use alloc::{string::{String, ToString}, vec::Vec, format};
+use crate::serialize::{date_to_epoch_ms, epoch_ms_to_date, epoch_ms_to_time, prev_day, weekday_name};
+
+const DAY_MS: u64 = 86400 * 1000;
+
+/// Aggregate word statistics for journal entries over a date range.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct JournalStats {
+    pub entries: usize,
+    pub total_words: usize,
+    pub longest_entry_date: Option<String>,
+    pub avg_words: usize,
+}
+
+/// Compute stats for every day in `from..=to` (YYYY-MM-DD), fetching each
+/// day's entry with `load`. Days without an entry, or with only whitespace,
+/// are skipped. An inverted or unparseable range yields empty stats.
+pub fn journal_stats<F>(from: &str, to: &str, mut load: F) -> JournalStats
+where
+    F: FnMut(&str) -> Option<String>,
+{
+    let mut stats = JournalStats::default();
+    let (start, end) = match (date_to_epoch_ms(from), date_to_epoch_ms(to)) {
+        (Some(start), Some(end)) if start <= end => (start, end),
+        _ => return stats,
+    };
+
+    let mut longest = 0;
+    let mut day = start;
+    while day <= end {
+        let date = epoch_ms_to_date(day);
+        if let Some(content) = load(&date) {
+            let words = content.split_whitespace().count();
+            if words > 0 {
+                stats.entries += 1;
+                stats.total_words += words;
+                if words > longest {
+                    longest = words;
+                    stats.longest_entry_date = Some(date);
+                }
+            }
+        }
+        day += DAY_MS;
+    }
+
+    stats.avg_words = stats.total_words.checked_div(stats.entries).unwrap_or(0);
+    stats
+}
+
+/// First day of the month containing `date` (YYYY-MM-DD).
+pub fn month_start(date: &str) -> String {
+    match date.get(..8) {
+        Some(prefix) => format!("{}01", prefix),
+        None => date.to_string(),
+    }
+}
+
+/// Dates listed in the journal index (newline-separated YYYY-MM-DD).
+pub fn parse_index(data: &str) -> Vec<String> {
+    data.lines()
+        .filter(|l| !l.is_empty())
+        .map(|l| l.to_string())
+        .collect()
+}
+
+/// Drop `date` from the journal index (every copy, should a stale duplicate
+/// have crept in). Returns false if it wasn't there.
+pub fn remove_date(dates: &mut Vec<String>, date: &str) -> bool {
+    let before = dates.len();
+    dates.retain(|d| d != date);
+    dates.len() != before
+}
+
+/// The most recent of `dates` (YYYY-MM-DD, which sort as plain strings),
+/// or None if there are none.
+pub fn latest_date(dates: &[String]) -> Option<&str> {
+    dates.iter().max().map(|d| d.as_str())
+}
+
+/// What saving the day being edited should do to its stored entry.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EntrySave {
+    /// Nothing changed since the day was loaded or last saved
+    Skip,
+    /// Store the text as the day's entry
+    Write,
+    /// The text was cleared: remove the day's entry
+    Delete,
+}
+
+/// Decide how to save a journal day. An untouched day (including one still
+/// showing the template) is never written, so just visiting a day doesn't
+/// create an entry. A day edited down to blank deletes its entry, so
+/// clearing one sticks rather than leaving the old text stored.
+pub fn save_decision(modified: bool, content: &str) -> EntrySave {
+    if !modified {
+        EntrySave::Skip
+    } else if content.trim().is_empty() {
+        EntrySave::Delete
+    } else {
+        EntrySave::Write
+    }
+}
+
+/// Consecutive days with an entry in `dates`, counting back from `today`.
+/// A streak that reached yesterday is still current until today ends: a
+/// missing entry for today doesn't reset it, it just isn't counted yet.
+pub fn current_streak(dates: &[String], today: &str) -> usize {
+    let has = |day: &str| dates.iter().any(|d| d == day);
+    let mut day = if has(today) { today.to_string() } else { prev_day(today) };
+    let mut streak = 0;
+    while has(&day) {
+        streak += 1;
+        let before = prev_day(&day);
+        if before == day {
+            break; // prev_day stops at the epoch
+        }
+        day = before;
+    }
+    streak
+}
+
+/// Most results a journal search shows.
+pub const SEARCH_RESULT_LIMIT: usize = 10;
+
+/// Most results from any one day when every matching line is listed, so a
+/// day that mentions the term often doesn't crowd out the others.
+pub const SEARCH_MATCHES_PER_DATE: usize = 3;
+
+/// Lines of `content` containing `query` (case-insensitive) as (line index,
+/// line), at most `max` of them. Line indexes count '\n'-separated lines,
+/// as the editor buffer does.
+pub fn matching_lines(content: &str, query: &str, max: usize) -> Vec<(usize, String)> {
+    let query = query.to_lowercase();
+    if query.is_empty() {
+        return Vec::new();
+    }
+    content.split('\n')
+        .enumerate()
+        .filter(|(_, line)| line.to_lowercase().contains(&query))
+        .take(max)
+        .map(|(i, line)| (i, line.to_string()))
+        .collect()
+}
+
+/// Template offered when the journal template is first switched on.
+pub const DEFAULT_TEMPLATE: &str = "# {weekday} {date}\n\nGrateful for:\n\nToday:\n";
+
+/// A journal template filled in for `date` (YYYY-MM-DD): `{date}` becomes
+/// the date and `{weekday}` the day's name. Other text is kept as written.
+pub fn render_template(template: &str, date: &str) -> String {
+    template
+        .replace("{date}", date)
+        .replace("{weekday}", weekday_name(date).unwrap_or(""))
+}
+
+/// A timestamped line appended to the day's journal entry without opening it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct QuickNote {
+    /// Entry the note belongs to (YYYY-MM-DD)
+    pub date: String,
+    /// "- HH:MM note"
+    pub line: String,
+}
+
+impl QuickNote {
+    /// Note written at `now_ms` (local time), filed under that day.
+    pub fn new(now_ms: u64, note: &str) -> Self {
+        Self {
+            date: epoch_ms_to_date(now_ms),
+            line: format!("- {} {}", epoch_ms_to_time(now_ms), note.trim()),
+        }
+    }
+
+    /// The entry text with this note appended on its own line.
+    pub fn append_to(&self, entry: &str) -> String {
+        let entry = entry.trim_end_matches('\n');
+        if entry.is_empty() {
+            self.line.clone()
+        } else {
+            format!("{}\n{}", entry, self.line)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    fn entry(date: &str) -> Option<String> {
+        match date {
+            "2025-03-01" => Some("one two three".to_string()),
+            "2025-03-02" => Some("   \n".to_string()), // empty day
+            "2025-03-04" => Some("a b c d e f g".to_string()),
+            "2025-03-05" => Some("x".to_string()),
+            _ => None,
+        }
+    }
+
+    fn dates(list: &[&str]) -> Vec<String> {
+        list.iter().map(|d| d.to_string()).collect()
+    }
+
+    #[test]
+    fn test_streak_counts_consecutive_days() {
+        let index = dates(&["2025-02-27", "2025-02-28", "2025-03-01", "2025-03-02"]);
+        assert_eq!(current_streak(&index, "2025-03-02"), 4);
+        // The index order doesn't matter
+        let index = dates(&["2025-03-02", "2025-02-28", "2025-03-01"]);
+        assert_eq!(current_streak(&index, "2025-03-02"), 3);
+    }
+
+    #[test]
+    fn test_streak_broken_by_gap() {
+        let index = dates(&["2025-03-01", "2025-03-03", "2025-03-04"]);
+        assert_eq!(current_streak(&index, "2025-03-04"), 2);
+        // Nothing yesterday or today: no streak
+        assert_eq!(current_streak(&index, "2025-03-06"), 0);
+        assert_eq!(current_streak(&[], "2025-03-06"), 0);
+    }
+
+    #[test]
+    fn test_streak_without_today_counts_to_yesterday() {
+        let index = dates(&["2025-03-02", "2025-03-03"]);
+        assert_eq!(current_streak(&index, "2025-03-04"), 2);
+        let mut index = index;
+        index.push("2025-03-04".into());
+        assert_eq!(current_streak(&index, "2025-03-04"), 3);
+    }
+
+    #[test]
+    fn test_matching_lines_collects_every_match() {
+        let entry = "Walked to the harbour\nlunch with Sam\nthe HARBOUR again\n\nharbour at dusk";
+        assert_eq!(
+            matching_lines(entry, "harbour", 10),
+            vec![
+                (0, "Walked to the harbour".to_string()),
+                (2, "the HARBOUR again".to_string()),
+                (4, "harbour at dusk".to_string()),
+            ]
+        );
+        // Capped per date; one per date keeps the first
+        assert_eq!(matching_lines(entry, "Harbour", 2).len(), 2);
+        assert_eq!(matching_lines(entry, "harbour", 1), vec![(0, "Walked to the harbour".to_string())]);
+        assert!(matching_lines(entry, "", 10).is_empty());
+        assert!(matching_lines(entry, "beach", 10).is_empty());
+    }
+
+    #[test]
+    fn test_stats_over_range() {
+        let stats = journal_stats("2025-03-01", "2025-03-05", entry);
+        assert_eq!(stats.entries, 3);
+        assert_eq!(stats.total_words, 11);
+        assert_eq!(stats.longest_entry_date.as_deref(), Some("2025-03-04"));
+        assert_eq!(stats.avg_words, 3);
+    }
+
+    #[test]
+    fn test_stats_single_day() {
+        let stats = journal_stats("2025-03-04", "2025-03-04", entry);
+        assert_eq!(stats.entries, 1);
+        assert_eq!(stats.total_words, 7);
+        assert_eq!(stats.avg_words, 7);
+        assert_eq!(stats.longest_entry_date.as_deref(), Some("2025-03-04"));
+    }
+
+    #[test]
+    fn test_stats_empty_range() {
+        assert_eq!(journal_stats("2025-03-02", "2025-03-03", entry), JournalStats::default());
+        // Inverted and invalid ranges
+        assert_eq!(journal_stats("2025-03-05", "2025-03-01", entry), JournalStats::default());
+        assert_eq!(journal_stats("bogus", "2025-03-01", entry), JournalStats::default());
+    }
+
+    #[test]
+    fn test_stats_crosses_month_boundary() {
+        let mut seen = Vec::new();
+        journal_stats("2024-02-28", "2024-03-01", |d| {
+            seen.push(d.to_string());
+            None
+        });
+        assert_eq!(seen, vec!["2024-02-28", "2024-02-29", "2024-03-01"]);
+    }
+
+    #[test]
+    fn test_month_start() {
+        assert_eq!(month_start("2025-03-17"), "2025-03-01");
+    }
+
+    #[test]
+    fn test_quick_note_targets_today() {
+        let now = date_to_epoch_ms("2025-03-04").unwrap() + (9 * 3600 + 5 * 60) * 1000;
+        let note = QuickNote::new(now, "  call the bank ");
+        assert_eq!(note.date, "2025-03-04");
+        assert_eq!(note.line, "- 09:05 call the bank");
+    }
+
+    #[test]
+    fn test_quick_note_append() {
+        let note = QuickNote::new(date_to_epoch_ms("2025-03-04").unwrap(), "idea");
+        assert_eq!(note.append_to("Morning pages"), "Morning pages\n- 00:00 idea");
+        // No blank line after a trailing newline, none before the first line
+        assert_eq!(note.append_to("Morning pages\n"), "Morning pages\n- 00:00 idea");
+        assert_eq!(note.append_to(""), "- 00:00 idea");
+    }
+
+    #[test]
+    fn test_render_template() {
+        assert_eq!(
+            render_template("# {weekday} {date}\n\nToday:", "2026-01-21"),
+            "# Wednesday 2026-01-21\n\nToday:"
+        );
+        // Repeated and unknown placeholders
+        assert_eq!(render_template("{date}/{date} {mood}", "2026-01-21"), "2026-01-21/2026-01-21 {mood}");
+        assert_eq!(render_template("", "2026-01-21"), "");
+    }
+
+    #[test]
+    fn test_latest_date_across_boundaries() {
+        let dates = parse_index("2025-12-31\n2026-01-02\n2025-09-30\n2026-01-10\n2025-10-01");
+        assert_eq!(latest_date(&dates), Some("2026-01-10"));
+        // Month and year rollovers order correctly, wherever they sit in the index
+        assert_eq!(latest_date(&parse_index("2026-02-01\n2026-01-31")), Some("2026-02-01"));
+        assert_eq!(latest_date(&parse_index("2025-12-31\n2024-12-31")), Some("2025-12-31"));
+        assert_eq!(latest_date(&[]), None);
+    }
+
+    #[test]
+    fn test_removed_date_leaves_index() {
+        let mut dates = parse_index("2026-01-20\n2026-01-21\n2026-01-22\n");
+        assert!(remove_date(&mut dates, "2026-01-21"));
+        // What gets written back no longer lists the date
+        let stored = dates.join("\n");
+        assert_eq!(parse_index(&stored), vec!["2026-01-20", "2026-01-22"]);
+    }
+
+    #[test]
+    fn test_remove_unsaved_date_is_noop() {
+        let mut dates = parse_index("2026-01-20");
+        assert!(!remove_date(&mut dates, "2026-02-01"));
+        assert_eq!(dates, vec!["2026-01-20"]);
+        assert!(parse_index("").is_empty());
+    }
+
+    #[test]
+    fn test_save_decision() {
+        // Visiting a day without typing never creates or rewrites an entry
+        assert_eq!(save_decision(false, ""), EntrySave::Skip);
+        assert_eq!(save_decision(false, "old entry"), EntrySave::Skip);
+        assert_eq!(save_decision(false, &render_template(DEFAULT_TEMPLATE, "2026-01-21")), EntrySave::Skip);
+        // Edits are written
+        assert_eq!(save_decision(true, "new text"), EntrySave::Write);
+        // Clearing an entry removes it, whitespace-only counts as cleared
+        assert_eq!(save_decision(true, ""), EntrySave::Delete);
+        assert_eq!(save_decision(true, "  \n\n"), EntrySave::Delete);
+    }
+}
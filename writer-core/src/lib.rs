@@ -1,7 +1,19 @@
+//! Pure logic for the Writer app. Builds without std (only `alloc`) when
+//! the default `std` feature is turned off, for use in other Xous services.
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+pub mod backup;
 pub mod buffer;
+pub mod docs;
+pub mod export;
+pub mod highlight;
+pub mod journal;
 pub mod markdown;
 pub mod serialize;
+pub mod stats;
 
-pub use buffer::{Cursor, TextBuffer};
+pub use buffer::{BufferSnapshot, BufferStats, Cursor, TextBuffer};
 pub use markdown::LineKind;
 pub use serialize::{WriterConfig, serialize_document, deserialize_document, serialize_config, deserialize_config};
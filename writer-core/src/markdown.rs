@@ -1,3 +1,5 @@
+use alloc::{string::{String, ToString}, vec::Vec, format};
+
 #[derive(Clone, Copy, PartialEq, Debug)]
 pub enum LineKind {
     Normal,
@@ -7,6 +9,7 @@ pub enum LineKind {
     CodeBlock,
     BlockQuote,
     UnorderedList,
+    TaskList { checked: bool },
     OrderedList,
     HorizontalRule,
     Empty,
@@ -26,7 +29,7 @@ impl LineKind {
         }
 
         // Code block fence: ```
-        if trimmed.starts_with("```") {
+        if is_fence(trimmed) {
             return LineKind::CodeBlock;
         }
 
@@ -51,6 +54,11 @@ impl LineKind {
             return LineKind::BlockQuote;
         }
 
+        // Task list item: "- [ ] " / "- [x] "
+        if let Some(checked) = task_checkbox(trimmed) {
+            return LineKind::TaskList { checked };
+        }
+
         // Unordered list
         if trimmed.starts_with("- ") || trimmed.starts_with("* ") {
             return LineKind::UnorderedList;
@@ -89,6 +97,11 @@ impl LineKind {
                     trimmed
                 }
             }
+            LineKind::TaskList { .. } => {
+                // Bullet (2) + checkbox (3), then the separating space if any
+                let rest = trimmed.get(5..).unwrap_or("");
+                rest.strip_prefix(' ').unwrap_or(rest)
+            }
             LineKind::OrderedList => {
                 // Strip "N. " prefix
                 if let Some(dot_pos) = trimmed.find(". ") {
@@ -103,12 +116,12 @@ impl LineKind {
                 }
             }
             LineKind::CodeBlock => {
-                if trimmed.starts_with("```") {
+                if is_fence(trimmed) {
                     ""
-                } else if line.starts_with("    ") {
-                    &line[4..]
-                } else if line.starts_with('\t') {
-                    &line[1..]
+                } else if let Some(rest) = line.strip_prefix("    ") {
+                    rest
+                } else if let Some(rest) = line.strip_prefix('\t') {
+                    rest
                 } else {
                     line
                 }
@@ -120,6 +133,509 @@ impl LineKind {
     }
 }
 
+/// Extra vertical space (pixels) around block elements: headings, rules
+/// and code blocks, plus optionally above each new paragraph. Padding
+/// between two adjacent lines collapses to the larger of the two, and a
+/// run of code lines is padded only at its ends.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BlockPadding {
+    pub heading_before: usize,
+    pub heading_after: usize,
+    pub rule: usize,
+    pub code: usize,
+    /// Above the first line after a blank line (0 = off)
+    pub paragraph: usize,
+}
+
+impl Default for BlockPadding {
+    fn default() -> Self {
+        Self {
+            heading_before: 6,
+            heading_after: 2,
+            rule: 4,
+            code: 4,
+            paragraph: 0,
+        }
+    }
+}
+
+impl BlockPadding {
+    /// No padding at all (uniform line advance).
+    pub fn none() -> Self {
+        Self { heading_before: 0, heading_after: 0, rule: 0, code: 0, paragraph: 0 }
+    }
+
+    fn around(&self, kind: LineKind) -> (usize, usize) {
+        match kind {
+            LineKind::Heading1 | LineKind::Heading2 | LineKind::Heading3 => {
+                (self.heading_before, self.heading_after)
+            }
+            LineKind::HorizontalRule => (self.rule, self.rule),
+            LineKind::CodeBlock => (self.code, self.code),
+            _ => (0, 0),
+        }
+    }
+
+    /// Space to leave above `cur` when it follows `prev` (None at the top).
+    pub fn gap(&self, prev: Option<LineKind>, cur: LineKind) -> usize {
+        match prev {
+            None => 0,
+            Some(LineKind::CodeBlock) if cur == LineKind::CodeBlock => 0,
+            Some(prev) => {
+                let block = self.around(prev).1.max(self.around(cur).0);
+                if is_paragraph_start(Some(prev), cur) {
+                    block.max(self.paragraph)
+                } else {
+                    block
+                }
+            }
+        }
+    }
+
+    /// Top y of each line in `kinds`, stacking down from `top`. Stops before
+    /// the first line that would extend past `bottom`, so the result may be
+    /// shorter than `kinds`.
+    pub fn layout<F>(&self, kinds: &[LineKind], height: F, top: usize, bottom: usize) -> Vec<usize>
+    where
+        F: Fn(LineKind) -> usize,
+    {
+        let mut tops = Vec::with_capacity(kinds.len());
+        let mut y = top;
+        let mut prev = None;
+        for &kind in kinds {
+            let line_top = y + self.gap(prev, kind);
+            let line_bottom = line_top + height(kind);
+            if line_bottom > bottom {
+                break;
+            }
+            tops.push(line_top);
+            y = line_bottom;
+            prev = Some(kind);
+        }
+        tops
+    }
+
+    /// How many of `kinds`, from the first, fit in `available` pixels as
+    /// `layout` stacks them: the viewport's line capacity when those are
+    /// the lines it starts with. Never less than one, so the cursor's line
+    /// always has room.
+    pub fn fitting_lines<F>(&self, kinds: &[LineKind], height: F, available: usize) -> usize
+    where
+        F: Fn(LineKind) -> usize,
+    {
+        self.layout(kinds, height, 0, available).len().max(1)
+    }
+
+    /// How many of the last lines of `kinds` fit in `available` pixels:
+    /// the capacity of a view scrolled to the end of the document. Never
+    /// less than one.
+    pub fn fitting_lines_from_end<F>(&self, kinds: &[LineKind], height: F, available: usize) -> usize
+    where
+        F: Fn(LineKind) -> usize,
+    {
+        let mut fit = 1;
+        while fit < kinds.len() {
+            let tail = &kinds[kinds.len() - fit - 1..];
+            if self.layout(tail, &height, 0, available).len() < tail.len() {
+                break;
+            }
+            fit += 1;
+        }
+        fit
+    }
+}
+
+/// Whether `cur` starts a new paragraph: the first non-blank line after a
+/// blank one. The top of the document isn't counted, as there's nothing
+/// to separate it from.
+pub fn is_paragraph_start(prev: Option<LineKind>, cur: LineKind) -> bool {
+    prev == Some(LineKind::Empty) && cur != LineKind::Empty
+}
+
+/// List marker at the start of `line`: its length in bytes (indent
+/// included) and the marker that continues the list on the next line.
+/// Task items continue unchecked; ordered items count up by one.
+pub fn list_marker(line: &str) -> Option<(usize, String)> {
+    if LineKind::classify(line) == LineKind::HorizontalRule {
+        return None;
+    }
+    let rest = line.trim_start();
+    let indent = &line[..line.len() - rest.len()];
+
+    if let Some(after) = rest.strip_prefix("- ").or_else(|| rest.strip_prefix("* ")) {
+        let bullet = &rest[..2];
+        if task_checkbox(rest).is_some() {
+            let len = indent.len() + 5 + usize::from(after[3..].starts_with(' '));
+            return Some((len, format!("{}{}[ ] ", indent, bullet)));
+        }
+        return Some((indent.len() + 2, format!("{}{}", indent, bullet)));
+    }
+
+    if is_ordered_list(rest) {
+        let digits = rest.find(". ").unwrap_or(0);
+        let n: u64 = rest[..digits].parse().ok()?;
+        return Some((indent.len() + digits + 2, format!("{}{}. ", indent, n + 1)));
+    }
+    None
+}
+
+/// Kind of every line in `lines`, tracking fenced code blocks across
+/// lines: the ``` lines and everything between them are CodeBlock whatever
+/// they look like on their own, so a `# comment` or `- item` in a snippet
+/// stays code. A fence left open runs to the end of the document.
+pub fn classify_lines(lines: &[String]) -> Vec<LineKind> {
+    let mut in_fence = false;
+    lines
+        .iter()
+        .map(|line| {
+            if is_fence(line) {
+                in_fence = !in_fence;
+                LineKind::CodeBlock
+            } else if in_fence {
+                LineKind::CodeBlock
+            } else {
+                LineKind::classify(line)
+            }
+        })
+        .collect()
+}
+
+/// Whether `line` opens or closes a fenced code block.
+pub fn is_fence(line: &str) -> bool {
+    line.trim_start().starts_with("```")
+}
+
+/// Every heading in `text` as (line index, level 1-3, title), in document
+/// order. Lines inside fenced code blocks are skipped, so a `# comment`
+/// in a shell snippet isn't mistaken for a heading.
+pub fn extract_headings(text: &str) -> Vec<(usize, usize, String)> {
+    let lines: Vec<String> = text.split('\n').map(String::from).collect();
+    let mut headings = Vec::new();
+    for (i, (line, kind)) in lines.iter().zip(classify_lines(&lines)).enumerate() {
+        let level = match kind {
+            LineKind::Heading1 => 1,
+            LineKind::Heading2 => 2,
+            LineKind::Heading3 => 3,
+            _ => continue,
+        };
+        headings.push((i, level, LineKind::strip_prefix(line, kind).trim().to_string()));
+    }
+    headings
+}
+
+// Block being built by to_html
+#[derive(Clone, Copy, PartialEq)]
+enum HtmlBlock {
+    None,
+    Paragraph,
+    List(&'static str),
+    Quote,
+    IndentedCode,
+}
+
+/// Render a markdown document as an HTML fragment: `#`-`######`
+/// headings, paragraphs, `<ul>`/`<ol>` lists (consecutive items share one
+/// list), block quotes, fenced and indented code in `<pre><code>`, `<hr>`,
+/// and inline emphasis. `<`, `>` and `&` are escaped everywhere, code
+/// included.
+pub fn to_html(markdown: &str) -> String {
+    let mut out: Vec<String> = Vec::new();
+    let mut block = HtmlBlock::None;
+    // Text of the fenced code block being read, if inside one
+    let mut fence: Option<String> = None;
+    let lines: Vec<String> = markdown.lines().map(String::from).collect();
+    let kinds = classify_lines(&lines);
+
+    for (line, &kind) in lines.iter().zip(&kinds) {
+        if is_fence(line) {
+            match fence.take() {
+                Some(code) => out.push(format!("<pre><code>{}</code></pre>", code)),
+                None => {
+                    close_html_block(&mut out, &mut block);
+                    fence = Some(String::new());
+                }
+            }
+            continue;
+        }
+        if let Some(code) = fence.as_mut() {
+            code.push_str(&escape_html(line));
+            code.push('\n');
+            continue;
+        }
+
+        let (wanted, html) = if let Some((level, text)) = heading_level(line) {
+            (HtmlBlock::None, format!("<h{0}>{1}</h{0}>", level, inline_html(text)))
+        } else {
+            match kind {
+                LineKind::Empty => {
+                    close_html_block(&mut out, &mut block);
+                    continue;
+                }
+                LineKind::HorizontalRule => (HtmlBlock::None, String::from("<hr>")),
+                LineKind::CodeBlock => {
+                    (HtmlBlock::IndentedCode, escape_html(LineKind::strip_prefix(line, kind)))
+                }
+                LineKind::BlockQuote => {
+                    (HtmlBlock::Quote, inline_html(LineKind::strip_prefix(line, kind)))
+                }
+                LineKind::UnorderedList => {
+                    (HtmlBlock::List("ul"), format!("<li>{}</li>", inline_html(LineKind::strip_prefix(line, kind))))
+                }
+                LineKind::TaskList { checked } => {
+                    let text = inline_html(LineKind::strip_prefix(line, kind));
+                    let checkbox = if checked { "<input type=\"checkbox\" checked disabled>" } else { "<input type=\"checkbox\" disabled>" };
+                    (HtmlBlock::List("ul"), format!("<li>{} {}</li>", checkbox, text))
+                }
+                LineKind::OrderedList => {
+                    (HtmlBlock::List("ol"), format!("<li>{}</li>", inline_html(LineKind::strip_prefix(line, kind))))
+                }
+                _ => (HtmlBlock::Paragraph, inline_html(line.trim())),
+            }
+        };
+
+        if wanted != block {
+            close_html_block(&mut out, &mut block);
+            match wanted {
+                HtmlBlock::Paragraph => out.push(format!("<p>{}", html)),
+                HtmlBlock::Quote => out.push(format!("<blockquote><p>{}", html)),
+                HtmlBlock::IndentedCode => out.push(format!("<pre><code>{}", html)),
+                HtmlBlock::List(tag) => {
+                    out.push(format!("<{}>", tag));
+                    out.push(html);
+                }
+                HtmlBlock::None => out.push(html),
+            }
+            block = wanted;
+        } else if let (HtmlBlock::Paragraph | HtmlBlock::Quote | HtmlBlock::IndentedCode, Some(last)) = (block, out.last_mut()) {
+            // Continuation line of the same paragraph, quote or code block
+            last.push('\n');
+            last.push_str(&html);
+        } else {
+            out.push(html);
+        }
+    }
+
+    close_html_block(&mut out, &mut block);
+    if let Some(code) = fence {
+        // Unclosed fence runs to the end of the document
+        out.push(format!("<pre><code>{}</code></pre>", code));
+    }
+    let mut html = out.join("\n");
+    html.push('\n');
+    html
+}
+
+fn close_html_block(out: &mut Vec<String>, block: &mut HtmlBlock) {
+    let close = match *block {
+        HtmlBlock::None => return,
+        HtmlBlock::Paragraph => "</p>",
+        HtmlBlock::Quote => "</p></blockquote>",
+        HtmlBlock::IndentedCode => "</code></pre>",
+        HtmlBlock::List(tag) => {
+            out.push(format!("</{}>", tag));
+            *block = HtmlBlock::None;
+            return;
+        }
+    };
+    if let Some(last) = out.last_mut() {
+        last.push_str(close);
+    }
+    *block = HtmlBlock::None;
+}
+
+// "#".."######" followed by a space: (level, heading text)
+fn heading_level(line: &str) -> Option<(usize, &str)> {
+    let trimmed = line.trim();
+    let level = trimmed.chars().take_while(|&c| c == '#').count();
+    if !(1..=6).contains(&level) {
+        return None;
+    }
+    trimmed[level..].strip_prefix(' ').map(|text| (level, text.trim()))
+}
+
+fn inline_html(text: &str) -> String {
+    let mut html = String::new();
+    for span in parse_inline(text) {
+        let text = escape_html(&span.text);
+        if span.code {
+            html.push_str(&format!("<code>{}</code>", text));
+            continue;
+        }
+        let tags: Vec<&str> = [(span.bold, "strong"), (span.italic, "em"), (span.strikethrough, "del")]
+            .iter()
+            .filter(|(on, _)| *on)
+            .map(|&(_, tag)| tag)
+            .collect();
+        for tag in &tags {
+            html.push_str(&format!("<{}>", tag));
+        }
+        html.push_str(&text);
+        for tag in tags.iter().rev() {
+            html.push_str(&format!("</{}>", tag));
+        }
+    }
+    html
+}
+
+/// Escape `&`, `<` and `>` for HTML text.
+pub fn escape_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            _ => out.push(ch),
+        }
+    }
+    out
+}
+
+/// A run of text with the same inline emphasis.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct InlineSpan {
+    pub text: String,
+    pub bold: bool,
+    pub italic: bool,
+    pub code: bool,
+    pub strikethrough: bool,
+}
+
+// Inline tokens before emphasis markers are paired up
+enum Inline {
+    Text(String),
+    Code(String),
+    Marker { delim: &'static str, can_open: bool, can_close: bool, matched: bool },
+}
+
+/// Split a line's text into emphasis spans: `**bold**`, `*italic*` or
+/// `_italic_`, `~~strikethrough~~`, and `` `code` ``. Markers without a
+/// partner stay as literal text, `\*`, `\_`, `\~`, `` \` `` and `\\` give
+/// the literal character, and code spans are taken verbatim. An underscore
+/// inside a word (snake_case) is never a marker.
+pub fn parse_inline(line: &str) -> Vec<InlineSpan> {
+    let mut tokens = tokenize_inline(line);
+    pair_markers(&mut tokens);
+
+    let mut spans: Vec<InlineSpan> = Vec::new();
+    let (mut bold, mut italic, mut strike) = (false, false, false);
+    for token in tokens {
+        let (text, code) = match token {
+            Inline::Text(text) => (text, false),
+            Inline::Code(text) => (text, true),
+            Inline::Marker { delim, matched: true, .. } => {
+                match delim {
+                    "**" => bold = !bold,
+                    "~~" => strike = !strike,
+                    _ => italic = !italic,
+                }
+                continue;
+            }
+            Inline::Marker { delim, .. } => (delim.to_string(), false),
+        };
+        match spans.last_mut() {
+            Some(last) if (last.bold, last.italic, last.code, last.strikethrough) == (bold, italic, code, strike) => {
+                last.text.push_str(&text);
+            }
+            _ => spans.push(InlineSpan { text, bold, italic, code, strikethrough: strike }),
+        }
+    }
+    spans.retain(|s| !s.text.is_empty());
+    spans
+}
+
+fn tokenize_inline(line: &str) -> Vec<Inline> {
+    let chars: Vec<char> = line.chars().collect();
+    let mut tokens = Vec::new();
+    let mut text = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let ch = chars[i];
+        match ch {
+            '\\' if matches!(chars.get(i + 1), Some('*' | '_' | '`' | '~' | '\\')) => {
+                text.push(chars[i + 1]);
+                i += 2;
+            }
+            '`' => match chars[i + 1..].iter().position(|&c| c == '`') {
+                Some(len) => {
+                    tokens.push(Inline::Text(core::mem::take(&mut text)));
+                    tokens.push(Inline::Code(chars[i + 1..i + 1 + len].iter().collect()));
+                    i += len + 2;
+                }
+                None => {
+                    text.push(ch);
+                    i += 1;
+                }
+            },
+            '*' | '_' | '~' if ch != '~' || chars.get(i + 1) == Some(&'~') => {
+                let double = ch != '_' && chars.get(i + 1) == Some(&ch);
+                let len = if double { 2 } else { 1 };
+                let before = i.checked_sub(1).map(|j| chars[j]);
+                let after = chars.get(i + len).copied();
+                let mut can_open = after.is_some_and(|c| !c.is_whitespace());
+                let mut can_close = before.is_some_and(|c| !c.is_whitespace());
+                if ch == '_' && before.is_some_and(char::is_alphanumeric) && after.is_some_and(char::is_alphanumeric) {
+                    can_open = false;
+                    can_close = false;
+                }
+                tokens.push(Inline::Text(core::mem::take(&mut text)));
+                let delim = match (ch, double) {
+                    ('*', true) => "**",
+                    ('*', false) => "*",
+                    ('~', _) => "~~",
+                    _ => "_",
+                };
+                tokens.push(Inline::Marker { delim, can_open, can_close, matched: false });
+                i += len;
+            }
+            _ => {
+                text.push(ch);
+                i += 1;
+            }
+        }
+    }
+    tokens.push(Inline::Text(text));
+    tokens
+}
+
+// Pair each closing marker with the nearest open marker of the same kind
+fn pair_markers(tokens: &mut [Inline]) {
+    let mut open: Vec<usize> = Vec::new();
+    for i in 0..tokens.len() {
+        let Inline::Marker { delim, can_open, can_close, .. } = tokens[i] else {
+            continue;
+        };
+        let opener = open.iter().rposition(|&j| matches!(tokens[j], Inline::Marker { delim: d, .. } if d == delim));
+        match opener {
+            Some(pos) if can_close => {
+                let j = open.remove(pos);
+                for at in [j, i] {
+                    if let Inline::Marker { matched, .. } = &mut tokens[at] {
+                        *matched = true;
+                    }
+                }
+            }
+            _ if can_open => open.push(i),
+            _ => {}
+        }
+    }
+}
+
+/// Checkbox state of a task list item ("- [ ] text" or "- [x] text",
+/// `*` bullets and `X` also accepted), or None if `trimmed` isn't one.
+fn task_checkbox(trimmed: &str) -> Option<bool> {
+    let rest = trimmed.strip_prefix("- ").or_else(|| trimmed.strip_prefix("* "))?;
+    let checked = match rest.get(..3)? {
+        "[ ]" => false,
+        "[x]" | "[X]" => true,
+        _ => return None,
+    };
+    match rest[3..].chars().next() {
+        None | Some(' ') => Some(checked),
+        _ => None,
+    }
+}
+
 fn is_horizontal_rule(s: &str) -> bool {
     let chars: Vec<char> = s.chars().filter(|c| !c.is_whitespace()).collect();
     if chars.len() < 3 {
@@ -144,6 +660,7 @@ fn is_ordered_list(s: &str) -> bool {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use alloc::vec;
 
     #[test]
     fn test_classify_empty() {
@@ -231,4 +748,308 @@ mod tests {
         assert_eq!(LineKind::classify("#nospace"), LineKind::Normal);
         assert_eq!(LineKind::classify("##nospace"), LineKind::Normal);
     }
+
+    #[test]
+    fn test_block_padding_gaps() {
+        let pad = BlockPadding::default();
+        assert_eq!(pad.gap(None, LineKind::Heading1), 0);
+        assert_eq!(pad.gap(Some(LineKind::Normal), LineKind::Normal), 0);
+        // Rule then heading: collapses to the larger padding
+        assert_eq!(pad.gap(Some(LineKind::HorizontalRule), LineKind::Heading2), 6);
+        assert_eq!(pad.gap(Some(LineKind::Heading1), LineKind::Normal), 2);
+        // Code lines stay together
+        assert_eq!(pad.gap(Some(LineKind::CodeBlock), LineKind::CodeBlock), 0);
+        assert_eq!(pad.gap(Some(LineKind::Normal), LineKind::CodeBlock), 4);
+    }
+
+    #[test]
+    fn test_paragraph_starts() {
+        let lines = ["First para", "continues", "", "", "Second", "", "# Heading", "text"];
+        let mut prev = None;
+        let mut starts = Vec::new();
+        for (i, line) in lines.iter().enumerate() {
+            let kind = LineKind::classify(line);
+            if is_paragraph_start(prev, kind) {
+                starts.push(i);
+            }
+            prev = Some(kind);
+        }
+        assert_eq!(starts, vec![4, 6]);
+    }
+
+    #[test]
+    fn test_paragraph_padding() {
+        let pad = BlockPadding { paragraph: 5, ..BlockPadding::default() };
+        assert_eq!(pad.gap(Some(LineKind::Empty), LineKind::Normal), 5);
+        assert_eq!(pad.gap(Some(LineKind::Normal), LineKind::Normal), 0);
+        assert_eq!(pad.gap(Some(LineKind::Empty), LineKind::Empty), 0);
+        // A heading after a blank line keeps its larger padding
+        assert_eq!(pad.gap(Some(LineKind::Empty), LineKind::Heading1), 6);
+        // Off by default
+        assert_eq!(BlockPadding::default().gap(Some(LineKind::Empty), LineKind::Normal), 0);
+    }
+
+    #[test]
+    fn test_block_padding_layout() {
+        let kinds = [
+            LineKind::Normal,
+            LineKind::HorizontalRule,
+            LineKind::Heading1,
+            LineKind::Normal,
+            LineKind::CodeBlock,
+            LineKind::CodeBlock,
+            LineKind::Normal,
+        ];
+        let height = |k: LineKind| if k == LineKind::Heading1 { 28 } else { 18 };
+        let tops = BlockPadding::default().layout(&kinds, height, 0, 1000);
+        assert_eq!(tops, vec![0, 22, 46, 76, 98, 116, 138]);
+
+        // Without padding the advance is uniform by line height
+        let tops = BlockPadding::none().layout(&kinds, height, 0, 1000);
+        assert_eq!(tops, vec![0, 18, 36, 64, 82, 100, 118]);
+
+        // Lines that would cross the bottom are dropped
+        let tops = BlockPadding::default().layout(&kinds, height, 0, 94);
+        assert_eq!(tops, vec![0, 22, 46, 76]);
+    }
+
+    #[test]
+    fn test_fitting_lines() {
+        let height = |k: LineKind| if k == LineKind::Heading1 { 28 } else { 18 };
+        let plain = [LineKind::Normal; 40];
+        assert_eq!(BlockPadding::default().fitting_lines(&plain, height, 504), 28);
+        assert_eq!(BlockPadding::default().fitting_lines(&plain, height, 503), 27);
+        // Fewer lines than room: all of them
+        assert_eq!(BlockPadding::default().fitting_lines(&plain[..5], height, 504), 5);
+
+        // Headings are taller and padded, so fewer lines fit
+        let mut mixed = [LineKind::Normal; 40];
+        for i in (0..40).step_by(4) {
+            mixed[i] = LineKind::Heading1;
+        }
+        let fit = BlockPadding::default().fitting_lines(&mixed, height, 504);
+        assert_eq!(fit, 22);
+        assert!(fit < 28);
+        assert_eq!(BlockPadding::none().fitting_lines(&mixed, height, 504), 24);
+
+        // Not even one line fits: still one
+        assert_eq!(BlockPadding::default().fitting_lines(&[LineKind::Heading1], height, 10), 1);
+        assert_eq!(BlockPadding::default().fitting_lines(&[], height, 504), 1);
+    }
+
+    #[test]
+    fn test_fitting_lines_from_end() {
+        let height = |k: LineKind| if k == LineKind::Heading1 { 28 } else { 18 };
+        let plain = [LineKind::Normal; 40];
+        assert_eq!(BlockPadding::default().fitting_lines_from_end(&plain, height, 504), 28);
+
+        // A heading near the end takes room from the last page
+        let mut kinds = [LineKind::Normal; 40];
+        kinds[35] = LineKind::Heading1;
+        let fit = BlockPadding::default().fitting_lines_from_end(&kinds, height, 504);
+        assert_eq!(fit, 27);
+        let tail = &kinds[kinds.len() - fit..];
+        assert_eq!(BlockPadding::default().layout(tail, height, 0, 504).len(), fit);
+
+        assert_eq!(BlockPadding::default().fitting_lines_from_end(&plain[..3], height, 504), 3);
+        assert_eq!(BlockPadding::default().fitting_lines_from_end(&[], height, 504), 1);
+    }
+
+    #[test]
+    fn test_classify_task_list() {
+        assert_eq!(LineKind::classify("- [ ] buy milk"), LineKind::TaskList { checked: false });
+        assert_eq!(LineKind::classify("- [x] done"), LineKind::TaskList { checked: true });
+        assert_eq!(LineKind::classify("  * [X] shouted"), LineKind::TaskList { checked: true });
+        assert_eq!(LineKind::classify("- [ ]"), LineKind::TaskList { checked: false });
+        // Not checkboxes
+        assert_eq!(LineKind::classify("- [y] nope"), LineKind::UnorderedList);
+        assert_eq!(LineKind::classify("- [ ]nospace"), LineKind::UnorderedList);
+        assert_eq!(LineKind::classify("[ ] bare"), LineKind::Normal);
+    }
+
+    #[test]
+    fn test_strip_prefix_task_list() {
+        let line = "- [x] call mum";
+        assert_eq!(LineKind::strip_prefix(line, LineKind::classify(line)), "call mum");
+        let line = "  - [ ] indented";
+        assert_eq!(LineKind::strip_prefix(line, LineKind::classify(line)), "indented");
+        let line = "- [ ]";
+        assert_eq!(LineKind::strip_prefix(line, LineKind::classify(line)), "");
+    }
+
+    #[test]
+    fn test_list_marker() {
+        assert_eq!(list_marker("- item"), Some((2, String::from("- "))));
+        assert_eq!(list_marker("  * item"), Some((4, String::from("  * "))));
+        assert_eq!(list_marker("- [x] done"), Some((6, String::from("- [ ] "))));
+        assert_eq!(list_marker("9. nine"), Some((3, String::from("10. "))));
+        assert_eq!(list_marker("plain"), None);
+        assert_eq!(list_marker("- - -"), None);
+    }
+
+    fn span(text: &str, bold: bool, italic: bool, code: bool) -> InlineSpan {
+        InlineSpan { text: text.into(), bold, italic, code, strikethrough: false }
+    }
+
+    fn struck(text: &str) -> InlineSpan {
+        InlineSpan { text: text.into(), strikethrough: true, ..InlineSpan::default() }
+    }
+
+    #[test]
+    fn test_inline_plain_text() {
+        assert_eq!(parse_inline("just text"), vec![span("just text", false, false, false)]);
+        assert!(parse_inline("").is_empty());
+    }
+
+    #[test]
+    fn test_inline_mixed_spans() {
+        assert_eq!(parse_inline("a **bold** and *it* or _it_ with `co*de`"), vec![
+            span("a ", false, false, false),
+            span("bold", true, false, false),
+            span(" and ", false, false, false),
+            span("it", false, true, false),
+            span(" or ", false, false, false),
+            span("it", false, true, false),
+            span(" with ", false, false, false),
+            span("co*de", false, false, true),
+        ]);
+    }
+
+    #[test]
+    fn test_inline_bold_inside_italic() {
+        assert_eq!(parse_inline("*very **much** so*"), vec![
+            span("very ", false, true, false),
+            span("much", true, true, false),
+            span(" so", false, true, false),
+        ]);
+        assert_eq!(parse_inline("***both***"), vec![span("both", true, true, false)]);
+    }
+
+    #[test]
+    fn test_inline_unmatched_markers_are_literal() {
+        assert_eq!(parse_inline("**not closed"), vec![span("**not closed", false, false, false)]);
+        assert_eq!(parse_inline("a `tick"), vec![span("a `tick", false, false, false)]);
+        assert_eq!(parse_inline("2 * 3 * 4"), vec![span("2 * 3 * 4", false, false, false)]);
+        assert_eq!(parse_inline("snake_case_name"), vec![span("snake_case_name", false, false, false)]);
+    }
+
+    #[test]
+    fn test_inline_escapes() {
+        assert_eq!(parse_inline("\\*not italic\\*"), vec![span("*not italic*", false, false, false)]);
+        assert_eq!(parse_inline("a \\\\ b"), vec![span("a \\ b", false, false, false)]);
+        // Other backslashes are kept
+        assert_eq!(parse_inline("C:\\dir"), vec![span("C:\\dir", false, false, false)]);
+        assert_eq!(parse_inline("*a\\*b*"), vec![span("a*b", false, true, false)]);
+    }
+
+    #[test]
+    fn test_inline_strikethrough() {
+        assert_eq!(parse_inline("- ~~old plan~~ new plan"), vec![
+            span("- ", false, false, false),
+            struck("old plan"),
+            span(" new plan", false, false, false),
+        ]);
+        let bold_struck = InlineSpan { bold: true, ..struck("both") };
+        assert_eq!(parse_inline("~~**both**~~"), vec![bold_struck]);
+    }
+
+    #[test]
+    fn test_inline_single_tilde_is_literal() {
+        assert_eq!(parse_inline("about ~5 km"), vec![span("about ~5 km", false, false, false)]);
+        assert_eq!(parse_inline("~one~"), vec![span("~one~", false, false, false)]);
+        assert_eq!(parse_inline("~~unclosed"), vec![span("~~unclosed", false, false, false)]);
+        assert_eq!(parse_inline("\\~~kept\\~~"), vec![span("~~kept~~", false, false, false)]);
+    }
+
+    fn owned(text: &str) -> Vec<String> {
+        text.split('\n').map(String::from).collect()
+    }
+
+    #[test]
+    fn test_classify_lines_tracks_fences() {
+        let lines = owned("# Title\n```sh\n# comment\n- not a list\n> nor a quote\n```\n- item\n## After");
+        assert_eq!(classify_lines(&lines), vec![
+            LineKind::Heading1,
+            LineKind::CodeBlock,
+            LineKind::CodeBlock,
+            LineKind::CodeBlock,
+            LineKind::CodeBlock,
+            LineKind::CodeBlock,
+            LineKind::UnorderedList,
+            LineKind::Heading2,
+        ]);
+    }
+
+    #[test]
+    fn test_classify_lines_unterminated_fence_runs_to_end() {
+        let lines = owned("text\n```\n## heading\n1. step\n");
+        assert_eq!(classify_lines(&lines), vec![
+            LineKind::Normal,
+            LineKind::CodeBlock,
+            LineKind::CodeBlock,
+            LineKind::CodeBlock,
+            LineKind::CodeBlock,
+        ]);
+        assert!(classify_lines(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_extract_headings_levels_and_lines() {
+        let text = "# Title\nintro\n\n## Part one\ntext\n### Detail\n## Part two";
+        assert_eq!(extract_headings(text), vec![
+            (0, 1, "Title".to_string()),
+            (3, 2, "Part one".to_string()),
+            (5, 3, "Detail".to_string()),
+            (6, 2, "Part two".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn test_extract_headings_skips_code_blocks() {
+        let text = "# Setup\n```sh\n# install deps\nmake\n```\n## Usage\n    # indented code";
+        assert_eq!(extract_headings(text), vec![
+            (0, 1, "Setup".to_string()),
+            (5, 2, "Usage".to_string()),
+        ]);
+        assert!(extract_headings("no headings\n#hashtag").is_empty());
+    }
+
+    #[test]
+    fn test_html_headings() {
+        assert_eq!(to_html("# Title\n## Part\n###### Small"), "<h1>Title</h1>\n<h2>Part</h2>\n<h6>Small</h6>\n");
+        // Seven hashes is not a heading
+        assert_eq!(to_html("####### no"), "<p>####### no</p>\n");
+    }
+
+    #[test]
+    fn test_html_paragraphs_and_inline() {
+        let html = to_html("Some **bold** and *it*\nstill `a<b>`\n\nNext ~~old~~");
+        assert_eq!(html, "<p>Some <strong>bold</strong> and <em>it</em>\nstill <code>a&lt;b&gt;</code></p>\n<p>Next <del>old</del></p>\n");
+    }
+
+    #[test]
+    fn test_html_lists_are_grouped() {
+        let html = to_html("- one\n- two\n1. first\n2. second\n\n- [x] done");
+        assert_eq!(html, "<ul>\n<li>one</li>\n<li>two</li>\n</ul>\n<ol>\n<li>first</li>\n<li>second</li>\n</ol>\n\
+<ul>\n<li><input type=\"checkbox\" checked disabled> done</li>\n</ul>\n");
+    }
+
+    #[test]
+    fn test_html_quote_and_rule() {
+        assert_eq!(to_html("> quoted\n> more\n---"), "<blockquote><p>quoted\nmore</p></blockquote>\n<hr>\n");
+    }
+
+    #[test]
+    fn test_html_code_blocks_are_escaped() {
+        let html = to_html("```\nif a < b && c > d {\n# not a heading\n```\n    x <= y");
+        assert_eq!(html, "<pre><code>if a &lt; b &amp;&amp; c &gt; d {\n# not a heading\n</code></pre>\n<pre><code>x &lt;= y</code></pre>\n");
+        assert_eq!(to_html("```\n<tag>"), "<pre><code>&lt;tag&gt;\n</code></pre>\n");
+        assert_eq!(to_html("```\n- item\n1. step\n```"), "<pre><code>- item\n1. step\n</code></pre>\n");
+    }
+
+    #[test]
+    fn test_html_escapes_text() {
+        assert_eq!(escape_html("a < b & c > d"), "a &lt; b &amp; c &gt; d");
+        assert_eq!(to_html("Fish & <chips>"), "<p>Fish &amp; &lt;chips&gt;</p>\n");
+    }
 }
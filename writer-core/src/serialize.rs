@@ -1,35 +1,286 @@
+use alloc::{borrow::Cow, string::{String, ToString}, vec::Vec, format, vec};
+use crate::buffer::{BufferSnapshot, Cursor};
+use crate::export::ExportResume;
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct WriterConfig {
     pub default_mode: u8,      // 0=editor, 1=journal, 2=typewriter
     pub autosave: bool,
     pub show_line_numbers: bool,
+    pub max_line_length: u16,  // warn when a line is longer (0=off)
+    pub confirm_usb_export: bool,
+    pub confirm_tcp_export: bool,
+    pub autosave_interval_secs: u16, // idle seconds before autosave (0=off)
+    pub export_page_size: u16,       // chars per export page (0=single page)
+    pub auto_capitalize: bool,       // capitalize sentence starts in the editor
+    pub paragraph_spacing: bool,     // extra space above paragraphs in preview
+    pub journal_date_format: DateFormat,
+    pub prose_word_count: bool,      // status bar counts prose words, not markdown syntax
+    pub tcp_timeout_secs: u16,       // how long TCP export waits for a client
+    pub horizontal_scroll: bool,     // long lines scroll sideways instead of wrapping
+    pub theme: Theme,
+    pub word_goal: usize,            // words to aim for per document/entry (0=off)
+    pub typewriter_allow_backspace: bool, // typewriter backspace within the current line
+    pub expand_tabs: bool,           // Tab inserts spaces rather than a tab character
+    pub tab_width: u8,               // spaces per Tab when expanding
+    pub auto_indent: bool,           // Enter keeps the previous line's indentation
+    pub number_separator: NumberSeparator,
+    pub journal_search_all_matches: bool, // list every matching line, not just the first per day
+    pub journal_template: String,    // pre-fills new journal entries (empty=off)
+    pub tz_offset_minutes: i16,      // minutes added to the device's local clock (0 = as is)
+    pub autotype_newline: AutotypeNewline,
+    pub autotype_by_line: bool,      // autotype a line at a time instead of in byte chunks
+}
+
+/// How the journal header shows a date.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DateFormat {
+    /// 2026-01-23 Fri
+    Iso,
+    /// Friday, January 23, 2026
+    Long,
+    /// Fri 23 Jan 2026
+    DayMonth,
+}
+
+impl DateFormat {
+    /// Stored config byte back to a format (unknown values fall back to Iso).
+    pub fn from_u8(b: u8) -> Self {
+        match b {
+            1 => DateFormat::Long,
+            2 => DateFormat::DayMonth,
+            _ => DateFormat::Iso,
+        }
+    }
+
+    pub fn to_u8(self) -> u8 {
+        match self {
+            DateFormat::Iso => 0,
+            DateFormat::Long => 1,
+            DateFormat::DayMonth => 2,
+        }
+    }
+
+    /// The next preset, for cycling through them in settings.
+    pub fn next(self) -> Self {
+        Self::from_u8((self.to_u8() + 1) % 3)
+    }
+}
+
+/// What goes between groups of three digits in counts (1,000 / 1.000 / 1 000).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NumberSeparator {
+    Comma,
+    Period,
+    Space,
+}
+
+impl NumberSeparator {
+    /// Stored config byte back to a separator (unknown values fall back to Comma).
+    pub fn from_u8(b: u8) -> Self {
+        match b {
+            1 => NumberSeparator::Period,
+            2 => NumberSeparator::Space,
+            _ => NumberSeparator::Comma,
+        }
+    }
+
+    pub fn to_u8(self) -> u8 {
+        match self {
+            NumberSeparator::Comma => 0,
+            NumberSeparator::Period => 1,
+            NumberSeparator::Space => 2,
+        }
+    }
+
+    /// The next separator, for cycling through them in settings.
+    pub fn next(self) -> Self {
+        Self::from_u8((self.to_u8() + 1) % 3)
+    }
+
+    pub fn as_char(self) -> char {
+        match self {
+            NumberSeparator::Comma => ',',
+            NumberSeparator::Period => '.',
+            NumberSeparator::Space => ' ',
+        }
+    }
+}
+
+/// What USB autotype types for each line break. Hosts that want Enter as
+/// a carriage return get `Cr`/`CrLf`; chat boxes where Enter sends the
+/// message get `Space`, which joins the lines instead.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AutotypeNewline {
+    Lf,
+    CrLf,
+    Cr,
+    Space,
+}
+
+impl AutotypeNewline {
+    /// Stored config byte back to a mode (unknown values fall back to Lf).
+    pub fn from_u8(b: u8) -> Self {
+        match b {
+            1 => AutotypeNewline::CrLf,
+            2 => AutotypeNewline::Cr,
+            3 => AutotypeNewline::Space,
+            _ => AutotypeNewline::Lf,
+        }
+    }
+
+    pub fn to_u8(self) -> u8 {
+        match self {
+            AutotypeNewline::Lf => 0,
+            AutotypeNewline::CrLf => 1,
+            AutotypeNewline::Cr => 2,
+            AutotypeNewline::Space => 3,
+        }
+    }
+
+    /// The next mode, for cycling through them in settings.
+    pub fn next(self) -> Self {
+        Self::from_u8((self.to_u8() + 1) % 4)
+    }
+
+    /// What is typed in place of each '\n'.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AutotypeNewline::Lf => "\n",
+            AutotypeNewline::CrLf => "\r\n",
+            AutotypeNewline::Cr => "\r",
+            AutotypeNewline::Space => " ",
+        }
+    }
+}
+
+/// Screen colours: dark text on a light background, or inverted.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Theme {
+    Light,
+    Dark,
 }
 
-impl WriterConfig {
-    pub fn default() -> Self {
+impl Theme {
+    /// Stored config byte back to a theme (unknown values fall back to Light).
+    pub fn from_u8(b: u8) -> Self {
+        match b {
+            1 => Theme::Dark,
+            _ => Theme::Light,
+        }
+    }
+
+    pub fn to_u8(self) -> u8 {
+        match self {
+            Theme::Light => 0,
+            Theme::Dark => 1,
+        }
+    }
+
+    /// The other theme, for the settings toggle.
+    pub fn toggled(self) -> Self {
+        match self {
+            Theme::Light => Theme::Dark,
+            Theme::Dark => Theme::Light,
+        }
+    }
+}
+
+impl Default for WriterConfig {
+    fn default() -> Self {
         Self {
             default_mode: 0,
             autosave: true,
             show_line_numbers: false,
+            max_line_length: 0,
+            confirm_usb_export: true,
+            confirm_tcp_export: false,
+            autosave_interval_secs: 30,
+            export_page_size: 0,
+            auto_capitalize: false,
+            paragraph_spacing: false,
+            journal_date_format: DateFormat::Iso,
+            prose_word_count: false,
+            tcp_timeout_secs: 60,
+            horizontal_scroll: false,
+            theme: Theme::Light,
+            word_goal: 0,
+            typewriter_allow_backspace: false,
+            expand_tabs: true,
+            tab_width: 4,
+            auto_indent: false,
+            number_separator: NumberSeparator::Comma,
+            journal_search_all_matches: false,
+            journal_template: String::new(),
+            tz_offset_minutes: 0,
+            autotype_newline: AutotypeNewline::Lf,
+            autotype_by_line: false,
         }
     }
 }
 
-/// Serialize a document: [u16 title_len][title_utf8][content_utf8...]
+/// Magic bytes at the start of a versioned document blob.
+pub const DOC_MAGIC: &[u8; 4] = b"WDOC";
+/// Document format written by `serialize_document`.
+pub const DOC_VERSION: u8 = 2;
+/// First document format, without a checksum. Still read.
+const DOC_VERSION_NO_CRC: u8 = 1;
+
+/// Serialize a document: [b"WDOC"][u8 version][u16 title_len][title_utf8][content_utf8...]
+/// [u32 crc32], the CRC covering every byte before it.
 pub fn serialize_document(title: &str, content: &str) -> Vec<u8> {
     let title_bytes = title.as_bytes();
     let title_len = title_bytes.len() as u16;
     let content_bytes = content.as_bytes();
 
-    let mut data = Vec::with_capacity(2 + title_bytes.len() + content_bytes.len());
+    let mut data = Vec::with_capacity(11 + title_bytes.len() + content_bytes.len());
+    data.extend_from_slice(DOC_MAGIC);
+    data.push(DOC_VERSION);
     data.extend_from_slice(&title_len.to_le_bytes());
     data.extend_from_slice(title_bytes);
     data.extend_from_slice(content_bytes);
+    let crc = crc32(&data);
+    data.extend_from_slice(&crc.to_le_bytes());
     data
 }
 
-/// Deserialize a document: returns (title, content)
+/// Deserialize a document: returns (title, content). Blobs without the
+/// magic header are read as the original headerless layout
+/// ([u16 title_len][title][content]), and v1 blobs have no checksum. A
+/// header with an unknown version, a blob cut short, or a checksum that
+/// doesn't match (a partial write, say) is rejected.
 pub fn deserialize_document(bytes: &[u8]) -> Option<(String, String)> {
+    match bytes.strip_prefix(DOC_MAGIC) {
+        Some(rest) => match rest.split_first()? {
+            (&DOC_VERSION, body) => {
+                let (body, stored) = body.split_at(body.len().checked_sub(4)?);
+                if crc32(&bytes[..bytes.len() - 4]).to_le_bytes() != stored {
+                    return None;
+                }
+                deserialize_document_body(body)
+            }
+            (&DOC_VERSION_NO_CRC, body) => deserialize_document_body(body),
+            _ => None,
+        },
+        None => deserialize_document_body(bytes),
+    }
+}
+
+/// CRC-32 (IEEE 802.3, as used by zip and PNG) of `bytes`.
+pub fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+// [u16 title_len][title_utf8][content_utf8...], shared by every version
+fn deserialize_document_body(bytes: &[u8]) -> Option<(String, String)> {
     if bytes.len() < 2 {
         return None;
     }
@@ -42,28 +293,199 @@ pub fn deserialize_document(bytes: &[u8]) -> Option<(String, String)> {
     Some((title, content))
 }
 
-/// Serialize config: [u8 default_mode][u8 autosave][u8 show_line_numbers]
+/// Serialize config: [u8 default_mode][u8 autosave][u8 show_line_numbers][u16 max_line_length]
+/// [u8 confirm_usb_export][u8 confirm_tcp_export][u16 autosave_interval_secs]
+/// [u16 export_page_size][u8 auto_capitalize][u8 paragraph_spacing][u8 journal_date_format]
+/// [u8 prose_word_count][u16 tcp_timeout_secs]
+/// [u8 horizontal_scroll][u8 theme][u32 word_goal][u8 typewriter_allow_backspace]
+/// [u8 expand_tabs][u8 tab_width][u8 auto_indent][u8 number_separator]
+/// [u8 journal_search_all_matches][u16 template_len][journal_template_utf8]
+/// [i16 tz_offset_minutes][u8 autotype_newline][u8 autotype_by_line]
+///
+/// New fields are only ever appended, so older blobs stay readable.
 pub fn serialize_config(config: &WriterConfig) -> Vec<u8> {
-    vec![
+    let mut data = vec![
         config.default_mode,
         config.autosave as u8,
         config.show_line_numbers as u8,
-    ]
+    ];
+    data.extend_from_slice(&config.max_line_length.to_le_bytes());
+    data.push(config.confirm_usb_export as u8);
+    data.push(config.confirm_tcp_export as u8);
+    data.extend_from_slice(&config.autosave_interval_secs.to_le_bytes());
+    data.extend_from_slice(&config.export_page_size.to_le_bytes());
+    data.push(config.auto_capitalize as u8);
+    data.push(config.paragraph_spacing as u8);
+    data.push(config.journal_date_format.to_u8());
+    data.push(config.prose_word_count as u8);
+    data.extend_from_slice(&config.tcp_timeout_secs.to_le_bytes());
+    data.push(config.horizontal_scroll as u8);
+    data.push(config.theme.to_u8());
+    data.extend_from_slice(&(config.word_goal as u32).to_le_bytes());
+    data.push(config.typewriter_allow_backspace as u8);
+    data.push(config.expand_tabs as u8);
+    data.push(config.tab_width);
+    data.push(config.auto_indent as u8);
+    data.push(config.number_separator.to_u8());
+    data.push(config.journal_search_all_matches as u8);
+    // The length is a u16, so a longer template is cut, between characters
+    let template = &config.journal_template;
+    let mut len = template.len().min(u16::MAX as usize);
+    while !template.is_char_boundary(len) {
+        len -= 1;
+    }
+    let template = &template.as_bytes()[..len];
+    data.extend_from_slice(&(template.len() as u16).to_le_bytes());
+    data.extend_from_slice(template);
+    data.extend_from_slice(&config.tz_offset_minutes.to_le_bytes());
+    data.push(config.autotype_newline.to_u8());
+    data.push(config.autotype_by_line as u8);
+    data
 }
 
-/// Deserialize config
+/// Deserialize config. Fields missing from older (shorter) blobs keep their defaults.
 pub fn deserialize_config(bytes: &[u8]) -> Option<WriterConfig> {
     if bytes.len() < 3 {
         return None;
     }
-    Some(WriterConfig {
+    let mut config = WriterConfig {
         default_mode: bytes[0],
         autosave: bytes[1] != 0,
         show_line_numbers: bytes[2] != 0,
+        ..WriterConfig::default()
+    };
+    if let Some(b) = bytes.get(3..5) {
+        config.max_line_length = u16::from_le_bytes([b[0], b[1]]);
+    }
+    if let Some(&b) = bytes.get(5) {
+        config.confirm_usb_export = b != 0;
+    }
+    if let Some(&b) = bytes.get(6) {
+        config.confirm_tcp_export = b != 0;
+    }
+    if let Some(b) = bytes.get(7..9) {
+        config.autosave_interval_secs = u16::from_le_bytes([b[0], b[1]]);
+    }
+    if let Some(b) = bytes.get(9..11) {
+        config.export_page_size = u16::from_le_bytes([b[0], b[1]]);
+    }
+    if let Some(&b) = bytes.get(11) {
+        config.auto_capitalize = b != 0;
+    }
+    if let Some(&b) = bytes.get(12) {
+        config.paragraph_spacing = b != 0;
+    }
+    if let Some(&b) = bytes.get(13) {
+        config.journal_date_format = DateFormat::from_u8(b);
+    }
+    if let Some(&b) = bytes.get(14) {
+        config.prose_word_count = b != 0;
+    }
+    if let Some(b) = bytes.get(15..17) {
+        config.tcp_timeout_secs = u16::from_le_bytes([b[0], b[1]]);
+    }
+    if let Some(&b) = bytes.get(17) {
+        config.horizontal_scroll = b != 0;
+    }
+    if let Some(&b) = bytes.get(18) {
+        config.theme = Theme::from_u8(b);
+    }
+    if let Some(b) = bytes.get(19..23) {
+        config.word_goal = u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize;
+    }
+    if let Some(&b) = bytes.get(23) {
+        config.typewriter_allow_backspace = b != 0;
+    }
+    if let Some(&b) = bytes.get(24) {
+        config.expand_tabs = b != 0;
+    }
+    if let Some(&b) = bytes.get(25) {
+        config.tab_width = b;
+    }
+    if let Some(&b) = bytes.get(26) {
+        config.auto_indent = b != 0;
+    }
+    if let Some(&b) = bytes.get(27) {
+        config.number_separator = NumberSeparator::from_u8(b);
+    }
+    if let Some(&b) = bytes.get(28) {
+        config.journal_search_all_matches = b != 0;
+    }
+    if let Some(b) = bytes.get(29..31) {
+        let len = u16::from_le_bytes([b[0], b[1]]) as usize;
+        if let Some(text) = bytes.get(31..31 + len) {
+            config.journal_template = String::from_utf8_lossy(text).to_string();
+        }
+        if let Some(b) = bytes.get(31 + len..33 + len) {
+            config.tz_offset_minutes = i16::from_le_bytes([b[0], b[1]]);
+        }
+        if let Some(&b) = bytes.get(33 + len) {
+            config.autotype_newline = AutotypeNewline::from_u8(b);
+        }
+        if let Some(&b) = bytes.get(34 + len) {
+            config.autotype_by_line = b != 0;
+        }
+    }
+    Some(config)
+}
+
+/// Serialize a buffer snapshot: [u32 cursor_line][u32 cursor_col][u32 viewport_top]
+/// [u8 modified][content_utf8...] with lines joined by '\n'
+pub fn serialize_snapshot(snapshot: &BufferSnapshot) -> Vec<u8> {
+    let content = snapshot.lines.join("\n");
+    let mut data = Vec::with_capacity(13 + content.len());
+    data.extend_from_slice(&(snapshot.cursor.line as u32).to_le_bytes());
+    data.extend_from_slice(&(snapshot.cursor.col as u32).to_le_bytes());
+    data.extend_from_slice(&(snapshot.viewport_top as u32).to_le_bytes());
+    data.push(snapshot.modified as u8);
+    data.extend_from_slice(content.as_bytes());
+    data
+}
+
+/// Deserialize a buffer snapshot
+pub fn deserialize_snapshot(bytes: &[u8]) -> Option<BufferSnapshot> {
+    let read_u32 = |at: usize| -> Option<usize> {
+        Some(u32::from_le_bytes(bytes.get(at..at + 4)?.try_into().ok()?) as usize)
+    };
+    let cursor = Cursor { line: read_u32(0)?, col: read_u32(4)? };
+    let viewport_top = read_u32(8)?;
+    let modified = *bytes.get(12)? != 0;
+    let content = String::from_utf8_lossy(&bytes[13..]);
+    Some(BufferSnapshot {
+        lines: content.split('\n').map(|l| l.to_string()).collect(),
+        cursor,
+        viewport_top,
+        modified,
     })
 }
 
-/// Serialize a document index: [u32 count][u16 name_len][name_utf8]...
+/// Serialize an export resume point:
+/// [u32 offset][u32 total][u32 crc32 of the text][doc_name_utf8...]
+pub fn serialize_export_resume(resume: &ExportResume) -> Vec<u8> {
+    let mut data = Vec::with_capacity(12 + resume.doc.len());
+    data.extend_from_slice(&(resume.offset as u32).to_le_bytes());
+    data.extend_from_slice(&(resume.total as u32).to_le_bytes());
+    data.extend_from_slice(&resume.crc.to_le_bytes());
+    data.extend_from_slice(resume.doc.as_bytes());
+    data
+}
+
+/// Deserialize an export resume point
+pub fn deserialize_export_resume(bytes: &[u8]) -> Option<ExportResume> {
+    let offset = u32::from_le_bytes(bytes.get(0..4)?.try_into().ok()?) as usize;
+    let total = u32::from_le_bytes(bytes.get(4..8)?.try_into().ok()?) as usize;
+    let crc = u32::from_le_bytes(bytes.get(8..12)?.try_into().ok()?);
+    let doc = String::from_utf8(bytes[12..].to_vec()).ok()?;
+    Some(ExportResume { doc, offset, total, crc })
+}
+
+/// Magic bytes at the start of an index that stores word counts.
+pub const INDEX_MAGIC: &[u8; 4] = b"WIDX";
+/// Stored in place of a word count that isn't known yet.
+const UNKNOWN_WORDS: u32 = u32::MAX;
+
+/// Serialize a document index of names only: [u32 count][u16 name_len][name_utf8]...
+/// This is the original layout; the stored index uses `serialize_index_words`.
 pub fn serialize_index(names: &[String]) -> Vec<u8> {
     let mut data = Vec::new();
     let count = names.len() as u32;
@@ -77,30 +499,96 @@ pub fn serialize_index(names: &[String]) -> Vec<u8> {
     data
 }
 
-/// Deserialize a document index
+/// Serialize a document index with each document's word count:
+/// [b"WIDX"][u32 count][u16 name_len][name_utf8][u32 words]...
+/// An unknown count is stored as u32::MAX.
+pub fn serialize_index_words(entries: &[(String, Option<u32>)]) -> Vec<u8> {
+    let mut data = Vec::new();
+    data.extend_from_slice(INDEX_MAGIC);
+    data.extend_from_slice(&(entries.len() as u32).to_le_bytes());
+    for (name, words) in entries {
+        let name_bytes = name.as_bytes();
+        data.extend_from_slice(&(name_bytes.len() as u16).to_le_bytes());
+        data.extend_from_slice(name_bytes);
+        data.extend_from_slice(&words.unwrap_or(UNKNOWN_WORDS).to_le_bytes());
+    }
+    data
+}
+
+/// Deserialize a document index, in either layout, to its names.
 pub fn deserialize_index(bytes: &[u8]) -> Vec<String> {
-    let mut names = Vec::new();
-    if bytes.len() < 4 {
-        return names;
-    }
-    let count = u32::from_le_bytes(bytes[0..4].try_into().unwrap_or([0; 4])) as usize;
-    let mut offset = 4;
-    for _ in 0..count {
-        if offset + 2 > bytes.len() {
-            break;
-        }
-        let name_len = u16::from_le_bytes(
-            bytes[offset..offset + 2].try_into().unwrap_or([0; 2])
-        ) as usize;
-        offset += 2;
-        if offset + name_len > bytes.len() {
-            break;
+    IndexIter::new(bytes).map(|(name, _)| name.into_owned()).collect()
+}
+
+/// Deserialize a document index to (name, word count) pairs. An index in
+/// the original names-only layout has every count unknown (None), as does
+/// any entry saved before its count was. Entries cut short are dropped.
+pub fn deserialize_index_words(bytes: &[u8]) -> Vec<(String, Option<u32>)> {
+    IndexIter::new(bytes).map(|(name, words)| (name.into_owned(), words)).collect()
+}
+
+/// Walks a serialized document index (either layout) one entry at a time,
+/// yielding (name, word count) as `deserialize_index_words` would without
+/// building the list. Names borrow from the bytes unless they need their
+/// invalid UTF-8 replaced. Stops at the first entry cut short.
+pub struct IndexIter<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+    remaining: usize,
+    has_words: bool,
+}
+
+impl<'a> IndexIter<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        let (bytes, has_words) = match bytes.strip_prefix(INDEX_MAGIC) {
+            Some(rest) => (rest, true),
+            None => (bytes, false),
+        };
+        let remaining = bytes.get(0..4)
+            .map_or(0, |b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize);
+        Self { bytes, offset: 4, remaining, has_words }
+    }
+}
+
+impl<'a> Iterator for IndexIter<'a> {
+    type Item = (Cow<'a, str>, Option<u32>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
         }
-        let name = String::from_utf8_lossy(&bytes[offset..offset + name_len]).to_string();
-        offset += name_len;
-        names.push(name);
+        // Anything cut short ends the walk
+        let left = self.remaining - 1;
+        self.remaining = 0;
+        let b = self.bytes.get(self.offset..self.offset + 2)?;
+        let name_len = u16::from_le_bytes([b[0], b[1]]) as usize;
+        let start = self.offset + 2;
+        let name = String::from_utf8_lossy(self.bytes.get(start..start + name_len)?);
+        let mut end = start + name_len;
+        let words = if self.has_words {
+            let b = self.bytes.get(end..end + 4)?;
+            end += 4;
+            Some(u32::from_le_bytes([b[0], b[1], b[2], b[3]])).filter(|&w| w != UNKNOWN_WORDS)
+        } else {
+            None
+        };
+        self.offset = end;
+        self.remaining = left;
+        Some((name, words))
+    }
+}
+
+/// The device clock's epoch milliseconds moved `offset_minutes` later
+/// (earlier if negative), for the date and time functions below. The
+/// device clock already reads local time, so the offset is only a
+/// correction on top of it. Dates before the epoch clamp to it.
+pub fn local_epoch_ms(clock_ms: u64, offset_minutes: i16) -> u64 {
+    let shift = offset_minutes.unsigned_abs() as u64 * 60 * 1000;
+    if offset_minutes < 0 {
+        clock_ms.saturating_sub(shift)
+    } else {
+        clock_ms.saturating_add(shift)
     }
-    names
 }
 
 /// Convert epoch milliseconds to a date string (YYYY-MM-DD)
@@ -141,20 +629,51 @@ pub fn epoch_ms_to_date(epoch_ms: u64) -> String {
     format!("{:04}-{:02}-{:02}", year, month, day)
 }
 
+/// Convert epoch milliseconds to a time of day (HH:MM, 24-hour)
+pub fn epoch_ms_to_time(epoch_ms: u64) -> String {
+    let seconds_of_day = (epoch_ms / 1000) % 86400;
+    format!("{:02}:{:02}", seconds_of_day / 3600, (seconds_of_day % 3600) / 60)
+}
+
+const WEEKDAY_NAMES: [&str; 7] = [
+    "Sunday", "Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday",
+];
+
+const MONTH_NAMES: [&str; 12] = [
+    "January", "February", "March", "April", "May", "June",
+    "July", "August", "September", "October", "November", "December",
+];
+
+/// Day of week from epoch ms, 0 = Sunday
+fn weekday_index(epoch_ms: u64) -> usize {
+    let days = epoch_ms / 1000 / 86400;
+    // 1970-01-01 was a Thursday (index 4)
+    ((days + 4) % 7) as usize
+}
+
 /// Get day-of-week abbreviation from epoch ms (0=Thu for 1970-01-01)
 pub fn epoch_ms_to_weekday(epoch_ms: u64) -> &'static str {
-    let days = (epoch_ms / 1000 / 86400) as u64;
-    // 1970-01-01 was a Thursday (index 4)
-    let weekday = (days + 4) % 7;
-    match weekday {
-        0 => "Sun",
-        1 => "Mon",
-        2 => "Tue",
-        3 => "Wed",
-        4 => "Thu",
-        5 => "Fri",
-        6 => "Sat",
-        _ => "???",
+    &WEEKDAY_NAMES[weekday_index(epoch_ms)][..3]
+}
+
+/// Full weekday name ("Monday") of a YYYY-MM-DD date.
+pub fn weekday_name(date: &str) -> Option<&'static str> {
+    date_to_epoch_ms(date).map(|ms| WEEKDAY_NAMES[weekday_index(ms)])
+}
+
+/// Format a YYYY-MM-DD date for display. Unparseable dates are returned as-is.
+pub fn format_date(date: &str, fmt: DateFormat) -> String {
+    let Some(ms) = date_to_epoch_ms(date) else {
+        return date.to_string();
+    };
+    let weekday = WEEKDAY_NAMES[weekday_index(ms)];
+    // date_to_epoch_ms has validated the three numeric parts
+    let parts: Vec<u32> = date.split('-').filter_map(|p| p.parse().ok()).collect();
+    let (year, month, day) = (parts[0], MONTH_NAMES[parts[1] as usize - 1], parts[2]);
+    match fmt {
+        DateFormat::Iso => format!("{} {}", date, &weekday[..3]),
+        DateFormat::Long => format!("{}, {} {}, {}", weekday, month, day, year),
+        DateFormat::DayMonth => format!("{} {} {} {}", &weekday[..3], day, &month[..3], year),
     }
 }
 
@@ -168,7 +687,7 @@ pub fn date_to_epoch_ms(date: &str) -> Option<u64> {
     let month: u32 = parts[1].parse().ok()?;
     let day: u32 = parts[2].parse().ok()?;
 
-    if month < 1 || month > 12 || day < 1 || day > 31 {
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
         return None;
     }
 
@@ -184,9 +703,7 @@ pub fn date_to_epoch_ms(date: &str) -> Option<u64> {
         [31, 28, 31, 30, 31, 30, 31, 31, 30, 31, 30, 31]
     };
 
-    for i in 0..(month as usize - 1) {
-        total_days += days_in_months[i];
-    }
+    total_days += days_in_months.iter().take(month as usize - 1).sum::<u64>();
     total_days += (day - 1) as u64;
 
     Some(total_days * 86400 * 1000)
@@ -236,12 +753,150 @@ mod tests {
             default_mode: 1,
             autosave: true,
             show_line_numbers: false,
+            max_line_length: 80,
+            confirm_usb_export: false,
+            confirm_tcp_export: true,
+            autosave_interval_secs: 45,
+            export_page_size: 2000,
+            auto_capitalize: true,
+            paragraph_spacing: true,
+            journal_date_format: DateFormat::Long,
+            prose_word_count: true,
+            tcp_timeout_secs: 120,
+            horizontal_scroll: true,
+            theme: Theme::Dark,
+            word_goal: 750,
+            typewriter_allow_backspace: true,
+            expand_tabs: false,
+            tab_width: 8,
+            auto_indent: true,
+            number_separator: NumberSeparator::Space,
+            journal_search_all_matches: true,
+            journal_template: String::from("# {weekday}\n\n"),
+            tz_offset_minutes: -300,
+            autotype_newline: AutotypeNewline::Space,
+            autotype_by_line: true,
         };
         let data = serialize_config(&config);
         let restored = deserialize_config(&data).unwrap();
         assert_eq!(restored.default_mode, 1);
         assert!(restored.autosave);
         assert!(!restored.show_line_numbers);
+        assert_eq!(restored.max_line_length, 80);
+        assert!(!restored.confirm_usb_export);
+        assert!(restored.confirm_tcp_export);
+        assert_eq!(restored.autosave_interval_secs, 45);
+        assert_eq!(restored.export_page_size, 2000);
+        assert!(restored.auto_capitalize);
+        assert!(restored.paragraph_spacing);
+        assert_eq!(restored.journal_date_format, DateFormat::Long);
+        assert!(restored.prose_word_count);
+        assert_eq!(restored.tcp_timeout_secs, 120);
+        assert!(restored.horizontal_scroll);
+        assert_eq!(restored.theme, Theme::Dark);
+        assert_eq!(restored.word_goal, 750);
+        assert!(restored.typewriter_allow_backspace);
+        assert!(!restored.expand_tabs);
+        assert_eq!(restored.tab_width, 8);
+        assert!(restored.auto_indent);
+        assert_eq!(restored.number_separator, NumberSeparator::Space);
+        assert!(restored.journal_search_all_matches);
+        assert_eq!(restored.journal_template, "# {weekday}\n\n");
+        assert_eq!(restored.tz_offset_minutes, -300);
+        assert_eq!(restored.autotype_newline, AutotypeNewline::Space);
+        assert!(restored.autotype_by_line);
+
+        // Written before the clock correction: stays off
+        let restored = deserialize_config(&data[..data.len() - 4]).unwrap();
+        assert_eq!(restored.journal_template, "# {weekday}\n\n");
+        assert_eq!(restored.tz_offset_minutes, 0);
+    }
+
+    #[test]
+    fn test_config_long_template_is_cut() {
+        // 2-byte characters, so the u16 limit falls inside one
+        let config = WriterConfig {
+            journal_template: "é".repeat(40_000),
+            tz_offset_minutes: 90,
+            ..WriterConfig::default()
+        };
+        let restored = deserialize_config(&serialize_config(&config)).unwrap();
+        assert_eq!(restored.journal_template.len(), 65_534);
+        assert!(config.journal_template.starts_with(&restored.journal_template));
+        // The fields after it still line up
+        assert_eq!(restored.tz_offset_minutes, 90);
+    }
+
+    #[test]
+    fn test_deserialize_legacy_config() {
+        // Three-byte blob written before max_line_length existed
+        let restored = deserialize_config(&[2, 0, 1]).unwrap();
+        assert_eq!(restored.default_mode, 2);
+        assert!(!restored.autosave);
+        assert!(restored.show_line_numbers);
+        assert_eq!(restored.max_line_length, 0);
+        assert!(restored.confirm_usb_export);
+        assert!(!restored.confirm_tcp_export);
+        assert_eq!(restored.autosave_interval_secs, 30);
+        // Typewriter stays strict unless the user opts in
+        assert!(!restored.typewriter_allow_backspace);
+    }
+
+    #[test]
+    fn test_config_autosave_interval_round_trip() {
+        let config = WriterConfig {
+            autosave_interval_secs: 0,
+            ..WriterConfig::default()
+        };
+        let data = serialize_config(&config);
+        assert_eq!(data.len(), 35);
+        assert_eq!(deserialize_config(&data).unwrap(), config);
+        assert_eq!(deserialize_config(&data[..9]).unwrap(), config);
+
+        let config = WriterConfig {
+            autosave_interval_secs: 300,
+            ..WriterConfig::default()
+        };
+        assert_eq!(deserialize_config(&serialize_config(&config)).unwrap(), config);
+    }
+
+    #[test]
+    fn test_config_auto_capitalize_round_trip() {
+        let config = WriterConfig {
+            auto_capitalize: true,
+            ..WriterConfig::default()
+        };
+        let data = serialize_config(&config);
+        assert_eq!(deserialize_config(&data).unwrap(), config);
+        // Older blobs without the flag leave it off
+        assert!(!deserialize_config(&data[..11]).unwrap().auto_capitalize);
+    }
+
+    #[test]
+    fn test_config_without_paragraph_spacing() {
+        let config = WriterConfig {
+            paragraph_spacing: true,
+            ..WriterConfig::default()
+        };
+        let data = serialize_config(&config);
+        assert_eq!(deserialize_config(&data).unwrap(), config);
+        assert!(!deserialize_config(&data[..12]).unwrap().paragraph_spacing);
+    }
+
+    #[test]
+    fn test_config_without_autosave_interval() {
+        // Blob written before the interval existed: everything up to the export flags
+        let config = WriterConfig {
+            max_line_length: 80,
+            confirm_usb_export: false,
+            autosave_interval_secs: 120,
+            ..WriterConfig::default()
+        };
+        let data = serialize_config(&config);
+        let restored = deserialize_config(&data[..7]).unwrap();
+        assert_eq!(restored.max_line_length, 80);
+        assert!(!restored.confirm_usb_export);
+        assert_eq!(restored.autosave_interval_secs, 30);
     }
 
     #[test]
@@ -268,6 +923,69 @@ mod tests {
         assert_eq!(epoch_ms_to_date(86400 * 1000), "1970-01-02");
     }
 
+    #[test]
+    fn test_local_epoch_ms_around_midnight() {
+        let utc = |date: &str, h: u64, m: u64| date_to_epoch_ms(date).unwrap() + (h * 3600 + m * 60) * 1000;
+        let local = |ms: u64, offset: i16| {
+            let ms = local_epoch_ms(ms, offset);
+            format!("{} {}", epoch_ms_to_date(ms), epoch_ms_to_time(ms))
+        };
+
+        // 23:30 UTC is already tomorrow east of UTC, still today west of it
+        let late = utc("2026-01-23", 23, 30);
+        assert_eq!(local(late, 0), "2026-01-23 23:30");
+        assert_eq!(local(late, 60), "2026-01-24 00:30");
+        assert_eq!(local(late, -300), "2026-01-23 18:30");
+        // 00:15 UTC is still yesterday in the Americas
+        let early = utc("2026-01-24", 0, 15);
+        assert_eq!(local(early, -480), "2026-01-23 16:15");
+        assert_eq!(local(early, -15), "2026-01-24 00:00");
+        assert_eq!(local(early, -16), "2026-01-23 23:59");
+        assert_eq!(local(early, 330), "2026-01-24 05:45");
+
+        // Across a leap day, both ways
+        assert_eq!(local(utc("2024-02-28", 22, 0), 180), "2024-02-29 01:00");
+        assert_eq!(local(utc("2024-03-01", 1, 0), -120), "2024-02-29 23:00");
+        assert_eq!(local(utc("2023-03-01", 1, 0), -120), "2023-02-28 23:00");
+        assert_eq!(local(utc("2024-12-31", 23, 0), 840), "2025-01-01 13:00");
+
+        // Nothing before the epoch
+        assert_eq!(local_epoch_ms(60 * 1000, -60), 0);
+    }
+
+    #[test]
+    fn test_epoch_ms_to_time() {
+        assert_eq!(epoch_ms_to_time(0), "00:00");
+        assert_eq!(epoch_ms_to_time((14 * 3600 + 7 * 60 + 59) * 1000), "14:07");
+        // Time of day ignores the date
+        assert_eq!(epoch_ms_to_time(86400 * 1000 + 23 * 3600 * 1000), "23:00");
+    }
+
+    #[test]
+    fn test_format_date_presets() {
+        assert_eq!(format_date("2026-01-23", DateFormat::Iso), "2026-01-23 Fri");
+        assert_eq!(format_date("2026-01-23", DateFormat::Long), "Friday, January 23, 2026");
+        assert_eq!(format_date("2026-01-23", DateFormat::DayMonth), "Fri 23 Jan 2026");
+    }
+
+    #[test]
+    fn test_format_date_month_names() {
+        assert_eq!(format_date("2024-02-29", DateFormat::Long), "Thursday, February 29, 2024");
+        assert_eq!(format_date("2025-09-07", DateFormat::Long), "Sunday, September 7, 2025");
+        assert_eq!(format_date("2025-12-01", DateFormat::DayMonth), "Mon 1 Dec 2025");
+        assert_eq!(format_date("not a date", DateFormat::Long), "not a date");
+    }
+
+    #[test]
+    fn test_config_date_format_round_trip() {
+        for fmt in [DateFormat::Iso, DateFormat::Long, DateFormat::DayMonth] {
+            let config = WriterConfig { journal_date_format: fmt, ..WriterConfig::default() };
+            assert_eq!(deserialize_config(&serialize_config(&config)).unwrap(), config);
+        }
+        assert_eq!(DateFormat::DayMonth.next(), DateFormat::Iso);
+        assert_eq!(DateFormat::from_u8(200), DateFormat::Iso);
+    }
+
     #[test]
     fn test_date_to_epoch_and_back() {
         let date = "2026-01-23";
@@ -298,14 +1016,179 @@ mod tests {
         assert!(!is_leap_year(2023));
     }
 
+    #[test]
+    fn test_document_v2_round_trip() {
+        let data = serialize_document("Notes", "café\nau lait");
+        assert_eq!(&data[..5], b"WDOC\x02");
+        assert_eq!(
+            deserialize_document(&data),
+            Some(("Notes".to_string(), "café\nau lait".to_string())),
+        );
+    }
+
+    #[test]
+    fn test_crc32_known_value() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc32(b""), 0);
+    }
+
+    #[test]
+    fn test_flipped_byte_fails_checksum() {
+        let data = serialize_document("Notes", "Hello world");
+        for i in 0..data.len() {
+            let mut corrupt = data.clone();
+            corrupt[i] ^= 0x01;
+            assert_eq!(deserialize_document(&corrupt), None, "byte {}", i);
+        }
+        // A write cut short loses the checksum too
+        assert_eq!(deserialize_document(&data[..data.len() - 3]), None);
+    }
+
+    #[test]
+    fn test_deserialize_v1_document_without_checksum() {
+        let mut v1 = b"WDOC\x01".to_vec();
+        v1.extend_from_slice(&5u16.to_le_bytes());
+        v1.extend_from_slice(b"NotesHello");
+        assert_eq!(
+            deserialize_document(&v1),
+            Some(("Notes".to_string(), "Hello".to_string())),
+        );
+    }
+
+    #[test]
+    fn test_deserialize_legacy_document() {
+        // Headerless blob as written before versioning
+        let mut legacy = 6u16.to_le_bytes().to_vec();
+        legacy.extend_from_slice(b"My DocHello");
+        assert_eq!(
+            deserialize_document(&legacy),
+            Some(("My Doc".to_string(), "Hello".to_string())),
+        );
+    }
+
+    #[test]
+    fn test_deserialize_document_bad_header() {
+        // Magic with no version byte, or no title length after it
+        assert_eq!(deserialize_document(b"WDOC"), None);
+        assert_eq!(deserialize_document(b"WDOC\x01\x05"), None);
+        assert_eq!(deserialize_document(b"WDOC\x02\x05"), None);
+        // A version this build doesn't know
+        let mut future = serialize_document("T", "x");
+        future[4] = 3;
+        assert_eq!(deserialize_document(&future), None);
+    }
+
     #[test]
     fn test_deserialize_document_too_short() {
         assert_eq!(deserialize_document(&[0]), None);
         assert_eq!(deserialize_document(&[5, 0]), None); // title_len=5 but only 2 bytes
     }
 
+    fn entries(list: &[(&str, Option<u32>)]) -> Vec<(String, Option<u32>)> {
+        list.iter().map(|&(n, w)| (n.to_string(), w)).collect()
+    }
+
+    #[test]
+    fn test_index_words_round_trip() {
+        let index = entries(&[("Notes", Some(1240)), ("Draft", Some(0)), ("Café", None)]);
+        let data = serialize_index_words(&index);
+        assert_eq!(&data[..4], INDEX_MAGIC);
+        assert_eq!(deserialize_index_words(&data), index);
+        assert_eq!(deserialize_index(&data), vec!["Notes", "Draft", "Café"]);
+        assert!(deserialize_index_words(&serialize_index_words(&[])).is_empty());
+    }
+
+    #[test]
+    fn test_legacy_index_has_unknown_counts() {
+        let legacy = serialize_index(&["Notes".to_string(), "Draft".to_string()]);
+        assert_eq!(deserialize_index_words(&legacy), entries(&[("Notes", None), ("Draft", None)]));
+
+        // Saving one document rewrites the index in the new layout: its
+        // count is known, the others stay unknown until they are saved
+        let mut index = deserialize_index_words(&legacy);
+        index[1].1 = Some(57);
+        let mixed = deserialize_index_words(&serialize_index_words(&index));
+        assert_eq!(mixed, entries(&[("Notes", None), ("Draft", Some(57))]));
+    }
+
+    #[test]
+    fn test_truncated_index_keeps_whole_entries() {
+        let data = serialize_index_words(&entries(&[("A", Some(1)), ("B", Some(2))]));
+        // Cut inside B's word count: only A survives
+        assert_eq!(deserialize_index_words(&data[..data.len() - 2]), entries(&[("A", Some(1))]));
+    }
+
+    #[test]
+    fn test_index_iter_matches_collected_list() {
+        let index = entries(&[("Notes", Some(12)), ("Draft", None), ("Café", Some(3))]);
+        for data in [serialize_index_words(&index), serialize_index(&deserialize_index(&serialize_index_words(&index)))] {
+            let names: Vec<String> = IndexIter::new(&data).map(|(n, _)| n.into_owned()).collect();
+            assert_eq!(names, deserialize_index(&data));
+            assert!(IndexIter::new(&data).all(|(n, _)| matches!(n, Cow::Borrowed(_))));
+        }
+        assert_eq!(IndexIter::new(&[]).count(), 0);
+    }
+
+    #[test]
+    fn test_index_iter_stops_at_garbage_tail() {
+        let full = entries(&[("A", Some(1)), ("B", Some(2)), ("C", None)]);
+        let data = serialize_index_words(&full);
+        // Every prefix yields the entries that arrived whole, in order
+        for len in 0..data.len() {
+            let walked: Vec<(String, Option<u32>)> = IndexIter::new(&data[..len]).map(|(n, w)| (n.into_owned(), w)).collect();
+            assert!(walked.len() < full.len() && full.starts_with(&walked));
+        }
+        assert_eq!(IndexIter::new(&data).count(), full.len());
+        // A count larger than the entries present stops after the last one
+        let mut padded = serialize_index(&["A".to_string()]);
+        padded[0] = 200;
+        padded.extend_from_slice(&[0xff]);
+        assert_eq!(IndexIter::new(&padded).count(), 1);
+    }
+
     #[test]
     fn test_deserialize_config_too_short() {
         assert_eq!(deserialize_config(&[0, 1]), None);
     }
+
+    #[test]
+    fn test_snapshot_round_trip() {
+        let snapshot = BufferSnapshot {
+            lines: vec!["# Title".into(), String::new(), "café au lait".into(), String::new()],
+            cursor: Cursor { line: 2, col: 4 },
+            viewport_top: 1,
+            modified: true,
+        };
+        let data = serialize_snapshot(&snapshot);
+        assert_eq!(deserialize_snapshot(&data), Some(snapshot));
+    }
+
+    #[test]
+    fn test_snapshot_from_buffer_round_trip() {
+        let mut buf = crate::TextBuffer::from_text("one\ntwo");
+        buf.cursor.line = 1;
+        buf.move_end();
+        buf.newline();
+        let snapshot = buf.snapshot();
+        let restored = deserialize_snapshot(&serialize_snapshot(&snapshot)).unwrap();
+        assert_eq!(restored, snapshot);
+        assert_eq!(restored.lines, vec!["one", "two", ""]);
+    }
+
+    #[test]
+    fn test_export_resume_round_trip() {
+        let resume = ExportResume { doc: "Novel draft".into(), offset: 1234, total: 5678, crc: 0xDEAD_BEEF };
+        let data = serialize_export_resume(&resume);
+        assert_eq!(deserialize_export_resume(&data), Some(resume));
+        assert_eq!(deserialize_export_resume(&data[..11]), None);
+    }
+
+    #[test]
+    fn test_snapshot_truncated() {
+        assert_eq!(deserialize_snapshot(&[]), None);
+        assert_eq!(deserialize_snapshot(&[0; 12]), None);
+        let empty = deserialize_snapshot(&[0; 13]).unwrap();
+        assert_eq!(empty.lines, vec![String::new()]);
+        assert!(!empty.modified);
+    }
 }
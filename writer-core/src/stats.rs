@@ -0,0 +1,126 @@
+use alloc::{collections::BTreeMap, string::String, vec::Vec};
+use core::cmp::Reverse;
+
+/// Common English words left out of `top_content_words` by default.
+pub const STOPWORDS: &[&str] = &[
+    "a", "about", "after", "all", "also", "am", "an", "and", "any", "are", "as", "at",
+    "be", "been", "but", "by", "can", "could", "did", "do", "for", "from", "had",
+    "has", "have", "he", "her", "him", "his", "how", "i", "if", "in", "into", "is",
+    "it", "its", "just", "me", "my", "no", "not", "of", "on", "or", "our", "out",
+    "she", "so", "some", "than", "that", "the", "their", "them", "then", "there",
+    "they", "this", "to", "up", "us", "was", "we", "were", "what", "when", "which",
+    "who", "will", "with", "would", "you", "your",
+];
+
+/// The `n` most frequent words in `text`, most frequent first (ties in
+/// alphabetical order). Words are lowercased and stripped of surrounding
+/// punctuation; numbers and `stopwords` (the built-in `STOPWORDS` if None)
+/// are skipped.
+pub fn top_content_words(text: &str, n: usize, stopwords: Option<&[&str]>) -> Vec<(String, usize)> {
+    let stopwords = stopwords.unwrap_or(STOPWORDS);
+    let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+    for raw in text.split_whitespace() {
+        let word = raw
+            .trim_matches(|c: char| !c.is_alphanumeric())
+            .to_lowercase();
+        if !word.chars().any(char::is_alphabetic) || stopwords.contains(&word.as_str()) {
+            continue;
+        }
+        *counts.entry(word).or_insert(0) += 1;
+    }
+
+    let mut ranked: Vec<(String, usize)> = counts.into_iter().collect();
+    // Stable sort keeps the map's alphabetical order within equal counts
+    ranked.sort_by_key(|(_, count)| Reverse(*count));
+    ranked.truncate(n);
+    ranked
+}
+
+/// Words per minute for `words` written in `elapsed_secs`. A session
+/// that hasn't lasted a second yet has no pace: 0.
+pub fn wpm(words: usize, elapsed_secs: u64) -> usize {
+    if elapsed_secs == 0 {
+        return 0;
+    }
+    (words as u64 * 60 / elapsed_secs) as usize
+}
+
+/// Progress towards a word goal: (percent, met). The percent is clamped
+/// to 100 once the goal is passed. A goal of 0 means no goal: (0, false).
+pub fn goal_progress(words: usize, goal: usize) -> (u8, bool) {
+    if goal == 0 {
+        return (0, false);
+    }
+    let percent = (words.saturating_mul(100) / goal).min(100) as u8;
+    (percent, words >= goal)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::{string::ToString, vec};
+
+    fn words(ranked: &[(String, usize)]) -> Vec<&str> {
+        ranked.iter().map(|(w, _)| w.as_str()).collect()
+    }
+
+    #[test]
+    fn test_stopwords_excluded() {
+        let text = "The rain and the wind. The rain, the RAIN!";
+        let top = top_content_words(text, 5, None);
+        assert_eq!(top, vec![("rain".to_string(), 3), ("wind".to_string(), 1)]);
+    }
+
+    #[test]
+    fn test_ranking_and_ties() {
+        let text = "pear apple fig apple pear apple 42 1999";
+        let top = top_content_words(text, 2, None);
+        assert_eq!(words(&top), vec!["apple", "pear"]);
+        assert_eq!(top[0].1, 3);
+        // Equal counts rank alphabetically
+        let top = top_content_words("pear fig", 5, None);
+        assert_eq!(words(&top), vec!["fig", "pear"]);
+    }
+
+    #[test]
+    fn test_custom_stopwords() {
+        let text = "the cat sat on the mat with the cat";
+        let top = top_content_words(text, 3, Some(&["cat"]));
+        assert_eq!(words(&top), vec!["the", "mat", "on"]);
+        assert!(top_content_words("", 3, None).is_empty());
+    }
+
+    #[test]
+    fn test_goal_progress() {
+        assert_eq!(goal_progress(0, 500), (0, false));
+        assert_eq!(goal_progress(342, 500), (68, false));
+        assert_eq!(goal_progress(500, 500), (100, true));
+        // Clamped when exceeded
+        assert_eq!(goal_progress(1200, 500), (100, true));
+        // Small counts round down rather than up
+        assert_eq!(goal_progress(4, 500), (0, false));
+        assert_eq!(goal_progress(5, 500), (1, false));
+    }
+
+    #[test]
+    fn test_goal_progress_disabled() {
+        assert_eq!(goal_progress(0, 0), (0, false));
+        assert_eq!(goal_progress(250, 0), (0, false));
+    }
+
+    #[test]
+    fn test_wpm() {
+        assert_eq!(wpm(0, 60), 0);
+        assert_eq!(wpm(40, 60), 40);
+        assert_eq!(wpm(25, 30), 50);
+        assert_eq!(wpm(100, 600), 10);
+        // Rounds down
+        assert_eq!(wpm(1, 7), 8);
+    }
+
+    #[test]
+    fn test_wpm_zero_duration() {
+        assert_eq!(wpm(0, 0), 0);
+        assert_eq!(wpm(12, 0), 0);
+    }
+}